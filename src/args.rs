@@ -3,6 +3,11 @@ use crate::types::*;
 use crate::utils::num_range::*;
 use clap::{ArgAction, ArgGroup, Parser, Subcommand};
 
+#[cfg(feature = "kirikiri")]
+fn parse_simple_crypt_mode(mode: &str) -> Result<u8, String> {
+    crate::utils::num_range::number_range(mode, 0, 2)
+}
+
 #[cfg(feature = "flate2")]
 fn parse_compression_level(level: &str) -> Result<u32, String> {
     let lower = level.to_ascii_lowercase();
@@ -153,7 +158,6 @@ pub struct Arg {
     #[arg(short = 'e', long, value_enum, global = true, group = "encodingg")]
     /// Script encoding
     pub encoding: Option<TextEncoding>,
-    #[cfg(windows)]
     #[arg(short = 'c', long, value_enum, global = true, group = "encodingg")]
     /// Script code page
     pub code_page: Option<u32>,
@@ -166,7 +170,6 @@ pub struct Arg {
     )]
     /// Output text encoding
     pub output_encoding: Option<TextEncoding>,
-    #[cfg(windows)]
     #[arg(
         short = 'C',
         long,
@@ -185,7 +188,6 @@ pub struct Arg {
     )]
     /// Archive filename encoding
     pub archive_encoding: Option<TextEncoding>,
-    #[cfg(windows)]
     #[arg(
         short = 'A',
         long,
@@ -241,9 +243,21 @@ pub struct Arg {
     /// Workers count for decode BGI compressed images v2 in parallel. Default is half of CPU cores.
     /// Set this to 1 to disable parallel decoding. 0 means same as 1.
     pub bgi_img_workers: usize,
+    #[cfg(feature = "bgi-img")]
+    #[arg(long, global = true, value_parser = clap::value_parser!(u16).range(1..=2))]
+    /// Version of BGI compressed images (1 or 2) when creating or importing images.
+    /// When in import mode, the default value depends on the original image.
+    /// When in creation mode, version 1 is used by default.
+    pub bgi_cbg_version: Option<u16>,
+    #[cfg(feature = "bgi-img")]
+    #[arg(long, global = true, value_parser = clap::value_parser!(u8).range(1..=100))]
+    /// Quality of BGI compressed images v2, 1-100. 100 means best quality.
+    /// When importing a v2 image, the quantization tables of the original image are used by default.
+    /// Otherwise, 80 is used by default.
+    pub bgi_cbg_quality: Option<u8>,
     #[cfg(feature = "cat-system-arc")]
     #[arg(long, global = true, group = "cat_system_int_encrypt_passwordg")]
-    /// CatSystem2 engine int archive password
+    /// CatSystem2 engine int archive password. When packing, archive is encrypted with this password.
     pub cat_system_int_encrypt_password: Option<String>,
     #[cfg(feature = "cat-system-arc")]
     #[arg(long, global = true, group = "cat_system_int_encrypt_passwordg")]
@@ -309,6 +323,19 @@ pub struct Arg {
     )]
     /// Kirikiri message commands, used to extract more message from ks script.
     pub kirikiri_message_commands: Vec<String>,
+    #[cfg(feature = "kirikiri")]
+    #[arg(
+        long,
+        global = true,
+        value_delimiter = ',',
+        default_value = "voice,playvoice,vo"
+    )]
+    /// Kirikiri voice commands, used to extract voice files played with messages in ks script.
+    pub kirikiri_voice_commands: Vec<String>,
+    #[cfg(feature = "kirikiri")]
+    #[arg(long, global = true, default_value_t = 2, value_parser = parse_simple_crypt_mode)]
+    /// Kirikiri SimpleCrypt mode (0-2) used when creating SimpleCrypt files.
+    pub kirikiri_simple_crypt_mode: u8,
     #[cfg(feature = "image")]
     #[arg(short = 'f', long, global = true)]
     /// Output multiple image as `<basename>_<name>.<ext>` instead of `<basename>/<name>.<ext>`
@@ -409,6 +436,16 @@ pub struct Arg {
     #[arg(long, global = true, action = ArgAction::SetTrue)]
     /// Draw Circus CRX images on canvas (if canvas width and height are specified in file)
     pub circus_crx_canvas: bool,
+    #[cfg(feature = "circus-img")]
+    #[arg(long, global = true, value_parser = clap::value_parser!(u16).range(1..=2))]
+    /// Version of Circus CRX images (1 or 2) when creating or importing images.
+    /// When in import mode, the default value depends on the original image. (V3 images are kept as V3 unless version 1 is specified.)
+    /// When in creation mode, version 2 is used by default.
+    pub circus_crx_version: Option<u16>,
+    #[cfg(feature = "circus-audio")]
+    #[arg(long, global = true, action = ArgAction::SetTrue)]
+    /// Use ADPCM compression when creating Circus PCM audio files from WAV/FLAC files.
+    pub circus_pcm_adpcm: bool,
     #[arg(short = 'F', long, global = true, action = ArgAction::SetTrue)]
     /// Force all files in archive to be treated as script files.
     pub force_script: bool,
@@ -549,6 +586,15 @@ pub struct Arg {
     /// Disable decompressing mdf files in Kirikiri XP3 archive when extracting.
     pub xp3_no_mdf_decompress: bool,
     #[cfg(feature = "kirikiri-arc")]
+    #[arg(long, global = true, value_delimiter = ',')]
+    /// File extensions of UTF-16LE text files to encrypt with SimpleCrypt in Kirikiri XP3 archive when creating, e.g. ks,tjs.
+    /// The mode is specified by --kirikiri-simple-crypt-mode.
+    pub xp3_simple_crypt_exts: Vec<String>,
+    #[cfg(feature = "kirikiri-arc")]
+    #[arg(long, global = true, value_delimiter = ',')]
+    /// File extensions of files to compress into mdf in Kirikiri XP3 archive when creating, e.g. ks,tjs.
+    pub xp3_mdf_compress_exts: Vec<String>,
+    #[cfg(feature = "kirikiri-arc")]
     #[arg(long, global = true, default_value = "cdc:32KiB:256KiB:8MiB", value_parser = crate::scripts::kirikiri::archive::xp3::parse_segmenter_config)]
     /// Configuration for Kirikiri XP3 segmenter when creating XP3 archive.
     /// none segmenter - none
@@ -590,6 +636,11 @@ pub struct Arg {
     #[arg(long, global = true)]
     /// Insert new language at the specified index in Kirikiri SCN script. If index is out of bounds, this flags will be ignored.
     pub kirikiri_language_insert: bool,
+    #[cfg(feature = "kirikiri-img")]
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(5..=6))]
+    /// TLG version used when creating or importing Kirikiri TLG images (5 or 6).
+    /// By default, new images are TLG5 and imported images keep the version of the original image.
+    pub kirikiri_tlg_version: Option<u32>,
     #[cfg(feature = "musica-arc")]
     #[arg(long, global = true, value_parser = get_musica_game_title_value_parser())]
     /// Musica game title for paz archive.
@@ -614,6 +665,12 @@ pub struct Arg {
     #[arg(long, global = true)]
     /// Use original text as translated text if translated text and llm text are all empty.
     pub m3t_use_original_text: bool,
+    #[arg(long, global = true)]
+    /// Language code of original text in XLIFF/TMX files. Default is `ja`.
+    pub source_lang: Option<String>,
+    #[arg(long, global = true)]
+    /// Language code of translated text in XLIFF/TMX files. Default is `zh-CN`.
+    pub target_lang: Option<String>,
     #[cfg(feature = "kirikiri-arc")]
     #[arg(long, global = true)]
     /// Disable adler32 checksum for Kirikiri XP3 archive when creating.
@@ -674,6 +731,15 @@ pub struct Arg {
     #[arg(long, global = true, action = ArgAction::SetTrue)]
     /// Whether to compress files in Qlie pack archive.
     pub qlie_pack_compress_files: bool,
+    #[cfg(feature = "qlie-arc")]
+    #[arg(long, global = true, value_enum, default_value_t = crate::scripts::qlie::archive::pack::QliePackVersion::V31)]
+    /// Qlie pack archive version used when packing.
+    pub qlie_pack_version: crate::scripts::qlie::archive::pack::QliePackVersion,
+    #[cfg(feature = "qlie-arc")]
+    #[arg(long, global = true)]
+    /// Path to the original Qlie pack archive.
+    /// Its key data is reused when packing version 2.0 or 3.0, otherwise a new key is generated.
+    pub qlie_pack_original: Option<String>,
     #[cfg(feature = "qlie-img")]
     #[arg(long, global = true, action = ArgAction::SetTrue)]
     /// Whether to use PNG file directly for Qlie DPNG images when importing.
@@ -723,6 +789,7 @@ pub struct Arg {
     #[cfg(feature = "kirikiri-arc")]
     #[arg(long, global = true, value_parser = get_xp3_game_title_value_parser())]
     /// Game title for Kirikiri XP3 archive. This is used to decrypt file in archives.
+    /// When creating archive, files will be encrypted with the same method if supported.
     pub xp3_game_title: Option<String>,
     #[cfg(feature = "kirikiri-arc")]
     #[arg(long, global = true)]
@@ -760,6 +827,23 @@ pub struct Arg {
     #[arg(long, global = true)]
     /// Path to the ysc.ybn file
     pub yuris_ysc_path: Option<String>,
+    #[cfg(feature = "yuris")]
+    #[arg(long, global = true, value_delimiter = ',', default_value = "SEL")]
+    /// Labels of Yu-Ris subroutines which show choices. Texts passed to these subroutines are extracted from YSTB script.
+    pub yuris_ystb_choice_labels: Vec<String>,
+    #[cfg(feature = "yuris-arc")]
+    #[arg(long, global = true, default_value_t = 500)]
+    /// Version of Yu-Ris YPF archive when creating.
+    pub ypf_version: u32,
+    #[cfg(feature = "yuris-arc")]
+    #[arg(long, global = true, default_value_t = 0xFF)]
+    /// Key used to obfuscate file names in Yu-Ris YPF archive when creating.
+    pub ypf_name_key: u8,
+    #[cfg(feature = "yuris-arc")]
+    #[arg(long, global = true, value_enum)]
+    /// Table used to obfuscate file name lengths in Yu-Ris YPF archive when creating.
+    /// Selected by --ypf-version if not specified.
+    pub ypf_length_table: Option<crate::scripts::yuris::archive::ypf::YpfLengthTable>,
     #[command(subcommand)]
     /// Command
     pub command: Command,
@@ -777,7 +861,6 @@ pub struct ImportArgs {
     #[arg(short = 'p', long, group = "patched_encodingg")]
     /// Patched script encoding
     pub patched_encoding: Option<TextEncoding>,
-    #[cfg(windows)]
     #[arg(short = 'P', long, group = "patched_encodingg")]
     /// Patched script code page
     pub patched_code_page: Option<u32>,
@@ -789,7 +872,6 @@ pub struct ImportArgs {
     )]
     /// Patched archive filename encoding
     pub patched_archive_encoding: Option<TextEncoding>,
-    #[cfg(windows)]
     #[arg(
         long,
         value_enum,
@@ -804,6 +886,20 @@ pub struct ImportArgs {
     #[arg(long)]
     /// Fixed length of one line in patched script (for fixed format)
     pub patched_fixed_length: Option<usize>,
+    #[arg(long)]
+    /// Maximum line width in pixels in patched script (for pixel format).
+    /// Defaults to fixed length multiplied by font size.
+    pub patched_pixel_width: Option<usize>,
+    #[arg(long)]
+    /// TTF/OTF font file used to measure glyph widths (for pixel format)
+    pub patched_font: Option<String>,
+    #[arg(long, default_value_t = 24)]
+    /// Font size in pixels (for pixel format)
+    pub patched_font_size: u32,
+    #[arg(long)]
+    /// JSON glyph width table, which maps characters to widths in pixels (for pixel format).
+    /// The key `default` sets the width of unknown characters. Widths in the table override the font.
+    pub patched_glyph_width_table: Option<String>,
     #[arg(long, action = ArgAction::SetTrue)]
     /// Keep original line breaks in patched script (for fixed format)
     pub patched_keep_original: bool,
@@ -834,6 +930,14 @@ pub struct ImportArgs {
     #[arg(long)]
     /// Output dependency file path. This file will contain a list of all files used during import.
     pub dep_file: Option<String>,
+    #[arg(long)]
+    /// Remap characters which can not be encoded in Shift-JIS to unused Shift-JIS code points (SJIS tunneling).
+    /// The mapping table is saved to this JSON file, and an existing file is reused so mappings stay the same across runs.
+    pub sjis_tunnel: Option<String>,
+    #[arg(long, requires = "sjis_tunnel")]
+    /// Output path of the binary SJIS tunnel mapping table (UTF-16LE characters in tunnel order).
+    /// Defaults to `sjis_ext.bin` in the same directory as the JSON mapping file.
+    pub sjis_tunnel_bin: Option<String>,
     #[arg(short = 'j', long, default_value_t = 1)]
     /// Workers count for import scripts in parallel.
     pub jobs: usize,
@@ -873,7 +977,7 @@ pub enum Command {
     },
     /// Create a new script file
     Create {
-        /// Input script
+        /// Input script, or a directory of images for multi-image types
         input: String,
         /// Output script file
         output: Option<String>,
@@ -906,12 +1010,422 @@ pub enum Command {
         /// Output script file
         output: Option<String>,
     },
+    /// Fill output scripts with translations from translation memory
+    ApplyTm {
+        /// Output script file or directory to fill (use --output-type to specify format)
+        input: String,
+        /// Output file or directory. Input files are overwritten if not specified.
+        output: Option<String>,
+        #[arg(long = "tm", required = true)]
+        /// Translated output script file or directory used as translation memory. Can be specified multiple times.
+        tm: Vec<String>,
+        #[arg(long, value_enum)]
+        /// Format of translation memory files. Defaults to the format of input files.
+        tm_type: Option<OutputScriptType>,
+        #[arg(long)]
+        /// Original exported file or directory of each --tm, matched by relative path.
+        /// Required if translation memory files do not contain source text (e.g. json/yaml).
+        tm_original: Vec<String>,
+        #[arg(long, default_value_t = 0.8)]
+        /// Minimum similarity (0.0-1.0) of fuzzy matches. Set to 1.0 to disable fuzzy matching.
+        tm_fuzzy_threshold: f64,
+    },
 }
 
 pub fn parse_args() -> Arg {
     Arg::parse()
 }
 
+/// Creates options of high-level operations from command line arguments.
+///
+/// Keys and tables referenced by the arguments are loaded into [crate::ops::Options::config] here.
+pub fn get_options(arg: &Arg) -> anyhow::Result<crate::ops::Options> {
+    #[allow(unused_imports)]
+    use anyhow::Context;
+    let mut opts = crate::ops::Options {
+        config: Default::default(),
+        script_type: arg.script_type,
+        encoding: arg.encoding,
+        code_page: arg.code_page,
+        archive_encoding: arg.archive_encoding,
+        archive_code_page: arg.archive_code_page,
+        output_type: arg.output_type,
+        output_encoding: arg.output_encoding,
+        output_code_page: arg.output_code_page,
+        output_no_extra_ext: arg.output_no_extra_ext,
+        #[cfg(feature = "image")]
+        image_type: arg.image_type,
+        #[cfg(feature = "image")]
+        image_output_flat: arg.image_output_flat,
+        #[cfg(feature = "image")]
+        image_workers: arg.image_workers,
+        force_script: arg.force_script,
+        no_multi_message: arg.no_multi_message,
+        llm_trans_mark: arg.llm_trans_mark.clone(),
+        m3t_no_quote: arg.m3t_no_quote,
+        m3t_use_original_text: arg.m3t_use_original_text,
+        source_lang: arg.source_lang.clone(),
+        target_lang: arg.target_lang.clone(),
+        recursive: arg.recursive,
+        backtrace: arg.backtrace,
+    };
+    opts.config = std::sync::Arc::new(ExtraConfig {
+        #[cfg(feature = "circus")]
+        circus_mes_type: arg.circus_mes_type.clone(),
+        #[cfg(feature = "escude-arc")]
+        escude_fake_compress: arg.escude_fake_compress,
+        #[cfg(feature = "escude")]
+        escude_enum_scr: arg.escude_enum_scr.clone(),
+        #[cfg(feature = "bgi")]
+        bgi_import_duplicate: arg.bgi_import_duplicate,
+        #[cfg(feature = "bgi")]
+        bgi_disable_append: arg.bgi_disable_append,
+        #[cfg(feature = "image")]
+        image_type: arg.image_type.clone(),
+        #[cfg(all(feature = "bgi-arc", feature = "bgi-img"))]
+        bgi_is_sysgrp_arc: arg.bgi_is_sysgrp_arc.clone(),
+        #[cfg(feature = "bgi-img")]
+        bgi_img_scramble: arg.bgi_img_scramble.clone(),
+        #[cfg(feature = "cat-system-arc")]
+        cat_system_int_encrypt_password: get_cat_system_int_encrypt_password(arg)
+            .context("Failed to get CatSystem2 int encrypt password")?,
+        #[cfg(feature = "cat-system-img")]
+        cat_system_image_canvas: arg.cat_system_image_canvas,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_language_index: arg.kirikiri_language_index.clone(),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_export_chat: arg.kirikiri_export_chat,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_chat_key: arg.kirikiri_chat_key.clone(),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_chat_json: load_kirikiri_chat_json(arg, crate::ops::get_output_encoding(&opts))
+            .context("Failed to load Kirikiri chat JSON")?,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_languages: arg
+            .kirikiri_languages
+            .clone()
+            .map(|s| std::sync::Arc::new(s)),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_remove_empty_lines: arg.kirikiri_remove_empty_lines,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_name_commands: std::sync::Arc::new(std::collections::HashSet::from_iter(
+            arg.kirikiri_name_commands.iter().cloned(),
+        )),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_message_commands: std::sync::Arc::new(std::collections::HashSet::from_iter(
+            arg.kirikiri_message_commands.iter().cloned(),
+        )),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_voice_commands: std::sync::Arc::new(std::collections::HashSet::from_iter(
+            arg.kirikiri_voice_commands.iter().cloned(),
+        )),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_simple_crypt_mode: arg.kirikiri_simple_crypt_mode,
+        #[cfg(feature = "bgi-arc")]
+        bgi_compress_file: arg.bgi_compress_file,
+        #[cfg(feature = "bgi-arc")]
+        bgi_compress_level: arg.bgi_compress_level,
+        #[cfg(feature = "emote-img")]
+        emote_pimg_overlay: arg.emote_pimg_overlay,
+        #[cfg(feature = "emote-img")]
+        emote_dpak_session: None,
+        #[cfg(feature = "artemis-arc")]
+        artemis_arc_disable_xor: arg.artemis_arc_disable_xor,
+        #[cfg(feature = "artemis")]
+        artemis_indent: arg.artemis_indent,
+        #[cfg(feature = "artemis")]
+        artemis_no_indent: arg.artemis_no_indent,
+        #[cfg(feature = "artemis")]
+        artemis_max_line_width: arg.artemis_max_line_width,
+        #[cfg(feature = "artemis")]
+        artemis_ast_lang: arg.artemis_ast_lang.clone(),
+        #[cfg(feature = "cat-system")]
+        cat_system_cstl_lang: arg.cat_system_cstl_lang.clone(),
+        #[cfg(feature = "flate2")]
+        zlib_compression_level: arg.zlib_compression_level,
+        #[cfg(feature = "image")]
+        png_compression_level: arg.png_compression_level,
+        #[cfg(feature = "circus-img")]
+        circus_crx_keep_original_bpp: arg.circus_crx_keep_original_bpp,
+        #[cfg(feature = "circus-img")]
+        circus_crx_zstd: arg.circus_crx_zstd,
+        #[cfg(feature = "zstd")]
+        zstd_compression_level: arg.zstd_compression_level,
+        #[cfg(feature = "circus-img")]
+        circus_crx_mode: arg.circus_crx_mode,
+        #[cfg(feature = "ex-hibit")]
+        ex_hibit_rld_xor_key: load_ex_hibit_rld_xor_key(arg)
+            .context("Failed to load RLD XOR key")?,
+        #[cfg(feature = "ex-hibit")]
+        ex_hibit_rld_def_xor_key: load_ex_hibit_rld_def_xor_key(arg)
+            .context("Failed to load RLD DEF XOR key")?,
+        #[cfg(feature = "ex-hibit")]
+        ex_hibit_rld_keys: crate::scripts::ex_hibit::rld::load_keys(arg.ex_hibit_rld_keys.as_ref())
+            .context("Failed to load RLD keys")?,
+        #[cfg(feature = "ex-hibit")]
+        ex_hibit_rld_def_keys: crate::scripts::ex_hibit::rld::load_keys(
+            arg.ex_hibit_rld_def_keys.as_ref(),
+        )
+        .context("Failed to load RLD DEF keys")?,
+        #[cfg(feature = "mozjpeg")]
+        jpeg_quality: arg.jpeg_quality,
+        #[cfg(feature = "webp")]
+        webp_lossless: arg.webp_lossless,
+        #[cfg(feature = "webp")]
+        webp_quality: arg.webp_quality,
+        #[cfg(feature = "circus-img")]
+        circus_crx_canvas: arg.circus_crx_canvas,
+        #[cfg(feature = "circus-img")]
+        circus_crx_version: arg.circus_crx_version,
+        #[cfg(feature = "circus-audio")]
+        circus_pcm_adpcm: arg.circus_pcm_adpcm,
+        custom_yaml: arg.custom_yaml.unwrap_or_else(|| {
+            arg.output_type
+                .map(|s| s == crate::types::OutputScriptType::Yaml)
+                .unwrap_or(false)
+        }),
+        #[cfg(feature = "entis-gls")]
+        entis_gls_srcxml_lang: arg.entis_gls_srcxml_lang.clone(),
+        #[cfg(feature = "will-plus")]
+        will_plus_ws2_no_disasm: arg.will_plus_ws2_no_disasm,
+        #[cfg(feature = "artemis-panmimisoft")]
+        artemis_panmimisoft_txt_blacklist_names: std::sync::Arc::new(
+            get_artemis_panmimisoft_txt_blacklist_names(arg)?,
+        ),
+        #[cfg(feature = "artemis-panmimisoft")]
+        artemis_panmimisoft_txt_lang: arg.artemis_panmimisoft_txt_lang.clone(),
+        #[cfg(feature = "lossless-audio")]
+        lossless_audio_fmt: arg.lossless_audio_fmt,
+        #[cfg(feature = "audio-flac")]
+        flac_compression_level: arg.flac_compression_level,
+        #[cfg(feature = "artemis")]
+        artemis_asb_format_lua: !arg.artemis_asb_no_format_lua,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_title: arg.kirikiri_title,
+        #[cfg(feature = "favorite")]
+        favorite_hcb_filter_ascii: !arg.favorite_hcb_no_filter_ascii,
+        #[cfg(feature = "bgi-img")]
+        bgi_img_workers: arg.bgi_img_workers,
+        #[cfg(feature = "bgi-img")]
+        bgi_cbg_version: arg.bgi_cbg_version,
+        #[cfg(feature = "bgi-img")]
+        bgi_cbg_quality: arg.bgi_cbg_quality,
+        #[cfg(feature = "image-jxl")]
+        jxl_lossless: !arg.jxl_lossy,
+        #[cfg(feature = "image-jxl")]
+        jxl_distance: arg.jxl_distance,
+        #[cfg(feature = "image-jxl")]
+        jxl_workers: arg.jxl_workers,
+        #[cfg(feature = "emote-img")]
+        psb_process_tlg: !arg.psb_no_process_tlg,
+        #[cfg(feature = "softpal-img")]
+        pgd_fake_compress: !arg.pgd_compress,
+        #[cfg(feature = "softpal")]
+        softpal_add_message_index: arg.softpal_add_message_index,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_chat_multilang: !arg.kirikiri_chat_no_multilang,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_simple_crypt: !arg.xp3_no_simple_crypt,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_mdf_decompress: !arg.xp3_no_mdf_decompress,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_simple_crypt_exts: std::sync::Arc::new(
+            arg.xp3_simple_crypt_exts
+                .iter()
+                .map(|s| s.to_ascii_lowercase())
+                .collect(),
+        ),
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_mdf_compress_exts: std::sync::Arc::new(
+            arg.xp3_mdf_compress_exts
+                .iter()
+                .map(|s| s.to_ascii_lowercase())
+                .collect(),
+        ),
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_segmenter: arg.xp3_segmenter.clone(),
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_compress_files: !arg.xp3_no_compress_files,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_compress_index: !arg.xp3_no_compress_index,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_compress_workers: arg.xp3_compress_workers,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_zstd: arg.xp3_zstd,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_zopfli: arg.xp3_zopfli,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_pack_workers: arg.xp3_pack_workers,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_language_insert: arg.kirikiri_language_insert,
+        #[cfg(feature = "kirikiri-img")]
+        kirikiri_tlg_version: arg.kirikiri_tlg_version,
+        #[cfg(feature = "musica-arc")]
+        musica_game_title: arg.musica_game_title.clone(),
+        #[cfg(feature = "musica-arc")]
+        musica_xor_key: arg.musica_xor_key,
+        #[cfg(feature = "musica-arc")]
+        musica_compress: arg.musica_compress,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_no_adler: arg.xp3_no_adler,
+        #[cfg(feature = "bgi")]
+        bgi_add_space: arg.bgi_add_space,
+        #[cfg(feature = "escude")]
+        escude_op: arg.escude_op,
+        #[cfg(feature = "zopfli")]
+        zopfli_iteration_count: arg.zopfli_iteration_count,
+        #[cfg(feature = "zopfli")]
+        zopfli_iterations_without_improvement: arg.zopfli_iterations_without_improvement,
+        #[cfg(feature = "zopfli")]
+        zopfli_maximum_block_splits: arg.zopfli_maximum_block_splits,
+        #[cfg(feature = "artemis-panmimisoft")]
+        artemis_panmimisoft_txt_multi_lang: arg.artemis_panmimisoft_txt_multi_lang,
+        #[cfg(feature = "entis-gls")]
+        entis_gls_csx_disasm: arg.entis_gls_csx_disasm,
+        #[cfg(feature = "entis-gls")]
+        entis_gls_csx_lf: arg.entis_gls_csx_lf.clone(),
+        #[cfg(feature = "entis-gls")]
+        entis_gls_csx_ver: arg.entis_gls_csx_ver,
+        #[cfg(feature = "entis-gls")]
+        entis_gls_csx_v2_ver: arg.entis_gls_csx_v2_ver,
+        #[cfg(feature = "entis-gls")]
+        entis_gls_csx_no_part_label: arg.entis_gls_csx_no_part_label,
+        #[cfg(feature = "qlie-img")]
+        qlie_abmp10_process_abmp10: !arg.qlie_abmp10_no_process_abmp10,
+        #[cfg(feature = "qlie-arc")]
+        qlie_pack_keyfile: arg.qlie_pack_keyfile.clone(),
+        #[cfg(feature = "qlie-arc")]
+        qlie_pack_compress_files: arg.qlie_pack_compress_files,
+        #[cfg(feature = "qlie-arc")]
+        qlie_pack_version: arg.qlie_pack_version,
+        #[cfg(feature = "qlie-arc")]
+        qlie_pack_original: arg.qlie_pack_original.clone(),
+        #[cfg(feature = "qlie-img")]
+        qlie_dpng_use_raw_png: arg.qlie_dpng_use_raw_png,
+        #[cfg(feature = "qlie-img")]
+        qlie_dpng_psd: arg.qlie_dpng_psd,
+        #[cfg(feature = "utils-psd")]
+        psd_compress: !arg.psd_no_compress,
+        #[cfg(feature = "emote-img")]
+        emote_pimg_psd: arg.emote_pimg_psd,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_ks_hitret: arg.kirikiri_ks_hitret,
+        #[cfg(feature = "kirikiri")]
+        kirikiri_ks_lf: arg.kirikiri_ks_lf.clone(),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_message_tags: std::sync::Arc::new(std::collections::HashSet::from_iter(
+            arg.kirikiri_message_tags.iter().cloned(),
+        )),
+        #[cfg(feature = "kirikiri")]
+        kirikiri_ks_bom: arg.kirikiri_ks_bom,
+        #[cfg(feature = "emote-img")]
+        bc7: arg.bc7,
+        #[cfg(feature = "artemis")]
+        artemis_asb_end_tags: std::sync::Arc::new(std::collections::HashSet::from_iter(
+            arg.artemis_asb_end_tags.iter().cloned(),
+        )),
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_game_title: arg.xp3_game_title.clone(),
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_debug_archive: arg.xp3_debug_archive,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_force_extract: arg.xp3_force_extract,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_force_decrypt: arg.xp3_force_decrypt,
+        #[cfg(feature = "emote-img")]
+        emote_pimg_psd_no_diff: arg.emote_pimg_psd_no_diff,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_file_list_path: arg.xp3_file_list_path.clone(),
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_cxdec_file_hash: arg.xp3_cxdec_file_hash,
+        #[cfg(feature = "kirikiri-arc")]
+        xp3_cxdec_path_hash: arg.xp3_cxdec_path_hash,
+        #[cfg(feature = "yuris")]
+        yuris_ysc_path: arg.yuris_ysc_path.clone(),
+        #[cfg(feature = "yuris")]
+        yuris_ystb_choice_labels: std::sync::Arc::new(std::collections::HashSet::from_iter(
+            arg.yuris_ystb_choice_labels.iter().cloned(),
+        )),
+        #[cfg(feature = "yuris-arc")]
+        ypf_version: arg.ypf_version,
+        #[cfg(feature = "yuris-arc")]
+        ypf_name_key: arg.ypf_name_key,
+        #[cfg(feature = "yuris-arc")]
+        ypf_length_table: arg.ypf_length_table,
+    });
+    Ok(opts)
+}
+
+/// Creates import options from command line arguments.
+///
+/// Name table and replacement table are loaded here.
+#[cfg_attr(not(feature = "jieba"), allow(unused_variables))]
+pub fn get_import_options(
+    arg: &Arg,
+    imp: &ImportArgs,
+) -> anyhow::Result<crate::ops::ImportOptions> {
+    let patched_format = imp.patched_format.map(|fmt| match fmt {
+        FormatType::Fixed => FormatOptions::Fixed {
+            length: imp.patched_fixed_length.unwrap_or(32),
+            keep_original: imp.patched_keep_original,
+            break_words: imp.patched_break_words,
+            insert_fullwidth_space_at_line_start: imp.patched_insert_fullwidth_space_at_line_start,
+            break_with_sentence: imp.patched_break_with_sentence,
+            #[cfg(feature = "jieba")]
+            break_chinese_words: !imp.patched_no_break_chinese_words,
+            #[cfg(feature = "jieba")]
+            jieba_dict: arg.jieba_dict.clone(),
+            no_remove_space_at_line_start: imp.patched_no_remove_space_at_line_start,
+        },
+        FormatType::Pixel => FormatOptions::Pixel {
+            width: imp
+                .patched_pixel_width
+                .unwrap_or(imp.patched_fixed_length.unwrap_or(32) * imp.patched_font_size as usize),
+            font: imp.patched_font.clone(),
+            font_size: imp.patched_font_size,
+            width_table: imp.patched_glyph_width_table.clone(),
+            keep_original: imp.patched_keep_original,
+            break_words: imp.patched_break_words,
+            insert_fullwidth_space_at_line_start: imp.patched_insert_fullwidth_space_at_line_start,
+            break_with_sentence: imp.patched_break_with_sentence,
+            #[cfg(feature = "jieba")]
+            break_chinese_words: !imp.patched_no_break_chinese_words,
+            #[cfg(feature = "jieba")]
+            jieba_dict: arg.jieba_dict.clone(),
+            no_remove_space_at_line_start: imp.patched_no_remove_space_at_line_start,
+        },
+        FormatType::None => FormatOptions::None,
+    });
+    let name_table = match &imp.name_csv {
+        Some(name_csv) => Some(crate::utils::name_replacement::read_csv(name_csv)?),
+        None => None,
+    };
+    let replacement = match &imp.replacement_json {
+        Some(replacement_json) => {
+            let b = crate::utils::files::read_file(replacement_json)?;
+            let s = String::from_utf8(b)?;
+            Some(serde_json::from_str::<ReplacementTable>(&s)?)
+        }
+        None => None,
+    };
+    Ok(crate::ops::ImportOptions {
+        output: imp.output.clone(),
+        patched: imp.patched.clone(),
+        patched_encoding: imp.patched_encoding,
+        patched_code_page: imp.patched_code_page,
+        patched_archive_encoding: imp.patched_archive_encoding,
+        patched_archive_code_page: imp.patched_archive_code_page,
+        patched_format,
+        name_table,
+        replacement,
+        warn_when_output_file_not_found: imp.warn_when_output_file_not_found,
+        jobs: imp.jobs,
+        dep_file: imp.dep_file.clone(),
+        sjis_tunnel: imp.sjis_tunnel.clone(),
+        sjis_tunnel_bin: imp.sjis_tunnel_bin.clone(),
+    })
+}
+
 #[cfg(feature = "ex-hibit")]
 pub fn load_ex_hibit_rld_xor_key(arg: &Arg) -> anyhow::Result<Option<u32>> {
     if let Some(key) = &arg.ex_hibit_rld_xor_key {
@@ -988,6 +1502,7 @@ pub fn get_artemis_panmimisoft_txt_blacklist_names(
 #[cfg(feature = "kirikiri")]
 pub fn load_kirikiri_chat_json(
     arg: &Arg,
+    output_encoding: Encoding,
 ) -> anyhow::Result<
     Option<
         std::sync::Arc<
@@ -1028,11 +1543,7 @@ pub fn load_kirikiri_chat_json(
             > = std::collections::HashMap::new();
             for file in files {
                 let f = crate::utils::files::read_file(&file)?;
-                let data = crate::utils::encoding::decode_to_string(
-                    crate::get_output_encoding(arg),
-                    &f,
-                    true,
-                )?;
+                let data = crate::utils::encoding::decode_to_string(output_encoding, &f, true)?;
                 let m3t = if outt.is_m3t() {
                     crate::output_scripts::m3t::M3tParser::new(
                         &data,
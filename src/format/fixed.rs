@@ -1,3 +1,5 @@
+use super::glyph_width::GlyphWidths;
+use super::markup::{InlineMarkup, Markup};
 use crate::types::*;
 use anyhow::Result;
 #[cfg(feature = "jieba")]
@@ -85,20 +87,32 @@ fn take_trailing_start_quotes(buffer: &mut String) -> String {
     trailing
 }
 
+/// Returns the end positions (in units) of words cut by jieba.
+///
+/// Word boundaries inside a unit (such as inline markup) are ignored.
 #[cfg(feature = "jieba")]
-fn check_chinese_word_is_break(segs: &[&str], pos: usize, jieba: &Jieba) -> bool {
+fn cut_chinese_words(segs: &[&str], jieba: &Jieba) -> Vec<usize> {
     let s = segs.join("");
-    let mut breaked = jieba
-        .cut(&s, false)
-        .iter()
-        .map(|s| s.graphemes(true).count())
-        .collect::<Vec<_>>();
+    let mut unit_ends = Vec::with_capacity(segs.len());
+    let mut sum = 0;
+    for seg in segs {
+        sum += seg.len();
+        unit_ends.push(sum);
+    }
+    let mut breaked = Vec::new();
     let mut sum = 0;
-    for i in breaked.iter_mut() {
-        sum += *i;
-        *i = sum;
+    for word in jieba.cut(&s, false) {
+        sum += word.len();
+        if let Ok(pos) = unit_ends.binary_search(&sum) {
+            breaked.push(pos + 1);
+        }
     }
-    breaked.binary_search(&pos).is_err()
+    breaked
+}
+
+#[cfg(feature = "jieba")]
+fn check_chinese_word_is_break(segs: &[&str], pos: usize, jieba: &Jieba) -> bool {
+    cut_chinese_words(segs, jieba).binary_search(&pos).is_err()
 }
 
 #[cfg(not(feature = "jieba"))]
@@ -122,6 +136,10 @@ pub struct FixedFormatter {
     jieba: Option<()>,
     /// Do not remove space at the start of the line
     no_remove_space_at_line_start: bool,
+    /// Glyph widths in pixels. If set, `length` is the maximum line width in pixels.
+    widths: Option<GlyphWidths>,
+    /// Inline markup tokenizer of the script type.
+    markup: Option<&'static dyn InlineMarkup>,
     #[allow(unused)]
     typ: Option<ScriptType>,
 }
@@ -136,6 +154,8 @@ impl FixedFormatter {
         #[cfg(feature = "jieba")] break_chinese_words: bool,
         #[cfg(feature = "jieba")] jieba_dict: Option<String>,
         no_remove_space_at_line_start: bool,
+        widths: Option<GlyphWidths>,
+        markup: Option<&'static dyn InlineMarkup>,
         typ: Option<ScriptType>,
     ) -> Result<Self> {
         #[cfg(feature = "jieba")]
@@ -161,6 +181,8 @@ impl FixedFormatter {
             #[cfg(not(feature = "jieba"))]
             jieba: None,
             no_remove_space_at_line_start,
+            widths,
+            markup,
             typ,
         })
     }
@@ -176,6 +198,8 @@ impl FixedFormatter {
             jieba: None,
             typ: None,
             no_remove_space_at_line_start: false,
+            widths: None,
+            markup: None,
         }
     }
 
@@ -228,6 +252,18 @@ impl FixedFormatter {
         self
     }
 
+    #[cfg(test)]
+    fn widths(mut self, widths: GlyphWidths) -> Self {
+        self.widths = Some(widths);
+        self
+    }
+
+    #[cfg(test)]
+    fn markup(mut self, markup: &'static dyn InlineMarkup) -> Self {
+        self.markup = Some(markup);
+        self
+    }
+
     #[cfg(test)]
     #[allow(dead_code)]
     fn typ(mut self, typ: Option<ScriptType>) -> Self {
@@ -255,9 +291,126 @@ impl FixedFormatter {
         false
     }
 
+    /// Returns the markup kind if `unit` is an inline markup.
+    fn get_markup(&self, unit: &str) -> Option<Markup> {
+        match self.markup?.parse(unit) {
+            Some((markup, len)) if len == unit.len() => Some(markup),
+            _ => None,
+        }
+    }
+
+    /// Returns the enclosed text if `unit` is a paired markup, such as `<ruby text="...">text</ruby>`,
+    /// or the grapheme following a markup which applies to it.
+    fn paired_markup_text<'a>(&self, unit: &'a str) -> Option<&'a str> {
+        let markup = self.markup?;
+        let (_, len) = markup.parse(unit)?;
+        if markup.binds_next_grapheme(&unit[..len]) {
+            return Some(&unit[len..]);
+        }
+        let close = markup.closing_tag(&unit[..len])?;
+        unit[len..].strip_suffix(close)
+    }
+
+    /// Splits text into graphemes, keeping each inline markup as a single unit.
+    ///
+    /// A paired markup is kept as a single unit together with the enclosed text,
+    /// and a markup which applies to the next grapheme together with that grapheme.
+    fn units<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let Some(markup) = self.markup else {
+            return text.graphemes(true).collect();
+        };
+        let mut units = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.graphemes(true).next() {
+            let len = match markup.parse(rest) {
+                Some((Markup::Tag, len)) if len > 0 && markup.binds_next_grapheme(&rest[..len]) => {
+                    match rest[len..].graphemes(true).next() {
+                        Some(next) if next != "\n" && markup.parse(&rest[len..]).is_none() => {
+                            len + next.len()
+                        }
+                        _ => len,
+                    }
+                }
+                Some((Markup::Tag, len)) if len > 0 => markup
+                    .closing_tag(&rest[..len])
+                    .and_then(|close| {
+                        let end = rest[len..].find(close)?;
+                        // Paired markup across lines is not kept together.
+                        if rest[len..len + end].contains('\n') {
+                            return None;
+                        }
+                        Some(len + end + close.len())
+                    })
+                    .unwrap_or(len),
+                Some((_, len)) if len > 0 => len,
+                _ => c.len(),
+            };
+            units.push(&rest[..len]);
+            rest = &rest[len..];
+        }
+        units
+    }
+
+    fn grapheme_width(&self, grapheme: &str) -> usize {
+        match self.get_markup(grapheme) {
+            Some(Markup::Tag | Markup::LineBreak) => return 0,
+            Some(Markup::Escape(c)) => return self.char_width(c),
+            None => {}
+        }
+        if let Some(text) = self.paired_markup_text(grapheme) {
+            return self.text_width(text);
+        }
+        match &self.widths {
+            Some(widths) => widths.grapheme_width(grapheme),
+            None => 1,
+        }
+    }
+
+    fn char_width(&self, c: char) -> usize {
+        match &self.widths {
+            Some(widths) => widths.char_width(c),
+            None => 1,
+        }
+    }
+
+    fn text_width(&self, text: &str) -> usize {
+        if self.markup.is_some() {
+            return self
+                .units(text)
+                .into_iter()
+                .map(|unit| self.grapheme_width(unit))
+                .sum();
+        }
+        match &self.widths {
+            Some(widths) => widths.text_width(text),
+            None => text.graphemes(true).count(),
+        }
+    }
+
+    /// Returns true if adding `grapheme` to the current line exceeds the limit.
+    fn is_overflow(&self, current_length: usize, grapheme: &str) -> bool {
+        // Inline tags never start a new line.
+        if matches!(
+            self.get_markup(grapheme),
+            Some(Markup::Tag | Markup::LineBreak)
+        ) {
+            return false;
+        }
+        let width = self.grapheme_width(grapheme);
+        match &self.widths {
+            // Always keep at least one grapheme in a line.
+            Some(_) => current_length > 0 && current_length + width > self.length,
+            // Paired markup may contain more than one grapheme.
+            None => {
+                current_length >= self.length
+                    || (current_length > 0 && current_length + width > self.length)
+            }
+        }
+    }
+
     pub fn format(&self, message: &str) -> String {
         let mut result = String::new();
-        let vec: Vec<_> = UnicodeSegmentation::graphemes(message, true).collect();
+        let vec = self.units(message);
         let mut current_length = 0;
         let mut is_command = false;
         let mut pre_is_lf = false;
@@ -273,11 +426,11 @@ impl FixedFormatter {
         while i < vec.len() {
             let grapheme = vec[i];
 
-            if grapheme == "\n" {
+            if grapheme == "\n" || self.get_markup(grapheme) == Some(Markup::LineBreak) {
                 if self.keep_original
                     || (self.is_circus() && last_command.as_ref().is_some_and(|cmd| cmd == "@n"))
                 {
-                    result.push('\n');
+                    result.push_str(grapheme);
                     current_length = 0;
                     if first_line {
                         if self.insert_fullwidth_space_at_line_start {
@@ -288,7 +441,7 @@ impl FixedFormatter {
                     }
                     if need_insert_fullwidth_space {
                         result.push('　');
-                        current_length += 1;
+                        current_length += self.grapheme_width("　");
                     }
                     main_content.clear();
                     first_line = false;
@@ -299,7 +452,7 @@ impl FixedFormatter {
             }
 
             // Check if we need to break and handle word breaking
-            if current_length >= self.length {
+            if self.is_overflow(current_length, grapheme) {
                 if self.break_with_sentence
                     && !is_command
                     && !is_ruby_rt
@@ -309,7 +462,7 @@ impl FixedFormatter {
                         || check_is_end_quote_or_symbol(&vec, i))
                 {
                     let mut break_pos = None;
-                    let segs = result.graphemes(true).collect::<Vec<_>>();
+                    let segs = self.units(&result);
                     let is_end_quote = check_is_end_quote(&vec, i);
                     let mut end = segs.len();
                     for (j, ch) in segs.iter().enumerate().rev() {
@@ -353,10 +506,10 @@ impl FixedFormatter {
                         }
                         if need_insert_fullwidth_space {
                             result.push('　');
-                            current_length += 1;
+                            current_length += self.grapheme_width("　");
                         }
                         result.push_str(&remaining);
-                        current_length += remaining.graphemes(true).count();
+                        current_length += self.text_width(&remaining);
                         main_content.clear();
                         pre_is_lf = true;
                     } else {
@@ -377,12 +530,12 @@ impl FixedFormatter {
                         }
                         if need_insert_fullwidth_space {
                             result.push('　');
-                            current_length += 1;
+                            current_length += self.grapheme_width("　");
                         }
                         main_content.clear();
                         if !trailing.is_empty() {
                             result.push_str(&trailing);
-                            current_length += trailing.graphemes(true).count();
+                            current_length += self.text_width(&trailing);
                             main_content.push_str(&trailing);
                         }
                         pre_is_lf = true;
@@ -398,18 +551,28 @@ impl FixedFormatter {
                     let mut j = result.len();
 
                     // Find the last space or non-ASCII character position
-                    for ch in result.chars().rev() {
-                        if ch == ' ' || ch == '　' || (!ch.is_ascii() && !is_non_gbk_char(ch)) {
-                            break_pos = Some(j);
-                            break;
+                    'find: for unit in self.units(&result).into_iter().rev() {
+                        // Never break inside inline markup
+                        if self.get_markup(unit).is_some()
+                            || self.paired_markup_text(unit).is_some()
+                        {
+                            j -= unit.len();
+                            continue;
                         }
-                        if is_non_gbk_char(ch) {
-                            temp_length -= 1;
-                            if temp_length == 0 {
-                                break;
+                        for ch in unit.chars().rev() {
+                            if ch == ' ' || ch == '　' || (!ch.is_ascii() && !is_non_gbk_char(ch))
+                            {
+                                break_pos = Some(j);
+                                break 'find;
+                            }
+                            if is_non_gbk_char(ch) {
+                                temp_length = temp_length.saturating_sub(self.char_width(ch));
+                                if temp_length == 0 {
+                                    break 'find;
+                                }
                             }
+                            j -= ch.len_utf8();
                         }
-                        j -= ch.len_utf8();
                     }
 
                     // If we found a good break point, move content after it to next line
@@ -435,10 +598,10 @@ impl FixedFormatter {
                         }
                         if need_insert_fullwidth_space {
                             result.push('　');
-                            current_length += 1;
+                            current_length += self.grapheme_width("　");
                         }
                         result.push_str(&remaining);
-                        current_length += remaining.chars().count();
+                        current_length += self.text_width(&remaining);
                         main_content.clear();
                         pre_is_lf = true;
                     } else {
@@ -459,12 +622,12 @@ impl FixedFormatter {
                         }
                         if need_insert_fullwidth_space {
                             result.push('　');
-                            current_length += 1;
+                            current_length += self.grapheme_width("　");
                         }
                         main_content.clear();
                         if !trailing.is_empty() {
                             result.push_str(&trailing);
-                            current_length += trailing.graphemes(true).count();
+                            current_length += self.text_width(&trailing);
                             main_content.push_str(&trailing);
                         }
                         pre_is_lf = true;
@@ -479,17 +642,7 @@ impl FixedFormatter {
                     #[cfg(feature = "jieba")]
                     {
                         let jieba = self.jieba.as_ref().unwrap();
-                        let s = vec.join("");
-                        let mut breaked = jieba
-                            .cut(&s, false)
-                            .iter()
-                            .map(|s| s.graphemes(true).count())
-                            .collect::<Vec<_>>();
-                        let mut sum = 0;
-                        for i in breaked.iter_mut() {
-                            sum += *i;
-                            *i = sum;
-                        }
+                        let breaked = cut_chinese_words(&vec, jieba);
                         let break_pos = match breaked.binary_search(&i) {
                             Ok(pos) => Some(pos),
                             Err(pos) => {
@@ -502,7 +655,7 @@ impl FixedFormatter {
                         };
                         if let Some(break_pos) = break_pos {
                             let pos = breaked[break_pos];
-                            let segs = result.graphemes(true).collect::<Vec<_>>();
+                            let segs = self.units(&result);
                             let remain_count = i - pos;
                             let pos = segs.len() - remain_count;
                             let mut head = segs[..pos].concat();
@@ -527,10 +680,10 @@ impl FixedFormatter {
                             }
                             if need_insert_fullwidth_space {
                                 result.push('　');
-                                current_length += 1;
+                                current_length += self.grapheme_width("　");
                             }
                             result.push_str(&remaining);
-                            current_length += remaining.graphemes(true).count();
+                            current_length += self.text_width(&remaining);
                             main_content.clear();
                             pre_is_lf = true;
                         } else {
@@ -551,12 +704,12 @@ impl FixedFormatter {
                             }
                             if need_insert_fullwidth_space {
                                 result.push('　');
-                                current_length += 1;
+                                current_length += self.grapheme_width("　");
                             }
                             main_content.clear();
                             if !trailing.is_empty() {
                                 result.push_str(&trailing);
-                                current_length += trailing.graphemes(true).count();
+                                current_length += self.text_width(&trailing);
                                 main_content.push_str(&trailing);
                             }
                             pre_is_lf = true;
@@ -580,12 +733,12 @@ impl FixedFormatter {
                     }
                     if need_insert_fullwidth_space {
                         result.push('　');
-                        current_length += 1;
+                        current_length += self.grapheme_width("　");
                     }
                     main_content.clear();
                     if !trailing.is_empty() {
                         result.push_str(&trailing);
-                        current_length += trailing.graphemes(true).count();
+                        current_length += self.text_width(&trailing);
                         main_content.push_str(&trailing);
                     }
                     pre_is_lf = true;
@@ -678,7 +831,7 @@ impl FixedFormatter {
             }
 
             if !is_command && !is_ruby_rt {
-                current_length += 1;
+                current_length += self.grapheme_width(grapheme);
                 main_content.push_str(grapheme);
             }
 
@@ -896,3 +1049,120 @@ fn test_format() {
         );
     }
 }
+
+#[test]
+fn test_format_pixel_width() {
+    let table = [("i", 4), ("W", 16), ("a", 5), ("b", 5)]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let formatter = FixedFormatter::builder(40).widths(GlyphWidths::from_table(table, 10));
+    assert_eq!(formatter.format("iiiiWWW"), "iiiiW\nWW");
+    assert_eq!(formatter.format("ab中文测试一下"), "ab中文测\n试一下");
+    let table = [("W", 50)]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let formatter = FixedFormatter::builder(40).widths(GlyphWidths::from_table(table, 10));
+    assert_eq!(formatter.format("WW"), "W\nW");
+}
+
+#[test]
+fn test_format_inline_markup() {
+    #[derive(Debug)]
+    struct TestMarkup;
+    impl InlineMarkup for TestMarkup {
+        fn parse(&self, text: &str) -> Option<(Markup, usize)> {
+            let len = super::markup::xml_tag_len(text)?;
+            if &text[..len] == "<r>" {
+                Some((Markup::LineBreak, len))
+            } else {
+                Some((Markup::Tag, len))
+            }
+        }
+    }
+    let formatter = FixedFormatter::builder(5).markup(&TestMarkup);
+    assert_eq!(
+        formatter.format("<ruby text=\"かんじ\">漢字</ruby>テストです"),
+        "<ruby text=\"かんじ\">漢字</ruby>テスト\nです"
+    );
+    assert_eq!(formatter.format("abc<r>def"), "abcde\nf");
+    let formatter = FixedFormatter::builder(10)
+        .markup(&TestMarkup)
+        .keep_original(true);
+    assert_eq!(formatter.format("abc<r>def"), "abc<r>def");
+    let formatter = FixedFormatter::builder(8)
+        .markup(&TestMarkup)
+        .break_words(false);
+    assert_eq!(
+        formatter.format("hello <b class=\"a b\">world</b>"),
+        "hello \n<b class=\"a b\">world</b>"
+    );
+}
+
+#[test]
+fn test_format_engine_markup() {
+    #[allow(unused_imports)]
+    use crate::scripts::base::ScriptBuilder;
+    #[cfg(feature = "kirikiri")]
+    {
+        let markup = crate::scripts::kirikiri::ks::KsBuilder::new()
+            .inline_markup()
+            .unwrap();
+        let formatter = FixedFormatter::builder(4).markup(markup);
+        // KAG ruby applies to the next character only, and has no closing tag.
+        use crate::scripts::base::Script;
+        let script = crate::scripts::kirikiri::ks::KsScript::new(
+            "あいう[ruby text=かん]漢[ruby text=じ]字\n"
+                .as_bytes()
+                .to_vec(),
+            crate::types::Encoding::Utf8,
+            &crate::types::ExtraConfig::default(),
+        )
+        .unwrap();
+        let message = script.extract_messages().unwrap().remove(0).message;
+        assert_eq!(message, "あいう<ruby text=\"かん\">漢<ruby text=\"じ\">字");
+        assert_eq!(
+            formatter.format(&message),
+            "あいう<ruby text=\"かん\">漢\n<ruby text=\"じ\">字"
+        );
+        assert_eq!(formatter.format("あいう<r>えお"), "あいうえ\nお");
+        let formatter = FixedFormatter::builder(3).markup(markup);
+        assert_eq!(
+            formatter.format(&message),
+            "あいう\n<ruby text=\"かん\">漢<ruby text=\"じ\">字"
+        );
+    }
+    #[cfg(feature = "bgi")]
+    {
+        let markup = crate::scripts::bgi::script::BGIScriptBuilder::new()
+            .inline_markup()
+            .unwrap();
+        let formatter = FixedFormatter::builder(4).markup(markup);
+        assert_eq!(
+            formatter.format("あいう<rかんじ>漢字</r>え"),
+            "あいう\n<rかんじ>漢字</r>え"
+        );
+    }
+    #[cfg(feature = "softpal")]
+    {
+        let markup = crate::scripts::softpal::scr::SoftpalScriptBuilder::new()
+            .inline_markup()
+            .unwrap();
+        let formatter = FixedFormatter::builder(4).markup(markup);
+        assert_eq!(formatter.format("あいう<font>えお"), "あいう<font>え\nお");
+        assert_eq!(formatter.format("ab<BR>cdefg"), "abcd\nefg");
+    }
+    #[cfg(feature = "artemis")]
+    {
+        let markup = crate::scripts::artemis::asb::ArtemisAsbBuilder::new()
+            .inline_markup()
+            .unwrap();
+        let formatter = FixedFormatter::builder(4).markup(markup);
+        assert_eq!(
+            formatter.format("あいう<ruby text=\"かんじ\">漢字</ruby>&lt;えお"),
+            "あいう\n<ruby text=\"かんじ\">漢字</ruby>&lt;え\nお"
+        );
+        assert_eq!(formatter.format("あ<rt2>いうえお"), "あいうえ\nお");
+    }
+}
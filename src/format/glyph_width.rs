@@ -0,0 +1,325 @@
+//! Glyph width source used by pixel-width line wrapping.
+use crate::ext::io::*;
+use crate::ext::mutex::*;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Horizontal metrics read from a TrueType/OpenType font.
+#[derive(Debug)]
+struct FontMetrics {
+    units_per_em: u16,
+    /// Advance width of each glyph in font units
+    advances: Vec<u16>,
+    /// Map of code point to glyph id
+    cmap: HashMap<u32, u16>,
+}
+
+fn find_table(font: &MemReaderRef, base: u64, tag: &[u8; 4]) -> Result<Option<u64>> {
+    let num_tables = font.cpeek_u16_be_at(base + 4)? as u64;
+    for i in 0..num_tables {
+        let record = base + 12 + i * 16;
+        let mut name = [0u8; 4];
+        font.cpeek_exact_at(record, &mut name)?;
+        if &name == tag {
+            return Ok(Some(font.cpeek_u32_be_at(record + 8)? as u64));
+        }
+    }
+    Ok(None)
+}
+
+fn read_cmap_format4(font: &MemReaderRef, offset: u64, cmap: &mut HashMap<u32, u16>) -> Result<()> {
+    let seg_count = (font.cpeek_u16_be_at(offset + 6)? / 2) as u64;
+    let end_codes = offset + 14;
+    let start_codes = end_codes + seg_count * 2 + 2;
+    let id_deltas = start_codes + seg_count * 2;
+    let id_range_offsets = id_deltas + seg_count * 2;
+    for seg in 0..seg_count {
+        let end = font.cpeek_u16_be_at(end_codes + seg * 2)?;
+        let start = font.cpeek_u16_be_at(start_codes + seg * 2)?;
+        let delta = font.cpeek_u16_be_at(id_deltas + seg * 2)?;
+        let range_offset_pos = id_range_offsets + seg * 2;
+        let range_offset = font.cpeek_u16_be_at(range_offset_pos)? as u64;
+        if start > end || start == 0xFFFF {
+            continue;
+        }
+        for code in start..=end {
+            let glyph = if range_offset == 0 {
+                code.wrapping_add(delta)
+            } else {
+                let pos = range_offset_pos + range_offset + (code - start) as u64 * 2;
+                let glyph = font.cpeek_u16_be_at(pos)?;
+                if glyph == 0 {
+                    0
+                } else {
+                    glyph.wrapping_add(delta)
+                }
+            };
+            if glyph != 0 {
+                cmap.insert(code as u32, glyph);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_cmap_format12(
+    font: &MemReaderRef,
+    offset: u64,
+    cmap: &mut HashMap<u32, u16>,
+) -> Result<()> {
+    let groups = font.cpeek_u32_be_at(offset + 12)? as u64;
+    for i in 0..groups {
+        let group = offset + 16 + i * 12;
+        let start = font.cpeek_u32_be_at(group)?;
+        let end = font.cpeek_u32_be_at(group + 4)?.min(0x10FFFF);
+        let start_glyph = font.cpeek_u32_be_at(group + 8)?;
+        for code in start..=end {
+            let glyph = start_glyph
+                .checked_add(code - start)
+                .and_then(|g| u16::try_from(g).ok())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Invalid glyph id in cmap group {} for U+{:04X}", i, code)
+                })?;
+            cmap.insert(code, glyph);
+        }
+    }
+    Ok(())
+}
+
+impl FontMetrics {
+    fn new(data: &[u8]) -> Result<Self> {
+        let font = MemReaderRef::new(data);
+        let mut base = 0;
+        let mut magic = [0u8; 4];
+        font.cpeek_exact_at(0, &mut magic)?;
+        if &magic == b"ttcf" {
+            // Use the first font in a font collection
+            base = font.cpeek_u32_be_at(12)? as u64;
+            font.cpeek_exact_at(base, &mut magic)?;
+        }
+        if magic != [0, 1, 0, 0] && &magic != b"OTTO" && &magic != b"true" {
+            anyhow::bail!("Unsupported font format");
+        }
+        let table = |tag: &[u8; 4]| -> Result<u64> {
+            find_table(&font, base, tag)?.ok_or_else(|| {
+                anyhow::anyhow!("Font table {} not found", String::from_utf8_lossy(tag))
+            })
+        };
+        let units_per_em = font.cpeek_u16_be_at(table(b"head")? + 18)?;
+        if units_per_em == 0 {
+            anyhow::bail!("Invalid unitsPerEm in font");
+        }
+        let num_h_metrics = font.cpeek_u16_be_at(table(b"hhea")? + 34)? as u64;
+        let num_glyphs = font.cpeek_u16_be_at(table(b"maxp")? + 4)? as u64;
+        let hmtx = table(b"hmtx")?;
+        let mut advances = Vec::with_capacity(num_glyphs as usize);
+        for i in 0..num_h_metrics.min(num_glyphs) {
+            advances.push(font.cpeek_u16_be_at(hmtx + i * 4)?);
+        }
+        let last = advances.last().copied().unwrap_or(0);
+        advances.resize(num_glyphs as usize, last);
+        let cmap_offset = table(b"cmap")?;
+        let num_subtables = font.cpeek_u16_be_at(cmap_offset + 2)? as u64;
+        let mut best: Option<(u8, u64, u16)> = None;
+        for i in 0..num_subtables {
+            let record = cmap_offset + 4 + i * 8;
+            let platform = font.cpeek_u16_be_at(record)?;
+            let encoding = font.cpeek_u16_be_at(record + 2)?;
+            let offset = cmap_offset + font.cpeek_u32_be_at(record + 4)? as u64;
+            let format = font.cpeek_u16_be_at(offset)?;
+            let priority = match (platform, encoding, format) {
+                (3, 10, 12) | (0, 4, 12) | (0, 6, 12) => 0,
+                (3, 1, 4) | (0, 3, 4) => 1,
+                (0, _, 4) => 2,
+                _ => continue,
+            };
+            if best.is_none_or(|(p, _, _)| priority < p) {
+                best = Some((priority, offset, format));
+            }
+        }
+        let (_, offset, format) =
+            best.ok_or_else(|| anyhow::anyhow!("No supported Unicode cmap in font"))?;
+        let mut cmap = HashMap::new();
+        if format == 12 {
+            read_cmap_format12(&font, offset, &mut cmap)?;
+        } else {
+            read_cmap_format4(&font, offset, &mut cmap)?;
+        }
+        Ok(Self {
+            units_per_em,
+            advances,
+            cmap,
+        })
+    }
+
+    /// Returns the metrics of a font file. Parsed fonts are cached by path.
+    fn load(path: &str) -> Result<Arc<Self>> {
+        lazy_static::lazy_static! {
+            static ref FONTS: Mutex<HashMap<String, Arc<FontMetrics>>> = Mutex::new(HashMap::new());
+        }
+        if let Some(font) = FONTS.lock_blocking().get(path) {
+            return Ok(font.clone());
+        }
+        let font = Arc::new(Self::new(&crate::utils::files::read_file(path)?)?);
+        FONTS.lock_blocking().insert(path.to_string(), font.clone());
+        Ok(font)
+    }
+
+    /// Returns the advance width of a character in font units.
+    fn advance(&self, c: char) -> Option<u16> {
+        let glyph = *self.cmap.get(&(c as u32))?;
+        self.advances.get(glyph as usize).copied()
+    }
+}
+
+/// Glyph widths in pixels loaded from a font file and/or a glyph width table.
+#[derive(Debug)]
+pub struct GlyphWidths {
+    font: Option<Arc<FontMetrics>>,
+    font_size: u32,
+    table: HashMap<String, usize>,
+    default_width: usize,
+}
+
+impl GlyphWidths {
+    /// Creates glyph widths from a font file and/or a glyph width table.
+    ///
+    /// * `font` - Path to a TTF/OTF/TTC font file
+    /// * `font_size` - Font size in pixels
+    /// * `table` - Path to a JSON glyph width table, which maps characters to widths in pixels.
+    ///   The special key `default` sets the width of characters not found in the table or the font.
+    ///   Widths in the table override the font.
+    pub fn new(font: Option<&str>, font_size: u32, table: Option<&str>) -> Result<Self> {
+        if font.is_none() && table.is_none() {
+            anyhow::bail!(
+                "A font file or a glyph width table is required for pixel-width wrapping"
+            );
+        }
+        let font = match font {
+            Some(path) => Some(FontMetrics::load(path)?),
+            None => None,
+        };
+        let mut table = match table {
+            Some(path) => {
+                let data = crate::utils::files::read_file(path)?;
+                serde_json::from_slice::<HashMap<String, usize>>(&data)?
+            }
+            None => HashMap::new(),
+        };
+        let default_width = table.remove("default").unwrap_or(font_size as usize);
+        Ok(Self {
+            font,
+            font_size,
+            table,
+            default_width,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn from_table(table: HashMap<String, usize>, default_width: usize) -> Self {
+        Self {
+            font: None,
+            font_size: default_width as u32,
+            table,
+            default_width,
+        }
+    }
+
+    /// Returns the width of a character in pixels.
+    pub fn char_width(&self, c: char) -> usize {
+        let mut buf = [0u8; 4];
+        if let Some(width) = self.table.get(c.encode_utf8(&mut buf) as &str) {
+            return *width;
+        }
+        if let Some(font) = &self.font
+            && let Some(advance) = font.advance(c)
+        {
+            let width = advance as u64 * self.font_size as u64;
+            let upm = font.units_per_em as u64;
+            return ((width + upm / 2) / upm) as usize;
+        }
+        self.default_width
+    }
+
+    /// Returns the width of a grapheme cluster in pixels.
+    pub fn grapheme_width(&self, grapheme: &str) -> usize {
+        if let Some(width) = self.table.get(grapheme) {
+            return *width;
+        }
+        grapheme.chars().map(|c| self.char_width(c)).sum()
+    }
+
+    /// Returns the width of a string in pixels.
+    pub fn text_width(&self, text: &str) -> usize {
+        text.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+    }
+}
+
+/// Builds a TrueType font which only contains tables used by [FontMetrics].
+///
+/// * `groups` - cmap format 12 groups of (start code, end code, start glyph)
+#[cfg(test)]
+fn synthetic_font(units_per_em: u16, advances: &[u16], groups: &[(u32, u32, u32)]) -> Vec<u8> {
+    let u16s = |v: &[u16]| v.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<_>>();
+    let u32s = |v: &[u32]| v.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<_>>();
+    let mut head = vec![0; 54];
+    head[18..20].copy_from_slice(&units_per_em.to_be_bytes());
+    let mut hhea = vec![0; 36];
+    hhea[34..36].copy_from_slice(&(advances.len() as u16).to_be_bytes());
+    let maxp = [u32s(&[0x5000]), u16s(&[advances.len() as u16])].concat();
+    let hmtx = advances.iter().flat_map(|a| u16s(&[*a, 0])).collect();
+    let mut cmap = [u16s(&[0, 1, 3, 10]), u32s(&[12]), u16s(&[12, 0])].concat();
+    cmap.extend(u32s(&[
+        16 + groups.len() as u32 * 12,
+        0,
+        groups.len() as u32,
+    ]));
+    for (start, end, glyph) in groups {
+        cmap.extend(u32s(&[*start, *end, *glyph]));
+    }
+    let tables: [(&[u8; 4], Vec<u8>); 5] = [
+        (b"cmap", cmap),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"maxp", maxp),
+    ];
+    let mut font = [u32s(&[0x10000]), u16s(&[tables.len() as u16, 0, 0, 0])].concat();
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in tables.iter() {
+        font.extend(*tag);
+        font.extend(u32s(&[0, offset as u32, data.len() as u32]));
+        offset += data.len();
+    }
+    for (_, data) in tables {
+        font.extend(data);
+    }
+    font
+}
+
+#[test]
+fn test_font_glyph_width() {
+    // Glyph 1 is "A" and glyph 2 is "あ".
+    let font = synthetic_font(
+        2048,
+        &[1024, 1229, 2048],
+        &[(0x41, 0x41, 1), (0x3042, 0x3042, 2)],
+    );
+    let widths = GlyphWidths {
+        font: Some(Arc::new(FontMetrics::new(&font).unwrap())),
+        font_size: 20,
+        table: HashMap::new(),
+        default_width: 7,
+    };
+    assert_eq!(widths.char_width('A'), 12);
+    assert_eq!(widths.char_width('あ'), 20);
+    assert_eq!(widths.char_width('B'), 7);
+    assert_eq!(widths.text_width("AあA"), 44);
+    // Glyph id out of range is rejected.
+    let font = synthetic_font(2048, &[1024], &[(0x41, 0x42, 0xFFFF)]);
+    assert!(FontMetrics::new(&font).is_err());
+    let font = synthetic_font(2048, &[1024], &[(0x41, 0x42, u32::MAX)]);
+    assert!(FontMetrics::new(&font).is_err());
+}
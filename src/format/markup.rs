@@ -0,0 +1,126 @@
+//! Inline markup support for formatters.
+//!
+//! Script builders can provide an [InlineMarkup] tokenizer with
+//! [ScriptBuilder::inline_markup](crate::scripts::ScriptBuilder::inline_markup),
+//! so inline tags are measured as zero width and never split when wrapping lines.
+//!
+//! Wrapped lines are always separated by `\n`, which is converted to the engine line break
+//! by the importer of each script type.
+
+/// Kind of an inline markup token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Markup {
+    /// Inline tag which is not displayed (ruby, color, wait, etc.)
+    Tag,
+    /// Engine line break
+    LineBreak,
+    /// Escaped character, displayed as the given character
+    Escape(char),
+}
+
+/// Inline markup tokenizer of a script type.
+pub trait InlineMarkup: std::fmt::Debug + Send + Sync {
+    /// If `text` starts with an inline markup, returns its kind and length in bytes.
+    fn parse(&self, text: &str) -> Option<(Markup, usize)>;
+
+    /// If `tag` opens a paired markup (such as ruby), returns the tag which closes it.
+    ///
+    /// The opening tag, the enclosed text and the closing tag are never split into different lines.
+    fn closing_tag(&self, _tag: &str) -> Option<&'static str> {
+        None
+    }
+
+    /// Returns true if `tag` applies to the grapheme following it (such as KAG ruby), which has no closing tag.
+    ///
+    /// The tag and the following grapheme are never split into different lines.
+    fn binds_next_grapheme(&self, _tag: &str) -> bool {
+        false
+    }
+}
+
+/// Returns the length of a tag enclosed by `open` and `close` (such as `[name attr="value"]`)
+/// at the start of `text`.
+///
+/// Quoted attribute values may contain `close`. Tags can not be empty or contain line breaks.
+pub fn bracket_tag_len(text: &str, open: char, close: char) -> Option<usize> {
+    let rest = text.strip_prefix(open)?;
+    if rest.starts_with(close) {
+        return None;
+    }
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == close => return Some(open.len_utf8() + i + c.len_utf8()),
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '\n' => return None,
+                _ if c == open => return None,
+                _ => {}
+            },
+        }
+    }
+    None
+}
+
+/// Returns the length of a XML-like tag (`<name attr="value">` or `</name>`) at the start of `text`.
+///
+/// The tag name must start with an ASCII letter.
+pub fn xml_tag_len(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix('<')?;
+    let name = rest.strip_prefix('/').unwrap_or(rest);
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    bracket_tag_len(text, '<', '>')
+}
+
+/// Returns the tag name of a XML-like tag, without the leading `/` of end tags.
+pub fn xml_tag_name(tag: &str) -> &str {
+    let tag = tag.trim_start_matches('<').trim_end_matches('>');
+    let tag = tag.strip_prefix('/').unwrap_or(tag);
+    tag.split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or("")
+}
+
+/// Parses a XML character entity (`&lt;`, `&amp;`, `&#12354;`, etc.) at the start of `text`.
+pub fn xml_entity(text: &str) -> Option<(Markup, usize)> {
+    let rest = text.strip_prefix('&')?;
+    let end = rest.find(';').filter(|&end| end <= 10)?;
+    let name = &rest[..end];
+    let c = match name {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "quot" => '"',
+        "apos" => '\'',
+        _ => {
+            let code = if let Some(hex) = name.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16).ok()?
+            } else {
+                name.strip_prefix('#')?.parse().ok()?
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((Markup::Escape(c), end + 2))
+}
+
+#[test]
+fn test_xml_markup() {
+    assert_eq!(xml_tag_len("<r>abc"), Some(3));
+    assert_eq!(xml_tag_len("</ruby>abc"), Some(7));
+    assert_eq!(xml_tag_len("<ruby text=\"a>b\">abc"), Some(17));
+    assert_eq!(xml_tag_len("< r>"), None);
+    assert_eq!(xml_tag_len("<r"), None);
+    assert_eq!(bracket_tag_len("[ruby text=\"]\"]a", '[', ']'), Some(15));
+    assert_eq!(bracket_tag_len("[]", '[', ']'), None);
+    assert_eq!(xml_tag_name("<ruby text=\"a\">"), "ruby");
+    assert_eq!(xml_tag_name("</ruby>"), "ruby");
+    assert_eq!(xml_tag_name("<r/>"), "r");
+    assert_eq!(xml_entity("&lt;a"), Some((Markup::Escape('<'), 4)));
+    assert_eq!(xml_entity("&#x3042;"), Some((Markup::Escape('あ'), 8)));
+    assert_eq!(xml_entity("& b;"), None);
+}
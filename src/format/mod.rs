@@ -1,11 +1,20 @@
 //! Module for formatting messages.
 mod fixed;
+mod glyph_width;
+pub mod markup;
 
 use crate::types::*;
 use anyhow::Result;
 
 /// Formats messages with the given options.
-pub fn fmt_message(mes: &mut Vec<Message>, opt: FormatOptions, typ: ScriptType) -> Result<()> {
+///
+/// * `markup` - Inline markup tokenizer of the script type. See [crate::scripts::ScriptBuilder::inline_markup].
+pub fn fmt_message(
+    mes: &mut Vec<Message>,
+    opt: FormatOptions,
+    typ: ScriptType,
+    markup: Option<&'static dyn markup::InlineMarkup>,
+) -> Result<()> {
     match opt {
         FormatOptions::Fixed {
             length,
@@ -30,6 +39,44 @@ pub fn fmt_message(mes: &mut Vec<Message>, opt: FormatOptions, typ: ScriptType)
                 #[cfg(feature = "jieba")]
                 jieba_dict,
                 no_remove_space_at_line_start,
+                None,
+                markup,
+                Some(typ),
+            )?;
+            for message in mes.iter_mut() {
+                message.message = formatter.format(&message.message);
+            }
+        }
+        FormatOptions::Pixel {
+            width,
+            font,
+            font_size,
+            width_table,
+            keep_original,
+            break_words,
+            insert_fullwidth_space_at_line_start,
+            break_with_sentence,
+            #[cfg(feature = "jieba")]
+            break_chinese_words,
+            #[cfg(feature = "jieba")]
+            jieba_dict,
+            no_remove_space_at_line_start,
+        } => {
+            let widths =
+                glyph_width::GlyphWidths::new(font.as_deref(), font_size, width_table.as_deref())?;
+            let formatter = fixed::FixedFormatter::new(
+                width,
+                keep_original,
+                break_words,
+                insert_fullwidth_space_at_line_start,
+                break_with_sentence,
+                #[cfg(feature = "jieba")]
+                break_chinese_words,
+                #[cfg(feature = "jieba")]
+                jieba_dict,
+                no_remove_space_at_line_start,
+                Some(widths),
+                markup,
                 Some(typ),
             )?;
             for message in mes.iter_mut() {
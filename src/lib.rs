@@ -4,8 +4,12 @@
 #![cfg_attr(any(docsrs, feature = "unstable"), feature(doc_cfg))]
 pub mod ext;
 pub mod format;
+pub mod ops;
 pub mod output_scripts;
 pub mod scripts;
+#[cfg(test)]
+mod tests;
+pub mod tm;
 pub mod types;
 pub mod utils;
 
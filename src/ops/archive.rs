@@ -0,0 +1,411 @@
+//! Packing and unpacking archives.
+use super::*;
+use crate::{scripts, types, utils};
+
+/// Packs all files in the directory into a new archive of type [Options::script_type].
+pub fn pack_archive(
+    input: &str,
+    output: Option<&str>,
+    opts: &Options,
+    backslash: bool,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<()> {
+    let typ = match &opts.script_type {
+        Some(t) => t,
+        None => {
+            return Err(anyhow::anyhow!("No script type specified"));
+        }
+    };
+    let (mut files, isdir) = utils::files::collect_files(input, opts.recursive, true)
+        .map_err(|e| anyhow::anyhow!("Error collecting files: {}", e))?;
+    if !isdir {
+        return Err(anyhow::anyhow!("Input must be a directory for packing"));
+    }
+    let re_files: Vec<String> = files
+        .iter()
+        .filter_map(|f| {
+            std::path::PathBuf::from(f)
+                .strip_prefix(input)
+                .ok()
+                .and_then(|p| {
+                    p.to_str().map(|s| {
+                        if backslash {
+                            s.replace("/", "\\").trim_start_matches("\\").to_owned()
+                        } else {
+                            s.replace("\\", "/").trim_start_matches("/").to_owned()
+                        }
+                    })
+                })
+        })
+        .collect();
+    let mut reff = re_files.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+    let builder = scripts::BUILDER
+        .iter()
+        .find(|b| b.script_type() == typ)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported script type"))?;
+    let output = match output {
+        Some(output) => output.to_string(),
+        None => {
+            let mut pb = std::path::PathBuf::from(input);
+            let ext = builder.extensions().first().unwrap_or(&"unk");
+            pb.set_extension(ext);
+            if pb.to_string_lossy() == input {
+                pb.set_extension(format!("{}.{}", ext, ext));
+            }
+            pb.to_string_lossy().into_owned()
+        }
+    };
+    let mut archive = builder.create_archive(
+        &output,
+        &reff,
+        get_archived_encoding(opts, builder.as_ref(), get_encoding(opts, builder.as_ref())),
+        &opts.config,
+    )?;
+    if let Some(pre) = archive.prelist()? {
+        let mut index = 0;
+        for name in pre {
+            let name = name?;
+            if let Some(pos) = reff.iter().position(|&n| n == name) {
+                let re = reff.remove(pos);
+                let ff = files.remove(pos);
+                reff.insert(index, re);
+                files.insert(index, ff);
+                index += 1;
+            } else {
+                report!(
+                    reporter,
+                    "Warning: Prelist file {} not found in input files",
+                    name
+                );
+                reporter.warning();
+            }
+        }
+    }
+    for (file, name) in files.iter().zip(reff) {
+        let mut f = match std::fs::File::open(file) {
+            Ok(f) => f,
+            Err(e) => {
+                report!(reporter, "Error opening file {}: {}", file, e);
+                reporter.error();
+                continue;
+            }
+        };
+        let size = match std::fs::metadata(file) {
+            Ok(meta) => meta.len(),
+            Err(e) => {
+                report!(reporter, "Error getting metadata for file {}: {}", file, e);
+                reporter.error();
+                continue;
+            }
+        };
+        let mut wf = match archive.new_file_non_seek(name, Some(size)) {
+            Ok(f) => f,
+            Err(e) => {
+                report!(reporter, "Error creating file {} in archive: {}", name, e);
+                reporter.error();
+                continue;
+            }
+        };
+        match std::io::copy(&mut f, &mut wf) {
+            Ok(_) => {
+                reporter.result(types::ScriptResult::Ok);
+            }
+            Err(e) => {
+                report!(reporter, "Error writing to file {} in archive: {}", name, e);
+                reporter.error();
+                continue;
+            }
+        }
+    }
+    archive.write_header()?;
+    Ok(())
+}
+
+/// Packs files and directories into a new archive of type [Options::script_type].
+pub fn pack_archive_v2(
+    input: &[&str],
+    output: Option<&str>,
+    opts: &Options,
+    backslash: bool,
+    no_dir: bool,
+    dep_file: Option<&str>,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<()> {
+    let typ = match &opts.script_type {
+        Some(t) => t,
+        None => {
+            return Err(anyhow::anyhow!("No script type specified"));
+        }
+    };
+    // File List in real path
+    let mut files = Vec::new();
+    // File list in archive path
+    let mut re_files = Vec::new();
+    for i in input {
+        let (fs, is_dir) = utils::files::collect_files(i, opts.recursive, true)?;
+        if is_dir {
+            files.extend_from_slice(&fs);
+            for n in fs.iter() {
+                if no_dir {
+                    if let Some(p) = std::path::PathBuf::from(n).file_name() {
+                        re_files.push(p.to_string_lossy().into_owned());
+                    } else {
+                        return Err(anyhow::anyhow!("Failed to get filename from {}", n));
+                    }
+                } else {
+                    if let Some(p) = {
+                        std::path::PathBuf::from(n)
+                            .strip_prefix(i)
+                            .ok()
+                            .and_then(|p| {
+                                p.to_str().map(|s| {
+                                    if backslash {
+                                        s.replace("/", "\\").trim_start_matches("\\").to_owned()
+                                    } else {
+                                        s.replace("\\", "/").trim_start_matches("/").to_owned()
+                                    }
+                                })
+                            })
+                    } {
+                        re_files.push(p);
+                    } else {
+                        return Err(anyhow::anyhow!("Failed to get relative path from {}", n));
+                    }
+                }
+            }
+        } else {
+            files.push(i.to_string());
+            let p = std::path::PathBuf::from(i);
+            if let Some(fname) = p.file_name() {
+                re_files.push(fname.to_string_lossy().into_owned());
+            } else {
+                return Err(anyhow::anyhow!("Failed to get filename from {}", i));
+            }
+        }
+    }
+    let mut reff = re_files.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+    let builder = scripts::BUILDER
+        .iter()
+        .find(|b| b.script_type() == typ)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported script type"))?;
+    let output = match output {
+        Some(output) => output.to_string(),
+        None => {
+            let mut pb = std::path::PathBuf::from(input[0]);
+            let ext = builder.extensions().first().unwrap_or(&"unk");
+            pb.set_extension(ext);
+            if pb.to_string_lossy() == input[0] {
+                pb.set_extension(format!("{}.{}", ext, ext));
+            }
+            pb.to_string_lossy().into_owned()
+        }
+    };
+    let mut archive = builder.create_archive(
+        &output,
+        &reff,
+        get_archived_encoding(opts, builder.as_ref(), get_encoding(opts, builder.as_ref())),
+        &opts.config,
+    )?;
+    if let Some(pre) = archive.prelist()? {
+        let mut index = 0;
+        for name in pre {
+            let name = name?;
+            if let Some(pos) = reff.iter().position(|&n| n == name) {
+                let re = reff.remove(pos);
+                let ff = files.remove(pos);
+                reff.insert(index, re);
+                files.insert(index, ff);
+                index += 1;
+            } else {
+                report!(
+                    reporter,
+                    "Warning: Prelist file {} not found in input files",
+                    name
+                );
+                reporter.warning();
+            }
+        }
+    }
+    if let Some(dep_file) = dep_file {
+        let df = std::fs::File::create(dep_file)
+            .map_err(|e| anyhow::anyhow!("Failed to create dep file {}: {}", dep_file, e))?;
+        let mut df = std::io::BufWriter::new(df);
+        use std::io::Write;
+        write!(df, "{}:", escape_dep_string(&output))
+            .map_err(|e| anyhow::anyhow!("Failed to write to dep file {}: {}", dep_file, e))?;
+        for f in &files {
+            write!(df, " {}", escape_dep_string(f))
+                .map_err(|e| anyhow::anyhow!("Failed to write to dep file {}: {}", dep_file, e))?;
+        }
+        writeln!(df)
+            .map_err(|e| anyhow::anyhow!("Failed to write to dep file {}: {}", dep_file, e))?;
+    }
+    for (file, name) in files.iter().zip(reff) {
+        let mut f = match std::fs::File::open(file) {
+            Ok(f) => f,
+            Err(e) => {
+                report!(reporter, "Error opening file {}: {}", file, e);
+                reporter.error();
+                continue;
+            }
+        };
+        let size = match std::fs::metadata(file) {
+            Ok(meta) => meta.len(),
+            Err(e) => {
+                report!(reporter, "Error getting metadata for file {}: {}", file, e);
+                reporter.error();
+                continue;
+            }
+        };
+        let mut wf = match archive.new_file_non_seek(name, Some(size)) {
+            Ok(f) => f,
+            Err(e) => {
+                report!(reporter, "Error creating file {} in archive: {}", name, e);
+                reporter.error();
+                continue;
+            }
+        };
+        match std::io::copy(&mut f, &mut wf) {
+            Ok(_) => {
+                reporter.result(types::ScriptResult::Ok);
+            }
+            Err(e) => {
+                report!(reporter, "Error writing to file {} in archive: {}", name, e);
+                reporter.error();
+                continue;
+            }
+        }
+    }
+    archive.write_header()?;
+    Ok(())
+}
+
+/// Extracts all files in an archive or all archives in a directory.
+pub fn unpack_files(
+    input: &str,
+    output: &Option<String>,
+    skip_existed: bool,
+    opts: &Options,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<()> {
+    let (scripts, root_dir) = collect_inputs(input, output.as_deref(), |input| {
+        utils::files::collect_arc_files(input, opts.recursive)
+    })?;
+    for script in scripts.iter() {
+        match unpack_archive(script, opts, output, root_dir, skip_existed, reporter) {
+            Ok(s) => reporter.result(s),
+            Err(e) => report_error(reporter, opts, &format!("Error unpacking {}", script), e),
+        }
+    }
+    Ok(())
+}
+
+/// Extracts all files in the archive.
+pub fn unpack_archive(
+    filename: &str,
+    opts: &Options,
+    output: &Option<String>,
+    root_dir: Option<&std::path::Path>,
+    skip_existed: bool,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<types::ScriptResult> {
+    report!(reporter, "Unpacking {}", filename);
+    let script = parse_script(filename, opts)?.0;
+    if !script.is_archive() {
+        return Ok(types::ScriptResult::Ignored);
+    }
+    let odir = match output.as_ref() {
+        Some(output) => {
+            let mut pb = std::path::PathBuf::from(output);
+            let filename = std::path::PathBuf::from(filename);
+            if let Some(root_dir) = root_dir {
+                let rpath = utils::files::relative_path(root_dir, &filename);
+                if let Some(parent) = rpath.parent() {
+                    pb.push(parent);
+                }
+                if let Some(fname) = filename.file_name() {
+                    pb.push(fname);
+                }
+            }
+            pb.set_extension("");
+            if let Some(ext) = script.archive_output_ext() {
+                pb.set_extension(ext);
+            }
+            pb.to_string_lossy().into_owned()
+        }
+        None => {
+            let mut pb = std::path::PathBuf::from(filename);
+            pb.set_extension("");
+            if let Some(ext) = script.archive_output_ext() {
+                pb.set_extension(ext);
+            }
+            pb.to_string_lossy().into_owned()
+        }
+    };
+    if !std::fs::exists(&odir)? {
+        std::fs::create_dir_all(&odir)?;
+    }
+    for (index, filename) in script.iter_archive_filename()?.enumerate() {
+        let filename = match filename {
+            Ok(f) => f,
+            Err(e) => {
+                report!(reporter, "Error reading archive filename: {}", e);
+                reporter.error();
+                if opts.backtrace {
+                    report!(reporter, "Backtrace: {}", e.backtrace());
+                }
+                continue;
+            }
+        };
+        let out_path = std::path::PathBuf::from(&odir).join(&filename);
+        if skip_existed && out_path.exists() {
+            continue;
+        }
+        let mut f = match script.open_file(index) {
+            Ok(f) => f,
+            Err(e) => {
+                report!(reporter, "Error opening file {}: {}", filename, e);
+                reporter.error();
+                if opts.backtrace {
+                    report!(reporter, "Backtrace: {}", e.backtrace());
+                }
+                continue;
+            }
+        };
+        match utils::files::make_sure_dir_exists(&out_path) {
+            Ok(_) => {}
+            Err(e) => {
+                report!(
+                    reporter,
+                    "Error creating parent directory for {}: {}",
+                    out_path.display(),
+                    e
+                );
+                reporter.error();
+                continue;
+            }
+        }
+        match utils::files::write_file(&out_path) {
+            Ok(mut fi) => match std::io::copy(&mut f, &mut fi) {
+                Ok(_) => {}
+                Err(e) => {
+                    report!(
+                        reporter,
+                        "Error writing to file {}: {}",
+                        out_path.display(),
+                        e
+                    );
+                    reporter.error();
+                    continue;
+                }
+            },
+            Err(e) => {
+                report!(reporter, "Error writing file {}: {}", out_path.display(), e);
+                reporter.error();
+                continue;
+            }
+        }
+        reporter.result(types::ScriptResult::Ok);
+    }
+    Ok(types::ScriptResult::Ok)
+}
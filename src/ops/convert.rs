@@ -0,0 +1,637 @@
+//! Reading, writing and converting output scripts.
+use super::*;
+use crate::ext::path::PathBufExt;
+use crate::{output_scripts, tm, types, utils};
+
+/// Reads messages with source text from an output script.
+pub fn parse_output_script_as_extend(
+    input: &str,
+    typ: types::OutputScriptType,
+    opts: &Options,
+) -> anyhow::Result<Vec<types::ExtendedMessage>> {
+    match typ {
+        types::OutputScriptType::M3t
+        | types::OutputScriptType::M3ta
+        | types::OutputScriptType::M3tTxt => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::m3t::M3tParser::new(
+                &s,
+                opts.llm_trans_mark.as_deref(),
+                opts.m3t_use_original_text,
+            );
+            let mes = parser.parse_as_extend()?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Po | types::OutputScriptType::Pot => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::po::PoParser::new(&s, opts.llm_trans_mark.as_deref());
+            let mes = parser.parse_as_extend()?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Xliff => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser =
+                output_scripts::xliff::XliffParser::new(&s, opts.llm_trans_mark.as_deref());
+            let mes = parser.parse_as_extend()?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Tmx => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::tmx::TmxParser::new(
+                &s,
+                get_target_lang(opts),
+                opts.llm_trans_mark.as_deref(),
+            );
+            let mes = parser.parse_as_extend()?;
+            Ok(mes)
+        }
+        _ => Err(anyhow::anyhow!(
+            "Output script type {:?} does not support extended messages",
+            typ
+        )),
+    }
+}
+
+/// Reads messages from an output script.
+pub fn parse_output_script(
+    input: &str,
+    typ: types::OutputScriptType,
+    opts: &Options,
+) -> anyhow::Result<Vec<types::Message>> {
+    match typ {
+        types::OutputScriptType::M3t
+        | types::OutputScriptType::M3ta
+        | types::OutputScriptType::M3tTxt => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::m3t::M3tParser::new(
+                &s,
+                opts.llm_trans_mark.as_deref(),
+                opts.m3t_use_original_text,
+            );
+            let mes = parser.parse()?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Po | types::OutputScriptType::Pot => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::po::PoParser::new(&s, opts.llm_trans_mark.as_deref());
+            let mes = parser.parse()?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Xliff => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser =
+                output_scripts::xliff::XliffParser::new(&s, opts.llm_trans_mark.as_deref());
+            let mes = parser.parse()?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Tmx => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::tmx::TmxParser::new(
+                &s,
+                get_target_lang(opts),
+                opts.llm_trans_mark.as_deref(),
+            );
+            let mes = parser.parse()?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Json => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mes = serde_json::from_str::<Vec<types::Message>>(&s)?;
+            Ok(mes)
+        }
+        types::OutputScriptType::Yaml => {
+            let enc = get_input_output_script_encoding(opts);
+            let b = utils::files::read_file(input)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mes = serde_yaml_ng::from_str::<Vec<types::Message>>(&s)?;
+            Ok(mes)
+        }
+        _ => Err(anyhow::anyhow!(
+            "Output script type {:?} does not support message parsing",
+            typ
+        )),
+    }
+}
+
+/// Writes messages with source text to an output script.
+pub fn dump_output_script_as_extend(
+    output: &str,
+    typ: types::OutputScriptType,
+    mes: &[types::ExtendedMessage],
+    opts: &Options,
+) -> anyhow::Result<()> {
+    match typ {
+        types::OutputScriptType::M3t
+        | types::OutputScriptType::M3ta
+        | types::OutputScriptType::M3tTxt => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::m3t::M3tDumper::dump_extended(mes);
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Po | types::OutputScriptType::Pot => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::po::PoDumper::new();
+            let s = s.dump_extended(mes, enc)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Xliff => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::xliff::XliffDumper::new(
+                get_source_lang(opts),
+                get_target_lang(opts),
+            );
+            let s = s.dump_extended(mes, enc)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Tmx => {
+            let enc = get_output_encoding(opts);
+            let s =
+                output_scripts::tmx::TmxDumper::new(get_source_lang(opts), get_target_lang(opts));
+            let s = s.dump_extended(mes, enc)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!(
+            "Output script type {:?} does not support extended messages",
+            typ
+        )),
+    }
+}
+
+/// Writes messages to an output script.
+pub fn dump_output_script(
+    output: &str,
+    typ: types::OutputScriptType,
+    mes: &[types::Message],
+    opts: &Options,
+) -> anyhow::Result<()> {
+    match typ {
+        types::OutputScriptType::M3t
+        | types::OutputScriptType::M3ta
+        | types::OutputScriptType::M3tTxt => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::m3t::M3tDumper::dump(mes, opts.m3t_no_quote);
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Po | types::OutputScriptType::Pot => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::po::PoDumper::new();
+            let s = s.dump(mes, enc)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Xliff => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::xliff::XliffDumper::new(
+                get_source_lang(opts),
+                get_target_lang(opts),
+            );
+            let s = s.dump(mes, enc)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Tmx => {
+            let enc = get_output_encoding(opts);
+            let s =
+                output_scripts::tmx::TmxDumper::new(get_source_lang(opts), get_target_lang(opts));
+            let s = s.dump(mes, enc)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Json => {
+            let enc = get_output_encoding(opts);
+            let s = serde_json::to_string_pretty(mes)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        types::OutputScriptType::Yaml => {
+            let enc = get_output_encoding(opts);
+            let s = serde_yaml_ng::to_string(mes)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            utils::files::write_file(output)?.write_all(&b)?;
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!(
+            "Output script type {:?} does not support message dumping",
+            typ
+        )),
+    }
+}
+
+/// Converts an output script file or all output script files of `input_type` in a directory.
+pub fn convert_files(
+    input: &str,
+    input_type: types::OutputScriptType,
+    output: Option<&str>,
+    output_type: types::OutputScriptType,
+    opts: &Options,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<()> {
+    if input_type.is_custom() {
+        anyhow::bail!("Custom input type is not supported for conversion.");
+    }
+    if output_type.is_custom() {
+        anyhow::bail!("Custom output type is not supported for conversion.");
+    }
+    let (scripts, root_dir) = collect_inputs(input, output, |input| {
+        utils::files::collect_ext_files(input, opts.recursive, &[input_type.as_ref()])
+    })?;
+    for script in scripts.iter() {
+        match convert_file(script, input_type, output, output_type, opts, root_dir) {
+            Ok(s) => reporter.result(s),
+            Err(e) => report_error(reporter, opts, &format!("Error converting {}", script), e),
+        }
+    }
+    Ok(())
+}
+
+/// Converts an output script to another format.
+pub fn convert_file(
+    input: &str,
+    input_type: types::OutputScriptType,
+    output: Option<&str>,
+    output_type: types::OutputScriptType,
+    opts: &Options,
+    root_dir: Option<&std::path::Path>,
+) -> anyhow::Result<types::ScriptResult> {
+    let input_support_src = input_type.is_src_supported();
+    let output_support_src = output_type.is_src_supported();
+    let output = match output {
+        Some(output) => match root_dir {
+            Some(root_dir) => {
+                let f = std::path::PathBuf::from(input);
+                let mut pb = std::path::PathBuf::from(output);
+                let rpath = utils::files::relative_path(root_dir, &f);
+                if let Some(parent) = rpath.parent() {
+                    pb.push(parent);
+                }
+                if let Some(fname) = f.file_name() {
+                    pb.push(fname);
+                }
+                if opts.output_no_extra_ext {
+                    pb.remove_all_extensions();
+                }
+                pb.set_extension(output_type.as_ref());
+                pb.to_string_lossy().into_owned()
+            }
+            None => output.to_string(),
+        },
+        None => {
+            let mut pb = std::path::PathBuf::from(input);
+            if opts.output_no_extra_ext {
+                pb.remove_all_extensions();
+            }
+            pb.set_extension(output_type.as_ref());
+            pb.to_string_lossy().into_owned()
+        }
+    };
+    if input_support_src && output_support_src {
+        let input_mes = parse_output_script_as_extend(input, input_type, opts)?;
+        dump_output_script_as_extend(&output, output_type, &input_mes, opts)?;
+        return Ok(types::ScriptResult::Ok);
+    }
+    let input_mes = parse_output_script(input, input_type, opts)?;
+    dump_output_script(&output, output_type, &input_mes, opts)?;
+    Ok(types::ScriptResult::Ok)
+}
+
+/// Builds a translation memory from translated output scripts.
+///
+/// `originals` contains the original exported file or directory of each path in `tm`.
+/// If it is empty, translation memory files must contain source text (m3t/po/xliff/tmx).
+pub fn load_translation_memory(
+    tm: &[String],
+    originals: &[String],
+    typ: types::OutputScriptType,
+    opts: &Options,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<tm::TranslationMemory> {
+    if !originals.is_empty() && originals.len() != tm.len() {
+        anyhow::bail!(
+            "Number of original paths ({}) does not match number of translation memory paths ({})",
+            originals.len(),
+            tm.len()
+        );
+    }
+    if typ.is_custom() {
+        anyhow::bail!("Custom output type is not supported for translation memory.");
+    }
+    if originals.is_empty() && !typ.is_src_supported() {
+        anyhow::bail!(
+            "Output script type {:?} does not contain source text, please specify original files with --tm-original",
+            typ
+        );
+    }
+    let mut memory = tm::TranslationMemory::new();
+    for (i, path) in tm.iter().enumerate() {
+        let (files, is_dir) =
+            utils::files::collect_ext_files(path, opts.recursive, &[typ.as_ref()])?;
+        for file in files {
+            let original = match originals.get(i) {
+                Some(original) if is_dir => {
+                    let mut pb = std::path::PathBuf::from(original);
+                    pb.push(utils::files::relative_path(path, &file));
+                    Some(pb.to_string_lossy().into_owned())
+                }
+                Some(original) => Some(original.clone()),
+                None => None,
+            };
+            match original {
+                Some(original) => {
+                    let translated = parse_output_script(&file, typ, opts)
+                        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", file, e))?;
+                    let source = parse_output_script(&original, typ, opts)
+                        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", original, e))?;
+                    if source.len() != translated.len() {
+                        report!(
+                            reporter,
+                            "Warning: {} contains {} messages, but {} contains {} messages, skipped.",
+                            file,
+                            translated.len(),
+                            original,
+                            source.len()
+                        );
+                        reporter.warning();
+                        continue;
+                    }
+                    for (s, t) in source.into_iter().zip(translated) {
+                        // Untranslated json/yaml messages still contain the source text.
+                        if t.message != s.message {
+                            memory.add(s.name, s.message, t.message);
+                        }
+                    }
+                }
+                None => {
+                    let mes = parse_output_script_as_extend(&file, typ, opts)
+                        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", file, e))?;
+                    for m in mes {
+                        // Fuzzy translations are not reviewed yet.
+                        if !m.fuzzy {
+                            memory.add(m.name, m.source, m.translated);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(memory)
+}
+
+/// Applies translation memory to an output script file or all output script files of `typ` in a directory.
+///
+/// Returns the statistics of all files. See [apply_translation_memory] for details.
+pub fn apply_translation_memory_files(
+    input: &str,
+    typ: types::OutputScriptType,
+    output: Option<&str>,
+    memory: &tm::TranslationMemory,
+    threshold: f64,
+    opts: &Options,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<tm::TmStats> {
+    if typ.is_custom() {
+        anyhow::bail!("Custom output type is not supported for translation memory.");
+    }
+    if !(0.0..=1.0).contains(&threshold) {
+        anyhow::bail!("Fuzzy threshold must be between 0.0 and 1.0.");
+    }
+    let (scripts, root_dir) = collect_inputs(input, output, |input| {
+        utils::files::collect_ext_files(input, opts.recursive, &[typ.as_ref()])
+    })?;
+    let mut stats = tm::TmStats::default();
+    for script in scripts.iter() {
+        match apply_translation_memory(script, typ, output, memory, threshold, opts, root_dir) {
+            Ok(s) => {
+                reporter.result(if s.exact + s.fuzzy == 0 {
+                    types::ScriptResult::Ignored
+                } else {
+                    types::ScriptResult::Ok
+                });
+                stats.merge(&s);
+            }
+            Err(e) => report_error(
+                reporter,
+                opts,
+                &format!("Error applying translation memory to {}", script),
+                e,
+            ),
+        }
+    }
+    Ok(stats)
+}
+
+/// Fills untranslated messages of an output script with translations from translation memory.
+///
+/// Exact matches are used as translation. Fuzzy matches are marked for review:
+/// * m3t: stored as LLM message
+/// * po: stored as translation with `fuzzy` flag
+/// * xliff: stored as translation with `initial` state
+/// * tmx: stored as translation with `x-fuzzy` property
+/// * json/yaml: stored in metadata `tm`
+///
+/// Returns the statistics of this file.
+pub fn apply_translation_memory(
+    input: &str,
+    typ: types::OutputScriptType,
+    output: Option<&str>,
+    memory: &tm::TranslationMemory,
+    threshold: f64,
+    opts: &Options,
+    root_dir: Option<&std::path::Path>,
+) -> anyhow::Result<tm::TmStats> {
+    let output = match output {
+        Some(output) => match root_dir {
+            Some(root_dir) => {
+                let mut pb = std::path::PathBuf::from(output);
+                pb.push(utils::files::relative_path(root_dir, input));
+                utils::files::make_sure_dir_exists(&pb)?;
+                pb.to_string_lossy().into_owned()
+            }
+            None => output.to_string(),
+        },
+        None => input.to_string(),
+    };
+    let mut stats = tm::TmStats::default();
+    if typ.is_src_supported() {
+        let mut mes = parse_output_script_as_extend(input, typ, opts)?;
+        for m in mes.iter_mut() {
+            if !tm::is_untranslated(&m.translated) {
+                continue;
+            }
+            match memory.lookup(m.name.as_deref(), &m.source, threshold) {
+                Some(tm::TmMatch::Exact(e)) => {
+                    m.translated = e.translated.clone();
+                    stats.exact += 1;
+                }
+                // Existing LLM messages are kept.
+                Some(tm::TmMatch::Fuzzy(..)) if typ.is_m3t() && m.llm.is_some() => {
+                    stats.missed += 1
+                }
+                Some(tm::TmMatch::Fuzzy(e, _)) => {
+                    if typ.is_m3t() {
+                        m.llm = Some(e.translated.clone());
+                    } else {
+                        m.translated = e.translated.clone();
+                        m.fuzzy = true;
+                    }
+                    stats.fuzzy += 1;
+                }
+                None => stats.missed += 1,
+            }
+        }
+        dump_output_script_as_extend(&output, typ, &mes, opts)?;
+    } else {
+        let mut mes = parse_output_script(input, typ, opts)?;
+        for m in mes.iter_mut() {
+            // Source text is not kept in these files. Empty messages have nothing to look up,
+            // and messages which are known translations are already translated.
+            if m.message.is_empty() {
+                stats.missed += 1;
+                continue;
+            }
+            match memory.lookup(m.name.as_deref(), &m.message, threshold) {
+                Some(tm::TmMatch::Exact(e)) => {
+                    m.message = e.translated.clone();
+                    stats.exact += 1;
+                }
+                Some(tm::TmMatch::Fuzzy(..)) if memory.is_translation(&m.message) => {}
+                Some(tm::TmMatch::Fuzzy(e, _)) => {
+                    m.meta
+                        .insert(types::meta_key::TM.to_string(), e.translated.clone().into());
+                    stats.fuzzy += 1;
+                }
+                None => stats.missed += 1,
+            }
+        }
+        dump_output_script(&output, typ, &mes, opts)?;
+    }
+    Ok(stats)
+}
+
+#[test]
+fn test_load_translation_memory_skips_untranslated() {
+    let dir = crate::utils::testing::temp_dir("tm");
+    let original = dir.join("original.json").to_string_lossy().into_owned();
+    let translated = dir.join("translated.json").to_string_lossy().into_owned();
+    std::fs::write(
+        &original,
+        r#"[{"message": "こんにちは"}, {"message": "さようなら"}, {"message": "おはよう"}]"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &translated,
+        r#"[{"message": "你好"}, {"message": "さようなら"}, {"message": ""}]"#,
+    )
+    .unwrap();
+    let memory = load_translation_memory(
+        &[translated],
+        &[original],
+        types::OutputScriptType::Json,
+        &Options::default(),
+        &crate::utils::counter::Counter::new(),
+    )
+    .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(matches!(
+        memory.lookup(None, "こんにちは", 1.0),
+        Some(tm::TmMatch::Exact(e)) if e.translated == "你好"
+    ));
+    assert!(memory.lookup(None, "さようなら", 0.5).is_none());
+    assert!(memory.lookup(None, "おはよう", 0.5).is_none());
+}
+
+#[test]
+fn test_apply_translation_memory_keeps_translated() {
+    let dir = crate::utils::testing::temp_dir("apply-tm");
+    let input = dir.join("input.json").to_string_lossy().into_owned();
+    std::fs::write(
+        &input,
+        r#"[{"message": "こんにちは"}, {"message": "再见"}, {"message": ""}]"#,
+    )
+    .unwrap();
+    let mut memory = tm::TranslationMemory::new();
+    memory.add(None, "こんにちは".into(), "你好".into());
+    memory.add(None, "さようなら".into(), "再见".into());
+    // Similar to the translated message "再见"
+    memory.add(None, "再见！".into(), "Goodbye!".into());
+    let stats = apply_translation_memory(
+        &input,
+        types::OutputScriptType::Json,
+        None,
+        &memory,
+        0.5,
+        &Options::default(),
+        None,
+    )
+    .unwrap();
+    let mes =
+        parse_output_script(&input, types::OutputScriptType::Json, &Options::default()).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(mes[0].message, "你好");
+    assert_eq!(mes[1].message, "再见");
+    assert!(!mes[1].meta.contains_key(types::meta_key::TM));
+    assert_eq!(mes[2].message, "");
+    assert_eq!((stats.exact, stats.fuzzy), (1, 0));
+}
+
+#[test]
+fn test_apply_translation_memory_keeps_m3t_llm() {
+    let dir = crate::utils::testing::temp_dir("apply-tm-m3t");
+    let input = dir.join("input.m3t").to_string_lossy().into_owned();
+    std::fs::write(
+        &input,
+        "○ こんにちは！\n△ 你好啊\n●\n\n○ さようなら！\n●\n\n",
+    )
+    .unwrap();
+    let mut memory = tm::TranslationMemory::new();
+    memory.add(None, "こんにちは".into(), "你好".into());
+    memory.add(None, "さようなら".into(), "再见".into());
+    let stats = apply_translation_memory(
+        &input,
+        types::OutputScriptType::M3t,
+        None,
+        &memory,
+        0.5,
+        &Options::default(),
+        None,
+    )
+    .unwrap();
+    let mes =
+        parse_output_script_as_extend(&input, types::OutputScriptType::M3t, &Options::default())
+            .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(mes[0].llm.as_deref(), Some("你好啊"));
+    assert_eq!(mes[1].llm.as_deref(), Some("再见"));
+    assert_eq!((stats.exact, stats.fuzzy, stats.missed), (0, 1, 1));
+}
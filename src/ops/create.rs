@@ -0,0 +1,121 @@
+//! Creating new script files.
+use super::*;
+use crate::{scripts, utils};
+
+/// Creates a new script file of type [Options::script_type] from `input`.
+pub fn create_file(input: &str, output: Option<&str>, opts: &Options) -> anyhow::Result<()> {
+    let typ = match &opts.script_type {
+        Some(t) => t,
+        None => {
+            return Err(anyhow::anyhow!("No script type specified"));
+        }
+    };
+    let builder = scripts::BUILDER
+        .iter()
+        .find(|b| b.script_type() == typ)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported script type"))?;
+
+    #[cfg(feature = "image")]
+    if builder.is_image() && std::path::Path::new(input).is_dir() {
+        if !builder.can_create_multi_image_file() {
+            return Err(anyhow::anyhow!(
+                "Script type {:?} does not support creating image file from multiple images",
+                typ
+            ));
+        }
+        let typ = opts.image_type.unwrap_or(ImageOutputType::Png);
+        let mut images = Vec::new();
+        for (name, path) in find_multi_images(std::path::Path::new(input), None, typ)? {
+            let data = utils::img::decode_img(typ, &path.to_string_lossy())?;
+            images.push(ImageDataWithName { name, data });
+        }
+        if images.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No {} images found in {}",
+                typ.as_ref(),
+                input
+            ));
+        }
+        let output = match output {
+            Some(output) => output.to_string(),
+            None => {
+                let mut pb = std::path::PathBuf::from(input.trim_end_matches(['/', '\\']));
+                let ext = builder.extensions().first().unwrap_or(&"unk");
+                pb.set_extension(ext);
+                pb.to_string_lossy().into_owned()
+            }
+        };
+        builder.create_multi_image_file_filename(images, &output, &opts.config)?;
+        return Ok(());
+    }
+
+    #[cfg(feature = "image")]
+    if builder.is_image() {
+        if !builder.can_create_image_file() {
+            return Err(anyhow::anyhow!(
+                "Script type {:?} does not support image file creation",
+                typ
+            ));
+        }
+        let data = utils::img::decode_img(
+            opts.image_type.unwrap_or_else(|| {
+                ImageOutputType::try_from(std::path::Path::new(input))
+                    .unwrap_or(ImageOutputType::Png)
+            }),
+            input,
+        )?;
+        let output = match output {
+            Some(output) => output.to_string(),
+            None => {
+                let mut pb = std::path::PathBuf::from(input);
+                let ext = builder.extensions().first().unwrap_or(&"");
+                pb.set_extension(ext);
+                if pb.to_string_lossy() == input {
+                    if ext.is_empty() {
+                        pb.set_extension("unk");
+                    } else {
+                        pb.set_extension(format!("{}.{}", ext, ext));
+                    }
+                }
+                pb.to_string_lossy().into_owned()
+            }
+        };
+        builder.create_image_file_filename(data, &output, input, &opts.config)?;
+        return Ok(());
+    }
+
+    if !builder.can_create_file() {
+        return Err(anyhow::anyhow!(
+            "Script type {:?} does not support file creation",
+            typ
+        ));
+    }
+
+    let output = match output {
+        Some(output) => output.to_string(),
+        None => {
+            let mut pb = std::path::PathBuf::from(input);
+            let ext = builder.extensions().first().unwrap_or(&"");
+            pb.set_extension(ext);
+            if pb.to_string_lossy() == input {
+                if ext.is_empty() {
+                    pb.set_extension("unk");
+                } else {
+                    pb.set_extension(format!("{}.{}", ext, ext));
+                }
+            }
+            pb.to_string_lossy().into_owned()
+        }
+    };
+
+    utils::files::make_sure_dir_exists(&output)?;
+
+    builder.create_file_filename(
+        input,
+        &output,
+        get_encoding(opts, builder.as_ref()),
+        get_output_encoding(opts),
+        &opts.config,
+    )?;
+    Ok(())
+}
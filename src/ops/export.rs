@@ -0,0 +1,1286 @@
+//! Exporting messages from scripts.
+use super::*;
+use crate::ext::path::PathBufExt;
+use crate::{output_scripts, types, utils};
+
+/// Exports messages from a script file or all script files in a directory.
+///
+/// Images are encoded in parallel if [Options::image_workers] is greater than 1.
+pub fn export_files(
+    input: &str,
+    output: &Option<String>,
+    opts: &Options,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<()> {
+    let (scripts, root_dir) = collect_inputs(input, output.as_deref(), |input| {
+        utils::files::collect_files(input, opts.recursive, false)
+    })?;
+    #[cfg(feature = "image")]
+    let img_threadpool = if opts.image_workers > 1 {
+        Some(
+            utils::threadpool::ThreadPool::<Result<(), anyhow::Error>>::new(
+                opts.image_workers,
+                Some("img-output-worker-"),
+                false,
+            )
+            .map_err(|e| anyhow::anyhow!("Failed to create image thread pool: {}", e))?,
+        )
+    } else {
+        None
+    };
+    #[cfg(feature = "image")]
+    let take_image_results = |threadpool: &utils::threadpool::ThreadPool<_>| {
+        for r in threadpool.take_results() {
+            match r {
+                Ok(_) => reporter.result(types::ScriptResult::Ok),
+                Err(e) => {
+                    reporter.error();
+                    report!(reporter, "{}", e);
+                }
+            }
+        }
+    };
+    for script in scripts.iter() {
+        #[cfg(feature = "image")]
+        let re = export_script(
+            script,
+            opts,
+            output,
+            root_dir,
+            img_threadpool.as_ref(),
+            reporter,
+        );
+        #[cfg(not(feature = "image"))]
+        let re = export_script(script, opts, output, root_dir, reporter);
+        match re {
+            Ok(s) => reporter.result(s),
+            Err(e) => report_error(reporter, opts, &format!("Error exporting {}", script), e),
+        }
+        #[cfg(feature = "image")]
+        if let Some(threadpool) = img_threadpool.as_ref() {
+            take_image_results(threadpool);
+        }
+    }
+    #[cfg(feature = "image")]
+    if let Some(threadpool) = img_threadpool {
+        threadpool.join();
+        take_image_results(&threadpool);
+    }
+    Ok(())
+}
+
+/// Exports messages (or images/files) from a script file.
+///
+/// `root_dir` is the input directory if `filename` is found in a directory, it is used to keep directory structure in `output`.
+pub fn export_script(
+    filename: &str,
+    opts: &Options,
+    output: &Option<String>,
+    root_dir: Option<&std::path::Path>,
+    #[cfg(feature = "image")] img_threadpool: Option<
+        &utils::threadpool::ThreadPool<Result<(), anyhow::Error>>,
+    >,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<types::ScriptResult> {
+    report!(reporter, "Exporting {}", filename);
+    let script = parse_script(filename, opts)?.0;
+    if script.is_archive() {
+        let odir = match output.as_ref() {
+            Some(output) => {
+                let mut pb = std::path::PathBuf::from(output);
+                let filename = std::path::PathBuf::from(filename);
+                if let Some(root_dir) = root_dir {
+                    let rpath = utils::files::relative_path(root_dir, &filename);
+                    if let Some(parent) = rpath.parent() {
+                        pb.push(parent);
+                    }
+                    if let Some(fname) = filename.file_name() {
+                        pb.push(fname);
+                    }
+                }
+                pb.set_extension("");
+                if let Some(ext) = script.archive_output_ext() {
+                    pb.set_extension(ext);
+                }
+                pb.to_string_lossy().into_owned()
+            }
+            None => {
+                let mut pb = std::path::PathBuf::from(filename);
+                pb.set_extension("");
+                if let Some(ext) = script.archive_output_ext() {
+                    pb.set_extension(ext);
+                }
+                pb.to_string_lossy().into_owned()
+            }
+        };
+        if !std::fs::exists(&odir)? {
+            std::fs::create_dir_all(&odir)?;
+        }
+        for (i, filename) in script.iter_archive_filename()?.enumerate() {
+            let filename = match filename {
+                Ok(f) => f,
+                Err(e) => {
+                    report!(reporter, "Error reading archive filename: {}", e);
+                    reporter.error();
+                    if opts.backtrace {
+                        report!(reporter, "Backtrace: {}", e.backtrace());
+                    }
+                    continue;
+                }
+            };
+            let mut f = match script.open_file(i) {
+                Ok(f) => f,
+                Err(e) => {
+                    report!(reporter, "Error opening file {}: {}", filename, e);
+                    reporter.error();
+                    if opts.backtrace {
+                        report!(reporter, "Backtrace: {}", e.backtrace());
+                    }
+                    continue;
+                }
+            };
+            if opts.force_script || f.is_script() {
+                let (script_file, _) =
+                    match parse_script_from_archive(&mut f, opts, script.as_ref()) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error parsing script '{}' from archive: {}",
+                                filename,
+                                e
+                            );
+                            reporter.error();
+                            if opts.backtrace {
+                                report!(reporter, "Backtrace: {}", e.backtrace());
+                            }
+                            continue;
+                        }
+                    };
+                #[cfg(feature = "image")]
+                if script_file.is_image() {
+                    if script_file.is_multi_image() {
+                        for i in script_file.export_multi_image()? {
+                            let img_data = match i {
+                                Ok(data) => data,
+                                Err(e) => {
+                                    report!(reporter, "Error exporting image: {}", e);
+                                    reporter.error();
+                                    if opts.backtrace {
+                                        report!(reporter, "Backtrace: {}", e.backtrace());
+                                    }
+                                    continue;
+                                }
+                            };
+                            let out_type = opts.image_type.unwrap_or(types::ImageOutputType::Png);
+                            let mut out_path = std::path::PathBuf::from(&odir);
+                            if !opts.image_output_flat {
+                                out_path.push(f.name());
+                                out_path.set_extension("");
+                                out_path.push(img_data.name);
+                            } else {
+                                let name = std::path::Path::new(f.name());
+                                out_path.push(format!(
+                                    "{}_{}",
+                                    name.file_stem().unwrap_or_default().to_string_lossy(),
+                                    img_data.name
+                                ));
+                            }
+                            out_path.set_extension(out_type.as_ref());
+                            match utils::files::make_sure_dir_exists(&out_path) {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    report!(
+                                        reporter,
+                                        "Error creating parent directory for {}: {}",
+                                        out_path.display(),
+                                        e
+                                    );
+                                    reporter.error();
+                                    continue;
+                                }
+                            }
+                            if let Some(threadpool) = img_threadpool {
+                                let outpath = out_path.to_string_lossy().into_owned();
+                                let config = opts.config.clone();
+                                threadpool.execute(
+                                    move |_| {
+                                        utils::img::encode_img(
+                                            img_data.data,
+                                            out_type,
+                                            &outpath,
+                                            &config,
+                                        )
+                                        .map_err(|e| {
+                                            anyhow::anyhow!(
+                                                "Failed to encode image {}: {}",
+                                                outpath,
+                                                e
+                                            )
+                                        })
+                                    },
+                                    true,
+                                )?;
+                                continue;
+                            } else {
+                                match utils::img::encode_img(
+                                    img_data.data,
+                                    out_type,
+                                    &out_path.to_string_lossy(),
+                                    &opts.config,
+                                ) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        report!(reporter, "Error encoding image: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                                reporter.result(types::ScriptResult::Ok);
+                            }
+                        }
+                        reporter.result(types::ScriptResult::Ok);
+                        continue;
+                    }
+                    let img_data = match script_file.export_image() {
+                        Ok(data) => data,
+                        Err(e) => {
+                            report!(reporter, "Error exporting image: {}", e);
+                            reporter.error();
+                            if opts.backtrace {
+                                report!(reporter, "Backtrace: {}", e.backtrace());
+                            }
+                            continue;
+                        }
+                    };
+                    let out_type = opts.image_type.unwrap_or(types::ImageOutputType::Png);
+                    let mut out_path = std::path::PathBuf::from(&odir).join(f.name());
+                    out_path.set_extension(out_type.as_ref());
+                    match utils::files::make_sure_dir_exists(&out_path) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error creating parent directory for {}: {}",
+                                out_path.display(),
+                                e
+                            );
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                    if let Some(threadpool) = img_threadpool {
+                        let outpath = out_path.to_string_lossy().into_owned();
+                        let config = opts.config.clone();
+                        threadpool.execute(
+                            move |_| {
+                                utils::img::encode_img(img_data, out_type, &outpath, &config)
+                                    .map_err(|e| {
+                                        anyhow::anyhow!("Failed to encode image {}: {}", outpath, e)
+                                    })
+                            },
+                            true,
+                        )?;
+                        continue;
+                    } else {
+                        match utils::img::encode_img(
+                            img_data,
+                            out_type,
+                            &out_path.to_string_lossy(),
+                            &opts.config,
+                        ) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                report!(reporter, "Error encoding image: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                        reporter.result(types::ScriptResult::Ok);
+                    }
+                    continue;
+                }
+                let mut of = match &opts.output_type {
+                    Some(t) => *t,
+                    None => script_file.default_output_script_type(),
+                };
+                if !script_file.is_output_supported(of) {
+                    of = script_file.default_output_script_type();
+                }
+                if !opts.no_multi_message && !of.is_custom() && script_file.multiple_message_files()
+                {
+                    let mmes = script_file.extract_multiple_messages()?;
+                    if mmes.is_empty() {
+                        report!(reporter, "No messages found in {}", f.name());
+                        reporter.result(types::ScriptResult::Ignored);
+                        continue;
+                    }
+                    let ext = of.as_ref();
+                    let mut out_dir = std::path::PathBuf::from(&odir).join(f.name());
+                    if opts.output_no_extra_ext {
+                        out_dir.remove_all_extensions();
+                    } else {
+                        out_dir.set_extension("");
+                    }
+                    std::fs::create_dir_all(&out_dir)?;
+                    for (name, data) in mmes {
+                        let ofp = out_dir.join(name).with_extension(ext);
+                        match of {
+                            types::OutputScriptType::Json => {
+                                let enc = get_output_encoding(opts);
+                                let s = match serde_json::to_string_pretty(&data) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error serializing messages to JSON: {}",
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let b = match utils::encoding::encode_string(enc, &s, false) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error encoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let mut f = match utils::files::write_file(&ofp) {
+                                    Ok(f) => f,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                match f.write_all(&b) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing to file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::M3t
+                            | types::OutputScriptType::M3ta
+                            | types::OutputScriptType::M3tTxt => {
+                                let enc = get_output_encoding(opts);
+                                let s =
+                                    output_scripts::m3t::M3tDumper::dump(&data, opts.m3t_no_quote);
+                                let b = match utils::encoding::encode_string(enc, &s, false) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error encoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let mut f = match utils::files::write_file(&ofp) {
+                                    Ok(f) => f,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                match f.write_all(&b) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing to file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::Yaml => {
+                                let enc = get_output_encoding(opts);
+                                let s = match serde_yaml_ng::to_string(&data) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error serializing messages to YAML: {}",
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let b = match utils::encoding::encode_string(enc, &s, false) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error encoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let mut f = match utils::files::write_file(&ofp) {
+                                    Ok(f) => f,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                match f.write_all(&b) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing to file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+                                let enc = get_output_encoding(opts);
+                                let s = match output_scripts::po::PoDumper::new().dump(&data, enc) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error dumping messages to PO format: {}",
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let b = match utils::encoding::encode_string(enc, &s, false) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error encoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let mut f = match utils::files::write_file(&ofp) {
+                                    Ok(f) => f,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                match f.write_all(&b) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error writing to file {}: {}",
+                                            ofp.display(),
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+                                if let Err(e) =
+                                    dump_output_script(&ofp.to_string_lossy(), of, &data, opts)
+                                {
+                                    report!(
+                                        reporter,
+                                        "Error writing file {}: {}",
+                                        ofp.display(),
+                                        e
+                                    );
+                                    reporter.error();
+                                    continue;
+                                }
+                            }
+                            types::OutputScriptType::Custom => {}
+                        }
+                    }
+                    reporter.result(types::ScriptResult::Ok);
+                    continue;
+                }
+                let mes = if of.is_custom() {
+                    Vec::new()
+                } else {
+                    match script_file.extract_messages() {
+                        Ok(mes) => mes,
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error extracting messages from {}: {}",
+                                f.name(),
+                                e
+                            );
+                            reporter.error();
+                            if opts.backtrace {
+                                report!(reporter, "Backtrace: {}", e.backtrace());
+                            }
+                            continue;
+                        }
+                    }
+                };
+                if !of.is_custom() && mes.is_empty() {
+                    report!(reporter, "No messages found in {}", f.name());
+                    reporter.result(types::ScriptResult::Ignored);
+                    continue;
+                }
+                let mut out_path = std::path::PathBuf::from(&odir).join(f.name());
+                if opts.output_no_extra_ext {
+                    out_path.remove_all_extensions();
+                }
+                out_path.set_extension(if of.is_custom() {
+                    script_file.custom_output_extension()
+                } else {
+                    of.as_ref()
+                });
+                match utils::files::make_sure_dir_exists(&out_path) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        report!(
+                            reporter,
+                            "Error creating parent directory for {}: {}",
+                            out_path.display(),
+                            e
+                        );
+                        reporter.error();
+                        continue;
+                    }
+                }
+                match of {
+                    types::OutputScriptType::Json => {
+                        let enc = get_output_encoding(opts);
+                        let s = match serde_json::to_string_pretty(&mes) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                report!(reporter, "Error serializing messages to JSON: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let b = match utils::encoding::encode_string(enc, &s, false) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(reporter, "Error encoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let mut f = match utils::files::write_file(&out_path) {
+                            Ok(f) => f,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        match f.write_all(&b) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing to file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::M3t
+                    | types::OutputScriptType::M3ta
+                    | types::OutputScriptType::M3tTxt => {
+                        let enc = get_output_encoding(opts);
+                        let s = output_scripts::m3t::M3tDumper::dump(&mes, opts.m3t_no_quote);
+                        let b = match utils::encoding::encode_string(enc, &s, false) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(reporter, "Error encoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let mut f = match utils::files::write_file(&out_path) {
+                            Ok(f) => f,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        match f.write_all(&b) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing to file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::Yaml => {
+                        let enc = get_output_encoding(opts);
+                        let s = match serde_yaml_ng::to_string(&mes) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                report!(reporter, "Error serializing messages to YAML: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let b = match utils::encoding::encode_string(enc, &s, false) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(reporter, "Error encoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let mut f = match utils::files::write_file(&out_path) {
+                            Ok(f) => f,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        match f.write_all(&b) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing to file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+                        let enc = get_output_encoding(opts);
+                        let s = match output_scripts::po::PoDumper::new().dump(&mes, enc) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                report!(reporter, "Error dumping messages to PO format: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let b = match utils::encoding::encode_string(enc, &s, false) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(reporter, "Error encoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let mut f = match utils::files::write_file(&out_path) {
+                            Ok(f) => f,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        match f.write_all(&b) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing to file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+                        if let Err(e) =
+                            dump_output_script(&out_path.to_string_lossy(), of, &mes, opts)
+                        {
+                            report!(reporter, "Error writing file {}: {}", out_path.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                    types::OutputScriptType::Custom => {
+                        let enc = get_output_encoding(opts);
+                        if let Err(e) = script_file.custom_export(&out_path, enc) {
+                            report!(reporter, "Error exporting custom script: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                }
+            } else {
+                let out_path = std::path::PathBuf::from(&odir).join(f.name());
+                match utils::files::make_sure_dir_exists(&out_path) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        report!(
+                            reporter,
+                            "Error creating parent directory for {}: {}",
+                            out_path.display(),
+                            e
+                        );
+                        reporter.error();
+                        continue;
+                    }
+                }
+                match utils::files::write_file(&out_path) {
+                    Ok(mut fi) => match std::io::copy(&mut f, &mut fi) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error writing to file {}: {}",
+                                out_path.display(),
+                                e
+                            );
+                            reporter.error();
+                            continue;
+                        }
+                    },
+                    Err(e) => {
+                        report!(reporter, "Error writing file {}: {}", out_path.display(), e);
+                        reporter.error();
+                        continue;
+                    }
+                }
+            }
+            reporter.result(types::ScriptResult::Ok);
+        }
+        return Ok(types::ScriptResult::Ok);
+    }
+    #[cfg(feature = "image")]
+    if script.is_image() {
+        if script.is_multi_image() {
+            for i in script.export_multi_image()? {
+                let img_data = match i {
+                    Ok(data) => data,
+                    Err(e) => {
+                        report!(reporter, "Error exporting image: {}", e);
+                        reporter.error();
+                        if opts.backtrace {
+                            report!(reporter, "Backtrace: {}", e.backtrace());
+                        }
+                        continue;
+                    }
+                };
+                let out_type = opts.image_type.unwrap_or(types::ImageOutputType::Png);
+                let f = match output.as_ref() {
+                    Some(output) => {
+                        if let Some(root_dir) = root_dir {
+                            let f = std::path::PathBuf::from(filename);
+                            let mut pb = std::path::PathBuf::from(output);
+                            let rpath = utils::files::relative_path(root_dir, &f);
+                            if let Some(parent) = rpath.parent() {
+                                pb.push(parent);
+                            }
+                            if !opts.image_output_flat {
+                                if let Some(fname) = f.file_name() {
+                                    pb.push(fname);
+                                    if opts.output_no_extra_ext {
+                                        pb.remove_all_extensions();
+                                    } else {
+                                        pb.set_extension("");
+                                    }
+                                }
+                                pb.push(img_data.name);
+                            } else {
+                                pb.push(format!(
+                                    "{}_{}",
+                                    f.file_stem().unwrap_or_default().to_string_lossy(),
+                                    img_data.name
+                                ));
+                            }
+                            pb.set_extension(out_type.as_ref());
+                            pb.to_string_lossy().into_owned()
+                        } else {
+                            let mut pb = std::path::PathBuf::from(output);
+                            if opts.image_output_flat {
+                                let f = std::path::PathBuf::from(filename);
+                                pb.push(format!(
+                                    "{}_{}",
+                                    f.file_stem().unwrap_or_default().to_string_lossy(),
+                                    img_data.name
+                                ));
+                            } else {
+                                pb.push(img_data.name);
+                                if opts.output_no_extra_ext {
+                                    pb.remove_all_extensions();
+                                } else {
+                                    pb.set_extension("");
+                                }
+                            }
+                            pb.set_extension(out_type.as_ref());
+                            pb.to_string_lossy().into_owned()
+                        }
+                    }
+                    None => {
+                        let mut pb = std::path::PathBuf::from(filename);
+                        if opts.image_output_flat {
+                            let f = std::path::PathBuf::from(filename);
+                            pb.set_file_name(format!(
+                                "{}_{}",
+                                f.file_stem().unwrap_or_default().to_string_lossy(),
+                                img_data.name
+                            ));
+                        } else {
+                            if opts.output_no_extra_ext {
+                                pb.remove_all_extensions();
+                            } else {
+                                pb.set_extension("");
+                            }
+                            pb.push(img_data.name);
+                        }
+                        pb.set_extension(out_type.as_ref());
+                        pb.to_string_lossy().into_owned()
+                    }
+                };
+                match utils::files::make_sure_dir_exists(&f) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        report!(reporter, "Error creating parent directory for {}: {}", f, e);
+                        reporter.error();
+                        continue;
+                    }
+                }
+                if let Some(threadpool) = img_threadpool {
+                    let outpath = f.clone();
+                    let config = opts.config.clone();
+                    threadpool.execute(
+                        move |_| {
+                            utils::img::encode_img(img_data.data, out_type, &outpath, &config)
+                                .map_err(|e| {
+                                    anyhow::anyhow!("Failed to encode image {}: {}", outpath, e)
+                                })
+                        },
+                        true,
+                    )?;
+                    continue;
+                } else {
+                    match utils::img::encode_img(img_data.data, out_type, &f, &opts.config) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(reporter, "Error encoding image: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                    reporter.result(types::ScriptResult::Ok);
+                }
+            }
+            return Ok(types::ScriptResult::Ok);
+        }
+        let img_data = script.export_image()?;
+        let out_type = opts.image_type.unwrap_or_else(|| {
+            if root_dir.is_some() {
+                types::ImageOutputType::Png
+            } else {
+                output
+                    .as_ref()
+                    .and_then(|s| types::ImageOutputType::try_from(std::path::Path::new(s)).ok())
+                    .unwrap_or(types::ImageOutputType::Png)
+            }
+        });
+        let f = if filename == "-" {
+            String::from("-")
+        } else {
+            match output.as_ref() {
+                Some(output) => {
+                    if let Some(root_dir) = root_dir {
+                        let f = std::path::PathBuf::from(filename);
+                        let mut pb = std::path::PathBuf::from(output);
+                        let rpath = utils::files::relative_path(root_dir, &f);
+                        if let Some(parent) = rpath.parent() {
+                            pb.push(parent);
+                        }
+                        if let Some(fname) = f.file_name() {
+                            pb.push(fname);
+                        }
+                        if opts.output_no_extra_ext {
+                            pb.remove_all_extensions();
+                        }
+                        pb.set_extension(out_type.as_ref());
+                        pb.to_string_lossy().into_owned()
+                    } else {
+                        output.clone()
+                    }
+                }
+                None => {
+                    let mut pb = std::path::PathBuf::from(filename);
+                    if opts.output_no_extra_ext {
+                        pb.remove_all_extensions();
+                    }
+                    pb.set_extension(out_type.as_ref());
+                    pb.to_string_lossy().into_owned()
+                }
+            }
+        };
+        utils::files::make_sure_dir_exists(&f)?;
+        if let Some(threadpool) = img_threadpool {
+            let outpath = f.clone();
+            let config = opts.config.clone();
+            threadpool.execute(
+                move |_| {
+                    utils::img::encode_img(img_data, out_type, &outpath, &config)
+                        .map_err(|e| anyhow::anyhow!("Failed to encode image {}: {}", outpath, e))
+                },
+                true,
+            )?;
+            return Ok(types::ScriptResult::Uncount);
+        } else {
+            utils::img::encode_img(img_data, out_type, &f, &opts.config)?;
+        }
+        return Ok(types::ScriptResult::Ok);
+    }
+    let mut of = match &opts.output_type {
+        Some(t) => *t,
+        None => script.default_output_script_type(),
+    };
+    if !script.is_output_supported(of) {
+        of = script.default_output_script_type();
+    }
+    if !opts.no_multi_message && !of.is_custom() && script.multiple_message_files() {
+        let mmes = script.extract_multiple_messages()?;
+        if mmes.is_empty() {
+            report!(reporter, "No messages found");
+            return Ok(types::ScriptResult::Ignored);
+        }
+        let ext = of.as_ref();
+        let out_dir = if let Some(output) = output.as_ref() {
+            if let Some(root_dir) = root_dir {
+                let f = std::path::PathBuf::from(filename);
+                let mut pb = std::path::PathBuf::from(output);
+                let rpath = utils::files::relative_path(root_dir, &f);
+                if let Some(parent) = rpath.parent() {
+                    pb.push(parent);
+                }
+                if let Some(fname) = f.file_name() {
+                    pb.push(fname);
+                }
+                if opts.output_no_extra_ext {
+                    pb.remove_all_extensions();
+                } else {
+                    pb.set_extension("");
+                }
+                pb.to_string_lossy().into_owned()
+            } else {
+                output.clone()
+            }
+        } else {
+            let mut pb = std::path::PathBuf::from(filename);
+            if opts.output_no_extra_ext {
+                pb.remove_all_extensions();
+            } else {
+                pb.set_extension("");
+            }
+            pb.to_string_lossy().into_owned()
+        };
+        std::fs::create_dir_all(&out_dir)?;
+        let outdir = std::path::PathBuf::from(&out_dir);
+        for (name, data) in mmes {
+            let ofp = outdir.join(name).with_extension(ext);
+            match of {
+                types::OutputScriptType::Json => {
+                    let enc = get_output_encoding(opts);
+                    let s = match serde_json::to_string_pretty(&data) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            report!(reporter, "Error serializing messages to JSON: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let b = match utils::encoding::encode_string(enc, &s, false) {
+                        Ok(b) => b,
+                        Err(e) => {
+                            report!(reporter, "Error encoding string: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let mut f = match utils::files::write_file(&ofp) {
+                        Ok(f) => f,
+                        Err(e) => {
+                            report!(reporter, "Error writing file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    match f.write_all(&b) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(reporter, "Error writing to file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                }
+                types::OutputScriptType::M3t
+                | types::OutputScriptType::M3ta
+                | types::OutputScriptType::M3tTxt => {
+                    let enc = get_output_encoding(opts);
+                    let s = output_scripts::m3t::M3tDumper::dump(&data, opts.m3t_no_quote);
+                    let b = match utils::encoding::encode_string(enc, &s, false) {
+                        Ok(b) => b,
+                        Err(e) => {
+                            report!(reporter, "Error encoding string: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let mut f = match utils::files::write_file(&ofp) {
+                        Ok(f) => f,
+                        Err(e) => {
+                            report!(reporter, "Error writing file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    match f.write_all(&b) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(reporter, "Error writing to file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                }
+                types::OutputScriptType::Yaml => {
+                    let enc = get_output_encoding(opts);
+                    let s = match serde_yaml_ng::to_string(&data) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            report!(reporter, "Error serializing messages to YAML: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let b = match utils::encoding::encode_string(enc, &s, false) {
+                        Ok(b) => b,
+                        Err(e) => {
+                            report!(reporter, "Error encoding string: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let mut f = match utils::files::write_file(&ofp) {
+                        Ok(f) => f,
+                        Err(e) => {
+                            report!(reporter, "Error writing file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    match f.write_all(&b) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(reporter, "Error writing to file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                }
+                types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+                    let enc = get_output_encoding(opts);
+                    let s = match output_scripts::po::PoDumper::new().dump(&data, enc) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            report!(reporter, "Error dumping messages to PO format: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let b = match utils::encoding::encode_string(enc, &s, false) {
+                        Ok(b) => b,
+                        Err(e) => {
+                            report!(reporter, "Error encoding string: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let mut f = match utils::files::write_file(&ofp) {
+                        Ok(f) => f,
+                        Err(e) => {
+                            report!(reporter, "Error writing file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    match f.write_all(&b) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(reporter, "Error writing to file {}: {}", ofp.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                }
+                types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+                    if let Err(e) = dump_output_script(&ofp.to_string_lossy(), of, &data, opts) {
+                        report!(reporter, "Error writing file {}: {}", ofp.display(), e);
+                        reporter.error();
+                        continue;
+                    }
+                }
+                types::OutputScriptType::Custom => {}
+            }
+            reporter.result(types::ScriptResult::Ok);
+        }
+        return Ok(types::ScriptResult::Ok);
+    }
+    let mes = if of.is_custom() {
+        Vec::new()
+    } else {
+        script.extract_messages()?
+    };
+    if !of.is_custom() && mes.is_empty() {
+        report!(reporter, "No messages found");
+        return Ok(types::ScriptResult::Ignored);
+    }
+    let ext = if of.is_custom() {
+        script.custom_output_extension()
+    } else {
+        of.as_ref()
+    };
+    let f = if filename == "-" {
+        String::from("-")
+    } else {
+        match output.as_ref() {
+            Some(output) => {
+                if let Some(root_dir) = root_dir {
+                    let f = std::path::PathBuf::from(filename);
+                    let mut pb = std::path::PathBuf::from(output);
+                    let rpath = utils::files::relative_path(root_dir, &f);
+                    if let Some(parent) = rpath.parent() {
+                        pb.push(parent);
+                    }
+                    if let Some(fname) = f.file_name() {
+                        pb.push(fname);
+                    }
+                    if opts.output_no_extra_ext {
+                        pb.remove_all_extensions();
+                    }
+                    pb.set_extension(ext);
+                    pb.to_string_lossy().into_owned()
+                } else {
+                    output.clone()
+                }
+            }
+            None => {
+                let mut pb = std::path::PathBuf::from(filename);
+                if opts.output_no_extra_ext {
+                    pb.remove_all_extensions();
+                }
+                pb.set_extension(ext);
+                pb.to_string_lossy().into_owned()
+            }
+        }
+    };
+    utils::files::make_sure_dir_exists(&f)?;
+    match of {
+        types::OutputScriptType::Json => {
+            let enc = get_output_encoding(opts);
+            let s = serde_json::to_string_pretty(&mes)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            let mut f = utils::files::write_file(&f)?;
+            f.write_all(&b)?;
+        }
+        types::OutputScriptType::M3t
+        | types::OutputScriptType::M3ta
+        | types::OutputScriptType::M3tTxt => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::m3t::M3tDumper::dump(&mes, opts.m3t_no_quote);
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            let mut f = utils::files::write_file(&f)?;
+            f.write_all(&b)?;
+        }
+        types::OutputScriptType::Yaml => {
+            let enc = get_output_encoding(opts);
+            let s = serde_yaml_ng::to_string(&mes)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            let mut f = utils::files::write_file(&f)?;
+            f.write_all(&b)?;
+        }
+        types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+            let enc = get_output_encoding(opts);
+            let s = output_scripts::po::PoDumper::new().dump(&mes, enc)?;
+            let b = utils::encoding::encode_string(enc, &s, false)?;
+            let mut f = utils::files::write_file(&f)?;
+            f.write_all(&b)?;
+        }
+        types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+            dump_output_script(&f, of, &mes, opts)?;
+        }
+        types::OutputScriptType::Custom => {
+            let enc = get_output_encoding(opts);
+            script.custom_export(f.as_ref(), enc)?;
+        }
+    }
+    Ok(types::ScriptResult::Ok)
+}
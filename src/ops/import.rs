@@ -0,0 +1,1305 @@
+//! Importing messages into scripts.
+use super::*;
+use crate::ext::path::PathBufExt;
+use crate::{format, output_scripts, types, utils};
+
+type DepFiles = std::sync::Mutex<HashMap<String, Vec<String>>>;
+
+/// Imports messages into a script file or all script files in a directory.
+///
+/// Scripts are imported in parallel if [ImportOptions::jobs] is greater than 1.
+/// DPAK files patched by DREF images, the dependency file and SJIS tunnel mapping are
+/// written once after all scripts are imported.
+pub fn import_files(
+    input: &str,
+    opts: &Arc<Options>,
+    imp_cfg: &Arc<ImportOptions>,
+    reporter: &Arc<dyn Reporter>,
+) -> anyhow::Result<()> {
+    let (scripts, root_dir) = collect_inputs(input, Some(&imp_cfg.patched), |input| {
+        utils::files::collect_files(input, opts.recursive, false)
+    })?;
+    #[cfg(feature = "emote-img")]
+    let dpak_session = Arc::new(scripts::emote::dref::DpakSession::new());
+    #[cfg(feature = "emote-img")]
+    let opts = &Arc::new(Options {
+        config: Arc::new(ExtraConfig {
+            emote_dpak_session: Some(dpak_session.clone()),
+            ..(*opts.config).clone()
+        }),
+        ..(**opts).clone()
+    });
+    let workers = if imp_cfg.jobs > 1 {
+        Some(
+            utils::threadpool::ThreadPool::<()>::new(imp_cfg.jobs, Some("import-worker-"), true)
+                .map_err(|e| anyhow::anyhow!("Failed to create import thread pool: {}", e))?,
+        )
+    } else {
+        None
+    };
+    if let Some(sjis_tunnel) = &imp_cfg.sjis_tunnel {
+        let tunnel = utils::sjis_tunnel::SjisTunnel::load_or_new(sjis_tunnel)
+            .map_err(|e| anyhow::anyhow!("Error loading SJIS tunnel mapping: {}", e))?;
+        utils::sjis_tunnel::enable(tunnel);
+    }
+    let dep_files = imp_cfg
+        .dep_file
+        .as_ref()
+        .map(|_| Arc::new(DepFiles::new(HashMap::new())));
+    for script in scripts {
+        match workers.as_ref() {
+            Some(workers) => {
+                let opts = opts.clone();
+                let imp_cfg = imp_cfg.clone();
+                let job_reporter = reporter.clone();
+                let root_dir = root_dir.map(|s| s.to_path_buf());
+                let dep_files = dep_files.clone();
+                if let Err(e) = workers.execute(
+                    move |_| {
+                        import_one(
+                            &script,
+                            &opts,
+                            &imp_cfg,
+                            root_dir.as_deref(),
+                            dep_files.as_deref(),
+                            job_reporter.as_ref(),
+                        )
+                    },
+                    true,
+                ) {
+                    reporter.error();
+                    report!(reporter, "Error executing import worker: {}", e);
+                }
+            }
+            None => import_one(
+                &script,
+                opts,
+                imp_cfg,
+                root_dir,
+                dep_files.as_deref(),
+                reporter.as_ref(),
+            ),
+        }
+    }
+    if let Some(workers) = workers.as_ref() {
+        workers.join();
+    }
+    #[cfg(feature = "emote-img")]
+    match dpak_session.save(&opts.config) {
+        Ok(0) => {}
+        Ok(count) => report!(reporter, "Saved {} patched DPAK files", count),
+        Err(e) => {
+            reporter.error();
+            report!(reporter, "Error saving DPAK files: {}", e);
+        }
+    }
+    if let Some(sjis_tunnel) = &imp_cfg.sjis_tunnel
+        && let Some(tunnel) = utils::sjis_tunnel::disable()
+    {
+        let bin = match &imp_cfg.sjis_tunnel_bin {
+            Some(bin) => bin.clone(),
+            None => std::path::Path::new(sjis_tunnel)
+                .with_file_name("sjis_ext.bin")
+                .to_string_lossy()
+                .into_owned(),
+        };
+        if let Err(e) = tunnel.save(sjis_tunnel, &bin) {
+            reporter.error();
+            report!(reporter, "Error saving SJIS tunnel mapping: {}", e);
+        } else if !tunnel.is_empty() {
+            report!(
+                reporter,
+                "Tunneled {} characters. Mapping saved to {}",
+                tunnel.len(),
+                sjis_tunnel
+            );
+        }
+    }
+    if let (Some(dep_file), Some(dep_files)) = (&imp_cfg.dep_file, dep_files) {
+        let dep_files = crate::ext::mutex::MutexExt::lock_blocking(dep_files.as_ref());
+        write_dep_file(dep_file, &dep_files)
+            .map_err(|e| anyhow::anyhow!("Error writing dependency file {}: {}", dep_file, e))?;
+    }
+    Ok(())
+}
+
+fn import_one(
+    script: &str,
+    opts: &Options,
+    imp_cfg: &ImportOptions,
+    root_dir: Option<&std::path::Path>,
+    dep_files: Option<&DepFiles>,
+    reporter: &dyn Reporter,
+) {
+    let mut dep_graph = dep_files.map(|_| (String::new(), Vec::new()));
+    match import_script(
+        script,
+        opts,
+        imp_cfg,
+        root_dir,
+        dep_graph.as_mut(),
+        reporter,
+    ) {
+        Ok(s) => {
+            reporter.result(s);
+            if let (Some((fname, deps)), Some(dep_files)) = (dep_graph, dep_files) {
+                crate::ext::mutex::MutexExt::lock_blocking(dep_files).insert(fname, deps);
+            }
+        }
+        Err(e) => report_error(reporter, opts, &format!("Error importing {}", script), e),
+    }
+}
+
+fn write_dep_file(path: &str, dep_files: &HashMap<String, Vec<String>>) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut df = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for (fname, deps) in dep_files.iter() {
+        write!(df, "{}:", escape_dep_string(fname))?;
+        for d in deps {
+            write!(df, " {}", escape_dep_string(d))?;
+        }
+        writeln!(df)?;
+    }
+    df.flush()
+}
+
+/// Imports messages into a script file and writes the patched script.
+///
+/// Files used are recorded in `dep_graph` as `(patched file, dependencies)`.
+/// DPAK files patched by imported DREF images are written at the end of [import_files],
+/// or before returning if the script is imported outside of it.
+pub fn import_script(
+    filename: &str,
+    opts: &Options,
+    imp_cfg: &ImportOptions,
+    root_dir: Option<&std::path::Path>,
+    mut dep_graph: Option<&mut (String, Vec<String>)>,
+    reporter: &dyn Reporter,
+) -> anyhow::Result<types::ScriptResult> {
+    let name_csv = imp_cfg.name_table.as_ref();
+    let repl = imp_cfg.replacement.as_ref();
+    report!(reporter, "Importing {}", filename);
+    if let Some(dep_graph) = dep_graph.as_mut() {
+        dep_graph.1.push(filename.to_string());
+    }
+    let (script, builder) = parse_script(filename, opts)?;
+    if script.is_archive() {
+        let odir = {
+            let mut pb = std::path::PathBuf::from(&imp_cfg.output);
+            let filename = std::path::PathBuf::from(filename);
+            if let Some(root_dir) = root_dir {
+                let rpath = utils::files::relative_path(root_dir, &filename);
+                if let Some(parent) = rpath.parent() {
+                    pb.push(parent);
+                }
+                if let Some(fname) = filename.file_name() {
+                    pb.push(fname);
+                }
+            }
+            pb.set_extension("");
+            if let Some(ext) = script.archive_output_ext() {
+                pb.set_extension(ext);
+            }
+            pb.to_string_lossy().into_owned()
+        };
+        let files: Vec<_> = script.iter_archive_filename()?.collect();
+        let files = files.into_iter().filter_map(|f| f.ok()).collect::<Vec<_>>();
+        let patched_f = if let Some(root_dir) = root_dir {
+            let f = std::path::PathBuf::from(filename);
+            let mut pb = std::path::PathBuf::from(&imp_cfg.patched);
+            let rpath = utils::files::relative_path(root_dir, &f);
+            if let Some(parent) = rpath.parent() {
+                pb.push(parent);
+            }
+            if let Some(fname) = f.file_name() {
+                pb.push(fname);
+            }
+            pb.set_extension(builder.extensions().first().unwrap_or(&""));
+            pb.to_string_lossy().into_owned()
+        } else {
+            imp_cfg.patched.clone()
+        };
+        if let Some(dep_graph) = dep_graph.as_mut() {
+            dep_graph.0 = patched_f.clone();
+        }
+        let files: Vec<_> = files.iter().map(|s| s.as_str()).collect();
+        let pencoding = get_patched_encoding(imp_cfg, builder.as_ref());
+        let enc = get_patched_archive_encoding(imp_cfg, builder.as_ref(), pencoding);
+        utils::files::make_sure_dir_exists(&patched_f)?;
+        let mut arch = builder.create_archive(&patched_f, &files, enc, &opts.config)?;
+        for (index, filename) in script.iter_archive_filename()?.enumerate() {
+            let filename = match filename {
+                Ok(f) => f,
+                Err(e) => {
+                    report!(reporter, "Error reading archive filename: {}", e);
+                    reporter.error();
+                    if opts.backtrace {
+                        report!(reporter, "Backtrace: {}", e.backtrace());
+                    }
+                    continue;
+                }
+            };
+            let mut f = match script.open_file(index) {
+                Ok(f) => f,
+                Err(e) => {
+                    report!(reporter, "Error opening file {}: {}", filename, e);
+                    reporter.error();
+                    if opts.backtrace {
+                        report!(reporter, "Backtrace: {}", e.backtrace());
+                    }
+                    continue;
+                }
+            };
+            if opts.force_script || f.is_script() {
+                let mut writer = arch.new_file(f.name(), None)?;
+                let (script_file, _) =
+                    match parse_script_from_archive(&mut f, opts, script.as_ref()) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error parsing script '{}' from archive: {}",
+                                filename,
+                                e
+                            );
+                            reporter.error();
+                            if opts.backtrace {
+                                report!(reporter, "Backtrace: {}", e.backtrace());
+                            }
+                            continue;
+                        }
+                    };
+                let mut of = match &opts.output_type {
+                    Some(t) => *t,
+                    None => script_file.default_output_script_type(),
+                };
+                if !script_file.is_output_supported(of) {
+                    of = script_file.default_output_script_type();
+                }
+                if !opts.no_multi_message && !of.is_custom() && script_file.multiple_message_files()
+                {
+                    let out_dir = std::path::PathBuf::from(&odir)
+                        .join(f.name())
+                        .with_extension("");
+                    let outfiles = utils::files::find_ext_files(
+                        &out_dir.to_string_lossy(),
+                        false,
+                        &[of.as_ref()],
+                    )?;
+                    if outfiles.is_empty() {
+                        if imp_cfg.warn_when_output_file_not_found {
+                            report!(
+                                reporter,
+                                "Warning: No output files found in {}, using file from original archive.",
+                                out_dir.display()
+                            );
+                            reporter.warning();
+                        } else {
+                            reporter.result(types::ScriptResult::Ignored);
+                        }
+                        continue;
+                    }
+                    if let Some(dep_graph) = dep_graph.as_mut() {
+                        dep_graph.1.extend_from_slice(&outfiles);
+                    }
+                    let fmt = match &imp_cfg.patched_format {
+                        Some(fmt) => fmt.clone(),
+                        None => script.default_format_type(),
+                    };
+                    let mut mmes = std::collections::HashMap::new();
+                    for out_f in outfiles {
+                        let name = utils::files::relative_path(&out_dir, &out_f)
+                            .with_extension("")
+                            .to_string_lossy()
+                            .into_owned();
+                        let mut mes = match of {
+                            types::OutputScriptType::Json => {
+                                let enc = get_output_encoding(opts);
+                                let b = match utils::files::read_file(&out_f) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error reading file {}: {}", out_f, e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        report!(reporter, "Error decoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                match serde_json::from_str::<Vec<types::Message>>(&s) {
+                                    Ok(mes) => mes,
+                                    Err(e) => {
+                                        report!(reporter, "Error parsing JSON: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::M3t
+                            | types::OutputScriptType::M3ta
+                            | types::OutputScriptType::M3tTxt => {
+                                let enc = get_output_encoding(opts);
+                                let b = match utils::files::read_file(&out_f) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error reading file {}: {}", out_f, e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        report!(reporter, "Error decoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let mut parser = output_scripts::m3t::M3tParser::new(
+                                    &s,
+                                    opts.llm_trans_mark.as_deref(),
+                                    opts.m3t_use_original_text,
+                                );
+                                match parser.parse() {
+                                    Ok(mes) => mes,
+                                    Err(e) => {
+                                        report!(reporter, "Error parsing M3T: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::Yaml => {
+                                let enc = get_output_encoding(opts);
+                                let b = match utils::files::read_file(&out_f) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error reading file {}: {}", out_f, e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        report!(reporter, "Error decoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                match serde_yaml_ng::from_str::<Vec<types::Message>>(&s) {
+                                    Ok(mes) => mes,
+                                    Err(e) => {
+                                        report!(reporter, "Error parsing YAML: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+                                let enc = get_output_encoding(opts);
+                                let b = match utils::files::read_file(&out_f) {
+                                    Ok(b) => b,
+                                    Err(e) => {
+                                        report!(reporter, "Error reading file {}: {}", out_f, e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                                    Ok(s) => s,
+                                    Err(e) => {
+                                        report!(reporter, "Error decoding string: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                };
+                                match output_scripts::po::PoParser::new(
+                                    &s,
+                                    opts.llm_trans_mark.as_deref(),
+                                )
+                                .parse()
+                                {
+                                    Ok(mes) => mes,
+                                    Err(e) => {
+                                        report!(reporter, "Error parsing PO: {}", e);
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+                                match parse_output_script(&out_f, of, &output_script_opts(opts)) {
+                                    Ok(mes) => mes,
+                                    Err(e) => {
+                                        report!(
+                                            reporter,
+                                            "Error parsing {:?} {}: {}",
+                                            of,
+                                            out_f,
+                                            e
+                                        );
+                                        reporter.error();
+                                        continue;
+                                    }
+                                }
+                            }
+                            types::OutputScriptType::Custom => Vec::new(),
+                        };
+                        if mes.is_empty() {
+                            report!(
+                                reporter,
+                                "No messages found in {}, using file from original archive.",
+                                out_f
+                            );
+                            continue;
+                        }
+                        if let Some(name_table) = name_csv {
+                            utils::name_replacement::replace_message(&mut mes, name_table);
+                        }
+                        format::fmt_message(
+                            &mut mes,
+                            fmt.clone(),
+                            *builder.script_type(),
+                            builder.inline_markup(),
+                        )?;
+                        mmes.insert(name, mes);
+                    }
+                    if mmes.is_empty() {
+                        reporter.result(types::ScriptResult::Ignored);
+                        continue;
+                    }
+                    let encoding = get_patched_encoding(imp_cfg, builder.as_ref());
+                    match script_file.import_multiple_messages(
+                        mmes,
+                        writer,
+                        f.name(),
+                        encoding,
+                        repl,
+                    ) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error importing messages to script '{}': {}",
+                                filename,
+                                e
+                            );
+                            reporter.error();
+                            if opts.backtrace {
+                                report!(reporter, "Backtrace: {}", e.backtrace());
+                            }
+                            continue;
+                        }
+                    }
+                    reporter.result(types::ScriptResult::Ok);
+                    continue;
+                }
+                #[cfg(feature = "image")]
+                if script_file.is_image() {
+                    let out_type = opts.image_type.unwrap_or(types::ImageOutputType::Png);
+                    let mut out_path = std::path::PathBuf::from(&odir).join(f.name());
+                    if opts.output_no_extra_ext {
+                        out_path.remove_all_extensions();
+                    }
+                    out_path.set_extension(out_type.as_ref());
+                    if !out_path.exists() {
+                        out_path = std::path::PathBuf::from(&odir).join(f.name());
+                        if !out_path.exists() {
+                            if imp_cfg.warn_when_output_file_not_found {
+                                report!(
+                                    reporter,
+                                    "Warning: File {} does not exist, using file from original archive.",
+                                    out_path.display()
+                                );
+                                reporter.warning();
+                            }
+                            match std::io::copy(&mut f, &mut writer) {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    report!(
+                                        reporter,
+                                        "Error writing to file {}: {}",
+                                        out_path.display(),
+                                        e
+                                    );
+                                    reporter.error();
+                                    continue;
+                                }
+                            }
+                        } else {
+                            if let Some(dep_graph) = dep_graph.as_mut() {
+                                dep_graph.1.push(out_path.to_string_lossy().into_owned());
+                            }
+                            let file = match std::fs::File::open(&out_path) {
+                                Ok(f) => f,
+                                Err(e) => {
+                                    report!(
+                                        reporter,
+                                        "Error opening file {}: {}",
+                                        out_path.display(),
+                                        e
+                                    );
+                                    reporter.error();
+                                    continue;
+                                }
+                            };
+                            let mut f = std::io::BufReader::new(file);
+                            match std::io::copy(&mut f, &mut writer) {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    report!(
+                                        reporter,
+                                        "Error writing to file {}: {}",
+                                        out_path.display(),
+                                        e
+                                    );
+                                    reporter.error();
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+                    if let Some(dep_graph) = dep_graph.as_mut() {
+                        dep_graph.1.push(out_path.to_string_lossy().into_owned());
+                    }
+                    let img_data =
+                        match utils::img::decode_img(out_type, &out_path.to_string_lossy()) {
+                            Ok(data) => data,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error decoding image {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                    if let Err(err) =
+                        script_file.import_image(img_data, &out_path.to_string_lossy(), writer)
+                    {
+                        report!(
+                            reporter,
+                            "Error importing image to script '{}': {}",
+                            filename,
+                            err
+                        );
+                        reporter.error();
+                        if opts.backtrace {
+                            report!(reporter, "Backtrace: {}", err.backtrace());
+                        }
+                        continue;
+                    }
+                    continue;
+                }
+                let mut out_path = std::path::PathBuf::from(&odir).join(f.name());
+                if opts.output_no_extra_ext {
+                    out_path.remove_all_extensions();
+                }
+                let ext = if of.is_custom() {
+                    script_file.custom_output_extension()
+                } else {
+                    of.as_ref()
+                };
+                out_path.set_extension(ext);
+                if !out_path.exists() {
+                    out_path = std::path::PathBuf::from(&odir).join(f.name());
+                    if !out_path.exists() {
+                        if imp_cfg.warn_when_output_file_not_found {
+                            report!(
+                                reporter,
+                                "Warning: File {} does not exist, using file from original archive.",
+                                out_path.display()
+                            );
+                            reporter.warning();
+                        }
+                        match std::io::copy(&mut f, &mut writer) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing to file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                        reporter.result(types::ScriptResult::Ok);
+                        continue;
+                    } else {
+                        if let Some(dep_graph) = dep_graph.as_mut() {
+                            dep_graph.1.push(out_path.to_string_lossy().into_owned());
+                        }
+                        let file = match std::fs::File::open(&out_path) {
+                            Ok(f) => f,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error opening file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let mut f = std::io::BufReader::new(file);
+                        match std::io::copy(&mut f, &mut writer) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error writing to file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                        reporter.result(types::ScriptResult::Ok);
+                        continue;
+                    }
+                }
+                if let Some(dep_graph) = dep_graph.as_mut() {
+                    dep_graph.1.push(out_path.to_string_lossy().into_owned());
+                }
+                let mut mes = match of {
+                    types::OutputScriptType::Json => {
+                        let enc = get_output_encoding(opts);
+                        let b = match utils::files::read_file(&out_path) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error reading file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                report!(reporter, "Error decoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        match serde_json::from_str::<Vec<types::Message>>(&s) {
+                            Ok(mes) => mes,
+                            Err(e) => {
+                                report!(reporter, "Error parsing JSON: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::M3t
+                    | types::OutputScriptType::M3ta
+                    | types::OutputScriptType::M3tTxt => {
+                        let enc = get_output_encoding(opts);
+                        let b = match utils::files::read_file(&out_path) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error reading file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                report!(reporter, "Error decoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let mut parser = output_scripts::m3t::M3tParser::new(
+                            &s,
+                            opts.llm_trans_mark.as_deref(),
+                            opts.m3t_use_original_text,
+                        );
+                        match parser.parse() {
+                            Ok(mes) => mes,
+                            Err(e) => {
+                                report!(reporter, "Error parsing M3T: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::Yaml => {
+                        let enc = get_output_encoding(opts);
+                        let b = match utils::files::read_file(&out_path) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error reading file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                report!(reporter, "Error decoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        match serde_yaml_ng::from_str::<Vec<types::Message>>(&s) {
+                            Ok(mes) => mes,
+                            Err(e) => {
+                                report!(reporter, "Error parsing YAML: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+                        let enc = get_output_encoding(opts);
+                        let b = match utils::files::read_file(&out_path) {
+                            Ok(b) => b,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error reading file {}: {}",
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let s = match utils::encoding::decode_to_string(enc, &b, true) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                report!(reporter, "Error decoding string: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        };
+                        let mut parser =
+                            output_scripts::po::PoParser::new(&s, opts.llm_trans_mark.as_deref());
+                        match parser.parse() {
+                            Ok(mes) => mes,
+                            Err(e) => {
+                                report!(reporter, "Error parsing PO: {}", e);
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+                        match parse_output_script(
+                            &out_path.to_string_lossy(),
+                            of,
+                            &output_script_opts(opts),
+                        ) {
+                            Ok(mes) => mes,
+                            Err(e) => {
+                                report!(
+                                    reporter,
+                                    "Error parsing {:?} {}: {}",
+                                    of,
+                                    out_path.display(),
+                                    e
+                                );
+                                reporter.error();
+                                continue;
+                            }
+                        }
+                    }
+                    types::OutputScriptType::Custom => {
+                        Vec::new() // Custom scripts handle their own messages
+                    }
+                };
+                if !of.is_custom() && mes.is_empty() {
+                    report!(reporter, "No messages found in {}", f.name());
+                    reporter.result(types::ScriptResult::Ignored);
+                    continue;
+                }
+                let encoding = get_patched_encoding(imp_cfg, builder.as_ref());
+                if of.is_custom() {
+                    let enc = get_output_encoding(opts);
+                    match script_file.custom_import(
+                        &out_path.to_string_lossy(),
+                        writer,
+                        encoding,
+                        enc,
+                    ) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(reporter, "Error importing custom script: {}", e);
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                    reporter.result(types::ScriptResult::Ok);
+                    continue;
+                }
+                let fmt = match &imp_cfg.patched_format {
+                    Some(fmt) => fmt.clone(),
+                    None => script_file.default_format_type(),
+                };
+                if let Some(name_table) = name_csv {
+                    utils::name_replacement::replace_message(&mut mes, name_table);
+                }
+                format::fmt_message(
+                    &mut mes,
+                    fmt,
+                    *builder.script_type(),
+                    builder.inline_markup(),
+                )?;
+                if let Err(e) = script_file.import_messages(
+                    mes,
+                    writer,
+                    &out_path.to_string_lossy(),
+                    encoding,
+                    repl,
+                ) {
+                    report!(reporter, "Error importing messages: {}", e);
+                    reporter.error();
+                    continue;
+                }
+            } else {
+                let out_path = std::path::PathBuf::from(&odir).join(f.name());
+                let size = if out_path.is_file() {
+                    match std::fs::metadata(&out_path) {
+                        Ok(meta) => Some(meta.len()),
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error getting metadata for file {}: {}",
+                                out_path.display(),
+                                e
+                            );
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+                let mut writer = arch.new_file_non_seek(f.name(), size)?;
+                if out_path.is_file() {
+                    if let Some(dep_graph) = dep_graph.as_mut() {
+                        dep_graph.1.push(out_path.to_string_lossy().into_owned());
+                    }
+                    let f = match std::fs::File::open(&out_path) {
+                        Ok(f) => f,
+                        Err(e) => {
+                            report!(reporter, "Error opening file {}: {}", out_path.display(), e);
+                            reporter.error();
+                            continue;
+                        }
+                    };
+                    let mut f = std::io::BufReader::new(f);
+                    match std::io::copy(&mut f, &mut writer) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error writing to file {}: {}",
+                                out_path.display(),
+                                e
+                            );
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                } else {
+                    report!(
+                        reporter,
+                        "Warning: File {} does not exist, use file from original archive.",
+                        out_path.display()
+                    );
+                    reporter.warning();
+                    match std::io::copy(&mut f, &mut writer) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            report!(
+                                reporter,
+                                "Error writing to file {}: {}",
+                                out_path.display(),
+                                e
+                            );
+                            reporter.error();
+                            continue;
+                        }
+                    }
+                }
+            }
+            reporter.result(types::ScriptResult::Ok);
+        }
+        arch.write_header()?;
+        return Ok(types::ScriptResult::Ok);
+    }
+    #[cfg(feature = "image")]
+    if script.is_image() {
+        let out_type = opts.image_type.unwrap_or_else(|| {
+            if root_dir.is_some() {
+                types::ImageOutputType::Png
+            } else {
+                types::ImageOutputType::try_from(std::path::Path::new(&imp_cfg.output))
+                    .unwrap_or(types::ImageOutputType::Png)
+            }
+        });
+        let patched_f = if let Some(root_dir) = root_dir {
+            let f = std::path::PathBuf::from(filename);
+            let mut pb = std::path::PathBuf::from(&imp_cfg.patched);
+            let rpath = utils::files::relative_path(root_dir, &f);
+            if let Some(parent) = rpath.parent() {
+                pb.push(parent);
+            }
+            if let Some(fname) = f.file_name() {
+                pb.push(fname);
+            }
+            pb.set_extension(builder.extensions().first().unwrap_or(&""));
+            pb.to_string_lossy().into_owned()
+        } else {
+            imp_cfg.patched.clone()
+        };
+        if let Some(dep_graph) = dep_graph.as_mut() {
+            dep_graph.0 = patched_f.clone();
+        }
+        utils::files::make_sure_dir_exists(&patched_f)?;
+        if script.is_multi_image() {
+            let f = std::path::PathBuf::from(filename);
+            let mut dir = std::path::PathBuf::from(&imp_cfg.output);
+            if let Some(root_dir) = root_dir {
+                let rpath = utils::files::relative_path(root_dir, &f);
+                if let Some(parent) = rpath.parent() {
+                    dir.push(parent);
+                }
+                if !opts.image_output_flat
+                    && let Some(fname) = f.file_name()
+                {
+                    dir.push(fname);
+                    if opts.output_no_extra_ext {
+                        dir.remove_all_extensions();
+                    } else {
+                        dir.set_extension("");
+                    }
+                }
+            }
+            let prefix = if opts.image_output_flat {
+                Some(format!(
+                    "{}_",
+                    f.file_stem().unwrap_or_default().to_string_lossy()
+                ))
+            } else {
+                None
+            };
+            let mut images = Vec::new();
+            for (name, path) in find_multi_images(&dir, prefix.as_deref(), out_type)? {
+                let path = path.to_string_lossy().into_owned();
+                if let Some(dep_graph) = dep_graph.as_mut() {
+                    dep_graph.1.push(path.clone());
+                }
+                let data = utils::img::decode_img(out_type, &path)?;
+                images.push(types::ImageDataWithName { name, data });
+            }
+            if images.is_empty() {
+                report!(reporter, "No images found in {}", dir.display());
+                return Ok(types::ScriptResult::Ignored);
+            }
+            script.import_multi_image_filename(images, &patched_f)?;
+            return Ok(types::ScriptResult::Ok);
+        }
+        let out_f = if let Some(root_dir) = root_dir {
+            let f = std::path::PathBuf::from(filename);
+            let mut pb = std::path::PathBuf::from(&imp_cfg.output);
+            let rpath = utils::files::relative_path(root_dir, &f);
+            if let Some(parent) = rpath.parent() {
+                pb.push(parent);
+            }
+            if let Some(fname) = f.file_name() {
+                pb.push(fname);
+            }
+            if opts.output_no_extra_ext {
+                pb.remove_all_extensions();
+            }
+            pb.set_extension(out_type.as_ref());
+            pb.to_string_lossy().into_owned()
+        } else {
+            imp_cfg.output.clone()
+        };
+        if let Some(dep_graph) = dep_graph.as_mut() {
+            dep_graph.1.push(out_f.clone());
+        }
+        let data = utils::img::decode_img(out_type, &out_f)?;
+        script.import_image_filename(data, &out_f, &patched_f)?;
+        return Ok(types::ScriptResult::Ok);
+    }
+    let mut of = match &opts.output_type {
+        Some(t) => *t,
+        None => script.default_output_script_type(),
+    };
+    if !script.is_output_supported(of) {
+        of = script.default_output_script_type();
+    }
+    if !opts.no_multi_message && !of.is_custom() && script.multiple_message_files() {
+        let out_dir = if let Some(root_dir) = root_dir {
+            let f = std::path::PathBuf::from(filename);
+            let mut pb = std::path::PathBuf::from(&imp_cfg.output);
+            let rpath = utils::files::relative_path(root_dir, &f);
+            if let Some(parent) = rpath.parent() {
+                pb.push(parent);
+            }
+            if let Some(fname) = f.file_name() {
+                pb.push(fname);
+            }
+            if opts.output_no_extra_ext {
+                pb.remove_all_extensions();
+            } else {
+                pb.set_extension("");
+            }
+            pb.to_string_lossy().into_owned()
+        } else {
+            imp_cfg.output.clone()
+        };
+        let outfiles = utils::files::find_ext_files(&out_dir, false, &[of.as_ref()])?;
+        if outfiles.is_empty() {
+            report!(reporter, "No output files found");
+            return Ok(types::ScriptResult::Ignored);
+        }
+        if let Some(dep_graph) = dep_graph.as_mut() {
+            dep_graph.1.extend_from_slice(&outfiles);
+        }
+        let fmt = match &imp_cfg.patched_format {
+            Some(fmt) => fmt.clone(),
+            None => script.default_format_type(),
+        };
+        let mut mmes = std::collections::HashMap::new();
+        for out_f in outfiles {
+            let name = utils::files::relative_path(&out_dir, &out_f)
+                .with_extension("")
+                .to_string_lossy()
+                .into_owned();
+            let mut mes = match of {
+                types::OutputScriptType::Json => {
+                    let enc = get_output_encoding(opts);
+                    let b = utils::files::read_file(&out_f)?;
+                    let s = utils::encoding::decode_to_string(enc, &b, true)?;
+                    serde_json::from_str::<Vec<types::Message>>(&s)?
+                }
+                types::OutputScriptType::M3t
+                | types::OutputScriptType::M3ta
+                | types::OutputScriptType::M3tTxt => {
+                    let enc = get_output_encoding(opts);
+                    let b = utils::files::read_file(&out_f)?;
+                    let s = utils::encoding::decode_to_string(enc, &b, true)?;
+                    let mut parser = output_scripts::m3t::M3tParser::new(
+                        &s,
+                        opts.llm_trans_mark.as_deref(),
+                        opts.m3t_use_original_text,
+                    );
+                    parser.parse()?
+                }
+                types::OutputScriptType::Yaml => {
+                    let enc = get_output_encoding(opts);
+                    let b = utils::files::read_file(&out_f)?;
+                    let s = utils::encoding::decode_to_string(enc, &b, true)?;
+                    serde_yaml_ng::from_str::<Vec<types::Message>>(&s)?
+                }
+                types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+                    let enc = get_output_encoding(opts);
+                    let b = utils::files::read_file(&out_f)?;
+                    let s = utils::encoding::decode_to_string(enc, &b, true)?;
+                    let mut parser =
+                        output_scripts::po::PoParser::new(&s, opts.llm_trans_mark.as_deref());
+                    parser.parse()?
+                }
+                types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+                    parse_output_script(&out_f, of, &output_script_opts(opts))?
+                }
+                types::OutputScriptType::Custom => {
+                    Vec::new() // Custom scripts handle their own messages
+                }
+            };
+            if mes.is_empty() {
+                report!(reporter, "No messages found in {}", out_f);
+                continue;
+            }
+            if let Some(name_table) = name_csv {
+                utils::name_replacement::replace_message(&mut mes, name_table);
+            }
+            format::fmt_message(
+                &mut mes,
+                fmt.clone(),
+                *builder.script_type(),
+                builder.inline_markup(),
+            )?;
+            mmes.insert(name, mes);
+        }
+        let patched_f = if let Some(root_dir) = root_dir {
+            let f = std::path::PathBuf::from(filename);
+            let mut pb = std::path::PathBuf::from(&imp_cfg.patched);
+            let rpath = utils::files::relative_path(root_dir, &f);
+            if let Some(parent) = rpath.parent() {
+                pb.push(parent);
+            }
+            if let Some(fname) = f.file_name() {
+                pb.push(fname);
+            }
+            pb.set_extension(builder.extensions().first().unwrap_or(&""));
+            pb.to_string_lossy().into_owned()
+        } else {
+            imp_cfg.patched.clone()
+        };
+        if let Some(dep_graph) = dep_graph.as_mut() {
+            dep_graph.0 = patched_f.clone();
+        }
+        utils::files::make_sure_dir_exists(&patched_f)?;
+        let encoding = get_patched_encoding(imp_cfg, builder.as_ref());
+        script.import_multiple_messages_filename(mmes, &patched_f, encoding, repl)?;
+        return Ok(types::ScriptResult::Ok);
+    }
+    let out_f = if let Some(root_dir) = root_dir {
+        let f = std::path::PathBuf::from(filename);
+        let mut pb = std::path::PathBuf::from(&imp_cfg.output);
+        let rpath = utils::files::relative_path(root_dir, &f);
+        if let Some(parent) = rpath.parent() {
+            pb.push(parent);
+        }
+        if let Some(fname) = f.file_name() {
+            pb.push(fname);
+        }
+        if opts.output_no_extra_ext {
+            pb.remove_all_extensions();
+        }
+        pb.set_extension(if of.is_custom() {
+            script.custom_output_extension()
+        } else {
+            of.as_ref()
+        });
+        pb.to_string_lossy().into_owned()
+    } else {
+        imp_cfg.output.clone()
+    };
+    if !std::fs::exists(&out_f).unwrap_or(false) {
+        report!(reporter, "Output file does not exist");
+        return Ok(types::ScriptResult::Ignored);
+    }
+    if let Some(dep_graph) = dep_graph.as_mut() {
+        dep_graph.1.push(out_f.clone());
+    }
+    let mut mes = match of {
+        types::OutputScriptType::Json => {
+            let enc = get_output_encoding(opts);
+            let b = utils::files::read_file(&out_f)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            serde_json::from_str::<Vec<types::Message>>(&s)?
+        }
+        types::OutputScriptType::M3t
+        | types::OutputScriptType::M3ta
+        | types::OutputScriptType::M3tTxt => {
+            let enc = get_output_encoding(opts);
+            let b = utils::files::read_file(&out_f)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::m3t::M3tParser::new(
+                &s,
+                opts.llm_trans_mark.as_deref(),
+                opts.m3t_use_original_text,
+            );
+            parser.parse()?
+        }
+        types::OutputScriptType::Yaml => {
+            let enc = get_output_encoding(opts);
+            let b = utils::files::read_file(&out_f)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            serde_yaml_ng::from_str::<Vec<types::Message>>(&s)?
+        }
+        types::OutputScriptType::Pot | types::OutputScriptType::Po => {
+            let enc = get_output_encoding(opts);
+            let b = utils::files::read_file(&out_f)?;
+            let s = utils::encoding::decode_to_string(enc, &b, true)?;
+            let mut parser = output_scripts::po::PoParser::new(&s, opts.llm_trans_mark.as_deref());
+            parser.parse()?
+        }
+        types::OutputScriptType::Xliff | types::OutputScriptType::Tmx => {
+            parse_output_script(&out_f, of, &output_script_opts(opts))?
+        }
+        types::OutputScriptType::Custom => {
+            Vec::new() // Custom scripts handle their own messages
+        }
+    };
+    if !of.is_custom() && mes.is_empty() {
+        report!(reporter, "No messages found");
+        return Ok(types::ScriptResult::Ignored);
+    }
+    let encoding = get_patched_encoding(imp_cfg, builder.as_ref());
+    let patched_f = if let Some(root_dir) = root_dir {
+        let f = std::path::PathBuf::from(filename);
+        let mut pb = std::path::PathBuf::from(&imp_cfg.patched);
+        let rpath = utils::files::relative_path(root_dir, &f);
+        if let Some(parent) = rpath.parent() {
+            pb.push(parent);
+        }
+        if let Some(fname) = f.file_name() {
+            pb.push(fname);
+        }
+        pb.set_extension(builder.extensions().first().unwrap_or(&""));
+        pb.to_string_lossy().into_owned()
+    } else {
+        imp_cfg.patched.clone()
+    };
+    if let Some(dep_graph) = dep_graph.as_mut() {
+        dep_graph.0 = patched_f.clone();
+    }
+    utils::files::make_sure_dir_exists(&patched_f)?;
+    if of.is_custom() {
+        let enc = get_output_encoding(opts);
+        script.custom_import_filename(&out_f, &patched_f, encoding, enc)?;
+        return Ok(types::ScriptResult::Ok);
+    }
+    let fmt = match &imp_cfg.patched_format {
+        Some(fmt) => fmt.clone(),
+        None => script.default_format_type(),
+    };
+    if let Some(name_table) = name_csv {
+        utils::name_replacement::replace_message(&mut mes, name_table);
+    }
+    format::fmt_message(
+        &mut mes,
+        fmt,
+        *builder.script_type(),
+        builder.inline_markup(),
+    )?;
+
+    script.import_messages_filename(mes, &patched_f, encoding, repl)?;
+    Ok(types::ScriptResult::Ok)
+}
+
+/// Returns options for [parse_output_script], which reads files with the input encoding.
+///
+/// Output scripts are read with the output encoding when importing.
+fn output_script_opts(opts: &Options) -> Options {
+    Options {
+        encoding: opts.output_encoding,
+        code_page: opts.output_code_page,
+        ..opts.clone()
+    }
+}
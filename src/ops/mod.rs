@@ -0,0 +1,521 @@
+//! High-level operations used by the command line tool.
+//!
+//! All operations are driven by [Options], which contains [ExtraConfig] and other
+//! options shared by all commands. Batch operations such as [export_files] walk the input
+//! directory themselves. Progress messages and results of processed files are sent to a
+//! [Reporter], [crate::utils::counter::Counter] can be used if only statistics are needed.
+
+/// Sends a formatted progress or diagnostic message to a [Reporter].
+macro_rules! report {
+    ($reporter:expr, $($arg:tt)*) => {
+        $reporter.message(format_args!($($arg)*))
+    };
+}
+
+mod archive;
+mod convert;
+mod create;
+mod export;
+mod import;
+
+pub use archive::*;
+pub use convert::*;
+pub use create::*;
+pub use export::*;
+pub use import::*;
+
+use crate::scripts;
+use crate::scripts::base::ArchiveContent;
+use crate::types::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Default)]
+/// Options of high-level operations.
+pub struct Options {
+    /// Extra config passed to script builders.
+    pub config: Arc<ExtraConfig>,
+    /// Script type. Detected automatically if not specified.
+    /// Required for packing archives and creating files.
+    pub script_type: Option<ScriptType>,
+    /// Script encoding
+    pub encoding: Option<TextEncoding>,
+    /// Script code page
+    pub code_page: Option<u32>,
+    /// Archive filename encoding
+    pub archive_encoding: Option<TextEncoding>,
+    /// Archive filename code page
+    pub archive_code_page: Option<u32>,
+    /// Output script type. Script's default type is used if not specified.
+    pub output_type: Option<OutputScriptType>,
+    /// Output script encoding
+    pub output_encoding: Option<TextEncoding>,
+    /// Output script code page
+    pub output_code_page: Option<u32>,
+    /// Disable extra extension when locating/export output script
+    pub output_no_extra_ext: bool,
+    #[cfg(feature = "image")]
+    /// Output image type
+    pub image_type: Option<ImageOutputType>,
+    #[cfg(feature = "image")]
+    /// Output multiple image as `<basename>_<name>.<ext>` instead of `<basename>/<name>.<ext>`
+    pub image_output_flat: bool,
+    #[cfg(feature = "image")]
+    /// Workers count for encoding images in parallel. 0 or 1 disables parallel encoding.
+    pub image_workers: usize,
+    /// Force all files in archive to be treated as script files.
+    pub force_script: bool,
+    /// Disable multiple messages section support.
+    pub no_multi_message: bool,
+    /// Add a mark to the end of each message for LLM translation.
+    pub llm_trans_mark: Option<String>,
+    /// Do not add quote to translated text when exporting to m3t files.
+    pub m3t_no_quote: bool,
+    /// Use original text as translated text if translated text and llm text are all empty.
+    pub m3t_use_original_text: bool,
+    /// Language code of original text in XLIFF/TMX files. Default is `ja`.
+    pub source_lang: Option<String>,
+    /// Language code of translated text in XLIFF/TMX files. Default is `zh-CN`.
+    pub target_lang: Option<String>,
+    /// Search for files in the directory recursively
+    pub recursive: bool,
+    /// Print backtrace on error
+    pub backtrace: bool,
+}
+
+#[derive(Default)]
+/// Options of importing messages.
+pub struct ImportOptions {
+    /// Output script (text) file or directory
+    pub output: String,
+    /// Patched script file or directory
+    pub patched: String,
+    /// Patched script encoding
+    pub patched_encoding: Option<TextEncoding>,
+    /// Patched script code page
+    pub patched_code_page: Option<u32>,
+    /// Patched archive filename encoding
+    pub patched_archive_encoding: Option<TextEncoding>,
+    /// Patched archive code page
+    pub patched_archive_code_page: Option<u32>,
+    /// Format options of patched script. Script's default is used if not specified.
+    pub patched_format: Option<FormatOptions>,
+    /// Name table, used to replace names in messages.
+    pub name_table: Option<HashMap<String, String>>,
+    /// Replacement table, used when encoding messages.
+    pub replacement: Option<ReplacementTable>,
+    /// Print a warning when output script is not found.
+    pub warn_when_output_file_not_found: bool,
+    /// Workers count for importing scripts in parallel.
+    pub jobs: usize,
+    /// Output dependency file path. All files used during import are written to it.
+    pub dep_file: Option<String>,
+    /// SJIS tunnel mapping file. Tunneling is enabled during import if specified.
+    pub sjis_tunnel: Option<String>,
+    /// Output path of the binary SJIS tunnel mapping table.
+    /// Defaults to `sjis_ext.bin` in the same directory as [ImportOptions::sjis_tunnel].
+    pub sjis_tunnel_bin: Option<String>,
+}
+
+/// Receives progress messages and results of batch operations.
+pub trait Reporter: Send + Sync {
+    /// Reports a progress or diagnostic message.
+    fn message(&self, _message: std::fmt::Arguments<'_>) {}
+    /// Records the result of a processed file.
+    fn result(&self, result: ScriptResult);
+    /// Records a failed file or entry.
+    fn error(&self);
+    /// Records a warning.
+    fn warning(&self);
+}
+
+impl Reporter for crate::utils::counter::Counter {
+    fn result(&self, result: ScriptResult) {
+        self.inc(result);
+    }
+
+    fn error(&self) {
+        self.inc_error();
+    }
+
+    fn warning(&self) {
+        self.inc_warning();
+    }
+}
+
+/// Reports a failed file and its backtrace if [Options::backtrace] is set.
+fn report_error(reporter: &dyn Reporter, opts: &Options, context: &str, e: anyhow::Error) {
+    reporter.error();
+    report!(reporter, "{}: {}", context, e);
+    if opts.backtrace {
+        report!(reporter, "Backtrace: {}", e.backtrace());
+    }
+}
+
+/// Collects input files with `collect` and makes sure output directory exists if input is a directory.
+///
+/// Returns the files and the root directory used to keep directory structure in output.
+fn collect_inputs<'a>(
+    input: &'a str,
+    output: Option<&str>,
+    collect: impl FnOnce(&str) -> std::io::Result<(Vec<String>, bool)>,
+) -> anyhow::Result<(Vec<String>, Option<&'a std::path::Path>)> {
+    let (files, is_dir) = collect(input)?;
+    if !is_dir {
+        return Ok((files, None));
+    }
+    if let Some(output) = output {
+        let op = std::path::Path::new(output);
+        if op.exists() {
+            if !op.is_dir() {
+                anyhow::bail!("Output path {} is not a directory", output);
+            }
+        } else {
+            std::fs::create_dir_all(op)?;
+        }
+    }
+    Ok((files, Some(std::path::Path::new(input))))
+}
+
+/// A parsed script and the builder which created it.
+pub type ParsedScript = (
+    Box<dyn scripts::Script>,
+    &'static Box<dyn scripts::ScriptBuilder + Send + Sync>,
+);
+
+/// Escapes a path in dependency files (Makefile syntax).
+pub fn escape_dep_string(s: &str) -> String {
+    s.replace("\\", "\\\\").replace(" ", "\\ ")
+}
+
+/// Converts the encoding specified in options.
+///
+/// `default` is used for [TextEncoding::Default] and `auto` is used for [TextEncoding::Auto].
+fn text_encoding(enc: TextEncoding, default: Encoding, auto: Encoding) -> Encoding {
+    match enc {
+        TextEncoding::Default => default,
+        TextEncoding::Auto => auto,
+        TextEncoding::Cp932 => Encoding::Cp932,
+        TextEncoding::Utf8 => Encoding::Utf8,
+        TextEncoding::Gb2312 => Encoding::Gb2312,
+    }
+}
+
+fn get_encoding(opts: &Options, builder: &(dyn scripts::ScriptBuilder + Send + Sync)) -> Encoding {
+    if let Some(enc) = opts.encoding {
+        return text_encoding(enc, builder.default_encoding(), Encoding::Auto);
+    }
+    if let Some(code_page) = opts.code_page {
+        return Encoding::CodePage(code_page);
+    }
+    builder.default_encoding()
+}
+
+fn get_archived_encoding(
+    opts: &Options,
+    builder: &(dyn scripts::ScriptBuilder + Send + Sync),
+    encoding: Encoding,
+) -> Encoding {
+    if let Some(enc) = opts.archive_encoding {
+        let default = builder
+            .default_archive_encoding()
+            .unwrap_or_else(|| builder.default_encoding());
+        return text_encoding(enc, default, Encoding::Auto);
+    }
+    if let Some(code_page) = opts.archive_code_page {
+        return Encoding::CodePage(code_page);
+    }
+    builder.default_archive_encoding().unwrap_or(encoding)
+}
+
+/// Returns the encoding of output scripts (m3t/po/json/yaml) when reading.
+pub fn get_input_output_script_encoding(opts: &Options) -> Encoding {
+    if let Some(enc) = opts.encoding {
+        return text_encoding(enc, Encoding::Utf8, Encoding::Utf8);
+    }
+    if let Some(code_page) = opts.code_page {
+        return Encoding::CodePage(code_page);
+    }
+    Encoding::Utf8
+}
+
+/// Returns the encoding of output scripts (m3t/po/json/yaml) when writing.
+pub fn get_output_encoding(opts: &Options) -> Encoding {
+    if let Some(enc) = opts.output_encoding {
+        return text_encoding(enc, Encoding::Utf8, Encoding::Utf8);
+    }
+    if let Some(code_page) = opts.output_code_page {
+        return Encoding::CodePage(code_page);
+    }
+    Encoding::Utf8
+}
+
+/// Returns the language code of original text in XLIFF/TMX files.
+pub fn get_source_lang(opts: &Options) -> &str {
+    opts.source_lang.as_deref().unwrap_or("ja")
+}
+
+/// Returns the language code of translated text in XLIFF/TMX files.
+pub fn get_target_lang(opts: &Options) -> &str {
+    opts.target_lang.as_deref().unwrap_or("zh-CN")
+}
+
+fn get_patched_encoding(
+    imp: &ImportOptions,
+    builder: &(dyn scripts::ScriptBuilder + Send + Sync),
+) -> Encoding {
+    if let Some(enc) = imp.patched_encoding {
+        return text_encoding(enc, builder.default_patched_encoding(), Encoding::Utf8);
+    }
+    if let Some(code_page) = imp.patched_code_page {
+        return Encoding::CodePage(code_page);
+    }
+    builder.default_patched_encoding()
+}
+
+fn get_patched_archive_encoding(
+    imp: &ImportOptions,
+    builder: &(dyn scripts::ScriptBuilder + Send + Sync),
+    encoding: Encoding,
+) -> Encoding {
+    if let Some(enc) = imp.patched_archive_encoding {
+        let default = builder.default_archive_encoding().unwrap_or(encoding);
+        return text_encoding(enc, default, Encoding::Utf8);
+    }
+    if let Some(code_page) = imp.patched_archive_code_page {
+        return Encoding::CodePage(code_page);
+    }
+    builder.default_archive_encoding().unwrap_or(encoding)
+}
+
+/// Finds images exported from a multi-image script in `dir`.
+///
+/// Returns the image names and paths sorted by name.
+/// If `prefix` is specified, only files starting with it are used and it is stripped from names.
+#[cfg(feature = "image")]
+fn find_multi_images(
+    dir: &std::path::Path,
+    prefix: Option<&str>,
+    typ: ImageOutputType,
+) -> anyhow::Result<Vec<(String, std::path::PathBuf)>> {
+    let mut images = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file()
+            || !path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(typ.as_ref()))
+        {
+            continue;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match prefix {
+            Some(prefix) => match stem.strip_prefix(prefix) {
+                Some(name) => name.to_string(),
+                None => continue,
+            },
+            None => stem.into_owned(),
+        };
+        images.push((name, path));
+    }
+    images.sort();
+    Ok(images)
+}
+
+/// Detects the type of the script file and parses it.
+///
+/// [Options::script_type] is used if specified, otherwise the type is detected by extension and content.
+pub fn parse_script(filename: &str, opts: &Options) -> anyhow::Result<ParsedScript> {
+    if let Some(typ) = &opts.script_type {
+        for builder in scripts::BUILDER.iter() {
+            if typ == builder.script_type() {
+                let encoding = get_encoding(opts, builder.as_ref());
+                let archive_encoding = get_archived_encoding(opts, builder.as_ref(), encoding);
+                return Ok((
+                    builder.build_script_from_file(
+                        filename,
+                        encoding,
+                        archive_encoding,
+                        &opts.config,
+                        None,
+                    )?,
+                    builder,
+                ));
+            }
+        }
+    }
+    let mut exts_builder = Vec::new();
+    for builder in scripts::BUILDER.iter() {
+        let exts = builder.extensions();
+        for ext in exts {
+            if filename.to_lowercase().ends_with(ext) {
+                exts_builder.push(builder);
+                break;
+            }
+        }
+    }
+    let exts_builder = if exts_builder.is_empty() {
+        scripts::BUILDER.iter().collect::<Vec<_>>()
+    } else {
+        exts_builder
+    };
+    if exts_builder.len() == 1 {
+        let builder = *exts_builder.first().unwrap();
+        let encoding = get_encoding(opts, builder.as_ref());
+        let archive_encoding = get_archived_encoding(opts, builder.as_ref(), encoding);
+        return Ok((
+            builder.build_script_from_file(
+                filename,
+                encoding,
+                archive_encoding,
+                &opts.config,
+                None,
+            )?,
+            builder,
+        ));
+    }
+    let mut buf = [0u8; 1024];
+    let mut size = 0;
+    if filename != "-" {
+        let mut f = std::fs::File::open(filename)?;
+        size = std::io::Read::read(&mut f, &mut buf)?;
+    }
+    let mut scores = Vec::new();
+    for builder in exts_builder.iter() {
+        if let Some(score) = builder.is_this_format(filename, &buf, size) {
+            scores.push((score, builder));
+        }
+    }
+    if scores.is_empty() {
+        return Err(anyhow::anyhow!("Unsupported script type"));
+    }
+    let max_score = scores.iter().map(|s| s.0).max().unwrap();
+    let mut best_builders = Vec::new();
+    for (score, builder) in scores.iter() {
+        if *score == max_score {
+            best_builders.push(builder);
+        }
+    }
+    if best_builders.len() == 1 {
+        let builder = **best_builders.first().unwrap();
+        let encoding = get_encoding(opts, builder.as_ref());
+        let archive_encoding = get_archived_encoding(opts, builder.as_ref(), encoding);
+        return Ok((
+            builder.build_script_from_file(
+                filename,
+                encoding,
+                archive_encoding,
+                &opts.config,
+                None,
+            )?,
+            builder,
+        ));
+    }
+    if best_builders.len() > 1 {
+        return Err(anyhow::anyhow!(
+            "Multiple script types found for {}: {:?}",
+            filename,
+            best_builders
+        ));
+    }
+    Err(anyhow::anyhow!("Unsupported script type"))
+}
+
+/// Detects the type of a file in archive and parses it.
+pub fn parse_script_from_archive<'a>(
+    file: &mut Box<dyn ArchiveContent + Send + Sync + 'a>,
+    opts: &Options,
+    archive: &dyn scripts::Script,
+) -> anyhow::Result<ParsedScript> {
+    if let Some(typ) = file.script_type() {
+        for builder in scripts::BUILDER.iter() {
+            if typ == builder.script_type() {
+                let encoding = get_encoding(opts, builder.as_ref());
+                let archive_encoding = get_archived_encoding(opts, builder.as_ref(), encoding);
+                return Ok((
+                    builder.build_script(
+                        file.data()?,
+                        file.name(),
+                        encoding,
+                        archive_encoding,
+                        &opts.config,
+                        Some(archive),
+                    )?,
+                    builder,
+                ));
+            }
+        }
+    }
+    let mut exts_builder = Vec::new();
+    for builder in scripts::BUILDER.iter() {
+        let exts = builder.extensions();
+        for ext in exts {
+            if file.name().to_lowercase().ends_with(ext) {
+                exts_builder.push(builder);
+                break;
+            }
+        }
+    }
+    let exts_builder = if exts_builder.is_empty() {
+        scripts::BUILDER.iter().collect::<Vec<_>>()
+    } else {
+        exts_builder
+    };
+    if exts_builder.len() == 1 {
+        let builder = *exts_builder.first().unwrap();
+        let encoding = get_encoding(opts, builder.as_ref());
+        let archive_encoding = get_archived_encoding(opts, builder.as_ref(), encoding);
+        return Ok((
+            builder.build_script(
+                file.data()?,
+                file.name(),
+                encoding,
+                archive_encoding,
+                &opts.config,
+                Some(archive),
+            )?,
+            builder,
+        ));
+    }
+    let buf = file.data()?;
+    let mut scores = Vec::new();
+    for builder in exts_builder.iter() {
+        if let Some(score) = builder.is_this_format(file.name(), buf.as_slice(), buf.len()) {
+            scores.push((score, builder));
+        }
+    }
+    if scores.is_empty() {
+        return Err(anyhow::anyhow!("Unsupported script type"));
+    }
+    let max_score = scores.iter().map(|s| s.0).max().unwrap();
+    let mut best_builders = Vec::new();
+    for (score, builder) in scores.iter() {
+        if *score == max_score {
+            best_builders.push(builder);
+        }
+    }
+    if best_builders.len() == 1 {
+        let builder = **best_builders.first().unwrap();
+        let encoding = get_encoding(opts, builder.as_ref());
+        let archive_encoding = get_archived_encoding(opts, builder.as_ref(), encoding);
+        return Ok((
+            builder.build_script(
+                buf,
+                file.name(),
+                encoding,
+                archive_encoding,
+                &opts.config,
+                Some(archive),
+            )?,
+            builder,
+        ));
+    }
+    if best_builders.len() > 1 {
+        return Err(anyhow::anyhow!(
+            "Multiple script types found for {}: {:?}",
+            file.name(),
+            best_builders
+        ));
+    }
+    Err(anyhow::anyhow!("Unsupported script type"))
+}
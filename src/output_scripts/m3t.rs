@@ -233,6 +233,9 @@ impl<'a> M3tParser<'a> {
                     source,
                     translated: message.replace("\\n", "\n"),
                     llm: llm.take(),
+                    // M3T files have no place for review state and metadata.
+                    fuzzy: false,
+                    meta: Default::default(),
                 };
                 messages.push(m);
             }
@@ -1,3 +1,50 @@
 //! Module for output scripts.
 pub mod m3t;
 pub mod po;
+pub mod tmx;
+pub mod xliff;
+mod xml;
+
+use crate::types::{ExtendedMessage, Message};
+
+/// Converts an extended message to a message used for importing.
+///
+/// If translated text is empty, LLM text is used, with `llm_mark` appended.
+/// Fuzzy translations are not reviewed yet, so they are treated as untranslated and
+/// source text is used if there is no LLM text.
+fn extended_to_message(m: ExtendedMessage, llm_mark: Option<&str>) -> Message {
+    let mut message = m.translated;
+    if m.fuzzy {
+        message = match &m.llm {
+            Some(_) => String::new(),
+            None => m.source,
+        };
+    }
+    match m.llm {
+        Some(llm) if message.is_empty() => {
+            message = llm;
+            if let Some(mark) = llm_mark {
+                message.push_str(mark);
+            }
+        }
+        Some(llm) if llm == message => {
+            if let Some(mark) = llm_mark {
+                message.push_str(mark);
+            }
+        }
+        _ => {}
+    }
+    let mut mes = Message::new(message, m.name);
+    mes.meta = m.meta;
+    mes
+}
+
+/// Parses a metadata value written as text.
+///
+/// JSON numbers, booleans, arrays and objects are kept, other text is stored as string.
+fn meta_value_from_str(s: &str) -> serde_json::Value {
+    match serde_json::from_str::<serde_json::Value>(s) {
+        Ok(v) if !v.is_string() && !v.is_null() => v,
+        _ => serde_json::Value::String(s.to_string()),
+    }
+}
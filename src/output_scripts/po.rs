@@ -3,7 +3,7 @@
 //! See [spec](https://www.gnu.org/software/gettext/manual/html_node/PO-Files.html)
 use crate::types::*;
 use anyhow::{Result, anyhow};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug)]
@@ -235,6 +235,67 @@ pub struct PoDumper {
     entries: Vec<PoLine>,
 }
 
+/// Converts message metadata to comments.
+///
+/// Where the message came from (label, line, offset) is stored as a reference (`#: line:12`),
+/// other metadata is stored as extracted comments (`#. voice: ...`).
+fn meta_comments(meta: &BTreeMap<String, serde_json::Value>) -> Vec<Comment> {
+    let mut references = Vec::new();
+    let mut comments = Vec::new();
+    for (key, value) in meta {
+        let value = match value {
+            serde_json::Value::String(s) => s.replace("\n", "\\n"),
+            v => v.to_string(),
+        };
+        match key.as_str() {
+            meta_key::LABEL | meta_key::LINE | meta_key::OFFSET => {
+                references.push(format!("{}:{}", key, value))
+            }
+            _ => comments.push(Comment::Extracted(format!("{}: {}", key, value))),
+        }
+    }
+    if !references.is_empty() {
+        comments.push(Comment::Reference(references.join(" ")));
+    }
+    comments
+}
+
+/// Reads metadata written by [meta_comments].
+fn read_meta_comment(comment: &Comment, meta: &mut BTreeMap<String, serde_json::Value>) {
+    match comment {
+        Comment::Extracted(s) => {
+            if let Some((key, value)) = s.split_once(": ")
+                && !key.is_empty()
+                && !key.contains(char::is_whitespace)
+            {
+                let value = super::meta_value_from_str(value);
+                let value = match value {
+                    serde_json::Value::String(s) => s.replace("\\n", "\n").into(),
+                    v => v,
+                };
+                meta.insert(key.to_string(), value);
+            }
+        }
+        Comment::Reference(s) => {
+            for reference in s.split_whitespace() {
+                // References written by other tools are file names.
+                if let Some((key, value)) = reference.split_once(':')
+                    && matches!(key, meta_key::LABEL | meta_key::LINE | meta_key::OFFSET)
+                {
+                    meta.insert(key.to_string(), super::meta_value_from_str(value));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_fuzzy(comments: &[Comment]) -> bool {
+    comments
+        .iter()
+        .any(|c| matches!(c, Comment::Flag(flags) if flags.iter().any(|f| f == "fuzzy")))
+}
+
 impl PoDumper {
     pub fn new() -> Self {
         Self {
@@ -291,6 +352,10 @@ impl PoDumper {
                     llm.replace("\n", "\\n")
                 )));
             }
+            comments.extend(meta_comments(&entry.meta));
+            if entry.fuzzy {
+                comments.push(Comment::Flag(vec![String::from("fuzzy")]));
+            }
             self.add_entry(PoEntry {
                 comments,
                 msgctxt: if count > 0 || inadded {
@@ -338,12 +403,13 @@ impl PoDumper {
                 .map(|&s| s)
                 .unwrap_or(0);
             let inadded = added.contains(&entry.message);
+            let mut comments = Vec::new();
+            if let Some(name) = &entry.name {
+                comments.push(Comment::Translator(format!("NAME: {}", name)));
+            }
+            comments.extend(meta_comments(&entry.meta));
             self.add_entry(PoEntry {
-                comments: entry
-                    .name
-                    .as_ref()
-                    .map(|name| vec![Comment::Translator(format!("NAME: {}", name))])
-                    .unwrap_or_default(),
+                comments,
                 msgctxt: if count > 0 || inadded {
                     Some(format!(
                         "{}{}",
@@ -680,9 +746,11 @@ impl<'a> PoParser<'a> {
                     }
                 }
             }
+            // Fuzzy translations are not reviewed yet.
+            let fuzzy = is_fuzzy(&entry.comments);
             let message = match entry.msgstr {
                 MsgStr::Single(s) => {
-                    let s = s.trim();
+                    let s = if fuzzy { "" } else { s.trim() };
                     if s.is_empty() {
                         llm.take()
                             .map(|mut llm| {
@@ -692,7 +760,7 @@ impl<'a> PoParser<'a> {
                                 llm
                             })
                             .unwrap_or_else(|| {
-                                String::from(if entry.msgid.is_empty() { "" } else { "" })
+                                String::from(if fuzzy { entry.msgid.as_str() } else { "" })
                             })
                     } else {
                         let mut tmp = s.to_string();
@@ -724,19 +792,25 @@ impl<'a> PoParser<'a> {
                 // This is the header entry, skip it
                 continue;
             }
+            let mut meta = BTreeMap::new();
             for comment in &entry.comments {
-                if let Comment::Translator(s) = comment {
-                    let s = s.trim();
-                    if s.starts_with("NAME:") {
-                        name = Some(s[5..].trim().to_string());
-                    } else if s.starts_with("LLM:") {
-                        llm = Some(s[4..].trim().replace("\\n", "\n"));
+                match comment {
+                    Comment::Translator(s) => {
+                        let s = s.trim();
+                        if s.starts_with("NAME:") {
+                            name = Some(s[5..].trim().to_string());
+                        } else if s.starts_with("LLM:") {
+                            llm = Some(s[4..].trim().replace("\\n", "\n"));
+                        }
                     }
+                    c => read_meta_comment(c, &mut meta),
                 }
             }
+            // Fuzzy translations are not reviewed yet.
+            let fuzzy = is_fuzzy(&entry.comments);
             let message = match entry.msgstr {
                 MsgStr::Single(s) => {
-                    let s = s.trim();
+                    let s = if fuzzy { "" } else { s.trim() };
                     if s.is_empty() {
                         llm.take()
                             .map(|mut llm| {
@@ -746,7 +820,7 @@ impl<'a> PoParser<'a> {
                                 llm
                             })
                             .unwrap_or_else(|| {
-                                String::from(if entry.msgid.is_empty() { "" } else { "" })
+                                String::from(if fuzzy { entry.msgid.as_str() } else { "" })
                             })
                     } else {
                         let mut tmp = s.to_string();
@@ -764,7 +838,8 @@ impl<'a> PoParser<'a> {
                     return Err(anyhow!("Plural msgstr not supported in this context"));
                 }
             };
-            let m = Message::new(message, name.take());
+            let mut m = Message::new(message, name.take());
+            m.meta = meta;
             messages.push(m);
         }
         Ok(messages)
@@ -779,14 +854,22 @@ impl<'a> PoParser<'a> {
             }
             let mut name = None;
             let mut llm = None;
+            let mut fuzzy = false;
+            let mut meta = BTreeMap::new();
             for comment in &entry.comments {
-                if let Comment::Translator(s) = comment {
-                    let s = s.trim();
-                    if s.starts_with("NAME:") {
-                        name = Some(s[5..].trim().to_string());
-                    } else if s.starts_with("LLM:") {
-                        llm = Some(s[4..].trim().replace("\\n", "\n"));
+                match comment {
+                    Comment::Translator(s) => {
+                        let s = s.trim();
+                        if s.starts_with("NAME:") {
+                            name = Some(s[5..].trim().to_string());
+                        } else if s.starts_with("LLM:") {
+                            llm = Some(s[4..].trim().replace("\\n", "\n"));
+                        }
+                    }
+                    Comment::Flag(flags) => {
+                        fuzzy |= flags.iter().any(|f| f == "fuzzy");
                     }
+                    c => read_meta_comment(c, &mut meta),
                 }
             }
             let message = match entry.msgstr {
@@ -800,6 +883,8 @@ impl<'a> PoParser<'a> {
                 source: entry.msgid,
                 translated: message,
                 llm: llm,
+                fuzzy,
+                meta,
             };
             messages.push(m);
         }
@@ -893,4 +978,64 @@ mod c_escape_tests {
         assert!(unescape_c_str(r#"\xFG"#).is_err());
         assert!(unescape_c_str(r#"\8"#).is_err());
     }
+
+    #[test]
+    fn test_dump_meta() {
+        let messages = vec![
+            Message::new("Hello".to_string(), Some("Alice".to_string()))
+                .with_kind(MessageKind::Dialogue)
+                .with_meta(meta_key::VOICE, "ali_0001")
+                .with_meta(meta_key::LINE, 12),
+        ];
+        let s = PoDumper::new().dump(&messages, Encoding::Utf8).unwrap();
+        assert!(s.contains(
+            "# NAME: Alice\n#. kind: dialogue\n#. voice: ali_0001\n#: line:12\nmsgid \"Hello\"\n"
+        ));
+        let parsed = PoParser::new(&s, None).parse().unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].name.as_deref(), Some("Alice"));
+        assert_eq!(parsed[0].meta, messages[0].meta);
+    }
+
+    #[test]
+    fn test_extended_meta_and_fuzzy() {
+        let messages = vec![
+            ExtendedMessage {
+                source: "こんにちは".to_string(),
+                translated: "你好".to_string(),
+                fuzzy: true,
+                meta: BTreeMap::from([
+                    (meta_key::VOICE.to_string(), "v\n1".into()),
+                    (meta_key::LINE.to_string(), 3.into()),
+                ]),
+                ..Default::default()
+            },
+            ExtendedMessage {
+                source: "さようなら".to_string(),
+                translated: "再见".to_string(),
+                llm: Some("拜拜".to_string()),
+                fuzzy: true,
+                ..Default::default()
+            },
+            ExtendedMessage {
+                source: "おはよう".to_string(),
+                translated: "早上好".to_string(),
+                ..Default::default()
+            },
+        ];
+        let s = PoDumper::new()
+            .dump_extended(&messages, Encoding::Utf8)
+            .unwrap();
+        let parsed = PoParser::new(&s, None).parse_as_extend().unwrap();
+        assert_eq!(parsed[0].meta, messages[0].meta);
+        assert!(parsed[0].fuzzy);
+        // Fuzzy translations are not imported.
+        let parsed = PoParser::new(&s, Some("(LLM)")).parse().unwrap();
+        assert_eq!(parsed[0].message, "こんにちは");
+        assert_eq!(parsed[0].meta, messages[0].meta);
+        assert_eq!(parsed[1].message, "拜拜(LLM)");
+        assert_eq!(parsed[2].message, "早上好");
+        let parsed = PoParser::new(&s, None).parse_as_vec().unwrap();
+        assert_eq!(parsed[0].1, "こんにちは");
+    }
 }
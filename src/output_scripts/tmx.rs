@@ -0,0 +1,308 @@
+//! TMX 1.4 translation memory files, which contain source/target pairs.
+//!
+//! Each message is stored as a translation unit:
+//! ```xml
+//! <tu>
+//!   <prop type="x-name">Example</prop>
+//!   <tuv xml:lang="ja"><seg>Original message</seg></tuv>
+//!   <tuv xml:lang="zh-CN"><seg>Translated message</seg></tuv>
+//! </tu>
+//! ```
+//! Untranslated messages have no target `<tuv>`. LLM message is stored as `x-llm` property,
+//! fuzzy translations are marked with `x-fuzzy` property and metadata is stored as
+//! `x-meta-<key>` properties. When reading multilingual files,
+//! the `<tuv>` matching the target language is used as translation.
+//!
+//! See [spec](https://www.gala-global.org/tmx-14b)
+use super::xml::{self, escape};
+use crate::types::*;
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+const META_PROP_PREFIX: &str = "x-meta-";
+
+/// A dumper for TMX format.
+pub struct TmxDumper<'a> {
+    source_lang: &'a str,
+    target_lang: &'a str,
+    result: String,
+}
+
+impl<'a> TmxDumper<'a> {
+    /// Creates a new dumper.
+    ///
+    /// * `source_lang` - Language code of the original messages, such as `ja`
+    /// * `target_lang` - Language code of the translated messages, such as `zh-CN`
+    pub fn new(source_lang: &'a str, target_lang: &'a str) -> Self {
+        Self {
+            source_lang,
+            target_lang,
+            result: String::new(),
+        }
+    }
+
+    fn start(&mut self, encoding: Encoding) {
+        self.result.push_str(&xml::declaration(encoding));
+        self.result.push_str("<tmx version=\"1.4\">\n");
+        self.result.push_str(&format!(
+            "  <header creationtool=\"msg-tool\" creationtoolversion=\"{}\" segtype=\"block\" o-tmf=\"msg-tool\" adminlang=\"en\" srclang=\"{}\" datatype=\"plaintext\"/>\n",
+            env!("CARGO_PKG_VERSION"),
+            escape(self.source_lang)
+        ));
+        self.result.push_str("  <body>\n");
+    }
+
+    fn end(mut self) -> String {
+        self.result.push_str("  </body>\n</tmx>\n");
+        self.result
+    }
+
+    fn add_tu(
+        &mut self,
+        props: &[(&str, &str)],
+        meta: &BTreeMap<String, serde_json::Value>,
+        source: &str,
+        target: &str,
+    ) {
+        self.result.push_str("    <tu>\n");
+        for (typ, value) in props {
+            self.result.push_str(&format!(
+                "      <prop type=\"{}\">{}</prop>\n",
+                escape(typ),
+                escape(value)
+            ));
+        }
+        for (key, value) in meta {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                v => v.to_string(),
+            };
+            self.result.push_str(&format!(
+                "      <prop type=\"{}{}\">{}</prop>\n",
+                META_PROP_PREFIX,
+                escape(key),
+                escape(&value)
+            ));
+        }
+        self.add_tuv(self.source_lang, source);
+        if !target.is_empty() {
+            self.add_tuv(self.target_lang, target);
+        }
+        self.result.push_str("    </tu>\n");
+    }
+
+    fn add_tuv(&mut self, lang: &str, seg: &str) {
+        self.result.push_str(&format!(
+            "      <tuv xml:lang=\"{}\"><seg>{}</seg></tuv>\n",
+            escape(lang),
+            escape(seg)
+        ));
+    }
+
+    /// Dumps the messages in TMX format. Only source text and metadata are written.
+    pub fn dump(mut self, messages: &[Message], encoding: Encoding) -> Result<String> {
+        self.start(encoding);
+        for message in messages {
+            let mut props = Vec::new();
+            if let Some(name) = &message.name {
+                props.push(("x-name", name.as_str()));
+            }
+            self.add_tu(&props, &message.meta, &message.message, "");
+        }
+        Ok(self.end())
+    }
+
+    /// Dumps the source/target pairs of extended messages in TMX format.
+    pub fn dump_extended(
+        mut self,
+        messages: &[ExtendedMessage],
+        encoding: Encoding,
+    ) -> Result<String> {
+        self.start(encoding);
+        for message in messages {
+            let mut props = Vec::new();
+            if let Some(name) = &message.name {
+                props.push(("x-name", name.as_str()));
+            }
+            if let Some(llm) = &message.llm {
+                props.push(("x-llm", llm.as_str()));
+            }
+            if message.fuzzy {
+                props.push(("x-fuzzy", "true"));
+            }
+            self.add_tu(&props, &message.meta, &message.source, &message.translated);
+        }
+        Ok(self.end())
+    }
+}
+
+/// A parser for TMX format.
+pub struct TmxParser<'a> {
+    str: &'a str,
+    target_lang: &'a str,
+    llm_mark: Option<&'a str>,
+}
+
+impl<'a> TmxParser<'a> {
+    /// Creates a new parser.
+    ///
+    /// * `target_lang` - Language code of the translated messages. If no `<tuv>` matches it,
+    ///   the first `<tuv>` which is not the source is used.
+    pub fn new(str: &'a str, target_lang: &'a str, llm_mark: Option<&'a str>) -> Self {
+        Self {
+            str,
+            target_lang,
+            llm_mark,
+        }
+    }
+
+    pub fn parse_as_extend(&mut self) -> Result<Vec<ExtendedMessage>> {
+        let root = xml::parse(self.str)?;
+        if root.local_name() != "tmx" {
+            anyhow::bail!("Root element is not tmx: {}", root.name);
+        }
+        let srclang = root
+            .child("header")
+            .and_then(|h| h.attr("srclang"))
+            .filter(|l| !l.eq_ignore_ascii_case("*all*"));
+        let body = root
+            .child("body")
+            .ok_or_else(|| anyhow::anyhow!("No body found in TMX"))?;
+        let mut messages = Vec::new();
+        for tu in body.children("tu") {
+            let mut name = None;
+            let mut llm = None;
+            let mut fuzzy = false;
+            let mut meta = BTreeMap::new();
+            for prop in tu.children("prop") {
+                match prop.attr("type") {
+                    Some("x-name") => name = Some(prop.text()),
+                    Some("x-llm") => llm = Some(prop.text()),
+                    Some("x-fuzzy") => fuzzy = prop.text() == "true",
+                    Some(typ) if typ.starts_with(META_PROP_PREFIX) => {
+                        meta.insert(
+                            typ[META_PROP_PREFIX.len()..].to_string(),
+                            super::meta_value_from_str(&prop.text()),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            let tuvs: Vec<_> = tu
+                .children("tuv")
+                .map(|tuv| {
+                    let lang = tuv.attr("xml:lang").or_else(|| tuv.attr("lang"));
+                    let seg = tuv.child("seg").map(|s| s.text()).unwrap_or_default();
+                    (lang, seg)
+                })
+                .collect();
+            let is_source = |lang: Option<&str>| match (lang, srclang) {
+                (Some(lang), Some(srclang)) => lang.eq_ignore_ascii_case(srclang),
+                _ => false,
+            };
+            let source_index = tuvs.iter().position(|(l, _)| is_source(*l)).unwrap_or(0);
+            let source = match tuvs.get(source_index) {
+                Some((_, seg)) => seg.clone(),
+                None => anyhow::bail!("Translation unit without tuv found in TMX"),
+            };
+            let targets = || {
+                tuvs.iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != source_index)
+                    .map(|(_, tuv)| tuv)
+            };
+            let translated = targets()
+                .find(|(l, _)| l.is_some_and(|l| l.eq_ignore_ascii_case(self.target_lang)))
+                .or_else(|| {
+                    targets().find(|(l, _)| {
+                        l.is_some_and(|l| {
+                            primary_subtag(l).eq_ignore_ascii_case(primary_subtag(self.target_lang))
+                        })
+                    })
+                })
+                .or_else(|| targets().next())
+                .map(|(_, seg)| seg.clone())
+                .unwrap_or_default();
+            messages.push(ExtendedMessage {
+                name,
+                source,
+                translated,
+                llm,
+                fuzzy,
+                meta,
+            });
+        }
+        Ok(messages)
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Message>> {
+        Ok(self
+            .parse_as_extend()?
+            .into_iter()
+            .map(|m| super::extended_to_message(m, self.llm_mark))
+            .collect())
+    }
+}
+
+/// Returns the primary language subtag, such as `zh` for `zh-CN`.
+fn primary_subtag(lang: &str) -> &str {
+    lang.split(['-', '_']).next().unwrap_or(lang)
+}
+
+#[test]
+fn test_tmx_round_trip() {
+    let messages = vec![
+        ExtendedMessage {
+            name: Some("名前".to_string()),
+            source: "「あ」\n<b>".to_string(),
+            translated: "「啊」\n<b>".to_string(),
+            llm: None,
+            fuzzy: true,
+            meta: BTreeMap::from([(meta_key::OFFSET.to_string(), 0x1234.into())]),
+        },
+        ExtendedMessage {
+            name: None,
+            source: "い".to_string(),
+            translated: String::new(),
+            llm: Some("咿".to_string()),
+            fuzzy: false,
+            meta: BTreeMap::from([(meta_key::LABEL.to_string(), "*start".into())]),
+        },
+    ];
+    let s = TmxDumper::new("ja", "zh-CN")
+        .dump_extended(&messages, Encoding::Utf8)
+        .unwrap();
+    let parsed = TmxParser::new(&s, "zh-CN", None).parse_as_extend().unwrap();
+    assert_eq!(parsed.len(), 2);
+    for (a, b) in messages.iter().zip(parsed.iter()) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.source, b.source);
+        assert_eq!(a.translated, b.translated);
+        assert_eq!(a.llm, b.llm);
+        assert_eq!(a.fuzzy, b.fuzzy);
+        assert_eq!(a.meta, b.meta);
+    }
+    // Fuzzy translations are not imported.
+    let parsed = TmxParser::new(&s, "zh-CN", None).parse().unwrap();
+    assert_eq!(parsed[0].message, "「あ」\n<b>");
+    assert_eq!(parsed[1].meta, messages[1].meta);
+    // Target tuv comes first
+    let s = "<tmx version=\"1.4\"><header srclang=\"ja-JP\"/><body><tu><tuv lang=\"en\"><seg>Hi</seg></tuv><tuv xml:lang=\"JA-jp\"><seg>やあ</seg></tuv></tu></body></tmx>";
+    let parsed = TmxParser::new(s, "en", None).parse().unwrap();
+    assert_eq!(parsed[0].message, "Hi");
+}
+
+#[test]
+fn test_tmx_target_lang() {
+    let s = "<tmx version=\"1.4\"><header srclang=\"ja\"/><body><tu><tuv xml:lang=\"ja\"><seg>やあ</seg></tuv><tuv xml:lang=\"en-US\"><seg>Hi</seg></tuv><tuv xml:lang=\"ZH-cn\"><seg>嗨</seg></tuv></tu></body></tmx>";
+    let translated = |lang| {
+        TmxParser::new(s, lang, None).parse_as_extend().unwrap()[0]
+            .translated
+            .clone()
+    };
+    assert_eq!(translated("zh-CN"), "嗨");
+    assert_eq!(translated("zh"), "嗨");
+    assert_eq!(translated("en"), "Hi");
+    // Falls back to the first tuv which is not the source.
+    assert_eq!(translated("ko"), "Hi");
+}
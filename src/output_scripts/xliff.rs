@@ -0,0 +1,342 @@
+//! XLIFF 2.x files, which can be used in CAT tools (memoQ, Trados, OmegaT, etc.)
+//!
+//! Each message is stored as a unit:
+//! ```xml
+//! <unit id="1">
+//!   <mtc:matches>
+//!     <mtc:match ref="#s1" origin="llm">
+//!       <source>Original message</source>
+//!       <target>LLM message</target>
+//!     </mtc:match>
+//!   </mtc:matches>
+//!   <notes>
+//!     <note category="name">Example</note>
+//!   </notes>
+//!   <segment id="s1" state="translated">
+//!     <source>Original message</source>
+//!     <target>Translated message</target>
+//!   </segment>
+//! </unit>
+//! ```
+//! LLM message is stored as a translation candidate of the
+//! [Translation Candidates module](https://docs.oasis-open.org/xliff/xliff-core/v2.0/xliff-core-v2.0.html#candidates),
+//! which is the XLIFF 2 equivalent of `<alt-trans>`.
+//! Fuzzy translations have `initial` state.
+//!
+//! See [spec](https://docs.oasis-open.org/xliff/xliff-core/v2.1/xliff-core-v2.1.html)
+use super::xml::{self, Element, escape};
+use crate::types::*;
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+const XLIFF_NS: &str = "urn:oasis:names:tc:xliff:document:2.0";
+const MTC_NS: &str = "urn:oasis:names:tc:xliff:matches:2.0";
+
+/// A dumper for XLIFF 2.x format.
+pub struct XliffDumper<'a> {
+    source_lang: &'a str,
+    target_lang: &'a str,
+    result: String,
+}
+
+impl<'a> XliffDumper<'a> {
+    /// Creates a new dumper.
+    ///
+    /// * `source_lang` - Language code of the original messages, such as `ja`
+    /// * `target_lang` - Language code of the translated messages, such as `zh-CN`
+    pub fn new(source_lang: &'a str, target_lang: &'a str) -> Self {
+        Self {
+            source_lang,
+            target_lang,
+            result: String::new(),
+        }
+    }
+
+    fn start(&mut self, encoding: Encoding) {
+        self.result.push_str(&xml::declaration(encoding));
+        self.result.push_str(&format!(
+            "<xliff xmlns=\"{}\" xmlns:mtc=\"{}\" version=\"2.0\" srcLang=\"{}\" trgLang=\"{}\">\n",
+            XLIFF_NS,
+            MTC_NS,
+            escape(self.source_lang),
+            escape(self.target_lang)
+        ));
+        self.result
+            .push_str("  <file id=\"f1\" original=\"msg-tool\" xml:space=\"preserve\">\n");
+    }
+
+    fn end(mut self) -> String {
+        self.result.push_str("  </file>\n</xliff>\n");
+        self.result
+    }
+
+    fn add_unit(
+        &mut self,
+        id: usize,
+        notes: &[(String, String)],
+        source: &str,
+        target: &str,
+        llm: Option<&str>,
+        fuzzy: bool,
+    ) {
+        self.result
+            .push_str(&format!("    <unit id=\"{}\">\n", id + 1));
+        if let Some(llm) = llm {
+            self.result.push_str(&format!(
+                "      <mtc:matches>\n        <mtc:match ref=\"#s1\" origin=\"llm\">\n          <source>{}</source>\n          <target>{}</target>\n        </mtc:match>\n      </mtc:matches>\n",
+                escape(source),
+                escape(llm)
+            ));
+        }
+        if !notes.is_empty() {
+            self.result.push_str("      <notes>\n");
+            for (category, note) in notes {
+                self.result.push_str(&format!(
+                    "        <note category=\"{}\">{}</note>\n",
+                    escape(category),
+                    escape(note)
+                ));
+            }
+            self.result.push_str("      </notes>\n");
+        }
+        let state = if target.is_empty() || fuzzy {
+            "initial"
+        } else {
+            "translated"
+        };
+        self.result.push_str(&format!(
+            "      <segment id=\"s1\" state=\"{}\">\n        <source>{}</source>\n",
+            state,
+            escape(source)
+        ));
+        if !target.is_empty() {
+            self.result
+                .push_str(&format!("        <target>{}</target>\n", escape(target)));
+        }
+        self.result.push_str("      </segment>\n    </unit>\n");
+    }
+
+    /// Dumps the messages in XLIFF format. Metadata is stored as notes.
+    pub fn dump(mut self, messages: &[Message], encoding: Encoding) -> Result<String> {
+        self.start(encoding);
+        for (i, message) in messages.iter().enumerate() {
+            let notes = notes(message.name.as_deref(), &message.meta);
+            self.add_unit(i, &notes, &message.message, "", None, false);
+        }
+        Ok(self.end())
+    }
+
+    /// Dumps the extended messages in XLIFF format. Metadata is stored as notes.
+    pub fn dump_extended(
+        mut self,
+        messages: &[ExtendedMessage],
+        encoding: Encoding,
+    ) -> Result<String> {
+        self.start(encoding);
+        for (i, message) in messages.iter().enumerate() {
+            let notes = notes(message.name.as_deref(), &message.meta);
+            self.add_unit(
+                i,
+                &notes,
+                &message.source,
+                &message.translated,
+                message.llm.as_deref(),
+                message.fuzzy,
+            );
+        }
+        Ok(self.end())
+    }
+}
+
+fn notes(name: Option<&str>, meta: &BTreeMap<String, serde_json::Value>) -> Vec<(String, String)> {
+    let mut notes = Vec::new();
+    if let Some(name) = name {
+        notes.push(("name".to_string(), name.to_string()));
+    }
+    for (key, value) in meta {
+        let value = match value {
+            serde_json::Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+        notes.push((key.clone(), value));
+    }
+    notes
+}
+
+/// A parser for XLIFF 2.x format.
+pub struct XliffParser<'a> {
+    str: &'a str,
+    llm_mark: Option<&'a str>,
+}
+
+fn collect_units<'a>(elem: &'a Element, units: &mut Vec<&'a Element>) {
+    for child in elem.elements() {
+        match child.local_name() {
+            "unit" => units.push(child),
+            "file" | "group" => collect_units(child, units),
+            _ => {}
+        }
+    }
+}
+
+impl<'a> XliffParser<'a> {
+    /// Creates a new parser.
+    pub fn new(str: &'a str, llm_mark: Option<&'a str>) -> Self {
+        Self { str, llm_mark }
+    }
+
+    pub fn parse_as_extend(&mut self) -> Result<Vec<ExtendedMessage>> {
+        let root = xml::parse(self.str)?;
+        if root.local_name() != "xliff" {
+            anyhow::bail!("Root element is not xliff: {}", root.name);
+        }
+        if !root.attr("version").is_some_and(|v| v.starts_with("2.")) {
+            anyhow::bail!(
+                "Unsupported XLIFF version: {}, only XLIFF 2.x is supported",
+                root.attr("version").unwrap_or("unknown")
+            );
+        }
+        let mut units = Vec::new();
+        collect_units(&root, &mut units);
+        let mut messages = Vec::new();
+        for unit in units {
+            let mut name = None;
+            let mut meta = BTreeMap::new();
+            for note in unit.child("notes").iter().flat_map(|n| n.children("note")) {
+                match note.attr("category") {
+                    Some("name") => name = Some(note.text()),
+                    Some(category) => {
+                        meta.insert(
+                            category.to_string(),
+                            super::meta_value_from_str(&note.text()),
+                        );
+                    }
+                    None => {}
+                }
+            }
+            // CAT tools may split a unit into multiple segments.
+            let mut source = String::new();
+            let mut translated = String::new();
+            let mut fuzzy = false;
+            for seg in unit
+                .elements()
+                .filter(|e| matches!(e.local_name(), "segment" | "ignorable"))
+            {
+                let src = seg.child("source").map(|s| s.text()).unwrap_or_default();
+                let target = seg.child("target").map(|t| t.text());
+                if seg.local_name() == "segment"
+                    && seg.attr("state").unwrap_or("initial") == "initial"
+                    && target.as_ref().is_some_and(|t| !t.is_empty())
+                {
+                    fuzzy = true;
+                }
+                translated.push_str(target.as_deref().unwrap_or(
+                    if seg.local_name() == "ignorable" {
+                        &src
+                    } else {
+                        ""
+                    },
+                ));
+                source.push_str(&src);
+            }
+            // Candidates from other origins (such as MT engines of CAT tools) are not LLM messages.
+            let llm = unit
+                .child("matches")
+                .and_then(|m| {
+                    m.children("match")
+                        .find(|m| m.attr("origin") == Some("llm"))
+                })
+                .and_then(|m| m.child("target"))
+                .map(|t| t.text());
+            if translated.trim().is_empty() {
+                translated.clear();
+            }
+            messages.push(ExtendedMessage {
+                name,
+                source,
+                translated,
+                llm,
+                fuzzy,
+                meta,
+            });
+        }
+        Ok(messages)
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Message>> {
+        Ok(self
+            .parse_as_extend()?
+            .into_iter()
+            .map(|m| super::extended_to_message(m, self.llm_mark))
+            .collect())
+    }
+}
+
+#[test]
+fn test_xliff_round_trip() {
+    let messages = vec![
+        ExtendedMessage {
+            name: Some("A&B".to_string()),
+            source: "「こんにちは」\n<tag>".to_string(),
+            translated: "「你好」\n<tag>".to_string(),
+            llm: Some("「你好啊」".to_string()),
+            fuzzy: false,
+            meta: BTreeMap::from([
+                (meta_key::VOICE.to_string(), "v001".into()),
+                (meta_key::LINE.to_string(), 12.into()),
+            ]),
+        },
+        ExtendedMessage {
+            name: None,
+            source: "さようなら".to_string(),
+            translated: "再见".to_string(),
+            llm: None,
+            fuzzy: true,
+            meta: BTreeMap::new(),
+        },
+        ExtendedMessage {
+            name: None,
+            source: "未訳".to_string(),
+            translated: String::new(),
+            llm: Some("未译".to_string()),
+            fuzzy: false,
+            meta: BTreeMap::new(),
+        },
+    ];
+    let s = XliffDumper::new("ja", "zh-CN")
+        .dump_extended(&messages, Encoding::Utf8)
+        .unwrap();
+    let parsed = XliffParser::new(&s, None).parse_as_extend().unwrap();
+    assert_eq!(parsed.len(), 3);
+    for (a, b) in messages.iter().zip(parsed.iter()) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.source, b.source);
+        assert_eq!(a.translated, b.translated);
+        assert_eq!(a.llm, b.llm);
+        assert_eq!(a.fuzzy, b.fuzzy);
+        assert_eq!(a.meta, b.meta);
+    }
+    let parsed = XliffParser::new(&s, Some("(LLM)")).parse().unwrap();
+    assert_eq!(parsed[0].message, "「你好」\n<tag>");
+    assert_eq!(parsed[0].name.as_deref(), Some("A&B"));
+    assert_eq!(parsed[0].meta, messages[0].meta);
+    // Translations in initial state are not imported.
+    assert_eq!(parsed[1].message, "さようなら");
+    assert_eq!(parsed[2].message, "未译(LLM)");
+    // Segmented unit written by CAT tools
+    let s = "<xliff version=\"2.1\" srcLang=\"ja\"><file id=\"f\"><group id=\"g\"><unit id=\"u\"><segment state=\"final\"><source>あ。</source><target>A.</target></segment><ignorable><source> </source></ignorable><segment><source>い</source><target>B</target></segment></unit></group></file></xliff>";
+    let parsed = XliffParser::new(s, None).parse_as_extend().unwrap();
+    assert_eq!(parsed[0].source, "あ。 い");
+    assert_eq!(parsed[0].translated, "A. B");
+    assert!(parsed[0].fuzzy);
+    assert_eq!(parsed[0].llm, None);
+    // Matches are written before notes.
+    let s = XliffDumper::new("ja", "zh-CN")
+        .dump_extended(&messages[..1], Encoding::Utf8)
+        .unwrap();
+    assert!(s.find("<mtc:matches>").unwrap() < s.find("<notes>").unwrap());
+    // Only candidates from LLM are read.
+    let s = "<xliff version=\"2.0\" xmlns:mtc=\"urn:oasis:names:tc:xliff:matches:2.0\"><file id=\"f\"><unit id=\"u\"><mtc:matches><mtc:match ref=\"#s1\" origin=\"mt\"><source>あ</source><target>MT</target></mtc:match></mtc:matches><segment id=\"s1\"><source>あ</source></segment></unit></file></xliff>";
+    let parsed = XliffParser::new(s, None).parse_as_extend().unwrap();
+    assert_eq!(parsed[0].llm, None);
+}
@@ -0,0 +1,304 @@
+//! Minimal XML reader and writer helpers used by XLIFF and TMX files.
+//!
+//! Only elements, attributes, text, CDATA and character entities are supported.
+//! Comments, processing instructions and doctype declarations are skipped.
+use crate::format::markup::{Markup, xml_entity};
+use anyhow::Result;
+
+#[derive(Debug)]
+/// A node in XML tree.
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Default)]
+/// A XML element.
+pub struct Element {
+    /// Qualified name, such as `mtc:match`
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    /// Returns the name without namespace prefix.
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    /// Returns the value of an attribute. Namespace prefix is ignored unless `name` contains one.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .or_else(|| self.attrs.iter().find(|(k, _)| local_name(k) == name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the child elements with the given local name.
+    pub fn children<'a, 'b>(
+        &'a self,
+        name: &'b str,
+    ) -> impl Iterator<Item = &'a Element> + use<'a, 'b> {
+        self.elements().filter(move |e| e.local_name() == name)
+    }
+
+    /// Returns the first child element with the given local name.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children(name).next()
+    }
+
+    /// Returns all child elements.
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|n| match n {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// Returns the text content of the element, including text of all descendants.
+    pub fn text(&self) -> String {
+        let mut s = String::new();
+        self.push_text(&mut s);
+        s
+    }
+
+    fn push_text(&self, s: &mut String) {
+        for node in &self.children {
+            match node {
+                Node::Element(e) => e.push_text(s),
+                Node::Text(t) => s.push_str(t),
+            }
+        }
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    match name.rfind(':') {
+        // xml:lang and xml:space are not namespaced attributes of the document
+        Some(_) if name.starts_with("xml:") => name,
+        Some(pos) => &name[pos + 1..],
+        None => name,
+    }
+}
+
+/// Escapes text or attribute value.
+pub fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            // Keep \r from being normalized by XML parsers.
+            '\r' => result.push_str("&#13;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Unescapes character entities in text or attribute value.
+pub fn unescape(s: &str) -> Result<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        match xml_entity(rest) {
+            Some((Markup::Escape(c), len)) => {
+                result.push(c);
+                rest = &rest[len..];
+            }
+            _ => anyhow::bail!(
+                "Invalid XML entity: {}",
+                rest.chars().take(12).collect::<String>()
+            ),
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Normalizes literal `\r\n` and `\r` to `\n` as XML parsers do. Escaped `&#13;` is kept.
+fn normalize_line_breaks(s: &str) -> String {
+    s.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+struct Reader<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn line(&self) -> usize {
+        self.s[..self.pos].matches('\n').count() + 1
+    }
+
+    fn skip_until(&mut self, end: &str) -> Result<&'a str> {
+        match self.rest().find(end) {
+            Some(p) => {
+                let skipped = &self.rest()[..p];
+                self.pos += p + end.len();
+                Ok(skipped)
+            }
+            None => anyhow::bail!(
+                "Unexpected end of XML at line {}, expect {}",
+                self.line(),
+                end
+            ),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn read_name(&mut self) -> Result<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '='))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            anyhow::bail!("Expect a XML name at line {}", self.line());
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Reads a start tag after `<`. Returns the element and whether it is an empty element.
+    fn read_start_tag(&mut self) -> Result<(Element, bool)> {
+        let mut elem = Element {
+            name: self.read_name()?.to_string(),
+            ..Default::default()
+        };
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok((elem, true));
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                return Ok((elem, false));
+            }
+            let key = self.read_name()?.to_string();
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                anyhow::bail!("Expect '=' after attribute {} at line {}", key, self.line());
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(q @ ('"' | '\'')) => q,
+                _ => anyhow::bail!("Expect quoted attribute value at line {}", self.line()),
+            };
+            self.pos += 1;
+            let value = self.skip_until(if quote == '"' { "\"" } else { "'" })?;
+            elem.attrs
+                .push((key, unescape(&normalize_line_breaks(value))?));
+        }
+    }
+}
+
+/// Parses a XML document and returns the root element.
+pub fn parse(s: &str) -> Result<Element> {
+    let mut reader = Reader {
+        s: s.trim_start_matches('\u{feff}'),
+        pos: 0,
+    };
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    while reader.pos < reader.s.len() {
+        let rest = reader.rest();
+        if rest.starts_with("<?") {
+            reader.skip_until("?>")?;
+        } else if rest.starts_with("<!--") {
+            reader.skip_until("-->")?;
+        } else if rest.starts_with("<![CDATA[") {
+            reader.pos += 9;
+            let text = reader.skip_until("]]>")?;
+            if let Some(parent) = stack.last_mut() {
+                parent
+                    .children
+                    .push(Node::Text(normalize_line_breaks(text)));
+            }
+        } else if rest.starts_with("<!") {
+            reader.skip_until(">")?;
+        } else if rest.starts_with("</") {
+            reader.pos += 2;
+            let name = reader.read_name()?;
+            reader.skip_until(">")?;
+            let elem = match stack.pop() {
+                Some(elem) if elem.name == name => elem,
+                _ => anyhow::bail!("Unexpected end tag {} at line {}", name, reader.line()),
+            };
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(Node::Element(elem)),
+                None => root = Some(elem),
+            }
+        } else if rest.starts_with('<') {
+            reader.pos += 1;
+            let (elem, empty) = reader.read_start_tag()?;
+            if !empty {
+                stack.push(elem);
+            } else {
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(Node::Element(elem)),
+                    None => root = Some(elem),
+                }
+            }
+        } else {
+            let len = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..len];
+            reader.pos += len;
+            if let Some(parent) = stack.last_mut() {
+                parent
+                    .children
+                    .push(Node::Text(unescape(&normalize_line_breaks(text))?));
+            } else if !text.trim().is_empty() {
+                anyhow::bail!("Text outside of root element at line {}", reader.line());
+            }
+        }
+    }
+    if let Some(elem) = stack.last() {
+        anyhow::bail!("Element {} is not closed", elem.name);
+    }
+    root.ok_or_else(|| anyhow::anyhow!("No root element found in XML"))
+}
+
+/// Returns the XML declaration for the given encoding.
+pub fn declaration(encoding: crate::types::Encoding) -> String {
+    match encoding.charset() {
+        Some(charset) => format!("<?xml version=\"1.0\" encoding=\"{}\"?>\n", charset),
+        None => String::from("<?xml version=\"1.0\"?>\n"),
+    }
+}
+
+#[test]
+fn test_xml_parse() {
+    let doc = parse(
+        "\u{feff}<?xml version=\"1.0\"?>\n<!DOCTYPE a>\n<!-- c -->\n<a x:b='1 &amp; 2'><c/><d>t&lt;<e>x</e><![CDATA[<y>]]></d></a>",
+    )
+    .unwrap();
+    assert_eq!(doc.name, "a");
+    assert_eq!(doc.attr("b"), Some("1 & 2"));
+    assert!(doc.child("c").is_some());
+    assert_eq!(doc.child("d").unwrap().text(), "t<x<y>");
+    assert_eq!(unescape(&escape("a<\"&\r\n")).unwrap(), "a<\"&\r\n");
+    let doc =
+        parse("<a b=\"1\r\n2\"><c>x\r\ny\rz&#13;\n</c><d><![CDATA[1\r\n2]]></d></a>").unwrap();
+    assert_eq!(doc.attr("b"), Some("1\n2"));
+    assert_eq!(doc.child("c").unwrap().text(), "x\ny\nz\r\n");
+    assert_eq!(doc.child("d").unwrap().text(), "1\n2");
+    assert!(parse("<a><b></a>").is_err());
+    assert!(parse("<a>").is_err());
+}
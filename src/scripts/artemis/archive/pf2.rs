@@ -37,7 +37,7 @@ impl ScriptBuilder for ArtemisPf2Builder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(ArtemisPf2::new(
             MemReader::new(buf),
@@ -53,7 +53,7 @@ impl ScriptBuilder for ArtemisPf2Builder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let f = std::fs::File::open(filename)?;
         let f = std::io::BufReader::new(f);
@@ -72,7 +72,7 @@ impl ScriptBuilder for ArtemisPf2Builder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(ArtemisPf2::new(
             reader,
@@ -101,6 +101,10 @@ impl ScriptBuilder for ArtemisPf2Builder {
         None
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
@@ -39,7 +39,7 @@ impl ScriptBuilder for ArtemisArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(ArtemisArc::new(
             MemReader::new(buf),
@@ -55,7 +55,7 @@ impl ScriptBuilder for ArtemisArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let f = std::fs::File::open(filename)?;
         let f = std::io::BufReader::new(f);
@@ -74,7 +74,7 @@ impl ScriptBuilder for ArtemisArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(ArtemisArc::new(
             reader,
@@ -103,6 +103,10 @@ impl ScriptBuilder for ArtemisArcBuilder {
         None
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
@@ -36,7 +36,7 @@ impl ScriptBuilder for ArtemisAsbBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Asb::new(buf, encoding, config, filename)?))
     }
@@ -49,6 +49,10 @@ impl ScriptBuilder for ArtemisAsbBuilder {
         &ScriptType::ArtemisAsb
     }
 
+    fn inline_markup(&self) -> Option<&'static dyn crate::format::markup::InlineMarkup> {
+        Some(&super::ArtemisMarkup)
+    }
+
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
         if buf_len >= 5 && buf.starts_with(b"ASB\0\0") {
             return Some(20);
@@ -535,6 +539,7 @@ impl Script for Asb {
                                 messages.push(Message {
                                     name: name.take(),
                                     message: cur_mes,
+                                    ..Default::default()
                                 });
                                 cur_mes = String::new();
                                 continue;
@@ -564,6 +569,7 @@ impl Script for Asb {
                             messages.push(Message {
                                 name: None,
                                 message: t.to_owned(),
+                                ..Default::default()
                             });
                         }
                     }
@@ -573,6 +579,7 @@ impl Script for Asb {
                             messages.push(Message {
                                 name: None,
                                 message: t.to_owned(),
+                                ..Default::default()
                             });
                         }
                     }
@@ -584,6 +591,7 @@ impl Script for Asb {
             messages.push(Message {
                 name: name.take(),
                 message: cur_mes,
+                ..Default::default()
             });
         }
         Ok(messages)
@@ -37,7 +37,7 @@ impl ScriptBuilder for AstScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(AstScript::new(buf, encoding, config)?))
     }
@@ -50,6 +50,10 @@ impl ScriptBuilder for AstScriptBuilder {
         &ScriptType::Artemis
     }
 
+    fn inline_markup(&self) -> Option<&'static dyn crate::format::markup::InlineMarkup> {
+        Some(&super::ArtemisMarkup)
+    }
+
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
         let parser = parser::Parser::new(&buf[..buf_len], Encoding::Utf8);
         if parser.try_parse_header().is_ok() {
@@ -89,6 +93,11 @@ impl AstScript {
     }
 }
 
+/// Returns the voice file in a `vo` block.
+fn voice_file(vo: &Value) -> Option<String> {
+    vo.members().find_map(|v| v["file"].as_string())
+}
+
 impl Script for AstScript {
     fn default_output_script_type(&self) -> OutputScriptType {
         OutputScriptType::Json
@@ -112,26 +121,26 @@ impl Script for AstScript {
                     if savetitle.is_array() {
                         if let Some(lang) = lang {
                             if let Some(title) = savetitle[lang].as_str() {
-                                messages.push(Message {
-                                    name: None,
-                                    message: title.to_string(),
-                                });
+                                messages.push(
+                                    Message::new(title.to_string(), None)
+                                        .with_kind(MessageKind::Title),
+                                );
                             } else if let Some(title) = savetitle["text"].as_str() {
-                                messages.push(Message {
-                                    name: None,
-                                    message: title.to_string(),
-                                });
+                                messages.push(
+                                    Message::new(title.to_string(), None)
+                                        .with_kind(MessageKind::Title),
+                                );
                             }
                         } else if let Some(title) = savetitle["text"].as_str() {
-                            messages.push(Message {
-                                name: None,
-                                message: title.to_string(),
-                            });
+                            messages.push(
+                                Message::new(title.to_string(), None).with_kind(MessageKind::Title),
+                            );
                         }
                     }
                     if !block[Key("text")].is_null() {
                         let tex = &text[NumKey(text_index)];
                         text_index += 1;
+                        let voice = voice_file(&tex["vo"]);
                         if tex.is_array() {
                             // For some old scripts, the text block is directly under text array
                             if tex.arr_len() > 0 {
@@ -150,12 +159,14 @@ impl Script for AstScript {
                                         continue;
                                     }
                                     let message = text::TextGenerator::new().generate(item)?;
-                                    messages.push(Message {
-                                        name: nam.clone(),
-                                        message: message
-                                            .replace("<rt2>", "\n")
-                                            .replace("<ret2>", "\n"),
-                                    });
+                                    messages.push(
+                                        Message::new(
+                                            message.replace("<rt2>", "\n").replace("<ret2>", "\n"),
+                                            nam.clone(),
+                                        )
+                                        .with_kind(MessageKind::Dialogue)
+                                        .with_optional_meta(meta_key::VOICE, voice.clone()),
+                                    );
                                 }
                                 continue;
                             }
@@ -215,10 +226,14 @@ impl Script for AstScript {
                                     continue;
                                 }
                                 let message = text::TextGenerator::new().generate(item)?;
-                                messages.push(Message {
-                                    name: nam.clone(),
-                                    message: message.replace("<rt2>", "\n").replace("<ret2>", "\n"),
-                                });
+                                messages.push(
+                                    Message::new(
+                                        message.replace("<rt2>", "\n").replace("<ret2>", "\n"),
+                                        nam.clone(),
+                                    )
+                                    .with_kind(MessageKind::Dialogue)
+                                    .with_optional_meta(meta_key::VOICE, voice.clone()),
+                                );
                             }
                         }
                     } else if !block[Key("select")].is_null() {
@@ -252,10 +267,10 @@ impl Script for AstScript {
                             }
                             for item in te.members() {
                                 if let Some(select) = item.as_str() {
-                                    messages.push(Message {
-                                        name: None,
-                                        message: select.to_string(),
-                                    });
+                                    messages.push(
+                                        Message::new(select.to_string(), None)
+                                            .with_kind(MessageKind::Choice),
+                                    );
                                 }
                             }
                         }
@@ -273,25 +288,29 @@ impl Script for AstScript {
             if savetitle.is_array() {
                 if let Some(lang) = lang {
                     if let Some(title) = savetitle[lang].as_str() {
-                        messages.push(Message {
-                            name: None,
-                            message: title.to_string(),
-                        });
+                        messages.push(
+                            Message::new(title.to_string(), None)
+                                .with_kind(MessageKind::Title)
+                                .with_meta(meta_key::LABEL, block_name),
+                        );
                     } else if let Some(title) = savetitle["text"].as_str() {
-                        messages.push(Message {
-                            name: None,
-                            message: title.to_string(),
-                        });
+                        messages.push(
+                            Message::new(title.to_string(), None)
+                                .with_kind(MessageKind::Title)
+                                .with_meta(meta_key::LABEL, block_name),
+                        );
                     }
                 } else if let Some(title) = savetitle["text"].as_str() {
-                    messages.push(Message {
-                        name: None,
-                        message: title.to_string(),
-                    });
+                    messages.push(
+                        Message::new(title.to_string(), None)
+                            .with_kind(MessageKind::Title)
+                            .with_meta(meta_key::LABEL, block_name),
+                    );
                 }
             }
             let text = &block["text"];
             if text.is_array() {
+                let voice = voice_file(&text["vo"]);
                 let lan = match lang {
                     Some(l) => l,
                     None => {
@@ -327,14 +346,19 @@ impl Script for AstScript {
                 for item in tex.members() {
                     let name = item["name"].last_member().as_string();
                     let message = text::TextGenerator::new().generate(item)?;
-                    messages.push(Message {
-                        name: name,
-                        message: message
-                            .replace("<rt2>", "\n")
-                            .replace("<ret2>", "\n")
-                            .trim_end_matches("\n")
-                            .to_string(),
-                    });
+                    messages.push(
+                        Message::new(
+                            message
+                                .replace("<rt2>", "\n")
+                                .replace("<ret2>", "\n")
+                                .trim_end_matches("\n")
+                                .to_string(),
+                            name,
+                        )
+                        .with_kind(MessageKind::Dialogue)
+                        .with_meta(meta_key::LABEL, block_name)
+                        .with_optional_meta(meta_key::VOICE, voice.clone()),
+                    );
                 }
             }
             let select = &block["select"];
@@ -373,10 +397,11 @@ impl Script for AstScript {
                 }
                 for item in select_text.members() {
                     if let Some(select) = item.as_str() {
-                        messages.push(Message {
-                            name: None,
-                            message: select.to_string(),
-                        });
+                        messages.push(
+                            Message::new(select.to_string(), None)
+                                .with_kind(MessageKind::Choice)
+                                .with_meta(meta_key::LABEL, block_name),
+                        );
                     }
                 }
             }
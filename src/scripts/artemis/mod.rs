@@ -6,3 +6,26 @@ pub mod ast;
 #[cfg(feature = "artemis-panmimisoft")]
 pub mod panmimisoft;
 pub mod txt;
+
+use crate::format::markup::*;
+
+#[derive(Debug)]
+/// Inline tags (`<ruby text="...">`, `<rt2>`, etc.) and XML escapes in Artemis messages
+pub(crate) struct ArtemisMarkup;
+
+impl InlineMarkup for ArtemisMarkup {
+    fn parse(&self, text: &str) -> Option<(Markup, usize)> {
+        if text.starts_with('&') {
+            return xml_entity(text);
+        }
+        let len = bracket_tag_len(text, '<', '>')?;
+        match xml_tag_name(&text[..len]) {
+            "rt" | "rt2" | "ret2" => Some((Markup::LineBreak, len)),
+            _ => Some((Markup::Tag, len)),
+        }
+    }
+
+    fn closing_tag(&self, tag: &str) -> Option<&'static str> {
+        (!tag.starts_with("</") && xml_tag_name(tag) == "ruby").then_some("</ruby>")
+    }
+}
@@ -31,7 +31,7 @@ impl ScriptBuilder for TxtBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(TxtScript::new(buf, encoding, config)?))
     }
@@ -927,7 +927,11 @@ impl Script for TxtScript {
                                 }),
                                 _ => None,
                             };
-                            messages.push(Message { name, message: mes });
+                            messages.push(Message {
+                                name,
+                                message: mes,
+                                ..Default::default()
+                            });
                         }
                         last_tag_block = None;
                     }
@@ -950,6 +954,7 @@ impl Script for TxtScript {
                                 messages.push(Message {
                                     name: None,
                                     message: text.to_string(),
+                                    ..Default::default()
                                 });
                             } else if node.is_tag("/selectbtn") {
                                 is_selectblk = false;
@@ -1016,7 +1021,11 @@ impl Script for TxtScript {
                                 }),
                                 _ => None,
                             };
-                            messages.push(Message { name, message: mes });
+                            messages.push(Message {
+                                name,
+                                message: mes,
+                                ..Default::default()
+                            });
                         }
                         last_tag_block = None;
                     } else if node.is_tag("selectbtn_init") {
@@ -1055,6 +1064,7 @@ impl Script for TxtScript {
                             messages.push(Message {
                                 name: None,
                                 message: t.to_string(),
+                                ..Default::default()
                             });
                         }
                     } else if node.is_tag("/selectbtn") {
@@ -27,7 +27,7 @@ impl ScriptBuilder for ArtemisTxtBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         _config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(ArtemisTxtScript::new(buf, encoding)?))
     }
@@ -239,6 +239,7 @@ impl Script for ArtemisTxtScript {
             messages.push(Message {
                 name: entry.speaker.clone(),
                 message: text,
+                ..Default::default()
             });
         }
         Ok(messages)
@@ -56,7 +56,7 @@ pub trait ScriptBuilder: std::fmt::Debug {
         encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>>;
 
     /// Builds a script from a file.
@@ -72,7 +72,7 @@ pub trait ScriptBuilder: std::fmt::Debug {
         encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let data = crate::utils::files::read_file(filename)?;
         self.build_script(data, filename, encoding, archive_encoding, config, archive)
@@ -93,7 +93,7 @@ pub trait ScriptBuilder: std::fmt::Debug {
         encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         let mut data = Vec::new();
         reader
@@ -119,6 +119,13 @@ pub trait ScriptBuilder: std::fmt::Debug {
     /// Returns the script type associated with this builder.
     fn script_type(&self) -> &'static ScriptType;
 
+    /// Returns the inline markup tokenizer of this script type.
+    ///
+    /// Inline markups are measured as zero width and never split when formatting messages.
+    fn inline_markup(&self) -> Option<&'static dyn crate::format::markup::InlineMarkup> {
+        None
+    }
+
     /// Returns true if this script is an archive.
     fn is_archive(&self) -> bool {
         false
@@ -129,6 +136,11 @@ pub trait ScriptBuilder: std::fmt::Debug {
         false
     }
 
+    /// Returns true if this script type can create archives with [Self::create_archive].
+    fn can_create_archive(&self) -> bool {
+        false
+    }
+
     /// Creates an archive with the given files.
     ///
     /// * `filename` - The path of the archive file to create.
@@ -241,6 +253,46 @@ pub trait ScriptBuilder: std::fmt::Debug {
         let f = std::io::BufWriter::new(f);
         self.create_image_file(data, image_filename, Box::new(f), options)
     }
+
+    /// Returns true if this script type can create from multiple image files directly.
+    #[cfg(feature = "image")]
+    fn can_create_multi_image_file(&self) -> bool {
+        false
+    }
+
+    /// Creates an image file which contains multiple images.
+    ///
+    /// * `data` - The images to write, sorted by name.
+    /// * `writer` - A writer with seek capabilities to write the image data.
+    /// * `options` - Additional configuration options.
+    #[cfg(feature = "image")]
+    fn create_multi_image_file<'a>(
+        &'a self,
+        _data: Vec<ImageDataWithName>,
+        _writer: Box<dyn WriteSeek + 'a>,
+        _options: &ExtraConfig,
+    ) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "This script type does not support creating an image file from multiple images."
+        ))
+    }
+
+    /// Creates an image file which contains multiple images to the specified filename.
+    ///
+    /// * `data` - The images to write, sorted by name.
+    /// * `filename` - The path to the output file.
+    /// * `options` - Additional configuration options.
+    #[cfg(feature = "image")]
+    fn create_multi_image_file_filename(
+        &self,
+        data: Vec<ImageDataWithName>,
+        filename: &str,
+        options: &ExtraConfig,
+    ) -> Result<()> {
+        let f = std::fs::File::create(filename)?;
+        let f = std::io::BufWriter::new(f);
+        self.create_multi_image_file(data, Box::new(f), options)
+    }
 }
 
 /// A trait to present the file in an archive.
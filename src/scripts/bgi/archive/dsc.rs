@@ -719,7 +719,7 @@ impl ScriptBuilder for DscBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Dsc::new(buf, config)?))
     }
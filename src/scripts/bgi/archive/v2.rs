@@ -39,7 +39,7 @@ impl ScriptBuilder for BgiArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(BgiArchive::new(
             MemReader::new(data),
@@ -55,7 +55,7 @@ impl ScriptBuilder for BgiArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -84,7 +84,7 @@ impl ScriptBuilder for BgiArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(BgiArchive::new(
             reader,
@@ -113,6 +113,10 @@ impl ScriptBuilder for BgiArchiveBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
@@ -2,6 +2,7 @@
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
+use crate::utils::ogg::*;
 use anyhow::Result;
 use std::io::{Read, Seek, SeekFrom, Write};
 
@@ -28,7 +29,7 @@ impl ScriptBuilder for BgiAudioBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(BgiAudio::new(MemReader::new(buf), config)?))
     }
@@ -39,7 +40,7 @@ impl ScriptBuilder for BgiAudioBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let file = std::fs::File::open(filename)?;
         let f = std::io::BufReader::new(file);
@@ -53,7 +54,7 @@ impl ScriptBuilder for BgiAudioBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(BgiAudio::new(reader, config)?))
     }
@@ -77,11 +78,43 @@ impl ScriptBuilder for BgiAudioBuilder {
     fn is_audio(&self) -> bool {
         true
     }
+
+    fn can_create_file(&self) -> bool {
+        true
+    }
+
+    fn create_file<'a>(
+        &'a self,
+        filename: &'a str,
+        writer: Box<dyn WriteSeek + 'a>,
+        _encoding: Encoding,
+        _file_encoding: Encoding,
+        _config: &ExtraConfig,
+    ) -> Result<()> {
+        let data = std::fs::read(filename)?;
+        BgiAudio::write(writer, Vec::new(), &data)
+    }
 }
 
+/// Size of the header written by the engine's tools.
+const HEADER_SIZE: usize = 0x40;
+/// Offset of the Ogg data size in the header.
+const DATA_SIZE_OFFSET: usize = 0x08;
+/// Offset of the total number of samples (per channel) in the header.
+const SAMPLES_OFFSET: usize = 0x0C;
+/// Offset of the sample rate in the header.
+const SAMPLE_RATE_OFFSET: usize = 0x10;
+/// Offset of the number of channels in the header.
+const CHANNELS_OFFSET: usize = 0x14;
+/// Offset of the loop start sample in the header.
+const LOOP_START_OFFSET: usize = 0x18;
+/// Offset of the loop end sample in the header.
+const LOOP_END_OFFSET: usize = 0x1C;
+
 #[derive(Debug)]
 /// BGI Audio script.
 pub struct BgiAudio {
+    header: Vec<u8>,
     data: MemReader,
 }
 
@@ -104,13 +137,56 @@ impl BgiAudio {
                 magic
             ));
         }
-        reader.seek(SeekFrom::Start(offset as u64))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut header = vec![0; offset as usize];
+        reader.read_exact(&mut header)?;
         let mut data = Vec::new();
         reader.read_to_end(&mut data)?;
         Ok(Self {
+            header,
             data: MemReader::new(data),
         })
     }
+
+    /// Writes an audio file with the given header and Ogg/Vorbis data.
+    ///
+    /// The size and sample fields in the header are updated to match the data.
+    /// A new header is created if `header` is empty.
+    fn write<W: Write>(mut writer: W, mut header: Vec<u8>, data: &[u8]) -> Result<()> {
+        if !data.starts_with(b"OggS") {
+            return Err(anyhow::anyhow!("Input file is not an Ogg/Vorbis file"));
+        }
+        let info = read_vorbis_info(data)?;
+        if header.len() < HEADER_SIZE {
+            header.resize(HEADER_SIZE, 0);
+            header[..4].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+            header[4..8].copy_from_slice(b"bw  ");
+        }
+        let r = MemReaderRef::new(&header);
+        let old_samples = r.cpeek_u32_at(SAMPLES_OFFSET as u64)?;
+        let loop_start = r.cpeek_u32_at(LOOP_START_OFFSET as u64)?;
+        let loop_end = r.cpeek_u32_at(LOOP_END_OFFSET as u64)?;
+        let mut h = MemWriterRef::new(&mut header);
+        let samples = info.samples as u32;
+        h.write_u32_at(DATA_SIZE_OFFSET as u64, data.len() as u32)?;
+        h.write_u32_at(SAMPLES_OFFSET as u64, samples)?;
+        h.write_u32_at(SAMPLE_RATE_OFFSET as u64, info.sample_rate)?;
+        h.write_u32_at(CHANNELS_OFFSET as u64, info.channels as u32)?;
+        // Keep loops which end at the end of the audio, and drop loops out of range.
+        if loop_end == old_samples || loop_end > samples {
+            h.write_u32_at(
+                LOOP_END_OFFSET as u64,
+                if loop_end == 0 { 0 } else { samples },
+            )?;
+        }
+        if loop_start >= samples {
+            h.write_u32_at(LOOP_START_OFFSET as u64, 0)?;
+        }
+        writer.write_all(&header)?;
+        writer.write_all(data)?;
+        writer.flush()?;
+        Ok(())
+    }
 }
 
 impl Script for BgiAudio {
@@ -136,4 +212,52 @@ impl Script for BgiAudio {
         writer.flush()?;
         Ok(())
     }
+
+    fn custom_import<'a>(
+        &'a self,
+        custom_filename: &'a str,
+        file: Box<dyn WriteSeek + 'a>,
+        _encoding: Encoding,
+        _output_encoding: Encoding,
+    ) -> Result<()> {
+        let data = std::fs::read(custom_filename)?;
+        Self::write(file, self.header.clone(), &data)
+    }
+}
+
+#[cfg(test)]
+fn ogg_page(header_type: u8, granule: i64, body: &[u8]) -> Vec<u8> {
+    let mut page = b"OggS\0".to_vec();
+    page.push(header_type);
+    page.extend_from_slice(&granule.to_le_bytes());
+    page.extend_from_slice(&1u32.to_le_bytes());
+    page.extend_from_slice(&[0; 8]);
+    page.push(1);
+    page.push(body.len() as u8);
+    page.extend_from_slice(body);
+    page
+}
+
+#[test]
+fn test_import() {
+    let mut ident = b"\x01vorbis".to_vec();
+    ident.extend_from_slice(&0u32.to_le_bytes());
+    ident.push(2);
+    ident.extend_from_slice(&44100u32.to_le_bytes());
+    let mut ogg = ogg_page(2, 0, &ident);
+    ogg.extend(ogg_page(0, 1000, &[0; 16]));
+    ogg.extend(ogg_page(4, 2345, &[0; 16]));
+    let mut writer = MemWriter::new();
+    BgiAudio::write(&mut writer, Vec::new(), &ogg).unwrap();
+    let data = writer.into_inner();
+    let audio = BgiAudio::new(MemReader::new(data), &ExtraConfig::default()).unwrap();
+    assert_eq!(audio.data.data, ogg);
+    let h = MemReaderRef::new(&audio.header);
+    assert_eq!(h.cpeek_u32_at(0).unwrap(), HEADER_SIZE as u32);
+    assert_eq!(
+        h.cpeek_u32_at(DATA_SIZE_OFFSET as u64).unwrap(),
+        ogg.len() as u32
+    );
+    assert_eq!(h.cpeek_u32_at(SAMPLES_OFFSET as u64).unwrap(), 2345);
+    assert_eq!(h.cpeek_u32_at(SAMPLE_RATE_OFFSET as u64).unwrap(), 44100);
 }
@@ -29,7 +29,7 @@ impl ScriptBuilder for BGIBpScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(BGIBpScript::new(buf, encoding, config)?))
     }
@@ -144,6 +144,7 @@ impl Script for BGIBpScript {
             messages.push(Message {
                 name: None,
                 message: str,
+                ..Default::default()
             });
         }
         Ok(messages)
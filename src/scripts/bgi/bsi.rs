@@ -30,7 +30,7 @@ impl ScriptBuilder for BGIBsiScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(BGIBsiScript::new(buf, encoding, config)?))
     }
@@ -36,7 +36,7 @@ impl ScriptBuilder for BgiCBGBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(BgiCBG::new(data, config)?))
     }
@@ -69,10 +69,13 @@ impl ScriptBuilder for BgiCBGBuilder {
         data: ImageData,
         _filename: &str,
         mut writer: Box<dyn WriteSeek + 'a>,
-        _options: &ExtraConfig,
+        options: &ExtraConfig,
     ) -> Result<()> {
         let encoder = CbgEncoder::new(data)?;
-        let data = encoder.encode()?;
+        let data = match options.bgi_cbg_version {
+            Some(2) => encoder.encode_v2(&quant_table(options.bgi_cbg_quality.unwrap_or(80)))?,
+            _ => encoder.encode()?,
+        };
         writer.write_all(&data)?;
         Ok(())
     }
@@ -136,6 +139,8 @@ pub struct BgiCBG {
     data: MemReader,
     color_type: CbgColorType,
     decode_workers: usize,
+    version: Option<u16>,
+    quality: Option<u8>,
 }
 
 impl BgiCBG {
@@ -171,8 +176,28 @@ impl BgiCBG {
             data: reader,
             color_type,
             decode_workers: config.bgi_img_workers.max(1),
+            version: config.bgi_cbg_version,
+            quality: config.bgi_cbg_quality,
         })
     }
+
+    /// Returns the quantization tables of a version 2 image.
+    fn quant_table(&self) -> Result<[u8; 0x80]> {
+        let mut decoder = CbgDecoder::new(
+            self.data.to_ref(),
+            &self.header,
+            self.color_type,
+            self.decode_workers,
+        )?;
+        decoder.stream.m_input.pos = 0x30;
+        let data = decoder.read_encoded()?;
+        if data.len() < 0x80 {
+            return Err(anyhow::anyhow!("Invalid encoded length: {}", data.len()));
+        }
+        let mut table = [0u8; 0x80];
+        table.copy_from_slice(&data[..0x80]);
+        Ok(table)
+    }
 }
 
 impl Script for BgiCBG {
@@ -205,7 +230,17 @@ impl Script for BgiCBG {
         mut file: Box<dyn WriteSeek + 'a>,
     ) -> Result<()> {
         let encoder = CbgEncoder::new(data)?;
-        let encoded_data = encoder.encode()?;
+        let version = self.version.unwrap_or(self.header.version);
+        let encoded_data = if version == 2 {
+            let table = match self.quality {
+                Some(quality) => quant_table(quality),
+                None if self.header.version == 2 => self.quant_table()?,
+                None => quant_table(80),
+            };
+            encoder.encode_v2(&table)?
+        } else {
+            encoder.encode()?
+        };
         file.write_all(&encoded_data)?;
         Ok(())
     }
@@ -393,8 +428,8 @@ impl<'a> CbgDecoder<'a> {
         if !has_alpha {
             let mut src_idx = 0;
             let mut dst_idx = 0;
-            for _ in 0..self.info.height {
-                for _ in 0..self.info.width {
+            for _ in 0..height {
+                for _ in 0..width {
                     output[dst_idx] = output[src_idx];
                     output[dst_idx + 1] = output[src_idx + 1];
                     output[dst_idx + 2] = output[src_idx + 2];
@@ -420,12 +455,12 @@ impl<'a> CbgDecoder<'a> {
         };
 
         if decoder.width != self.info.width as i32 || decoder.height != self.info.height as i32 {
-            return Ok(draw_on_canvas(
-                img,
-                self.info.width as u32,
-                self.info.height as u32,
+            return Ok(crop_image(
+                &img,
                 0,
                 0,
+                self.info.width as u32,
+                self.info.height as u32,
             )?);
         }
 
@@ -672,6 +707,18 @@ impl HuffmanTree {
         Ok(())
     }
 
+    /// Returns the codes of tokens `0..count`. Tokens which are not in the tree have empty codes.
+    fn codes(&self, count: usize) -> Vec<Vec<bool>> {
+        (0..count)
+            .map(|token| {
+                let mut path = Vec::new();
+                self.find_path(self.nodes.len() - 1, token, &mut path);
+                path.reverse();
+                path
+            })
+            .collect()
+    }
+
     fn find_path(&self, node_index: usize, token: usize, path: &mut Vec<bool>) -> bool {
         if node_index == usize::MAX {
             return false;
@@ -704,6 +751,41 @@ const DCT_TABLE: [f32; 64] = [
     0.27589938, 0.38268343, 0.36047992, 0.32442334, 0.27589938, 0.21677275, 0.14931567, 0.07612047,
 ];
 
+/// Standard JPEG luminance quantization table.
+const LUMINANCE_QUANT_TABLE: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// Standard JPEG chrominance quantization table.
+const CHROMINANCE_QUANT_TABLE: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// Returns the quantization tables (luminance and chrominance) for version 2 images.
+///
+/// The tables are scaled by `quality` (1-100) in the same way as libjpeg.
+fn quant_table(quality: u8) -> [u8; 0x80] {
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    let mut table = [0u8; 0x80];
+    for (i, &v) in LUMINANCE_QUANT_TABLE
+        .iter()
+        .chain(CHROMINANCE_QUANT_TABLE.iter())
+        .enumerate()
+    {
+        table[i] = ((v as u32 * scale + 50) / 100).clamp(1, 255) as u8;
+    }
+    table
+}
+
 const BLOCK_FILL_ORDER: [u8; 64] = [
     0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
     13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
@@ -1138,6 +1220,266 @@ impl CbgEncoder {
         Ok(self.stream.into_inner())
     }
 
+    /// Encodes the image as version 2 (DCT based, lossy) image.
+    ///
+    /// * `quant` - Quantization tables of luminance and chrominance.
+    pub fn encode_v2(mut self, quant: &[u8; 0x80]) -> Result<Vec<u8>> {
+        self.stream.write_all(b"CompressedBG___\0")?;
+        let header_pos = self.stream.pos;
+        self.stream.seek(std::io::SeekFrom::Current(0x20))?;
+        self.header.version = 2;
+        self.write_encoded(quant)?;
+        let base_offset = self.stream.pos;
+
+        let width = (self.header.width as usize + 7) & !7;
+        let height = (self.header.height as usize + 7) & !7;
+        let y_blocks = height / 8;
+        let planes = self.color_planes(width, height);
+        let cos = dct_matrix();
+        let mut rows = Vec::with_capacity(y_blocks);
+        let mut dc_weights = vec![0u32; 0x10];
+        let mut ac_weights = vec![0u32; 0xB0];
+        for by in 0..y_blocks {
+            let mut dc_tokens = Vec::new();
+            let mut ac_tokens = Vec::new();
+            let mut acc = 0;
+            for (c, plane) in planes.iter().enumerate() {
+                let table = &quant[if c > 0 { 0x40 } else { 0 }..][..0x40];
+                for bx in 0..width / 8 {
+                    let block = Self::dct_block(plane, width, bx * 8, by * 8, table, &cos);
+                    let diff = block[0] as i32 - acc;
+                    acc = block[0] as i32;
+                    dc_tokens.push((bit_length(diff), diff));
+                    Self::ac_tokens(&block, &mut ac_tokens);
+                }
+            }
+            for &(token, _) in &dc_tokens {
+                dc_weights[token] += 1;
+            }
+            for &(token, _) in &ac_tokens {
+                ac_weights[token] += 1;
+            }
+            rows.push((dc_tokens, ac_tokens));
+        }
+        if ac_weights.iter().all(|&w| w == 0) {
+            ac_weights[0] = 1;
+        }
+        for &weight in dc_weights.iter().chain(ac_weights.iter()) {
+            Self::write_int(&mut self.stream, weight as i32)?;
+        }
+        let dc_codes = HuffmanTree::new(&dc_weights, true).codes(dc_weights.len());
+        let ac_codes = HuffmanTree::new(&ac_weights, true).codes(ac_weights.len());
+
+        let offsets_pos = self.stream.pos;
+        self.stream
+            .seek(std::io::SeekFrom::Current((y_blocks as i64 + 1) * 4))?;
+        let pad_skip = ((width >> 3) + 7) >> 3;
+        let block_size = width * 8 * planes.len();
+        let mut offsets = Vec::with_capacity(y_blocks + 1);
+        for (dc_tokens, ac_tokens) in rows {
+            offsets.push((self.stream.pos - base_offset) as i32);
+            self.stream.write_all(&vec![0u8; pad_skip])?;
+            Self::write_int(&mut self.stream, block_size as i32)?;
+            let mut writer = MsbBitWriter::new(&mut self.stream);
+            for (token, value) in dc_tokens {
+                Self::put_token(&mut writer, &dc_codes[token], value, token as u8)?;
+            }
+            writer.flush()?;
+            for (token, value) in ac_tokens {
+                Self::put_token(&mut writer, &ac_codes[token], value, (token >> 4) as u8)?;
+            }
+            writer.flush()?;
+            // The decoder stops when all input is read, even if some bits are still cached.
+            self.stream.write_u8(0)?;
+        }
+        offsets.push((self.stream.pos - base_offset) as i32);
+        if self.header.bpp == 32 {
+            self.stream.write_i32(1)?;
+            let alpha = self.alpha_plane(width, height);
+            self.stream.write_all(&Self::pack_alpha(&alpha, width))?;
+        }
+
+        let final_pos = self.stream.pos;
+        self.stream.pos = offsets_pos;
+        for offset in offsets {
+            self.stream.write_i32(offset)?;
+        }
+        self.stream.pos = header_pos;
+        self.header
+            .pack(&mut self.stream, false, Encoding::Cp932, &None)?;
+        self.stream.pos = final_pos;
+
+        Ok(self.stream.into_inner())
+    }
+
+    /// Returns the pixel position in image data. Pixels out of image are clamped to the edge.
+    fn pixel_pos(&self, x: usize, y: usize) -> usize {
+        let x = x.min(self.header.width as usize - 1);
+        let y = y.min(self.header.height as usize - 1);
+        let pixel_size = (self.header.bpp / 8) as usize;
+        (y * self.header.width as usize + x) * pixel_size
+    }
+
+    /// Converts the image to Y (and Cb, Cr) planes of the padded size.
+    fn color_planes(&self, width: usize, height: usize) -> Vec<Vec<f32>> {
+        let channels = if self.header.bpp == 8 { 1 } else { 3 };
+        let mut planes = vec![Vec::with_capacity(width * height); channels];
+        for y in 0..height {
+            for x in 0..width {
+                let pos = self.pixel_pos(x, y);
+                if channels == 1 {
+                    planes[0].push(self.img.data[pos] as f32);
+                    continue;
+                }
+                let b = self.img.data[pos] as f32;
+                let g = self.img.data[pos + 1] as f32;
+                let r = self.img.data[pos + 2] as f32;
+                let cy = 0.299 * r + 0.587 * g + 0.114 * b;
+                planes[0].push(cy);
+                planes[1].push((b - cy) / 1.772 + 128.0);
+                planes[2].push((r - cy) / 1.402 + 128.0);
+            }
+        }
+        planes
+    }
+
+    fn alpha_plane(&self, width: usize, height: usize) -> Vec<u8> {
+        let mut alpha = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                alpha.push(self.img.data[self.pixel_pos(x, y) + 3]);
+            }
+        }
+        alpha
+    }
+
+    /// Transforms and quantizes an 8x8 block.
+    fn dct_block(
+        plane: &[f32],
+        width: usize,
+        x: usize,
+        y: usize,
+        table: &[u8],
+        cos: &[[f32; 8]; 8],
+    ) -> [i16; 64] {
+        let mut tmp = [[0f32; 8]; 8];
+        for (row, t) in tmp.iter_mut().enumerate() {
+            let line = &plane[(y + row) * width + x..][..8];
+            for (out, c) in t.iter_mut().zip(cos.iter()) {
+                // The decoder truncates the result, so samples are shifted by 0.5 to round it.
+                *out = c.iter().zip(line).map(|(c, s)| c * (s - 127.5)).sum();
+            }
+        }
+        let mut block = [0i16; 64];
+        for (v, c) in cos.iter().enumerate() {
+            for u in 0..8 {
+                let f: f32 = c.iter().zip(tmp.iter()).map(|(c, t)| c * t[u]).sum();
+                let k = v * 8 + u;
+                let limit = if k == 0 { 2047.0 } else { 1023.0 };
+                block[k] = (f / table[k] as f32).round().clamp(-limit, limit) as i16;
+            }
+        }
+        block
+    }
+
+    /// Collects tokens of AC coefficients. The token is `(bit_length << 4) | zero_run`.
+    fn ac_tokens(block: &[i16; 64], tokens: &mut Vec<(usize, i32)>) {
+        let last = (1..64)
+            .rev()
+            .find(|&i| block[BLOCK_FILL_ORDER[i] as usize] != 0)
+            .unwrap_or(0);
+        let mut run = 0;
+        for i in 1..=last {
+            let v = block[BLOCK_FILL_ORDER[i] as usize] as i32;
+            if v == 0 {
+                run += 1;
+                continue;
+            }
+            while run >= 16 {
+                tokens.push((0xF, 0));
+                run -= 16;
+            }
+            tokens.push(((bit_length(v) << 4) | run, v));
+            run = 0;
+        }
+        if last < 63 {
+            tokens.push((0, 0));
+        }
+    }
+
+    fn put_token<W: Write>(
+        writer: &mut MsbBitWriter<W>,
+        code: &[bool],
+        value: i32,
+        bits: u8,
+    ) -> Result<()> {
+        for &bit in code {
+            writer.put_bit(bit)?;
+        }
+        if bits > 0 {
+            let value = if value > 0 {
+                value
+            } else {
+                value + (1 << bits) - 1
+            };
+            writer.put_bits(value as u32, bits)?;
+        }
+        Ok(())
+    }
+
+    /// Compresses the alpha channel with back references in the previous 7 lines.
+    fn pack_alpha(alpha: &[u8], width: usize) -> Vec<u8> {
+        let mut candidates: Vec<(i32, i32)> = vec![(-1, 0), (0, -1)];
+        for y in -7..=0 {
+            for x in -32..32 {
+                if !candidates.contains(&(x, y)) && (x as isize + y as isize * width as isize) < 0 {
+                    candidates.push((x, y));
+                }
+            }
+        }
+        let mut output = Vec::new();
+        let mut ctl_pos = 0;
+        let mut ctl_bit = 8;
+        let mut pos = 0;
+        while pos < alpha.len() {
+            if ctl_bit == 8 {
+                ctl_pos = output.len();
+                output.push(0);
+                ctl_bit = 0;
+            }
+            let max_len = (alpha.len() - pos).min(0x7f + 3);
+            let mut best = (0, 0, 0);
+            for &(x, y) in &candidates {
+                let offset = x as isize + y as isize * width as isize;
+                if offset >= 0 || (pos as isize) + offset < 0 {
+                    continue;
+                }
+                let src = (pos as isize + offset) as usize;
+                let len = (0..max_len)
+                    .take_while(|&i| alpha[src + i] == alpha[pos + i])
+                    .count();
+                if len > best.0 {
+                    best = (len, x, y);
+                    if len == max_len {
+                        break;
+                    }
+                }
+            }
+            let (len, x, y) = best;
+            if len >= 3 {
+                output[ctl_pos] |= 1 << ctl_bit;
+                let v = (x & 0x3f) | ((y & 7) << 6) | ((len as i32 - 3) << 9);
+                output.extend_from_slice(&(v as u16).to_le_bytes());
+                pos += len;
+            } else {
+                output.push(alpha[pos]);
+                pos += 1;
+            }
+            ctl_bit += 1;
+        }
+        output
+    }
+
     fn average_sampling(&self, data: &mut [u8], stride: usize, pixel_size: usize) {
         for y in (0..self.header.height as usize).rev() {
             let line = y * stride;
@@ -1251,3 +1593,77 @@ impl CbgEncoder {
         v1 as u8
     }
 }
+
+/// Returns the number of bits of the absolute value.
+fn bit_length(v: i32) -> usize {
+    (32 - v.unsigned_abs().leading_zeros()) as usize
+}
+
+/// Returns the matrix of 1-D DCT, including the normalization factors.
+fn dct_matrix() -> [[f32; 8]; 8] {
+    let mut cos = [[0f32; 8]; 8];
+    for (k, row) in cos.iter_mut().enumerate() {
+        let c = if k == 0 {
+            std::f64::consts::FRAC_1_SQRT_2
+        } else {
+            1.0
+        };
+        for (n, v) in row.iter_mut().enumerate() {
+            *v = (c / 2.0 * ((2 * n + 1) as f64 * k as f64 * std::f64::consts::PI / 16.0).cos())
+                as f32;
+        }
+    }
+    cos
+}
+
+#[test]
+fn test_v2_roundtrip() {
+    let (width, height) = (21u32, 13u32);
+    let mut data = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            data.extend_from_slice(&[
+                (x * 10) as u8,
+                (y * 15) as u8,
+                (128 + x * 3 - y * 4) as u8,
+                if x < 10 { 0xff } else { (y * 20) as u8 },
+            ]);
+        }
+    }
+    let images = [
+        (ImageColorType::Bgra, data.clone()),
+        (
+            ImageColorType::Bgr,
+            data.chunks_exact(4)
+                .flat_map(|p| [p[0], p[1], p[2]])
+                .collect(),
+        ),
+    ];
+    for (color_type, data) in images {
+        let img = ImageData {
+            width,
+            height,
+            color_type,
+            depth: 8,
+            data,
+        };
+        let encoded = CbgEncoder::new(img.clone())
+            .unwrap()
+            .encode_v2(&quant_table(100))
+            .unwrap();
+        let cbg = BgiCBG::new(encoded, &ExtraConfig::default()).unwrap();
+        assert_eq!(cbg.header.version, 2);
+        assert_eq!(cbg.quant_table().unwrap(), quant_table(100));
+        let decoded = cbg.export_image().unwrap();
+        assert_eq!(decoded.color_type, img.color_type);
+        assert_eq!((decoded.width, decoded.height), (width, height));
+        let channels = img.data.len() / (width * height) as usize;
+        for (i, (a, b)) in img.data.iter().zip(decoded.data.iter()).enumerate() {
+            if i % channels == 3 {
+                assert_eq!(a, b, "alpha of pixel {}", i / channels);
+            } else {
+                assert!(a.abs_diff(*b) <= 4, "{} != {} at {}", a, b, i);
+            }
+        }
+    }
+}
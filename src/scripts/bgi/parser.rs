@@ -215,6 +215,10 @@ pub enum BGIStringType {
     /// For v1 instructions.
     /// Only old BGI scripts have this type. (Scripts that does not have a magic)
     Ruby,
+    /// Text of a choice. Imported like [BGIStringType::Message].
+    Choice,
+    /// Voice file passed to a voice function. Kept like [BGIStringType::Internal].
+    Voice,
 }
 
 #[derive(Debug, Clone)]
@@ -226,7 +230,7 @@ pub struct BGIString {
 
 impl BGIString {
     pub fn is_internal(&self) -> bool {
-        matches!(self.typ, BGIStringType::Internal)
+        matches!(self.typ, BGIStringType::Internal | BGIStringType::Voice)
     }
 }
 
@@ -475,6 +479,15 @@ impl<'a> V1Parser<'a> {
         let funcname = self.read_string_at_address(item.value)?;
         if funcname == "_SelectEx" || funcname == "_SelectExtend" {
             self.handle_choice_screen()?;
+        } else if funcname.to_ascii_lowercase().contains("voice") {
+            // The voice file is the last string pushed before calling the voice function.
+            if let Some(voice) = self.stacks.pop() {
+                self.strings.push(BGIString {
+                    offset: voice.offset,
+                    address: voice.value,
+                    typ: BGIStringType::Voice,
+                });
+            }
         }
         Ok(())
     }
@@ -587,7 +600,7 @@ impl<'a> V1Parser<'a> {
             self.strings.push(BGIString {
                 offset: choice.offset,
                 address: choice.value,
-                typ: BGIStringType::Message,
+                typ: BGIStringType::Choice,
             });
         }
         Ok(())
@@ -1,6 +1,7 @@
 //! Buriko General Interpreter/Ethornell Script
 use super::parser::*;
 use crate::ext::io::*;
+use crate::format::markup::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use crate::utils::encoding::{decode_to_string, encode_string};
@@ -36,7 +37,7 @@ impl ScriptBuilder for BGIScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(BGIScript::new(buf, encoding, config)?))
     }
@@ -49,6 +50,10 @@ impl ScriptBuilder for BGIScriptBuilder {
         &ScriptType::BGI
     }
 
+    fn inline_markup(&self) -> Option<&'static dyn InlineMarkup> {
+        Some(&BGIMarkup)
+    }
+
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
         if buf_len > 28 && buf.starts_with(b"BurikoCompiledScriptVer1.00\0") {
             return Some(255);
@@ -57,6 +62,29 @@ impl ScriptBuilder for BGIScriptBuilder {
     }
 }
 
+#[derive(Debug)]
+/// Ruby tags (`<r{ruby}>{text}</r>`) in BGI messages
+struct BGIMarkup;
+
+impl InlineMarkup for BGIMarkup {
+    fn parse(&self, text: &str) -> Option<(Markup, usize)> {
+        if text.starts_with("</r>") {
+            return Some((Markup::Tag, 4));
+        }
+        if text.starts_with("<r") {
+            let len = text.find('>')? + 1;
+            if !text[..len].contains('\n') {
+                return Some((Markup::Tag, len));
+            }
+        }
+        None
+    }
+
+    fn closing_tag(&self, tag: &str) -> Option<&'static str> {
+        tag.starts_with("<r").then_some("</r>")
+    }
+}
+
 /// BGI Script
 pub struct BGIScript {
     data: MemReader,
@@ -196,6 +224,7 @@ impl Script for BGIScript {
     fn extract_messages(&self) -> Result<Vec<Message>> {
         let mut messages = Vec::new();
         let mut name = None;
+        let mut voice = None;
         let mut ruby = Vec::new();
         for bgi_string in &self.strings {
             match bgi_string.typ {
@@ -207,10 +236,22 @@ impl Script for BGIScript {
                     if !ruby.is_empty() {
                         Self::output_with_ruby(&mut message, &mut ruby)?;
                     }
-                    messages.push(Message {
-                        name: name.take(),
-                        message: message,
-                    });
+                    messages.push(
+                        Message::new(message, name.take())
+                            .with_kind(MessageKind::Dialogue)
+                            .with_optional_meta(meta_key::VOICE, voice.take())
+                            .with_meta(meta_key::OFFSET, bgi_string.offset),
+                    );
+                }
+                BGIStringType::Choice => {
+                    messages.push(
+                        Message::new(self.read_string(bgi_string.address)?, None)
+                            .with_kind(MessageKind::Choice)
+                            .with_meta(meta_key::OFFSET, bgi_string.offset),
+                    );
+                }
+                BGIStringType::Voice => {
+                    voice = Some(self.read_string(bgi_string.address)?);
                 }
                 BGIStringType::Ruby => {
                     let ruby_str = self.read_string(bgi_string.address)?;
@@ -262,7 +303,9 @@ impl Script for BGIScript {
                     continue;
                 }
                 let nmes = match curs.typ {
-                    BGIStringType::Internal => self.read_string(curs.address)?,
+                    BGIStringType::Internal | BGIStringType::Voice => {
+                        self.read_string(curs.address)?
+                    }
                     BGIStringType::Ruby => {
                         if !self.is_v1 && self.is_v1_instr {
                             if rubys.is_empty() {
@@ -304,7 +347,7 @@ impl Script for BGIScript {
                         }
                         None => return Err(anyhow::anyhow!("No enough messages.")),
                     },
-                    BGIStringType::Message => {
+                    BGIStringType::Message | BGIStringType::Choice => {
                         if !rubys.is_empty() {
                             eprintln!("Warning: Some ruby strings are unused: {:?}", rubys);
                             crate::COUNTER.inc_warning();
@@ -416,7 +459,7 @@ impl Script for BGIScript {
                 .as_bytes_with_nul()
                 .len();
             let nmes = match curs.typ {
-                BGIStringType::Internal => self.read_string(curs.address)?,
+                BGIStringType::Internal | BGIStringType::Voice => self.read_string(curs.address)?,
                 BGIStringType::Ruby => {
                     if !self.is_v1 && self.is_v1_instr {
                         if rubys.is_empty() {
@@ -458,7 +501,7 @@ impl Script for BGIScript {
                     }
                     None => return Err(anyhow::anyhow!("No enough messages.")),
                 },
-                BGIStringType::Message => {
+                BGIStringType::Message | BGIStringType::Choice => {
                     if !rubys.is_empty() {
                         eprintln!("Warning: Some ruby strings are unused: {:?}", rubys);
                         crate::COUNTER.inc_warning();
@@ -718,3 +761,57 @@ fn test_parse_ruby_from_text() {
         ]
     );
 }
+
+#[test]
+fn test_extract_voice_and_choice() {
+    let strings = [
+        "v_0001",
+        "_PlayVoice",
+        "Alice",
+        "Hello.",
+        "Yes",
+        "No",
+        "_SelectEx",
+    ];
+    let code: &[u32] = &[
+        3, 0, 3, 1, 0x1c, 3, 2, 3, 3, 0x140, 3, 4, 3, 5, 3, 6, 0x1c, 0x1b,
+    ];
+    let mut data = b"BurikoCompiledScriptVer1.00\0".to_vec();
+    data.extend_from_slice(&4u32.to_le_bytes());
+    let mut string_data = Vec::new();
+    let mut addresses = Vec::new();
+    let code_len = code.len() * 4;
+    for s in strings {
+        addresses.push((code_len + string_data.len()) as u32);
+        string_data.extend_from_slice(s.as_bytes());
+        string_data.push(0);
+    }
+    let mut push = false;
+    for &op in code {
+        let value = if push { addresses[op as usize] } else { op };
+        push = !push && op == 3;
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&string_data);
+    let script = BGIScript::new(data, Encoding::Utf8, &ExtraConfig::default()).unwrap();
+    let messages = script.extract_messages().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].name.as_deref(), Some("Alice"));
+    assert_eq!(messages[0].message, "Hello.");
+    assert_eq!(messages[0].meta[meta_key::VOICE], "v_0001");
+    assert_eq!(messages[0].meta[meta_key::KIND], "dialogue");
+    assert_eq!(messages[1].message, "Yes");
+    assert_eq!(messages[1].meta[meta_key::KIND], "choice");
+    assert_eq!(messages[2].message, "No");
+    let mut messages = messages;
+    messages[2].message = "Nope".to_string();
+    let mut out = crate::ext::io::MemWriter::new();
+    script
+        .import_messages(messages, Box::new(&mut out), "", Encoding::Utf8, None)
+        .unwrap();
+    let imported =
+        BGIScript::new(out.into_inner(), Encoding::Utf8, &ExtraConfig::default()).unwrap();
+    let messages = imported.extract_messages().unwrap();
+    assert_eq!(messages[0].meta[meta_key::VOICE], "v_0001");
+    assert_eq!(messages[2].message, "Nope");
+}
@@ -3,12 +3,13 @@ use super::twister::MersenneTwister;
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
-use crate::utils::blowfish::Blowfish;
+use crate::utils::blowfish::{Blowfish, BlowfishLE};
 use crate::utils::crc32::CRC32NORMAL_TABLE;
 use crate::utils::encoding::{decode_to_string, encode_string};
 use anyhow::Result;
 use overf::wrapping;
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
 pub use super::int_password::get_password_from_exe;
@@ -40,7 +41,7 @@ impl ScriptBuilder for CSIntArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(CSIntArc::new(
             MemReader::new(data),
@@ -56,7 +57,7 @@ impl ScriptBuilder for CSIntArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -85,7 +86,7 @@ impl ScriptBuilder for CSIntArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(CSIntArc::new(
             reader,
@@ -113,6 +114,24 @@ impl ScriptBuilder for CSIntArcBuilder {
     fn is_archive(&self) -> bool {
         true
     }
+
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(CSIntArcWriter::new(
+            writer, files, encoding, config,
+        )?))
+    }
 }
 
 fn detect_script_type(buf: &[u8], buf_len: usize, _filename: &str) -> Option<&'static ScriptType> {
@@ -256,6 +275,24 @@ pub struct CSIntArc<'b, T: Read + Seek + std::fmt::Debug + 'b> {
 }
 
 const NAME_SIZES: [usize; 2] = [0x20, 0x40];
+const KEY_FILE_NAME: &[u8; 12] = b"__key__.dat\0";
+
+fn name_key_base(key: u32) -> u32 {
+    wrapping! {((key >> 24) + (key >> 16) + (key >> 8) + key) & 0xFF}
+}
+
+/// Maps `z..a` to `0..26` and `Z..A` to `26..52`.
+fn alpha_index(v: u8) -> u8 {
+    if v.is_ascii_lowercase() {
+        b'z' - v
+    } else {
+        b'Z' - v + 26
+    }
+}
+
+fn alpha_from_index(j: u8) -> u8 {
+    if j < 26 { b'z' - j } else { b'Z' - (j - 26) }
+}
 
 impl<'b, T: Read + Seek + std::fmt::Debug + 'b> CSIntArc<'b, T> {
     /// Creates a new instance of `CSIntArc` from a reader.
@@ -280,7 +317,7 @@ impl<'b, T: Read + Seek + std::fmt::Debug + 'b> CSIntArc<'b, T> {
         let entry_count = reader.read_u32()?;
         let mut keybuf = [0u8; 12];
         reader.read_exact(&mut keybuf)?;
-        if &keybuf == b"__key__.dat\0" {
+        if &keybuf == KEY_FILE_NAME {
             let key = match &config.cat_system_int_encrypt_password {
                 Some(password) => Self::get_key(password)?,
                 None => {
@@ -304,10 +341,10 @@ impl<'b, T: Read + Seek + std::fmt::Debug + 'b> CSIntArc<'b, T> {
             reader.seek(SeekFrom::Start(0x50))?;
             for i in 1..entry_count {
                 reader.read_exact(&mut name_buf)?;
-                let offset = reader.read_u32()? + i;
+                let offset = reader.read_u32()?.wrapping_add(i);
                 let size = reader.read_u32()?;
                 let decryped = encrypt.decrypt([offset, size]);
-                twister.s_rand(key + i);
+                twister.s_rand(key.wrapping_add(i));
                 let name_key = twister.rand();
                 let name = Self::decrypt_name(&mut name_buf, name_key, archive_encoding)?;
                 let entry = CSIntFileHeader {
@@ -363,26 +400,18 @@ impl<'b, T: Read + Seek + std::fmt::Debug + 'b> CSIntArc<'b, T> {
     }
 
     fn decrypt_name(name: &mut [u8; 0x40], key: u32, encoding: Encoding) -> Result<String> {
-        let mut k = wrapping! {((key >> 24) + (key >> 16) + (key >> 8) + key) & 0xFF};
+        let mut k = name_key_base(key);
         let mut i = 0;
         while i < 0x40 && name[i] != 0 {
             let v = name[i];
             if v.is_ascii_alphabetic() {
-                let mut j = if v.is_ascii_lowercase() {
-                    b'z' - v
-                } else {
-                    b'Z' - v + 26
-                } as i8;
+                let mut j = alpha_index(v) as i8;
                 j -= (k % 0x34) as i8;
                 if j < 0 {
                     j += 0x34;
                 }
                 j = 0x33 - j;
-                name[i] = if j < 26 {
-                    b'z' - j as u8
-                } else {
-                    b'Z' - (j as u8 - 26)
-                };
+                name[i] = alpha_from_index(j as u8);
             }
             k += 1;
             i += 1;
@@ -390,7 +419,8 @@ impl<'b, T: Read + Seek + std::fmt::Debug + 'b> CSIntArc<'b, T> {
         decode_to_string(encoding, &name[..i], true)
     }
 
-    fn get_key(password: &str) -> Result<u32> {
+    /// Computes the key used to encrypt file names from the password.
+    pub fn get_key(password: &str) -> Result<u32> {
         let bytes = encode_string(Encoding::Cp932, password, true)?;
         let mut key = 0xFFFFFFFF;
         for &c in bytes.iter() {
@@ -476,3 +506,295 @@ impl<'b, T: Read + Seek + std::fmt::Debug + Send + Sync + 'b> Script for CSIntAr
         Ok(Box::new(entry))
     }
 }
+
+fn encrypt_name(name: &mut [u8; 0x40], key: u32) {
+    let mut k = name_key_base(key);
+    let mut i = 0;
+    while i < 0x40 && name[i] != 0 {
+        let v = name[i];
+        if v.is_ascii_alphabetic() {
+            let j = (0x33 - alpha_index(v) as u32 + k % 0x34) % 0x34;
+            name[i] = alpha_from_index(j as u8);
+        }
+        k += 1;
+        i += 1;
+    }
+}
+
+struct CSIntEncryption {
+    key: u32,
+    seed: u32,
+    cipher: BlowfishLE,
+}
+
+/// CatSystem2 Archive Writer
+pub struct CSIntArcWriter<T: Write + Seek> {
+    writer: T,
+    entries: Vec<CSIntFileHeader>,
+    indices: HashMap<String, usize>,
+    encoding: Encoding,
+    encrypt: Option<CSIntEncryption>,
+}
+
+impl<T: Write + Seek> CSIntArcWriter<T> {
+    /// Creates a new CatSystem2 Archive Writer.
+    ///
+    /// The archive is encrypted if a password is specified in config.
+    ///
+    /// * `writer` - The writer to write the archive to.
+    /// * `files` - The list of files to include in the archive.
+    /// * `encoding` - The encoding used for file names.
+    /// * `config` - Extra configuration options.
+    pub fn new(
+        mut writer: T,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Self> {
+        let mut entries = Vec::with_capacity(files.len());
+        let mut indices = HashMap::new();
+        for (i, file) in files.iter().enumerate() {
+            let name = file.to_string();
+            if encode_string(encoding, &name, false)?.len() > 0x40 {
+                return Err(anyhow::anyhow!(
+                    "File name '{}' is too long (max 64 bytes)",
+                    name
+                ));
+            }
+            indices.insert(file.to_string(), i);
+            entries.push(CSIntFileHeader {
+                name,
+                offset: 0,
+                size: 0,
+            });
+        }
+        let encrypt = match &config.cat_system_int_encrypt_password {
+            Some(password) => {
+                let key = CSIntArc::<MemReader>::get_key(password)?;
+                let seed: u32 = rand::random();
+                let mut twister = MersenneTwister::new(seed);
+                let cipher = BlowfishLE::new(&twister.rand().to_le_bytes())?;
+                Some(CSIntEncryption { key, seed, cipher })
+            }
+            None => None,
+        };
+        let count = entries.len() + if encrypt.is_some() { 1 } else { 0 };
+        writer.write_all(b"KIF\0")?;
+        writer.write_u32(count as u32)?;
+        writer.write_all(&vec![0; count * 0x48])?;
+        Ok(CSIntArcWriter {
+            writer,
+            entries,
+            indices,
+            encoding,
+            encrypt,
+        })
+    }
+}
+
+impl<T: Write + Seek> Archive for CSIntArcWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let index = *self
+            .indices
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("File '{}' not found in archive", name))?;
+        let entry = &mut self.entries[index];
+        if entry.offset != 0 {
+            return Err(anyhow::anyhow!("File '{}' already exists in archive", name));
+        }
+        entry.offset = self.writer.seek(SeekFrom::End(0))? as u32;
+        let file = CSIntArcFile {
+            header: entry,
+            writer: &mut self.writer,
+            pos: 0,
+        };
+        Ok(match &self.encrypt {
+            Some(encrypt) => Box::new(CSIntArcEncryptedFile {
+                file,
+                buf: MemWriter::new(),
+                cipher: &encrypt.cipher,
+            }),
+            None => Box::new(file),
+        })
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        self.writer.seek(SeekFrom::Start(8))?;
+        match &self.encrypt {
+            Some(encrypt) => {
+                self.writer.write_all(KEY_FILE_NAME)?;
+                self.writer.write_all(&[0; 0x40 - KEY_FILE_NAME.len()])?;
+                self.writer.write_u32(0)?;
+                self.writer.write_u32(encrypt.seed)?;
+                let mut twister = MersenneTwister::new(encrypt.seed);
+                let mut name = [0u8; 0x40];
+                for (i, entry) in self.entries.iter().enumerate() {
+                    let i = i as u32 + 1;
+                    let encoded = encode_string(self.encoding, &entry.name, false)?;
+                    name.fill(0);
+                    name[..encoded.len()].copy_from_slice(&encoded);
+                    twister.s_rand(encrypt.key.wrapping_add(i));
+                    encrypt_name(&mut name, twister.rand());
+                    self.writer.write_all(&name)?;
+                    let [offset, size] = encrypt.cipher.encrypt([entry.offset, entry.size]);
+                    self.writer.write_u32(offset.wrapping_sub(i))?;
+                    self.writer.write_u32(size)?;
+                }
+            }
+            None => {
+                for entry in self.entries.iter() {
+                    self.writer
+                        .write_fstring(&entry.name, 0x40, self.encoding, 0, false)?;
+                    self.writer.write_u32(entry.offset)?;
+                    self.writer.write_u32(entry.size)?;
+                }
+            }
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// CatSystem2 Archive File Writer
+pub struct CSIntArcFile<'a, T: Write + Seek> {
+    header: &'a mut CSIntFileHeader,
+    writer: &'a mut T,
+    pos: usize,
+}
+
+impl<'a, T: Write + Seek> Write for CSIntArcFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer
+            .seek(SeekFrom::Start(self.header.offset as u64 + self.pos as u64))?;
+        let bytes_written = self.writer.write(buf)?;
+        self.pos += bytes_written;
+        self.header.size = self.header.size.max(self.pos as u32);
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for CSIntArcFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as usize,
+            SeekFrom::End(offset) => {
+                if offset < 0 {
+                    if (-offset) as usize > self.header.size as usize {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "Seek from end exceeds file length",
+                        ));
+                    }
+                    self.header.size as usize - (-offset) as usize
+                } else {
+                    self.header.size as usize + offset as usize
+                }
+            }
+            SeekFrom::Current(offset) => {
+                if offset < 0 {
+                    if (-offset) as usize > self.pos {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "Seek from current exceeds current position",
+                        ));
+                    }
+                    self.pos.saturating_sub((-offset) as usize)
+                } else {
+                    self.pos + offset as usize
+                }
+            }
+        };
+        self.pos = new_pos;
+        Ok(self.pos as u64)
+    }
+}
+
+/// CatSystem2 Archive File Writer with Blowfish encryption
+///
+/// Data is encrypted and written when dropped.
+pub struct CSIntArcEncryptedFile<'a, T: Write + Seek> {
+    file: CSIntArcFile<'a, T>,
+    buf: MemWriter,
+    cipher: &'a BlowfishLE,
+}
+
+impl<'a, T: Write + Seek> Write for CSIntArcEncryptedFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.buf.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for CSIntArcEncryptedFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.buf.seek(pos)
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        self.buf.stream_position()
+    }
+
+    fn rewind(&mut self) -> std::io::Result<()> {
+        self.buf.rewind()
+    }
+}
+
+impl<'a, T: Write + Seek> Drop for CSIntArcEncryptedFile<'a, T> {
+    fn drop(&mut self) {
+        let mut data = std::mem::take(&mut self.buf.data);
+        // Only full 8-byte blocks are encrypted, the remaining bytes are stored as is.
+        self.cipher.encrypt_block(&mut data);
+        if let Err(e) = self.file.write_all(&data) {
+            eprintln!("Failed to write encrypted data: {}", e);
+            crate::COUNTER.inc_error();
+        }
+    }
+}
+
+#[test]
+fn test_encrypted_roundtrip() {
+    let config = ExtraConfig {
+        cat_system_int_encrypt_password: Some("FW-6JD55162".to_string()),
+        ..Default::default()
+    };
+    let files = [
+        (
+            "scene01.cst",
+            (0..1000u32).map(|i| (i * 7) as u8).collect::<Vec<_>>(),
+        ),
+        ("Image_BG01.hg3", b"HG-3 short".to_vec()),
+    ];
+    let names: Vec<&str> = files.iter().map(|(n, _)| *n).collect();
+    let mut mem = MemWriter::new();
+    {
+        let mut arc = CSIntArcWriter::new(&mut mem, &names, Encoding::Cp932, &config).unwrap();
+        for (name, data) in files.iter() {
+            let mut f = arc.new_file(name, None).unwrap();
+            f.write_all(data).unwrap();
+        }
+        arc.write_header().unwrap();
+    }
+    let arc = CSIntArc::new(
+        MemReader::new(mem.into_inner()),
+        Encoding::Cp932,
+        &config,
+        "",
+    )
+    .unwrap();
+    for (i, (name, data)) in files.iter().enumerate() {
+        let mut f = arc.open_file(i).unwrap();
+        assert_eq!(f.name(), *name);
+        assert_eq!(&f.data().unwrap(), data);
+    }
+}
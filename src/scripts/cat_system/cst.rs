@@ -31,7 +31,7 @@ impl ScriptBuilder for CstScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(CstScript::new(buf, encoding, config)?))
     }
@@ -199,16 +199,21 @@ impl Script for CstScript {
     fn extract_messages(&self) -> Result<Vec<Message>> {
         let mut messages = Vec::new();
         let mut name = None;
+        let mut line = None;
         for s in self.strings.iter() {
             match s.typ {
                 CstStringType::Message => {
                     if s.text.is_empty() {
                         continue; // Skip empty messages
                     }
-                    messages.push(Message {
-                        message: s.text.replace("\\n", "\n"),
-                        name: name.take(),
-                    });
+                    messages.push(
+                        Message::new(s.text.replace("\\n", "\n"), name.take())
+                            .with_kind(MessageKind::Dialogue)
+                            .with_optional_meta(meta_key::LINE, line),
+                    );
+                }
+                CstStringType::LineNumber => {
+                    line = s.text.trim().parse::<u64>().ok();
                 }
                 CstStringType::Character => {
                     name = Some(s.text.clone());
@@ -216,10 +221,10 @@ impl Script for CstScript {
                 CstStringType::Command => {
                     if let Some(caps) = CST_COMMAND_REGEX.captures(&s.text)? {
                         if let Some(text) = caps.get(1) {
-                            messages.push(Message {
-                                message: text.as_str().to_string(),
-                                name: None,
-                            });
+                            messages.push(
+                                Message::new(text.as_str().to_string(), None)
+                                    .with_optional_meta(meta_key::LINE, line),
+                            );
                         }
                     }
                 }
@@ -30,7 +30,7 @@ impl ScriptBuilder for CstlScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(CstlScript::new(buf, encoding, config)?))
     }
@@ -217,7 +217,11 @@ impl CstlScript {
             };
             let mes_len = reader.read_size()?;
             let message = reader.read_fstring(mes_len, encoding, false)?;
-            data[i % lang_count].push(Message { name, message });
+            data[i % lang_count].push(Message {
+                name,
+                message,
+                ..Default::default()
+            });
             i += 1;
             if reader.is_eof() {
                 break;
@@ -286,6 +290,7 @@ impl Script for CstlScript {
             .map(|m| Message {
                 name: m.name.clone(),
                 message: m.message.replace("\\n", "\n"),
+                ..Default::default()
             })
             .collect())
     }
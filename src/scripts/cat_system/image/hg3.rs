@@ -6,7 +6,7 @@ use crate::utils::bit_stream::*;
 use crate::utils::img::*;
 use crate::utils::struct_pack::*;
 use anyhow::Result;
-use flate2::{Decompress, FlushDecompress};
+use flate2::{Compression, Decompress, FlushDecompress};
 use msg_tool_macro::*;
 use overf::wrapping;
 use std::collections::HashMap;
@@ -35,11 +35,61 @@ impl ScriptBuilder for Hg3ImageBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Hg3Image::new(data, config)?))
     }
 
+    fn is_image(&self) -> bool {
+        true
+    }
+
+    fn can_create_image_file(&self) -> bool {
+        true
+    }
+
+    fn create_image_file<'a>(
+        &'a self,
+        data: ImageData,
+        _filename: &str,
+        mut writer: Box<dyn WriteSeek + 'a>,
+        _options: &ExtraConfig,
+    ) -> Result<()> {
+        let bpp = match data.color_type {
+            ImageColorType::Rgb | ImageColorType::Bgr | ImageColorType::Grayscale => 24,
+            ImageColorType::Rgba | ImageColorType::Bgra => 32,
+        };
+        let img0000 = Hg3Writer::encode_img0000(data, bpp)?;
+        let mut stdinfo = MemWriter::new();
+        Hg3StdInfo {
+            width: img0000.width,
+            height: img0000.height,
+            bpp,
+            offset_x: 0,
+            offset_y: 0,
+            canvas_width: img0000.width,
+            canvas_height: img0000.height,
+            transparent: if bpp == 32 { 1 } else { 0 },
+            origin_x: 0,
+            origin_y: 0,
+        }
+        .pack(&mut stdinfo, false, Encoding::Cp932, &None)?;
+        let frame = Hg3Writer::build_frame(
+            0,
+            0,
+            &[
+                (*b"stdinfo\0", stdinfo.into_inner()),
+                (*b"img0000\0", img0000.data),
+            ],
+        );
+        writer.write_all(b"HG-3")?;
+        writer.write_u32(0xC)?;
+        writer.write_u32(0x300)?;
+        writer.write_all(&frame)?;
+        writer.flush()?;
+        Ok(())
+    }
+
     fn extensions(&self) -> &'static [&'static str] {
         &["hg3"]
     }
@@ -69,12 +119,42 @@ struct Hg3Entry {
     canvas_height: u32,
 }
 
+/// Data of `stdinfo` section.
+#[derive(Debug, Clone, StructPack, StructUnpack)]
+struct Hg3StdInfo {
+    width: u32,
+    height: u32,
+    bpp: u32,
+    offset_x: u32,
+    offset_y: u32,
+    canvas_width: u32,
+    canvas_height: u32,
+    transparent: u32,
+    origin_x: u32,
+    origin_y: u32,
+}
+
+#[derive(Debug)]
+/// A frame in HG-3 image.
+struct Hg3Frame {
+    /// Offset of the frame.
+    offset: usize,
+    /// Size of the frame.
+    size: usize,
+    /// Index in entries if the frame contains an image.
+    entry: Option<usize>,
+}
+
 #[derive(Debug)]
 /// CatSystem2 HG3 Image script.
 pub struct Hg3Image {
     data: MemReader,
     entries: Vec<(Hg3Entry, usize, usize)>,
+    frames: Vec<Hg3Frame>,
+    /// Offset of the data after the last frame.
+    end: usize,
     draw_canvas: bool,
+    jpeg_quality: u8,
 }
 
 impl Hg3Image {
@@ -91,6 +171,7 @@ impl Hg3Image {
         }
         let mut offset = 0xC;
         let mut entries = Vec::new();
+        let mut frames = Vec::new();
         let len = reader.data.len() as u64;
         while offset + 0x14 < len && reader.cpeek_and_equal_at(offset + 8, b"stdinfo").is_ok() {
             let mut section_size = reader.cpeek_u32_at(offset)?;
@@ -98,14 +179,21 @@ impl Hg3Image {
                 section_size = (len - offset) as u32;
             }
             let stdinfo_size = reader.cpeek_u32_at(offset + 0x10)?;
+            let mut entry_index = None;
             if reader
                 .cpeek_and_equal_at(offset + 8 + stdinfo_size as u64, b"img")
                 .is_ok()
             {
                 reader.pos = (offset + 16) as usize;
                 let entry = Hg3Entry::unpack(&mut reader, false, Encoding::Cp932, &None)?;
+                entry_index = Some(entries.len());
                 entries.push((entry, (offset + 8) as usize, section_size as usize - 8));
             }
+            frames.push(Hg3Frame {
+                offset: offset as usize,
+                size: section_size as usize,
+                entry: entry_index,
+            });
             offset += section_size as u64;
         }
         if entries.is_empty() {
@@ -114,9 +202,145 @@ impl Hg3Image {
         Ok(Hg3Image {
             data: reader,
             entries,
+            frames,
+            end: (offset as usize).min(len as usize),
             draw_canvas: config.cat_system_image_canvas,
+            jpeg_quality: config.jpeg_quality,
         })
     }
+
+    /// Writes the image with some frames replaced.
+    ///
+    /// Frames without new image are copied from the original file.
+    fn write_frames<'a>(
+        &self,
+        mut images: HashMap<usize, ImageData>,
+        mut file: Box<dyn WriteSeek + 'a>,
+    ) -> Result<()> {
+        file.write_all(&self.data.data[..0xC])?;
+        for frame in self.frames.iter() {
+            let raw = &self.data.data[frame.offset..frame.offset + frame.size];
+            match frame.entry.and_then(|i| images.remove(&i)) {
+                Some(img) => {
+                    let (entry, _, _) = &self.entries[frame.entry.unwrap()];
+                    let data = self.rebuild_frame(raw, entry, img)?;
+                    file.write_all(&data)?;
+                }
+                None => file.write_all(raw)?,
+            }
+        }
+        file.write_all(&self.data.data[self.end..])?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Rebuilds a frame with new image. Other sections in the frame are kept.
+    fn rebuild_frame(&self, raw: &[u8], entry: &Hg3Entry, mut img: ImageData) -> Result<Vec<u8>> {
+        let next = u32::from_le_bytes(raw[0..4].try_into()?);
+        let id = u32::from_le_bytes(raw[4..8].try_into()?);
+        let mut sections = Vec::new();
+        let mut pos = 8;
+        loop {
+            if pos + 16 > raw.len() {
+                return Err(anyhow::anyhow!(
+                    "Invalid section at offset {} in frame",
+                    pos
+                ));
+            }
+            let mut name = [0u8; 8];
+            name.copy_from_slice(&raw[pos..pos + 8]);
+            let section_next = u32::from_le_bytes(raw[pos + 8..pos + 12].try_into()?) as usize;
+            let section_len = u32::from_le_bytes(raw[pos + 12..pos + 16].try_into()?) as usize;
+            if pos + 16 + section_len > raw.len() {
+                return Err(anyhow::anyhow!(
+                    "Section {} is out of frame",
+                    String::from_utf8_lossy(&name)
+                ));
+            }
+            sections.push((name, raw[pos + 16..pos + 16 + section_len].to_vec()));
+            if section_next == 0 {
+                break;
+            }
+            pos += section_next;
+        }
+        if self.draw_canvas
+            && entry.canvas_width > 0
+            && entry.canvas_height > 0
+            && img.width == entry.canvas_width
+            && img.height == entry.canvas_height
+            && (img.width != entry.width || img.height != entry.height)
+        {
+            img = crop_image(
+                &img,
+                entry.offset_x,
+                entry.offset_y,
+                entry.width,
+                entry.height,
+            )?;
+        }
+        if img.width != entry.width || img.height != entry.height {
+            eprintln!(
+                "WARN: Image size changed from {}x{} to {}x{}.",
+                entry.width, entry.height, img.width, img.height
+            );
+            crate::COUNTER.inc_warning();
+        }
+        let has_imgmode = sections.iter().any(|(name, _)| name == b"imgmode\0");
+        let mut result = Vec::with_capacity(sections.len() + 1);
+        let mut alpha = None;
+        let mut img = Some(img);
+        for (name, data) in sections {
+            match &name {
+                b"stdinfo\0" => {
+                    let mut data = data;
+                    if data.len() < 8 {
+                        return Err(anyhow::anyhow!(
+                            "stdinfo section is too small: {} bytes",
+                            data.len()
+                        ));
+                    }
+                    if let Some(img) = &img {
+                        data[0..4].copy_from_slice(&img.width.to_le_bytes());
+                        data[4..8].copy_from_slice(&img.height.to_le_bytes());
+                    }
+                    result.push((name, data));
+                }
+                b"img0000\0" => {
+                    let img = img
+                        .take()
+                        .ok_or(anyhow::anyhow!("Multiple images in one frame"))?;
+                    result.push((name, Hg3Writer::encode_img0000(img, entry.bpp)?.data));
+                }
+                b"img_jpg\0" => {
+                    let img = img
+                        .take()
+                        .ok_or(anyhow::anyhow!("Multiple images in one frame"))?;
+                    let (jpg, al) = Hg3Writer::encode_jpg(img, has_imgmode, self.jpeg_quality)?;
+                    result.push((name, jpg));
+                    result.push((*b"img_al\0\0", Vec::new()));
+                    alpha = Some(al);
+                }
+                b"img_al\0\0" => {}
+                _ => result.push((name, data)),
+            }
+        }
+        if img.is_some() {
+            return Err(anyhow::anyhow!("Unsupported image type in HG-3 frame"));
+        }
+        if let Some(alpha) = alpha {
+            let index = result
+                .iter()
+                .position(|(name, _)| name == b"img_al\0\0")
+                .unwrap();
+            match alpha {
+                Some(alpha) => result[index].1 = alpha,
+                None => {
+                    result.remove(index);
+                }
+            }
+        }
+        Ok(Hg3Writer::build_frame(next, id, &result))
+    }
 }
 
 impl Script for Hg3Image {
@@ -175,6 +399,46 @@ impl Script for Hg3Image {
             draw_canvas: self.draw_canvas,
         }))
     }
+
+    fn import_image<'a>(
+        &'a self,
+        data: ImageData,
+        _filename: &str,
+        file: Box<dyn WriteSeek + 'a>,
+    ) -> Result<()> {
+        if self.entries.len() > 1 {
+            eprintln!(
+                "WARN: There are multiple entries in the HG-3 image, only the first one will be imported."
+            );
+            crate::COUNTER.inc_warning();
+        }
+        let mut images = HashMap::new();
+        images.insert(0, data);
+        self.write_frames(images, file)
+    }
+
+    fn import_multi_image<'a>(
+        &'a self,
+        data: Vec<ImageDataWithName>,
+        file: Box<dyn WriteSeek + 'a>,
+    ) -> Result<()> {
+        let mut images = HashMap::new();
+        for img in data {
+            let index = match img.name.parse::<usize>() {
+                Ok(index) if index < self.entries.len() => index,
+                _ => {
+                    eprintln!(
+                        "WARN: Unknown image name {} in HG-3 image, ignored.",
+                        img.name
+                    );
+                    crate::COUNTER.inc_warning();
+                    continue;
+                }
+            };
+            images.insert(index, img.data);
+        }
+        self.write_frames(images, file)
+    }
 }
 
 struct Hg3ImageIter<'a, T: Iterator<Item = &'a (Hg3Entry, usize, usize)> + 'a> {
@@ -512,3 +776,313 @@ impl<'a> Hg3Reader<'a> {
         Ok(sections)
     }
 }
+
+/// Encoded image section.
+struct Hg3EncodedImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+struct Hg3Writer {}
+
+impl Hg3Writer {
+    /// Builds a frame from sections.
+    ///
+    /// * `next` - Original offset of next frame. 0 means this is the last frame.
+    /// * `id` - Frame id.
+    fn build_frame(next: u32, id: u32, sections: &[([u8; 8], Vec<u8>)]) -> Vec<u8> {
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&next.to_le_bytes());
+        frame.extend_from_slice(&id.to_le_bytes());
+        for (i, (name, data)) in sections.iter().enumerate() {
+            let section_next = if i + 1 == sections.len() {
+                0
+            } else {
+                data.len() as u32 + 16
+            };
+            frame.extend_from_slice(name);
+            frame.extend_from_slice(&section_next.to_le_bytes());
+            frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            frame.extend_from_slice(data);
+        }
+        if next != 0 {
+            let len = frame.len() as u32;
+            frame[0..4].copy_from_slice(&len.to_le_bytes());
+        }
+        frame
+    }
+
+    /// Encodes an image to `img0000` section.
+    fn encode_img0000(mut img: ImageData, bpp: u32) -> Result<Hg3EncodedImage> {
+        if img.depth != 8 {
+            return Err(anyhow::anyhow!(
+                "Unsupported image depth {} for HG-3 image",
+                img.depth
+            ));
+        }
+        convert_to_bgra(&mut img)?;
+        match bpp {
+            24 => convert_bgra_to_bgr(&mut img)?,
+            32 => {}
+            _ => return Err(anyhow::anyhow!("Unsupported BPP: {} in HG-3 image", bpp)),
+        }
+        flip_image(&mut img)?;
+        let pixels = Self::encode_delta(&img.data, img.width as usize * bpp as usize / 8, bpp);
+        let (data, ctl) = Self::encode_rle(&pixels)?;
+        let packed_data = Self::compress(&data)?;
+        let packed_ctl = Self::compress(&ctl)?;
+        let mut section = MemWriter::new();
+        section.write_u32(0)?;
+        section.write_u32(img.height)?;
+        section.write_u32(packed_data.len() as u32)?;
+        section.write_u32(data.len() as u32)?;
+        section.write_u32(packed_ctl.len() as u32)?;
+        section.write_u32(ctl.len() as u32)?;
+        section.write_all(&packed_data)?;
+        section.write_all(&packed_ctl)?;
+        Ok(Hg3EncodedImage {
+            width: img.width,
+            height: img.height,
+            data: section.into_inner(),
+        })
+    }
+
+    fn compress(data: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn convert_value(val: u8) -> u8 {
+        if val < 0x80 {
+            val << 1
+        } else {
+            ((0xFF - val) << 1) | 1
+        }
+    }
+
+    /// The reverse of [Hg3Reader::apply_delta].
+    fn encode_delta(data: &[u8], stride: usize, bpp: u32) -> Vec<u8> {
+        let pixel_size = bpp as usize / 8;
+        let mut delta = data.to_vec();
+        for i in (stride..data.len()).rev() {
+            wrapping! {
+                delta[i] = data[i] - data[i - stride];
+            }
+        }
+        for x in (pixel_size..stride.min(data.len())).rev() {
+            wrapping! {
+                delta[x] = data[x] - data[x - pixel_size];
+            }
+        }
+        // Every 4 bytes are split into 4 planes by bits.
+        let len = delta.len().div_ceil(4) * 4;
+        delta.resize(len, 0);
+        let plane_size = len / 4;
+        let mut planes = vec![0u8; len];
+        for i in 0..plane_size {
+            let mut val = 0u32;
+            for j in 0..4 {
+                val |= (Self::convert_value(delta[i * 4 + j]) as u32) << (j * 8);
+            }
+            for (p, shift) in [6, 4, 2, 0].into_iter().enumerate() {
+                let mut v = 0u8;
+                for k in 0..4 {
+                    v |= (((val >> (k * 8 + shift)) & 3) as u8) << (k * 2);
+                }
+                planes[p * plane_size + i] = v;
+            }
+        }
+        planes
+    }
+
+    /// Splits data into non-zero data and control stream of zero/non-zero runs.
+    fn encode_rle(input: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let mut data = Vec::new();
+        let mut ctl = Vec::new();
+        {
+            let mut bits = LsbBitWriter::new(&mut ctl);
+            let first_copy = input.first().is_some_and(|&b| b != 0);
+            bits.put_bit(first_copy)?;
+            Self::put_bit_count(&mut bits, input.len() as u32)?;
+            let mut i = 0;
+            while i < input.len() {
+                let copy = input[i] != 0;
+                let start = i;
+                while i < input.len() && (input[i] != 0) == copy {
+                    i += 1;
+                }
+                if copy {
+                    data.extend_from_slice(&input[start..i]);
+                }
+                Self::put_bit_count(&mut bits, (i - start) as u32)?;
+            }
+            bits.flush()?;
+        }
+        Ok((data, ctl))
+    }
+
+    /// The reverse of [Hg3Reader::get_bit_count]. Counts start from 1, so 0 can not be written.
+    fn put_bit_count<T: Write>(bits: &mut LsbBitWriter<T>, value: u32) -> Result<()> {
+        if value == 0 {
+            return Err(anyhow::anyhow!(
+                "Can not write zero count in HG-3 bit stream"
+            ));
+        }
+        let n = 31 - value.leading_zeros();
+        for _ in 0..n {
+            bits.put_bit(false)?;
+        }
+        bits.put_bit(true)?;
+        for i in (0..n).rev() {
+            bits.put_bit((value >> i) & 1 != 0)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes an image to `img_jpg` section and `img_al` section (if image has alpha).
+    fn encode_jpg(
+        mut img: ImageData,
+        bgr: bool,
+        quality: u8,
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+        if img.depth != 8 {
+            return Err(anyhow::anyhow!(
+                "Unsupported image depth {} for HG-3 image",
+                img.depth
+            ));
+        }
+        let has_alpha = matches!(img.color_type, ImageColorType::Rgba | ImageColorType::Bgra);
+        convert_to_bgra(&mut img)?;
+        let pixel_count = img.width as usize * img.height as usize;
+        let mut color = Vec::with_capacity(pixel_count * 3);
+        let mut alpha = Vec::with_capacity(pixel_count);
+        for px in img.data.chunks_exact(4) {
+            if bgr {
+                color.extend_from_slice(&px[..3]);
+            } else {
+                color.extend_from_slice(&[px[2], px[1], px[0]]);
+            }
+            alpha.push(px[3]);
+        }
+        let color = ImageData {
+            width: img.width,
+            height: img.height,
+            color_type: ImageColorType::Rgb,
+            depth: 8,
+            data: color,
+        };
+        let mut jpg = Vec::new();
+        encode_jpg_writer(&color, mozjpeg::ColorSpace::JCS_RGB, &mut jpg, quality)?;
+        let alpha = if has_alpha {
+            let packed = Self::compress(&alpha)?;
+            let mut section = Vec::with_capacity(packed.len() + 8);
+            section.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+            section.extend_from_slice(&(alpha.len() as u32).to_le_bytes());
+            section.extend_from_slice(&packed);
+            Some(section)
+        } else {
+            None
+        };
+        Ok((jpg, alpha))
+    }
+}
+
+#[cfg(test)]
+fn test_image(width: u32, height: u32, seed: u8) -> ImageData {
+    let mut data = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let v = (x * 7 + y * 13) as u8 ^ seed;
+            let a = if x % 5 == 0 { 0 } else { 255 - (y as u8) };
+            data.extend_from_slice(&[v, v.wrapping_mul(3), 0, a]);
+        }
+    }
+    ImageData {
+        width,
+        height,
+        color_type: ImageColorType::Rgba,
+        depth: 8,
+        data,
+    }
+}
+
+#[test]
+fn test_img0000_roundtrip() {
+    let img = test_image(33, 17, 0x5A);
+    let mut file = MemWriter::new();
+    Hg3ImageBuilder::new()
+        .create_image_file(
+            img.clone(),
+            "test.png",
+            Box::new(&mut file),
+            &ExtraConfig::default(),
+        )
+        .unwrap();
+    let hg3 = Hg3Image::new(file.into_inner(), &ExtraConfig::default()).unwrap();
+    let mut exported = hg3.export_image().unwrap();
+    convert_bgra_to_rgba(&mut exported).unwrap();
+    assert_eq!(exported.data, img.data);
+
+    let img2 = test_image(33, 17, 0xC3);
+    let mut patched = MemWriter::new();
+    hg3.import_image(img2.clone(), "test.png", Box::new(&mut patched))
+        .unwrap();
+    let hg3 = Hg3Image::new(patched.into_inner(), &ExtraConfig::default()).unwrap();
+    let mut exported = hg3.export_image().unwrap();
+    convert_bgra_to_rgba(&mut exported).unwrap();
+    assert_eq!(exported.data, img2.data);
+}
+
+#[test]
+fn test_encode_rle_empty() {
+    assert!(Hg3Writer::encode_rle(&[]).is_err());
+    let (data, _) = Hg3Writer::encode_rle(&[0, 1, 2, 0]).unwrap();
+    assert_eq!(data, [1, 2]);
+}
+
+#[test]
+fn test_img_jpg_import() {
+    let img = test_image(16, 8, 0x11);
+    let mut stdinfo = MemWriter::new();
+    Hg3StdInfo {
+        width: 16,
+        height: 8,
+        bpp: 32,
+        offset_x: 4,
+        offset_y: 2,
+        canvas_width: 32,
+        canvas_height: 16,
+        transparent: 1,
+        origin_x: 0,
+        origin_y: 0,
+    }
+    .pack(&mut stdinfo, false, Encoding::Cp932, &None)
+    .unwrap();
+    let (jpg, alpha) = Hg3Writer::encode_jpg(img.clone(), false, 90).unwrap();
+    let mut file = b"HG-3\x0C\x00\x00\x00\x00\x03\x00\x00".to_vec();
+    file.extend(Hg3Writer::build_frame(
+        0,
+        0,
+        &[
+            (*b"stdinfo\0", stdinfo.into_inner()),
+            (*b"img_jpg\0", jpg),
+            (*b"img_al\0\0", alpha.unwrap()),
+        ],
+    ));
+    let hg3 = Hg3Image::new(file, &ExtraConfig::default()).unwrap();
+    let img2 = test_image(16, 8, 0x77);
+    let mut patched = MemWriter::new();
+    hg3.import_image(img2.clone(), "test.png", Box::new(&mut patched))
+        .unwrap();
+    let hg3 = Hg3Image::new(patched.into_inner(), &ExtraConfig::default()).unwrap();
+    let entry = &hg3.entries[0].0;
+    assert_eq!((entry.offset_x, entry.offset_y), (4, 2));
+    assert_eq!((entry.canvas_width, entry.canvas_height), (32, 16));
+    let exported = hg3.export_image().unwrap();
+    assert_eq!(exported.color_type, ImageColorType::Rgba);
+    let alpha: Vec<u8> = exported.data.chunks_exact(4).map(|p| p[3]).collect();
+    let expected: Vec<u8> = img2.data.chunks_exact(4).map(|p| p[3]).collect();
+    assert_eq!(alpha, expected);
+}
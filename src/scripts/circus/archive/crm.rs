@@ -2,9 +2,10 @@
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
+use crate::utils::encoding::decode_to_string;
 use anyhow::Result;
-use std::collections::BTreeMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
@@ -34,7 +35,7 @@ impl ScriptBuilder for CrmArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(CrmArchive::new(
             MemReader::new(data),
@@ -49,7 +50,7 @@ impl ScriptBuilder for CrmArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -72,7 +73,7 @@ impl ScriptBuilder for CrmArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(CrmArchive::new(reader, archive_encoding, config)?))
     }
@@ -89,6 +90,24 @@ impl ScriptBuilder for CrmArchiveBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(CrmArchiveWriter::new(
+            writer, files, encoding, config,
+        )?))
+    }
+
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
         if buf_len >= 4 && buf.starts_with(b"CRXB") {
             return Some(10);
@@ -304,3 +323,267 @@ fn detect_script_type(_buf: &[u8], _buf_len: usize, _filename: &str) -> Option<S
     }
     None
 }
+
+/// Length of the file head kept by [CrmArchiveWriter], which contains references of CRXD images.
+const HEAD_LEN: usize = 0x2C;
+
+#[derive(Debug)]
+struct CrmWriterEntry {
+    header: CrmFileHeader,
+    head: Vec<u8>,
+}
+
+/// Circus CRM Archive Writer
+pub struct CrmArchiveWriter<T: Write + Seek> {
+    writer: T,
+    headers: HashMap<String, CrmWriterEntry>,
+    encoding: Encoding,
+}
+
+impl<T: Write + Seek> CrmArchiveWriter<T> {
+    /// Creates a new `CrmArchiveWriter` for writing a CRM archive.
+    ///
+    /// * `writer` - The writer to write the CRM archive to.
+    /// * `files` - A list of file names to include in the archive.
+    /// * `encoding` - The encoding to use for string fields in the archive.
+    /// * `config` - Extra configuration options.
+    pub fn new(
+        mut writer: T,
+        files: &[&str],
+        encoding: Encoding,
+        _config: &ExtraConfig,
+    ) -> Result<Self> {
+        writer.write_all(b"CRXB")?;
+        writer.write_u32(0)?;
+        writer.write_u32(files.len() as u32)?;
+        writer.write_u32(0)?;
+        let mut headers = HashMap::new();
+        for file in files {
+            writer.write_u32(0)?; // Placeholder for offset
+            writer.write_u32(0)?;
+            writer.write_fstring(file, 0x18, encoding, 0, false)?;
+            headers.insert(
+                file.to_string(),
+                CrmWriterEntry {
+                    header: CrmFileHeader {
+                        offset: 0,
+                        size: 0,
+                        name: file.to_string(),
+                    },
+                    head: Vec::new(),
+                },
+            );
+        }
+        Ok(CrmArchiveWriter {
+            writer,
+            headers,
+            encoding,
+        })
+    }
+
+    /// Fixes the base image offsets of CRXD images, because images may be moved in the new archive.
+    fn fix_crxd_references(&mut self) -> Result<()> {
+        let mut offsets = HashMap::new();
+        for entry in self.headers.values() {
+            let name = entry.header.name.to_lowercase();
+            if let Some((stem, _)) = name.rsplit_once('.') {
+                offsets
+                    .entry(stem.to_string())
+                    .or_insert(entry.header.offset);
+            }
+            offsets.insert(name, entry.header.offset);
+        }
+        let valid: HashSet<u32> = offsets.values().cloned().collect();
+        for entry in self.headers.values() {
+            let (offset, head) = (entry.header.offset, &entry.head);
+            if head.len() < 0x24 || !head.starts_with(b"CRXD") {
+                continue;
+            }
+            let name = &head[0xC..0x20];
+            let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            let name = decode_to_string(self.encoding, &name[..len], true)?;
+            match offsets.get(&name.to_lowercase()) {
+                Some(base) => {
+                    self.writer.seek(SeekFrom::Start(offset as u64 + 8))?;
+                    self.writer.write_u32(*base)?;
+                }
+                None => {
+                    eprintln!(
+                        "Warning: Base image '{}' of CRXD image at {:#x} not found in archive",
+                        name, offset
+                    );
+                    crate::COUNTER.inc_warning();
+                }
+            }
+            if &head[0x20..0x24] == b"CRXJ" && head.len() >= HEAD_LEN {
+                let diff = u32::from_le_bytes([head[0x28], head[0x29], head[0x2A], head[0x2B]]);
+                if !valid.contains(&diff) {
+                    eprintln!(
+                        "Warning: Diff image of CRXD image at {:#x} references offset {:#x}, which is not a file in archive",
+                        offset, diff
+                    );
+                    crate::COUNTER.inc_warning();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Write + Seek> Archive for CrmArchiveWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let entry = self
+            .headers
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("File '{}' not found in archive", name))?;
+        if entry.header.offset != 0 {
+            return Err(anyhow::anyhow!("File '{}' already exists in archive", name));
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        entry.header.offset = self.writer.stream_position()? as u32;
+        let file = CrmArchiveFile {
+            entry,
+            writer: &mut self.writer,
+            pos: 0,
+        };
+        Ok(Box::new(file))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        // Entries which are not written have offset 0, which would point to the header.
+        if let Some(entry) = self.headers.values().find(|e| e.header.offset == 0) {
+            anyhow::bail!("File '{}' is not written to CRM archive", entry.header.name);
+        }
+        let mut files = self.headers.values().map(|e| &e.header).collect::<Vec<_>>();
+        files.sort_by(|a, b| a.offset.cmp(&b.offset).then(a.size.cmp(&b.size)));
+        // The reader keys entries by offset, so files sharing an offset would be merged.
+        if let Some(pair) = files.windows(2).find(|p| p[0].offset == p[1].offset) {
+            return Err(anyhow::anyhow!(
+                "File '{}' is empty and has the same offset as '{}', which is not supported by CRM archive",
+                pair[0].name,
+                pair[1].name
+            ));
+        }
+        self.writer.seek(SeekFrom::Start(0x10))?;
+        for file in files {
+            self.writer.write_u32(file.offset)?;
+            self.writer.write_u32(0)?;
+            self.writer
+                .write_fstring(&file.name, 0x18, self.encoding, 0, false)?;
+        }
+        self.fix_crxd_references()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// CRM Archive File
+pub struct CrmArchiveFile<'a, T: Write + Seek> {
+    entry: &'a mut CrmWriterEntry,
+    writer: &'a mut T,
+    pos: usize,
+}
+
+impl<'a, T: Write + Seek> Write for CrmArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.seek(SeekFrom::Start(
+            self.entry.header.offset as u64 + self.pos as u64,
+        ))?;
+        let bytes_written = self.writer.write(buf)?;
+        if self.pos < HEAD_LEN {
+            let end = (self.pos + bytes_written).min(HEAD_LEN);
+            if self.entry.head.len() < end {
+                self.entry.head.resize(end, 0);
+            }
+            self.entry.head[self.pos..end].copy_from_slice(&buf[..end - self.pos]);
+        }
+        self.pos += bytes_written;
+        self.entry.header.size = self.entry.header.size.max(self.pos as u32);
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for CrmArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let size = self.entry.header.size as usize;
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as usize,
+            SeekFrom::End(offset) => {
+                if offset < 0 {
+                    if (-offset) as usize > size {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "Seek from end exceeds file length",
+                        ));
+                    }
+                    size - (-offset) as usize
+                } else {
+                    size + offset as usize
+                }
+            }
+            SeekFrom::Current(offset) => {
+                if offset < 0 {
+                    if (-offset) as usize > self.pos {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "Seek from current exceeds current position",
+                        ));
+                    }
+                    self.pos - (-offset) as usize
+                } else {
+                    self.pos + offset as usize
+                }
+            }
+        };
+        self.pos = new_pos;
+        Ok(self.pos as u64)
+    }
+}
+
+#[test]
+fn test_writer_rejects_empty_files() {
+    let write = |empty: &[&str]| {
+        let files = ["a.crx", "b.crx", "c.crx"];
+        let mut writer = CrmArchiveWriter::new(
+            MemWriter::new(),
+            &files,
+            Encoding::Cp932,
+            &ExtraConfig::default(),
+        )
+        .unwrap();
+        for file in files {
+            let mut f = writer.new_file(file, None).unwrap();
+            if !empty.contains(&file) {
+                f.write_all(file.as_bytes()).unwrap();
+            }
+        }
+        writer.write_header()
+    };
+    assert!(write(&[]).is_ok());
+    // The last file can be empty, as its size is computed from the file length.
+    assert!(write(&["c.crx"]).is_ok());
+    assert!(write(&["a.crx"]).is_err());
+    assert!(write(&["b.crx", "c.crx"]).is_err());
+    let mut writer = CrmArchiveWriter::new(
+        MemWriter::new(),
+        &["a.crx", "b.crx"],
+        Encoding::Cp932,
+        &ExtraConfig::default(),
+    )
+    .unwrap();
+    writer
+        .new_file("a.crx", None)
+        .unwrap()
+        .write_all(b"a")
+        .unwrap();
+    let err = writer.write_header().unwrap_err().to_string();
+    assert!(err.contains("'b.crx' is not written"), "{}", err);
+}
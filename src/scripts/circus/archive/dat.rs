@@ -2,8 +2,10 @@
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
+use crate::utils::encoding::encode_string;
 use anyhow::Result;
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
@@ -33,7 +35,7 @@ impl ScriptBuilder for DatArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(DatArchive::new(
             MemReader::new(data),
@@ -48,7 +50,7 @@ impl ScriptBuilder for DatArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -71,7 +73,7 @@ impl ScriptBuilder for DatArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(DatArchive::new(reader, archive_encoding, config)?))
     }
@@ -88,6 +90,24 @@ impl ScriptBuilder for DatArchiveBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(DatArchiveWriter::new(
+            writer, files, encoding, config,
+        )?))
+    }
+
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
         is_this_format(&buf[..buf_len]).ok()
     }
@@ -401,3 +421,175 @@ pub fn is_this_format(buf: &[u8]) -> Result<u8> {
     }
     Err(anyhow::anyhow!("Not a valid DAT archive format"))
 }
+
+/// Circus DAT Archive Writer
+pub struct DatArchiveWriter<T: Write + Seek> {
+    writer: T,
+    headers: HashMap<String, DatFileHeader>,
+    name_len: usize,
+    encoding: Encoding,
+}
+
+impl<T: Write + Seek> DatArchiveWriter<T> {
+    /// Creates a new `DatArchiveWriter` for writing a DAT archive.
+    ///
+    /// The shortest name length which can hold all file names is used.
+    ///
+    /// * `writer` - The writer to write the DAT archive to.
+    /// * `files` - A list of file names to include in the archive.
+    /// * `encoding` - The encoding to use for string fields in the archive.
+    /// * `config` - Extra configuration options.
+    pub fn new(
+        mut writer: T,
+        files: &[&str],
+        encoding: Encoding,
+        _config: &ExtraConfig,
+    ) -> Result<Self> {
+        let mut max_len = 0;
+        for file in files {
+            max_len = max_len.max(encode_string(encoding, file, true)?.len());
+        }
+        let name_len = NAME_LEN
+            .iter()
+            .cloned()
+            .find(|&len| max_len < len)
+            .ok_or_else(|| anyhow::anyhow!("File name is too long for DAT archive"))?;
+        // The last entry marks the end of the archive.
+        let count = files.len() + 1;
+        writer.write_u32(count as u32)?;
+        writer.write_all(&vec![0; (name_len + 4) * count])?;
+        let headers = files
+            .iter()
+            .map(|file| {
+                (
+                    file.to_string(),
+                    DatFileHeader {
+                        name: file.to_string(),
+                        offset: 0,
+                        size: 0,
+                    },
+                )
+            })
+            .collect();
+        Ok(DatArchiveWriter {
+            writer,
+            headers,
+            name_len,
+            encoding,
+        })
+    }
+}
+
+impl<T: Write + Seek> Archive for DatArchiveWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let entry = self
+            .headers
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("File '{}' not found in archive", name))?;
+        if entry.offset != 0 {
+            return Err(anyhow::anyhow!("File '{}' already exists in archive", name));
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        entry.offset = self.writer.stream_position()? as u32;
+        let file = DatArchiveFile {
+            header: entry,
+            writer: &mut self.writer,
+            pos: 0,
+        };
+        Ok(Box::new(file))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let file_len = self.writer.seek(SeekFrom::End(0))? as u32;
+        let mut files = self.headers.values().cloned().collect::<Vec<_>>();
+        for file in files.iter_mut() {
+            if file.offset == 0 {
+                file.offset = file_len;
+            }
+        }
+        files.sort_by_key(|f| f.offset);
+        // Sizes are computed from offsets and the first file must not be empty,
+        // so leading empty files are moved after the first non-empty file.
+        if let Some(pos) = files.iter().position(|f| f.size != 0)
+            && pos > 0
+        {
+            let end = files[pos].offset + files[pos].size;
+            for file in files[..pos].iter_mut() {
+                file.offset = end;
+            }
+            files[..=pos].rotate_left(pos);
+        }
+        self.writer.seek(SeekFrom::Start(4))?;
+        for file in files {
+            self.writer
+                .write_fstring(&file.name, self.name_len, self.encoding, 0, false)?;
+            self.writer.write_u32(file.offset)?;
+        }
+        self.writer.write_all(&vec![0; self.name_len])?;
+        self.writer.write_u32(file_len)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// DAT Archive File
+pub struct DatArchiveFile<'a, T: Write + Seek> {
+    header: &'a mut DatFileHeader,
+    writer: &'a mut T,
+    pos: usize,
+}
+
+impl<'a, T: Write + Seek> Write for DatArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer
+            .seek(SeekFrom::Start(self.header.offset as u64 + self.pos as u64))?;
+        let bytes_written = self.writer.write(buf)?;
+        self.pos += bytes_written;
+        self.header.size = self.header.size.max(self.pos as u32);
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for DatArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as usize,
+            SeekFrom::End(offset) => {
+                if offset < 0 {
+                    if (-offset) as usize > self.header.size as usize {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "Seek from end exceeds file length",
+                        ));
+                    }
+                    self.header.size as usize - (-offset) as usize
+                } else {
+                    self.header.size as usize + offset as usize
+                }
+            }
+            SeekFrom::Current(offset) => {
+                if offset < 0 {
+                    if (-offset) as usize > self.pos {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "Seek from current exceeds current position",
+                        ));
+                    }
+                    self.pos.saturating_sub((-offset) as usize)
+                } else {
+                    self.pos + offset as usize
+                }
+            }
+        };
+        self.pos = new_pos;
+        Ok(self.pos as u64)
+    }
+}
@@ -37,7 +37,7 @@ impl ScriptBuilder for PckArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(PckArchive::new(
             MemReader::new(data),
@@ -52,7 +52,7 @@ impl ScriptBuilder for PckArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -75,7 +75,7 @@ impl ScriptBuilder for PckArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(PckArchive::new(reader, archive_encoding, config)?))
     }
@@ -92,6 +92,10 @@ impl ScriptBuilder for PckArchiveBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
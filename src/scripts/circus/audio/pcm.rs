@@ -3,6 +3,7 @@ use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use crate::utils::lossless_audio::*;
+use crate::utils::ogg::*;
 use crate::utils::pcm::*;
 use crate::utils::struct_pack::*;
 use anyhow::Result;
@@ -34,7 +35,7 @@ impl ScriptBuilder for PcmBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Pcm::new(MemReader::new(buf), config)?))
     }
@@ -45,7 +46,7 @@ impl ScriptBuilder for PcmBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let file = std::fs::File::open(filename)?;
         let f = std::io::BufReader::new(file);
@@ -59,7 +60,7 @@ impl ScriptBuilder for PcmBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(Pcm::new(reader, config)?))
     }
@@ -83,9 +84,34 @@ impl ScriptBuilder for PcmBuilder {
     fn is_audio(&self) -> bool {
         true
     }
+
+    fn can_create_file(&self) -> bool {
+        true
+    }
+
+    fn create_file<'a>(
+        &'a self,
+        filename: &'a str,
+        writer: Box<dyn WriteSeek + 'a>,
+        _encoding: Encoding,
+        _file_encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<()> {
+        let data = std::fs::read(filename)?;
+        if data.starts_with(b"OggS") {
+            return Pcm::write_ogg(writer, Mode::Ogg as u32, &data);
+        }
+        let (fmt, data) = read_audio(MemReader::new(data))?;
+        let mode = if config.circus_pcm_adpcm {
+            Mode::Adpcm
+        } else {
+            Mode::Raw
+        };
+        Pcm::write_wave(writer, mode as u32, fmt, data)
+    }
 }
 
-#[derive(Debug, IntEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, IntEnum)]
 #[repr(u32)]
 enum Mode {
     Raw = 0,
@@ -193,6 +219,115 @@ impl Pcm {
         }
         Ok(output)
     }
+
+    /// Encodes 16 bits PCM data in the ADPCM format read by [Self::decode_adpcm].
+    fn encode_adpcm(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(data.len() / 2);
+        let mut value = [0i32; 2];
+        let mut shift = [0u32; 2];
+        let mut channel = 0;
+        for sample in data.chunks_exact(2) {
+            let target = i16::from_le_bytes([sample[0], sample[1]]) as i32;
+            let cur = value[channel];
+            let s = shift[channel];
+            let diff = target - cur;
+            let half = (1 << s) >> 1;
+            let mut delta = if diff >= 0 {
+                (diff + half) >> s
+            } else {
+                -((half - diff) >> s)
+            }
+            .clamp(-0x80, 0x7F);
+            // The decoder only keeps the low 16 bits, so the value must not wrap around.
+            while cur + (delta << s) > i16::MAX as i32 {
+                delta -= 1;
+            }
+            while cur + (delta << s) < i16::MIN as i32 {
+                delta += 1;
+            }
+            value[channel] = cur + (delta << s);
+            if delta == 0 {
+                if s != 0 {
+                    shift[channel] -= 1;
+                }
+            } else if (delta == 0x7F || delta == -0x80) && s != 8 {
+                shift[channel] += 1;
+            }
+            output.push(delta as i8 as u8);
+            channel = 1 - channel;
+        }
+        output
+    }
+
+    /// Writes a PCM file which contains an Ogg/Vorbis stream.
+    fn write_ogg<W: Write>(mut writer: W, mode: u32, data: &[u8]) -> Result<()> {
+        let info = read_vorbis_info(data)?;
+        let header = Header {
+            src_size: (info.samples * info.channels as u64 * 2) as u32,
+            _mode: mode,
+            ogg_size: data.len() as u32,
+            pcm: None,
+        };
+        writer.write_all(b"XPCM")?;
+        header.pack(&mut writer, false, Encoding::Utf8, &None)?;
+        writer.write_all(data)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes a PCM file which contains wave data.
+    ///
+    /// `mode` is the full mode field of header, which includes the extra of [Mode::Lzss] and [Mode::Zlib].
+    fn write_wave<W: Write>(mut writer: W, mode: u32, fmt: PcmFormat, data: Vec<u8>) -> Result<()> {
+        if fmt.format_tag != 1 {
+            return Err(anyhow::anyhow!(
+                "Only integer PCM audio is supported, got format tag {}",
+                fmt.format_tag
+            ));
+        }
+        let src_size = data.len() as u32;
+        let typ = Mode::try_from(mode & 0xFF)
+            .map_err(|_| anyhow::anyhow!("Unsupported PCM mode: {}", mode & 0xFF))?;
+        if typ != Mode::Raw && fmt.bits_per_sample != 16 {
+            return Err(anyhow::anyhow!(
+                "{:?} requires 16 bits per sample, got {}",
+                typ,
+                fmt.bits_per_sample
+            ));
+        }
+        let data = match typ {
+            Mode::Raw => data,
+            Mode::Adpcm => Self::encode_adpcm(&data),
+            Mode::Lzss | Mode::Zlib => {
+                let extra = (mode >> 8) & 0xFF;
+                let encoded = PcmEncoder::new(&data, extra)?.pack();
+                let packed = if typ == Mode::Lzss {
+                    PcmEncoder::pack_v1(&encoded)
+                } else {
+                    let mut encoder =
+                        flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(&encoded)?;
+                    encoder.finish()?
+                };
+                let mut output = Vec::with_capacity(packed.len() + 4);
+                output.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+                output.extend_from_slice(&packed);
+                output
+            }
+            Mode::Ogg => return Err(anyhow::anyhow!("Unsupported PCM mode: {:?}", typ)),
+        };
+        let header = Header {
+            src_size,
+            _mode: mode,
+            ogg_size: 0,
+            pcm: Some(fmt),
+        };
+        writer.write_all(b"XPCM")?;
+        header.pack(&mut writer, false, Encoding::Utf8, &None)?;
+        writer.write_all(&data)?;
+        writer.flush()?;
+        Ok(())
+    }
 }
 
 impl Script for Pcm {
@@ -230,6 +365,48 @@ impl Script for Pcm {
         }
         Ok(())
     }
+
+    fn custom_import<'a>(
+        &'a self,
+        custom_filename: &'a str,
+        file: Box<dyn WriteSeek + 'a>,
+        _encoding: Encoding,
+        _output_encoding: Encoding,
+    ) -> Result<()> {
+        let data = std::fs::read(custom_filename)?;
+        if data.starts_with(b"OggS") {
+            let mode = if self.header.mode() == 5 {
+                self.header._mode
+            } else {
+                Mode::Ogg as u32
+            };
+            return Self::write_ogg(file, mode, &data);
+        }
+        let (fmt, data) = read_audio(MemReader::new(data))?;
+        let mode = match Mode::try_from(self.header.mode()) {
+            Ok(Mode::Raw) => Mode::Raw as u32,
+            Ok(Mode::Adpcm | Mode::Lzss | Mode::Zlib) if fmt.bits_per_sample == 16 => {
+                self.header._mode
+            }
+            _ if fmt.bits_per_sample == 16 => {
+                eprintln!(
+                    "WARN: Encoding PCM mode {} is not supported, ADPCM is used instead.",
+                    self.header.mode()
+                );
+                crate::COUNTER.inc_warning();
+                Mode::Adpcm as u32
+            }
+            _ => {
+                eprintln!(
+                    "WARN: {} bits audio can not be compressed, raw PCM is used instead.",
+                    fmt.bits_per_sample
+                );
+                crate::COUNTER.inc_warning();
+                Mode::Raw as u32
+            }
+        };
+        Self::write_wave(file, mode, fmt, data)
+    }
 }
 
 const UNK_43A254: [u8; 320] = [
@@ -885,3 +1062,258 @@ impl PcmDecoder {
         Ok(output)
     }
 }
+
+/// Encodes 16 bits PCM data in the transform coding format read by [PcmDecoder].
+struct PcmEncoder {
+    samples: Vec<i16>,
+    scales: [i32; 8],
+    /// cos and sin of `-2πk/4096`
+    twiddles: Vec<(f64, f64)>,
+}
+
+impl PcmEncoder {
+    pub fn new(data: &[u8], extra: u32) -> Result<Self> {
+        if extra > 4 {
+            return Err(anyhow::anyhow!("Unsupported PCM extra: {}", extra));
+        }
+        let mut reader = MemReaderRef::new(&UNK_43A254);
+        reader.pos = extra as usize * 0x40;
+        let mut scales = [0; 8];
+        for scale in scales.iter_mut() {
+            *scale = reader.read_i32()?;
+        }
+        let twiddles = (0..0x800)
+            .map(|k| {
+                let (sin, cos) = (-std::f64::consts::TAU * k as f64 / 4096.0).sin_cos();
+                (cos, sin)
+            })
+            .collect();
+        Ok(Self {
+            samples: data
+                .chunks_exact(2)
+                .map(|s| i16::from_le_bytes([s[0], s[1]]))
+                .collect(),
+            scales,
+            twiddles,
+        })
+    }
+
+    /// Returns the encoded data before compression.
+    ///
+    /// Each block contains 4096 samples and the next block starts 4064 samples later,
+    /// as the decoder blends first 32 samples of a block with the end of the previous one.
+    pub fn pack(self) -> Vec<u8> {
+        let blocks = self.samples.len().div_ceil(4064);
+        let mut output = vec![0u8; blocks * 8192];
+        let mut re = vec![0f64; 4096];
+        let mut im = vec![0f64; 4096];
+        let mut indexes = vec![0u16; 4096];
+        for (block, out) in output.chunks_exact_mut(8192).enumerate() {
+            for (n, (r, i)) in re.iter_mut().zip(im.iter_mut()).enumerate() {
+                *r = self.samples.get(block * 4064 + n).copied().unwrap_or(0) as f64;
+                *i = 0.0;
+            }
+            self.fft(&mut re, &mut im);
+            // The decoder computes sum(a2[k] * cos + a3[k] * sin) / 1024 for k < 2048.
+            for k in 0..0x800 {
+                let factor = if k == 0 { 0.25 } else { 0.5 } / self.scales[k / 256] as f64;
+                indexes[k * 2] = Self::table_index(re[k] * factor);
+                indexes[k * 2 + 1] = Self::table_index(-im[k] * factor);
+            }
+            for (j, index) in indexes.iter().enumerate() {
+                out[j] = (index >> 8) as u8;
+            }
+            for (m, pair) in indexes.chunks_exact(2).enumerate() {
+                let a = pair[0] as u8;
+                let b = pair[1] as u8;
+                out[0x1000 + m] = (a & 0xF0) | (b >> 4);
+                out[0x1800 + m] = (a << 4) | (b & 0x0F);
+            }
+        }
+        output
+    }
+
+    /// Returns the index of the nearest value in `WORD_6A56C8`.
+    fn table_index(value: f64) -> u16 {
+        let value = value.round().clamp(-32767.0, 32767.0) as i32;
+        if value > 0 {
+            (value * 2 - 1) as u16
+        } else {
+            (-value * 2) as u16
+        }
+    }
+
+    /// Forward FFT of 4096 points.
+    fn fft(&self, re: &mut [f64], im: &mut [f64]) {
+        let n = re.len();
+        let mut j = 0;
+        for i in 1..n {
+            let mut bit = n >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                re.swap(i, j);
+                im.swap(i, j);
+            }
+        }
+        let mut len = 2;
+        while len <= n {
+            let half = len / 2;
+            let step = n / len;
+            for start in (0..n).step_by(len) {
+                for k in 0..half {
+                    let (cos, sin) = self.twiddles[k * step];
+                    let a = start + k;
+                    let b = a + half;
+                    let tr = re[b] * cos - im[b] * sin;
+                    let ti = re[b] * sin + im[b] * cos;
+                    re[b] = re[a] - tr;
+                    im[b] = im[a] - ti;
+                    re[a] += tr;
+                    im[a] += ti;
+                }
+            }
+            len <<= 1;
+        }
+    }
+
+    /// Compresses data in the format read by [PcmDecoder::unpack_v1].
+    fn pack_v1(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(data.len() / 2);
+        let mut last = std::collections::HashMap::new();
+        let mut flag_pos = 0;
+        let mut bit = 8;
+        let mut pos = 0;
+        while pos < data.len() {
+            if bit == 8 {
+                flag_pos = output.len();
+                output.push(0);
+                bit = 0;
+            }
+            let mut offset = 0;
+            let mut count = 0;
+            if pos + 4 <= data.len() {
+                let key = &data[pos..pos + 4];
+                if let Some(&cand) = last.get(key)
+                    && pos - cand <= 0xFFFF
+                {
+                    let max = (data.len() - pos).min(0xFFFF + 2);
+                    while count < max && data[cand + count] == data[pos + count] {
+                        count += 1;
+                    }
+                    offset = pos - cand;
+                }
+            }
+            if count >= 4 {
+                if offset < 0x400 && count <= 19 {
+                    output.push(0xC0 | ((count - 4) << 2) as u8 | (offset >> 8) as u8);
+                    output.push(offset as u8);
+                } else if count <= 130 {
+                    output.push((count - 4) as u8);
+                    output.extend_from_slice(&(offset as u16).to_le_bytes());
+                } else {
+                    output.push(0x7F);
+                    output.extend_from_slice(&((count - 2) as u16).to_le_bytes());
+                    output.extend_from_slice(&(offset as u16).to_le_bytes());
+                }
+            } else {
+                output[flag_pos] |= 1 << bit;
+                output.push(data[pos]);
+                count = 1;
+            }
+            for p in pos..(pos + count).min(data.len().saturating_sub(3)) {
+                last.insert(&data[p..p + 4], p);
+            }
+            pos += count;
+            bit += 1;
+        }
+        output
+    }
+}
+
+#[test]
+fn test_lzss_roundtrip() {
+    let mut data = Vec::new();
+    for i in 0..70000u32 {
+        data.push(if i % 1000 < 300 {
+            (i % 7) as u8
+        } else {
+            (i / 3) as u8
+        });
+    }
+    let packed = PcmEncoder::pack_v1(&data);
+    assert!(packed.len() < data.len());
+    assert_eq!(PcmDecoder::unpack_v1(&packed).unwrap(), data);
+}
+
+#[test]
+fn test_transform_roundtrip() {
+    let mut data = Vec::new();
+    for i in 0..10000 {
+        let t = i as f64 / 22050.0;
+        let value = (t * 440.0 * std::f64::consts::TAU).sin() * 8000.0
+            + (t * 3000.0 * std::f64::consts::TAU).sin() * 2000.0;
+        data.extend_from_slice(&(value as i16).to_le_bytes());
+    }
+    for mode in [Mode::Lzss as u32, Mode::Zlib as u32 | 0x100] {
+        let fmt = PcmFormat {
+            format_tag: 1,
+            channels: 1,
+            sample_rate: 22050,
+            average_bytes_per_second: 22050 * 2,
+            block_align: 2,
+            bits_per_sample: 16,
+        };
+        let mut writer = MemWriter::new();
+        Pcm::write_wave(&mut writer, mode, fmt, data.clone()).unwrap();
+        let pcm = Pcm::new(MemReader::new(writer.into_inner()), &ExtraConfig::default()).unwrap();
+        assert_eq!(pcm.header._mode, mode);
+        assert_eq!(pcm.data.data.len(), data.len());
+        let error: i64 = data
+            .chunks_exact(2)
+            .zip(pcm.data.data.chunks_exact(2))
+            .map(|(a, b)| {
+                (i16::from_le_bytes([a[0], a[1]]) as i64 - i16::from_le_bytes([b[0], b[1]]) as i64)
+                    .abs()
+            })
+            .sum();
+        assert!(error / (data.len() as i64 / 2) < 64);
+    }
+}
+
+#[test]
+fn test_adpcm_roundtrip() {
+    let mut data = Vec::new();
+    for i in 0..4410 {
+        let t = i as f64 / 44100.0;
+        let left = (t * 440.0 * std::f64::consts::TAU).sin() * 8000.0;
+        let right = (t * 660.0 * std::f64::consts::TAU).sin() * 12000.0;
+        data.extend_from_slice(&(left as i16).to_le_bytes());
+        data.extend_from_slice(&(right as i16).to_le_bytes());
+    }
+    let fmt = PcmFormat {
+        format_tag: 1,
+        channels: 2,
+        sample_rate: 44100,
+        average_bytes_per_second: 44100 * 4,
+        block_align: 4,
+        bits_per_sample: 16,
+    };
+    let mut writer = MemWriter::new();
+    Pcm::write_wave(&mut writer, Mode::Adpcm as u32, fmt, data.clone()).unwrap();
+    let pcm = Pcm::new(MemReader::new(writer.into_inner()), &ExtraConfig::default()).unwrap();
+    assert_eq!(pcm.header.mode(), Mode::Adpcm as u32);
+    assert_eq!(pcm.data.data.len(), data.len());
+    let error: i64 = data
+        .chunks_exact(2)
+        .zip(pcm.data.data.chunks_exact(2))
+        .map(|(a, b)| {
+            (i16::from_le_bytes([a[0], a[1]]) as i64 - i16::from_le_bytes([b[0], b[1]]) as i64)
+                .abs()
+        })
+        .sum();
+    assert!(error / (data.len() as i64 / 2) < 64);
+}
@@ -113,7 +113,7 @@ impl ScriptBuilder for CrxImageBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(CrxImage::new(MemReader::new(data), config)?))
     }
@@ -186,6 +186,7 @@ enum CrxImageData {
         pixels: Vec<u8>,
         stride: usize,
         palette: Vec<u8>,
+        raw_palette: Vec<u8>,
         palette_format: PaletteFormat,
         pixel_depth_bits: usize,
     },
@@ -209,6 +210,7 @@ pub struct CrxImage {
     zstd_compression_level: i32,
     row_type: CircusCrxMode,
     canvas: bool,
+    version: Option<u16>,
 }
 
 impl std::fmt::Debug for CrxImage {
@@ -284,10 +286,8 @@ impl CrxImage {
 
             let palette = if bits_per_pixel == 8 {
                 let raw_colors = usize::from(header.bpp);
-                Some((
-                    Self::read_v1_palette(&mut reader, raw_colors)?,
-                    PaletteFormat::Rgb,
-                ))
+                let (palette, raw_palette) = Self::read_v1_palette(&mut reader, raw_colors)?;
+                Some((palette, raw_palette, PaletteFormat::Rgb))
             } else {
                 None
             };
@@ -298,11 +298,12 @@ impl CrxImage {
 
             let pixels = Self::unpack_v1(&mut reader, output_len)?;
 
-            if let Some((palette, palette_format)) = palette {
+            if let Some((palette, raw_palette, palette_format)) = palette {
                 let data = CrxImageData::IndexedV1 {
                     pixels,
                     stride,
                     palette,
+                    raw_palette,
                     palette_format,
                     pixel_depth_bits: bits_per_pixel,
                 };
@@ -373,6 +374,7 @@ impl CrxImage {
             zstd_compression_level: config.zstd_compression_level,
             row_type: config.circus_crx_mode.for_importing(),
             canvas: config.circus_crx_canvas,
+            version: config.circus_crx_version,
         })
     }
 
@@ -388,23 +390,7 @@ impl CrxImage {
     pub fn draw_diff(&self, diff: &Self) -> Result<ImageData> {
         let base_header = &self.header;
         let diff_header = &diff.header;
-        let (img_width, img_height) =
-            if base_header.clips.is_empty() && diff_header.clips.is_empty() {
-                (
-                    (base_header.width + base_header.inner_x)
-                        .max(diff_header.width + diff_header.inner_x),
-                    (base_header.height + base_header.inner_y)
-                        .max(diff_header.height + diff_header.inner_y),
-                )
-            } else {
-                if base_header.clips.is_empty() {
-                    let clip = &diff_header.clips[0];
-                    (clip.img_width, clip.img_height)
-                } else {
-                    let clip = &base_header.clips[0];
-                    (clip.img_width, clip.img_height)
-                }
-            };
+        let (img_width, img_height) = self.diff_canvas_size(diff);
         let base = self.export_image()?;
         let mut nw = draw_on_canvas(
             base,
@@ -422,6 +408,85 @@ impl CrxImage {
         Ok(nw)
     }
 
+    /// Computes the differential image which turns this image into `img`.
+    ///
+    /// This is the reverse of [Self::draw_diff]. The returned image has the same size as `diff`.
+    pub fn compute_diff(&self, diff: &Self, mut img: ImageData) -> Result<ImageData> {
+        let (img_width, img_height) = self.diff_canvas_size(diff);
+        if img.width != img_width as u32 || img.height != img_height as u32 {
+            return Err(anyhow::anyhow!(
+                "Image size does not match: expected {}x{}, got {}x{}",
+                img_width,
+                img_height,
+                img.width,
+                img.height
+            ));
+        }
+        if img.depth != 8 {
+            return Err(anyhow::anyhow!("Image depth must be 8, got {}", img.depth));
+        }
+        if self.color_type != diff.color_type {
+            return Err(anyhow::anyhow!(
+                "Color types do not match: {:?} vs {:?}",
+                self.color_type,
+                diff.color_type
+            ));
+        }
+        match img.color_type {
+            ImageColorType::Bgr | ImageColorType::Bgra => {}
+            ImageColorType::Rgb => convert_rgb_to_bgr(&mut img)?,
+            ImageColorType::Rgba => convert_rgba_to_bgra(&mut img)?,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported color type: {:?}",
+                    img.color_type
+                ));
+            }
+        }
+        if img.color_type != self.color_type {
+            if self.color_type == ImageColorType::Bgr {
+                convert_bgra_to_bgr(&mut img)?;
+            } else {
+                convert_bgr_to_bgra(&mut img)?;
+            }
+        }
+        let base = draw_on_canvas(
+            self.export_image()?,
+            img_width as u32,
+            img_height as u32,
+            self.header.inner_x as u32,
+            self.header.inner_y as u32,
+        )?;
+        diff_from_img(
+            &base,
+            &img,
+            diff.header.inner_x as u32,
+            diff.header.inner_y as u32,
+            diff.header.width as u32,
+            diff.header.height as u32,
+        )
+    }
+
+    /// Returns the canvas size of this image with a differential image drawn on it.
+    fn diff_canvas_size(&self, diff: &Self) -> (u16, u16) {
+        let base_header = &self.header;
+        let diff_header = &diff.header;
+        if base_header.clips.is_empty() && diff_header.clips.is_empty() {
+            (
+                (base_header.width + base_header.inner_x)
+                    .max(diff_header.width + diff_header.inner_x),
+                (base_header.height + base_header.inner_y)
+                    .max(diff_header.height + diff_header.inner_y),
+            )
+        } else if base_header.clips.is_empty() {
+            let clip = &diff_header.clips[0];
+            (clip.img_width, clip.img_height)
+        } else {
+            let clip = &base_header.clips[0];
+            (clip.img_width, clip.img_height)
+        }
+    }
+
     fn decode_row0(
         dst: &mut Vec<u8>,
         mut dst_p: usize,
@@ -574,7 +639,10 @@ impl CrxImage {
         Ok(src_p)
     }
 
-    fn read_v1_palette<T: Read>(reader: &mut T, raw_colors: usize) -> Result<Vec<u8>> {
+    /// Reads the palette of a CRX v1 image.
+    ///
+    /// Returns the palette in RGB format and the raw palette data.
+    fn read_v1_palette<T: Read>(reader: &mut T, raw_colors: usize) -> Result<(Vec<u8>, Vec<u8>)> {
         if raw_colors == 0 {
             return Err(anyhow::anyhow!("CRX v1 palette has zero colors"));
         }
@@ -605,7 +673,7 @@ impl CrxImage {
             palette.push(b);
             pos += color_size;
         }
-        Ok(palette)
+        Ok((palette, palette_raw))
     }
 
     fn unpack_v1<T: Read>(reader: &mut T, output_len: usize) -> Result<Vec<u8>> {
@@ -669,6 +737,178 @@ impl CrxImage {
         Ok(dst)
     }
 
+    /// Compresses data with the LZ scheme used by CRX v1 images. (See [Self::unpack_v1])
+    fn pack_v1(data: &[u8]) -> Vec<u8> {
+        const MAX_OFFSET: usize = 0xFFFF;
+        const MAX_COUNT: usize = 0xFFFF + 2;
+        const MAX_CHAIN: usize = 64;
+        const HASH_BITS: u32 = 16;
+        const NONE: usize = usize::MAX;
+        let hash = |p: usize| {
+            let v = data[p] as u32 | (data[p + 1] as u32) << 8 | (data[p + 2] as u32) << 16;
+            (v.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+        };
+        let mut head = vec![NONE; 1 << HASH_BITS];
+        let mut prev = vec![NONE; data.len()];
+        let mut dst = Vec::with_capacity(data.len() / 2 + 16);
+        let mut flag_pos = 0;
+        let mut flag_bit = 8;
+        let mut pos = 0;
+        while pos < data.len() {
+            if flag_bit == 8 {
+                flag_pos = dst.len();
+                dst.push(0);
+                flag_bit = 0;
+            }
+            let mut count = 0;
+            let mut offset = 0;
+            if pos + 3 <= data.len() {
+                let limit = (data.len() - pos).min(MAX_COUNT);
+                let mut candidate = head[hash(pos)];
+                let mut chain = 0;
+                while candidate != NONE && chain < MAX_CHAIN && pos - candidate <= MAX_OFFSET {
+                    let mut len = 0;
+                    while len < limit && data[candidate + len] == data[pos + len] {
+                        len += 1;
+                    }
+                    if len > count {
+                        count = len;
+                        offset = pos - candidate;
+                        if len == limit {
+                            break;
+                        }
+                    }
+                    candidate = prev[candidate];
+                    chain += 1;
+                }
+            }
+            if (4..=19).contains(&count) && offset <= 0x3FF {
+                dst.push(0xC0 | ((count - 4) << 2) as u8 | (offset >> 8) as u8);
+                dst.push(offset as u8);
+            } else if (4..=130).contains(&count) {
+                dst.push((count - 4) as u8);
+                dst.extend_from_slice(&(offset as u16).to_le_bytes());
+            } else if count > 130 {
+                dst.push(0x7F);
+                dst.extend_from_slice(&((count - 2) as u16).to_le_bytes());
+                dst.extend_from_slice(&(offset as u16).to_le_bytes());
+            } else if count == 3 && offset <= 0xFF {
+                if offset < 0x20 {
+                    dst.push(0xA0 | offset as u8);
+                } else {
+                    dst.push(0xA0);
+                    dst.push(offset as u8);
+                }
+            } else {
+                count = 1;
+                dst[flag_pos] |= 1 << flag_bit;
+                dst.push(data[pos]);
+            }
+            flag_bit += 1;
+            let end = pos + count;
+            while pos < end {
+                if pos + 3 <= data.len() {
+                    let h = hash(pos);
+                    prev[pos] = head[h];
+                    head[h] = pos;
+                }
+                pos += 1;
+            }
+        }
+        dst
+    }
+
+    /// Builds the raw palette of a CRX v1 image.
+    ///
+    /// Returns the value of `bpp` field in header and the raw palette data.
+    fn build_v1_palette(palette: &[[u8; 3]], four_bytes: bool) -> (u16, Vec<u8>) {
+        // 0 and 1 mean 24-bit and 32-bit images.
+        let colors = if four_bytes {
+            0x100
+        } else {
+            palette.len().max(2)
+        };
+        let mut raw_palette = Vec::with_capacity(colors * 4);
+        for i in 0..colors {
+            let mut color = palette.get(i).copied().unwrap_or_default();
+            // Magenta is read as white, so store a close color instead.
+            if color == [0xFF, 0x00, 0xFF] {
+                color[1] = 0x01;
+            }
+            raw_palette.extend_from_slice(&color);
+            if four_bytes {
+                raw_palette.push(0);
+            }
+        }
+        let bpp = if four_bytes { 0x102 } else { colors as u16 };
+        (bpp, raw_palette)
+    }
+
+    /// Maps a BGR image to palette indices.
+    ///
+    /// The original palette is reused if it contains all colors of the image,
+    /// otherwise a new palette is generated.
+    /// Returns the value of `bpp` field in header, the raw palette data and the indices.
+    fn map_v1_palette(
+        data: &ImageData,
+        palette: &[u8],
+        raw_palette: &[u8],
+        raw_colors: u16,
+    ) -> Result<(u16, Vec<u8>, Vec<u8>)> {
+        let colors: Vec<[u8; 3]> = data
+            .data
+            .chunks_exact(3)
+            .map(|p| [p[2], p[1], p[0]])
+            .collect();
+        let mut lookup = std::collections::HashMap::new();
+        for (i, color) in palette.chunks_exact(3).enumerate() {
+            lookup
+                .entry([color[0], color[1], color[2]])
+                .or_insert(i as u8);
+        }
+        if let Some(indices) = colors
+            .iter()
+            .map(|c| lookup.get(c).copied())
+            .collect::<Option<Vec<u8>>>()
+        {
+            return Ok((raw_colors, raw_palette.to_vec(), indices));
+        }
+        let (palette, indices) = quantize_colors(&colors, 256)?;
+        let (bpp, raw_palette) = Self::build_v1_palette(&palette, raw_colors == 0x102);
+        Ok((bpp, raw_palette, indices))
+    }
+
+    /// Writes a CRX v1 image.
+    ///
+    /// * `palette` - Raw palette data for indexed images.
+    /// * `pixels` - Pixel data without row padding.
+    /// * `row_bytes` - Size of a row in `pixels`.
+    fn write_v1<W: Write>(
+        mut writer: W,
+        header: &Header,
+        palette: Option<&[u8]>,
+        pixels: &[u8],
+        row_bytes: usize,
+    ) -> Result<()> {
+        let stride = (row_bytes + 3) & !3;
+        let mut data = Vec::with_capacity(stride * header.height as usize);
+        for row in pixels.chunks_exact(row_bytes) {
+            data.extend_from_slice(row);
+            data.resize(data.len() + stride - row_bytes, 0);
+        }
+        let compressed = Self::pack_v1(&data);
+        writer.write_all(b"CRXG")?;
+        header.pack(&mut writer, false, Encoding::Utf8, &None)?;
+        if let Some(palette) = palette {
+            writer.write_all(palette)?;
+        }
+        if (header.flags & 0x10) != 0 {
+            writer.write_u32(compressed.len() as u32)?;
+        }
+        writer.write_all(&compressed)?;
+        Ok(())
+    }
+
     fn decode_image(
         dst: &mut Vec<u8>,
         src: &[u8],
@@ -947,13 +1187,14 @@ impl CrxImage {
         mut writer: T,
         config: &ExtraConfig,
     ) -> Result<()> {
+        let version = config.circus_crx_version.unwrap_or(2);
         let header = Header {
             inner_x: 0,
             inner_y: 0,
             width: data.width as u16,
             height: data.height as u16,
-            version: 2,
-            flags: 0x10, // Force add compressed data length
+            version,
+            flags: if version == 1 { 0 } else { 0x10 }, // Force add compressed data length for v2
             bpp: match data.color_type {
                 ImageColorType::Bgr => 0,
                 ImageColorType::Bgra => 1,
@@ -989,6 +1230,9 @@ impl CrxImage {
                 data.data[i + 3] = r;
             }
         }
+        if header.version == 1 {
+            return Self::create_v1(header, data, writer);
+        }
         let mode = config.circus_crx_mode.for_creating();
         let encoded = if mode.is_best() {
             Self::encode_image_best(&data.data, header.width, header.height, pixel_size)?
@@ -1020,6 +1264,34 @@ impl CrxImage {
         writer.write_all(&compressed)?;
         Ok(())
     }
+
+    /// Creates a CRX v1 image.
+    ///
+    /// RGB images with 256 colors or less are stored as indexed images.
+    fn create_v1<W: Write>(mut header: Header, data: ImageData, writer: W) -> Result<()> {
+        if data.color_type == ImageColorType::Bgr {
+            let colors: Vec<[u8; 3]> = data
+                .data
+                .chunks_exact(3)
+                .map(|p| [p[2], p[1], p[0]])
+                .collect();
+            let unique: std::collections::HashSet<&[u8; 3]> = colors.iter().collect();
+            if unique.len() <= 256 {
+                let (palette, indices) = quantize_colors(&colors, 256)?;
+                let (bpp, raw_palette) = Self::build_v1_palette(&palette, false);
+                header.bpp = bpp;
+                return Self::write_v1(
+                    writer,
+                    &header,
+                    Some(&raw_palette),
+                    &indices,
+                    data.width as usize,
+                );
+            }
+        }
+        let row_bytes = data.width as usize * data.color_type.bpp(1) as usize;
+        Self::write_v1(writer, &header, None, &data.data, row_bytes)
+    }
 }
 
 impl Script for CrxImage {
@@ -1107,6 +1379,7 @@ impl Script for CrxImage {
                 palette,
                 palette_format,
                 pixel_depth_bits,
+                ..
             } => {
                 let total_pixels = width
                     .checked_mul(height)
@@ -1208,16 +1481,26 @@ impl Script for CrxImage {
             }
             color_type = self.color_type;
         }
+        let version = self.version.unwrap_or(self.header.version);
+        if version == 1 && self.header.version == 3 {
+            return Err(anyhow::anyhow!(
+                "CRX v3 images can not be converted to version 1"
+            ));
+        }
+        let indexed = version == 1 && matches!(self.data, CrxImageData::IndexedV1 { .. });
+        if indexed && color_type == ImageColorType::Bgra {
+            if data.data.chunks_exact(4).any(|p| p[3] != 0xFF) {
+                eprintln!("Warning: Alpha channel is discarded for indexed CRX v1 image.");
+            }
+            convert_bgra_to_bgr(&mut data)?;
+            color_type = ImageColorType::Bgr;
+        }
         let mut new_header = self.header.clone();
         new_header.bpp = match color_type {
             ImageColorType::Bgr => 0,
             ImageColorType::Bgra => 1,
             _ => return Err(anyhow::anyhow!("Unsupported color type: {:?}", color_type)),
         };
-        if new_header.version == 1 {
-            new_header.version = 2; // Upgrade to version 2
-        }
-        new_header.flags |= 0x10; // Force add compressed data length
         let pixel_size = color_type.bpp(1) as u8;
         if color_type == ImageColorType::Bgra && self.header.mode != 1 {
             let alpha_flip = if self.header.mode == 2 { 0 } else { 0xFF };
@@ -1232,6 +1515,32 @@ impl Script for CrxImage {
                 data.data[i + 3] = r;
             }
         }
+        if version == 1 {
+            new_header.version = 1;
+            if let CrxImageData::IndexedV1 {
+                palette,
+                raw_palette,
+                ..
+            } = &self.data
+            {
+                let (bpp, raw_palette, indices) =
+                    Self::map_v1_palette(&data, palette, raw_palette, self.header.bpp)?;
+                new_header.bpp = bpp;
+                return Self::write_v1(
+                    file,
+                    &new_header,
+                    Some(&raw_palette),
+                    &indices,
+                    data.width as usize,
+                );
+            }
+            let row_bytes = data.width as usize * pixel_size as usize;
+            return Self::write_v1(file, &new_header, None, &data.data, row_bytes);
+        }
+        if new_header.version == 1 {
+            new_header.version = 2; // Upgrade to version 2
+        }
+        new_header.flags |= 0x10; // Force add compressed data length
         let encoded = if self.row_type.is_origin() && self.data.is_row_encoded() {
             let mut row_type = Vec::with_capacity(self.header.height as usize);
             let pixel_size_bytes = self.color_type.bpp(1) as usize;
@@ -1396,3 +1705,138 @@ fn draw_on_img(base: &mut ImageData, diff: &ImageData, left: u32, top: u32) -> R
     }
     Ok(())
 }
+
+/// Computes the differential pixels of `img` over `base`. (Reverse of [draw_on_img])
+///
+/// `base` and `img` must have the same size and color type.
+fn diff_from_img(
+    base: &ImageData,
+    img: &ImageData,
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+) -> Result<ImageData> {
+    let bpp = base.color_type.bpp(1) as usize;
+    let base_stride = base.width as usize * bpp;
+    let diff_stride = width as usize * bpp;
+    let mut data = vec![0u8; diff_stride * height as usize];
+    for y in 0..height {
+        let base_y = top + y;
+        if base_y >= base.height {
+            continue; // Outside of the base image
+        }
+        for x in 0..width {
+            let base_x = left + x;
+            if base_x >= base.width {
+                continue; // Outside of the base image
+            }
+            let base_index = (base_y as usize * base_stride) + (base_x as usize * bpp);
+            let diff_index = (y as usize * diff_stride) + (x as usize * bpp);
+            for c in 0..3 {
+                data[diff_index + c] =
+                    img.data[base_index + c].wrapping_sub(base.data[base_index + c]);
+            }
+            if bpp == 4 {
+                data[diff_index + 3] =
+                    base.data[base_index + 3].wrapping_sub(img.data[base_index + 3]);
+            }
+        }
+    }
+    let outside = (0..base.height)
+        .flat_map(|y| (0..base.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| x < left || x >= left + width || y < top || y >= top + height)
+        .filter(|&(x, y)| {
+            let index = (y as usize * base_stride) + (x as usize * bpp);
+            img.data[index..index + bpp] != base.data[index..index + bpp]
+        })
+        .count();
+    if outside > 0 {
+        eprintln!(
+            "Warning: {} changed pixels are outside of the differential image area ({}x{} at {},{}) and are discarded.",
+            outside, width, height, left, top
+        );
+        crate::COUNTER.inc_warning();
+    }
+    Ok(ImageData {
+        width,
+        height,
+        depth: 8,
+        color_type: base.color_type,
+        data,
+    })
+}
+
+#[test]
+fn test_v1_roundtrip() {
+    let config = ExtraConfig {
+        circus_crx_version: Some(1),
+        ..Default::default()
+    };
+    let (width, height) = (37u32, 11u32);
+    let image = |color_type: ImageColorType, pixel: &dyn Fn(u32, u32) -> Vec<u8>| ImageData {
+        width,
+        height,
+        depth: 8,
+        color_type,
+        data: (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| pixel(x, y)))
+            .collect(),
+    };
+    let create = |img: &ImageData| {
+        let mut writer = MemWriter::new();
+        CrxImage::create_image(img.clone(), &mut writer, &config).unwrap();
+        writer.into_inner()
+    };
+    // Indexed images are exported as RGB images.
+    let export = |crx: &CrxImage| {
+        let mut img = crx.export_image().unwrap();
+        if img.color_type == ImageColorType::Rgb {
+            convert_rgb_to_bgr(&mut img).unwrap();
+        }
+        img.data
+    };
+    let few_colors = image(ImageColorType::Bgr, &|x, y| {
+        vec![
+            ((x / 4) % 2 * 255) as u8,
+            (y % 3 * 100) as u8,
+            (x % 4 * 80) as u8,
+        ]
+    });
+    let indexed = create(&few_colors);
+    let crx = CrxImage::new(MemReaderRef::new(&indexed), &config).unwrap();
+    assert_eq!(crx.header.version, 1);
+    assert!(matches!(crx.data, CrxImageData::IndexedV1 { .. }));
+    assert_eq!(export(&crx), few_colors.data);
+
+    // Original palette is reused if possible.
+    let mut writer = MemWriter::new();
+    crx.import_image(few_colors.clone(), "", Box::new(&mut writer))
+        .unwrap();
+    assert_eq!(writer.into_inner(), indexed);
+
+    let many_colors = image(ImageColorType::Bgr, &|x, y| {
+        vec![(x * 7) as u8, (y * 23) as u8, ((x + y) * 5) as u8]
+    });
+    let mut writer = MemWriter::new();
+    crx.import_image(many_colors.clone(), "", Box::new(&mut writer))
+        .unwrap();
+    let quantized = CrxImage::new(MemReader::new(writer.into_inner()), &config).unwrap();
+    assert!(matches!(quantized.data, CrxImageData::IndexedV1 { .. }));
+    for (a, b) in export(&quantized).iter().zip(many_colors.data.iter()) {
+        assert!(a.abs_diff(*b) <= 16, "{} != {}", a, b);
+    }
+
+    let direct = image(ImageColorType::Bgra, &|x, y| {
+        vec![
+            (x * 7) as u8,
+            (y * 23) as u8,
+            0x40,
+            if x < 20 { 0xFF } else { (y * 9) as u8 },
+        ]
+    });
+    let crx = CrxImage::new(MemReader::new(create(&direct)), &config).unwrap();
+    assert_eq!(crx.header.version, 1);
+    assert!(matches!(crx.data, CrxImageData::Direct(_)));
+    assert_eq!(export(&crx), direct.data);
+}
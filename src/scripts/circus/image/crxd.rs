@@ -4,7 +4,7 @@ use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use anyhow::Result;
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 #[derive(Debug)]
 /// Circus CRXD Image Builder
@@ -29,7 +29,7 @@ impl ScriptBuilder for CrxdImageBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(CrxdImage::new(
             MemReader::new(data),
@@ -63,6 +63,8 @@ impl ScriptBuilder for CrxdImageBuilder {
 #[derive(Debug)]
 /// Circus CRXD Image
 pub struct CrxdImage {
+    /// The first 0x20 bytes of file, which contains the base image's offset and name.
+    header: Vec<u8>,
     base: CrxImage,
     diff: CrxImage,
 }
@@ -80,7 +82,7 @@ impl CrxdImage {
         filename: &str,
         encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Self> {
         let mut reader = data;
         let mut magic = [0; 4];
@@ -104,6 +106,8 @@ impl CrxdImage {
             CrxImage::new(std::io::BufReader::new(f), config)?
         }
         .with_canvas(false);
+        reader.seek(SeekFrom::Start(0))?;
+        let header = reader.read_exact_vec(0x20)?;
         let mut typ = [0; 4];
         reader.read_exact(&mut typ)?;
         if typ == *b"CRXJ" {
@@ -117,18 +121,18 @@ impl CrxdImage {
                 archive.clone(),
                 config,
             )?;
-            return Ok(Self { base, diff });
+            return Ok(Self { header, base, diff });
         } else if typ == *b"CRXG" {
             let reader = StreamRegion::with_start_pos(reader, 0x20)?;
             let diff = CrxImage::new(reader, config)?.with_canvas(false);
-            return Ok(Self { base, diff });
+            return Ok(Self { header, base, diff });
         }
         Err(anyhow::anyhow!("Unsupported diff CRXD type: {:?}", typ))
     }
 
     fn read_diff<T: Read + Seek>(
         mut reader: T,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
         config: &ExtraConfig,
     ) -> Result<CrxImage> {
         let mut magic = [0; 4];
@@ -174,4 +178,73 @@ impl Script for CrxdImage {
     fn export_image(&self) -> Result<ImageData> {
         self.base.draw_diff(&self.diff)
     }
+
+    fn import_image<'a>(
+        &'a self,
+        data: ImageData,
+        filename: &str,
+        mut file: Box<dyn WriteSeek + 'a>,
+    ) -> Result<()> {
+        let diff = self.base.compute_diff(&self.diff, data)?;
+        // The diff image is always embedded, even if the original one is referenced in archive.
+        file.write_all(&self.header)?;
+        self.diff.import_image(diff, filename, file)
+    }
+}
+
+#[test]
+fn test_import() {
+    use crate::utils::testing::test_image;
+    let config = ExtraConfig::default();
+    let create = |img: ImageData| {
+        let mut writer = MemWriter::new();
+        CrxImage::create_image(img, &mut writer, &config).unwrap();
+        writer.into_inner()
+    };
+    let dir = crate::utils::testing::temp_dir("crxd");
+    std::fs::write(dir.join("BASE.CRX"), create(test_image(8, 6, 7))).unwrap();
+    let mut diff = create(test_image(4, 3, 13));
+    diff[4..6].copy_from_slice(&2u16.to_le_bytes()); // inner_x
+    diff[6..8].copy_from_slice(&1u16.to_le_bytes()); // inner_y
+    let mut crxd = b"CRXD\0\0\0\0\0\0\0\0base.crx".to_vec();
+    crxd.resize(0x20, 0);
+    crxd.extend_from_slice(&diff);
+    let filename = dir.join("diff.crx").to_string_lossy().into_owned();
+    let open = |data: Vec<u8>| {
+        CrxdImage::new(
+            MemReader::new(data),
+            &filename,
+            Encoding::Cp932,
+            &config,
+            None,
+        )
+        .unwrap()
+    };
+
+    let script = open(crxd);
+    let mut img = script.export_image().unwrap();
+    assert_eq!((img.width, img.height), (8, 6));
+    for y in 1..4 {
+        for x in 2..6 {
+            let pos = (y * 8 + x) * 4;
+            img.data[pos] ^= 0x5A;
+            img.data[pos + 3] = (x * y * 20) as u8;
+        }
+    }
+    let mut writer = MemWriter::new();
+    script
+        .import_image(img.clone(), &filename, Box::new(&mut writer))
+        .unwrap();
+    let imported = open(writer.into_inner()).export_image().unwrap();
+    assert_eq!(imported.data, img.data);
+    // Pixels outside of the differential image area can not be stored.
+    let mut outside = img.clone();
+    outside.data[0] ^= 0xFF;
+    let mut writer = MemWriter::new();
+    script
+        .import_image(outside, &filename, Box::new(&mut writer))
+        .unwrap();
+    let imported = open(writer.into_inner()).export_image().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(imported.data, img.data);
 }
@@ -30,7 +30,7 @@ impl ScriptBuilder for CircusMesScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(CircusMesScript::new(buf, encoding, config)?))
     }
@@ -254,7 +254,10 @@ impl Script for CircusMesScript {
                     if token.value == self.info.nameopcode {
                         name = Some(t);
                     } else {
-                        let message = Message::new(t, name.take());
+                        let message = Message::new(t, name.take())
+                            .with_kind(MessageKind::Dialogue)
+                            .with_meta(meta_key::OFFSET, self.asm_bin_offset + token.offset)
+                            .with_meta(meta_key::OPCODE, token.value);
                         mes.push(message);
                     }
                 }
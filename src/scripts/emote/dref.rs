@@ -1,5 +1,6 @@
 //! Emote DPAK-referenced Image File (.dref)
 use crate::ext::io::*;
+use crate::ext::mutex::*;
 use crate::ext::psb::*;
 use crate::scripts::base::*;
 use crate::types::*;
@@ -9,8 +10,9 @@ use anyhow::Result;
 use emote_psb::PsbReader;
 use libtlg_rs::TlgColorType;
 use std::collections::HashMap;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use url::Url;
 
 #[derive(Debug)]
@@ -36,7 +38,7 @@ impl ScriptBuilder for DrefBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Dref::new(
             buf, encoding, filename, config, archive,
@@ -80,7 +82,7 @@ impl Dpak {
         Ok(Self { psb })
     }
 
-    pub fn load_image(&self, name: &str) -> Result<(ImageData, Option<OffsetData>)> {
+    fn resource_id(&self, name: &str) -> Result<usize> {
         let root = self.psb.root();
         let rid = root[name]
             .resource_id()
@@ -93,10 +95,58 @@ impl Dpak {
                 self.psb.resources().len()
             ));
         }
-        let resource = &self.psb.resources()[rid];
+        Ok(rid)
+    }
+
+    pub fn load_image(&self, name: &str) -> Result<(ImageData, Option<OffsetData>)> {
+        let resource = &self.psb.resources()[self.resource_id(name)?];
         Self::load_img(&resource)
     }
 
+    /// Replaces the image with the given RGBA image, encoded in the same format as the original one.
+    pub fn replace_image(
+        &mut self,
+        name: &str,
+        img: ImageData,
+        offset: Option<&OffsetData>,
+        config: &ExtraConfig,
+    ) -> Result<()> {
+        let rid = self.resource_id(name)?;
+        let resource = &self.psb.resources()[rid];
+        let mut writer = MemWriter::new();
+        if libtlg_rs::is_valid_tlg(resource) {
+            let tlg = libtlg_rs::load_tlg(MemReaderRef::new(resource))
+                .map_err(|e| anyhow::anyhow!("Failed to decode TLG image: {:?}", e))?;
+            super::pimg::encode_tlg(&tlg, img, &mut writer)?;
+        } else {
+            let mut encoder = png::Encoder::new(&mut writer, img.width, img.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_compression(config.png_compression_level.to_compression());
+            let mut png = encoder.write_header()?;
+            if let Some(o) = offset {
+                let mut offs = Vec::with_capacity(9);
+                offs.extend_from_slice(&o.left.to_be_bytes());
+                offs.extend_from_slice(&o.top.to_be_bytes());
+                offs.push(0); // Unit: pixel
+                png.write_chunk(png::chunk::ChunkType(*b"oFFs"), &offs)?;
+            }
+            png.write_image_data(&img.data)?;
+            png.finish()?;
+        }
+        self.psb.resources_mut()[rid] = writer.into_inner();
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let f = std::fs::File::create(path)?;
+        let f = std::io::BufWriter::new(f);
+        let psb = self.psb.clone().to_psb(false);
+        psb.finish_v4(f)
+            .map_err(|e| anyhow::anyhow!("Failed to write DPAK file: {:?}", e))?;
+        Ok(())
+    }
+
     fn load_img(data: &[u8]) -> Result<(ImageData, Option<OffsetData>)> {
         if libtlg_rs::is_valid_tlg(data) {
             Ok((Self::load_tlg(data)?, None))
@@ -169,6 +219,8 @@ impl Dpak {
 #[derive(Default)]
 struct DpakLoader {
     map: HashMap<String, Dpak>,
+    /// Paths of DPAK files loaded from disk
+    paths: HashMap<String, PathBuf>,
 }
 
 impl DpakLoader {
@@ -185,6 +237,7 @@ impl DpakLoader {
                 path = crate::utils::files::get_ignorecase_path(&path)?;
                 let ndpak = Dpak::new(&path)?;
                 self.map.insert(dpak.to_string(), ndpak);
+                self.paths.insert(dpak.to_string(), path);
                 self.map.get(dpak).unwrap()
             }
         };
@@ -202,11 +255,113 @@ impl DpakLoader {
     }
 }
 
+/// An image referenced by DREF, decoded as RGBA.
+struct DrefTile<'a> {
+    dpak: &'a str,
+    name: &'a str,
+    img: ImageData,
+    offset: Option<OffsetData>,
+    left: u32,
+    top: u32,
+    /// Changed pixels as (offset in tile, offset in imported image)
+    edits: Vec<(usize, usize)>,
+}
+
+/// A DPAK file which has images replaced by imported DREF files.
+struct PendingDpak {
+    /// Path of the original DPAK file
+    source: PathBuf,
+    /// Replaced images, decoded as RGBA.
+    images: HashMap<String, (ImageData, Option<OffsetData>)>,
+}
+
+/// DPAK files patched by DREF files imported in one import run.
+///
+/// Multiple DREF files may reference the same DPAK file, so replaced images are collected
+/// here and every DPAK file is written once by [DpakSession::save] at the end of the run.
+#[derive(Default)]
+pub struct DpakSession {
+    pending: Mutex<HashMap<PathBuf, PendingDpak>>,
+}
+
+impl std::fmt::Debug for DpakSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DpakSession")
+            .field("pending", &self.pending.lock_blocking().len())
+            .finish()
+    }
+}
+
+impl DpakSession {
+    /// Creates a new empty session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds changed pixels of DREF tiles to DPAK files in `out_dir`.
+    fn add(
+        &self,
+        out_dir: &Path,
+        loader: &DpakLoader,
+        tiles: Vec<DrefTile<'_>>,
+        data: &ImageData,
+    ) -> Result<()> {
+        let mut pending = self.pending.lock_blocking();
+        for tile in tiles {
+            if tile.edits.is_empty() {
+                continue;
+            }
+            // Keep the file name of the original DPAK, the domain of URL is lowercased.
+            let source = &loader.paths[tile.dpak];
+            let path = out_dir.join(source.file_name().unwrap_or_default());
+            let entry = pending.entry(path).or_insert_with(|| PendingDpak {
+                source: source.clone(),
+                images: HashMap::new(),
+            });
+            if &entry.source != source {
+                return Err(anyhow::anyhow!(
+                    "DPAK files {} and {} are written to the same path.",
+                    entry.source.display(),
+                    source.display()
+                ));
+            }
+            // Images shared by DREF files keep changes from all of them.
+            let (img, _) = entry
+                .images
+                .entry(tile.name.to_string())
+                .or_insert((tile.img, tile.offset));
+            for (tpos, pos) in tile.edits {
+                img.data[tpos..tpos + 4].copy_from_slice(&data.data[pos..pos + 4]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes all patched DPAK files and returns the number of written files.
+    ///
+    /// The session is empty afterwards.
+    pub fn save(&self, config: &ExtraConfig) -> Result<usize> {
+        let pending = std::mem::take(&mut *self.pending.lock_blocking());
+        let count = pending.len();
+        for (path, pending) in pending {
+            let mut dpak = Dpak::new(&pending.source)?;
+            for (name, (img, offset)) in pending.images {
+                dpak.replace_image(&name, img, offset.as_ref(), config)?;
+            }
+            crate::utils::files::make_sure_dir_exists(&path)?;
+            dpak.save(&path)?;
+        }
+        Ok(count)
+    }
+}
+
 /// Emote DREF Script
 pub struct Dref {
+    data: Vec<u8>,
     urls: Vec<Url>,
     dir: PathBuf,
     in_archives: HashMap<String, Vec<u8>>,
+    config: ExtraConfig,
 }
 
 impl std::fmt::Debug for Dref {
@@ -230,8 +385,8 @@ impl Dref {
         buf: Vec<u8>,
         encoding: Encoding,
         filename: &str,
-        _config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        config: &ExtraConfig,
+        archive: Option<&dyn Script>,
     ) -> Result<Self> {
         let text = decode_with_bom_detect(encoding, &buf, true)?.0;
         let mut urls = Vec::new();
@@ -274,9 +429,11 @@ impl Dref {
             }
         }
         Ok(Self {
+            data: buf,
             urls,
             dir,
             in_archives,
+            config: config.clone(),
         })
     }
 }
@@ -342,4 +499,291 @@ impl Script for Dref {
         }
         Ok(base_img)
     }
+
+    fn import_image<'a>(
+        &'a self,
+        _data: ImageData,
+        _filename: &str,
+        _file: Box<dyn WriteSeek + 'a>,
+    ) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "DREF import needs an output path to write referenced DPAK files."
+        ))
+    }
+
+    /// Writes the patched DREF file. Referenced DPAK files are written by [DpakSession::save].
+    fn import_image_filename(
+        &self,
+        mut data: ImageData,
+        _image_filename: &str,
+        filename: &str,
+    ) -> Result<()> {
+        let mut original = self.export_image()?;
+        convert_to_rgba(&mut original)?;
+        convert_to_rgba(&mut data)?;
+        if data.width != original.width || data.height != original.height {
+            return Err(anyhow::anyhow!(
+                "Image size mismatch: expected {}x{}, got {}x{}",
+                original.width,
+                original.height,
+                data.width,
+                data.height
+            ));
+        }
+        let mut loader = DpakLoader::default();
+        loader.load_archives(&self.in_archives)?;
+        let mut tiles = Vec::with_capacity(self.urls.len());
+        for (i, url) in self.urls.iter().enumerate() {
+            let dpak = url.domain().ok_or(anyhow::anyhow!(
+                "Invalid URL in DREF file: {} (missing domain)",
+                url
+            ))?;
+            let name = url.path().trim_start_matches("/");
+            let (mut img, offset) = loader.load_image(&self.dir, dpak, name)?;
+            convert_to_rgba(&mut img)?;
+            // Offset of base image is ignored when composing.
+            let (left, top) = match &offset {
+                Some(o) if i > 0 => (o.left, o.top),
+                _ => (0, 0),
+            };
+            tiles.push(DrefTile {
+                dpak,
+                name,
+                img,
+                offset,
+                left,
+                top,
+                edits: Vec::new(),
+            });
+        }
+        // Changed pixels are written to the topmost image which is visible at that pixel.
+        // Translucent pixels of overlay images are blended with images below them,
+        // so changes on them can not be written back.
+        let width = data.width as usize;
+        let mut translucent = 0usize;
+        let mut uncovered = 0usize;
+        for y in 0..data.height {
+            for x in 0..data.width {
+                let pos = (y as usize * width + x as usize) * 4;
+                if data.data[pos..pos + 4] == original.data[pos..pos + 4] {
+                    continue;
+                }
+                let target = tiles.iter().enumerate().rev().find_map(|(i, t)| {
+                    if x < t.left
+                        || y < t.top
+                        || x >= t.left + t.img.width
+                        || y >= t.top + t.img.height
+                    {
+                        return None;
+                    }
+                    let tpos = (((y - t.top) * t.img.width + x - t.left) * 4) as usize;
+                    let alpha = t.img.data[tpos + 3];
+                    if i > 0 && alpha == 0 {
+                        return None;
+                    }
+                    Some((i, tpos, alpha))
+                });
+                match target {
+                    Some((i, tpos, alpha)) if i == 0 || alpha == 0xFF => {
+                        tiles[i].edits.push((tpos, pos));
+                    }
+                    Some(_) => translucent += 1,
+                    None => uncovered += 1,
+                }
+            }
+        }
+        if translucent > 0 {
+            eprintln!(
+                "Warning: {} changed pixels are on translucent pixels of overlay images and are discarded.",
+                translucent
+            );
+            crate::COUNTER.inc_warning();
+        }
+        if uncovered > 0 {
+            eprintln!(
+                "Warning: {} changed pixels are not covered by any image and are discarded.",
+                uncovered
+            );
+            crate::COUNTER.inc_warning();
+        }
+        for tile in tiles.iter() {
+            if !tile.edits.is_empty() && !loader.paths.contains_key(tile.dpak) {
+                return Err(anyhow::anyhow!(
+                    "DPAK '{}' is stored in archive, patching DPAK in archive is not supported.",
+                    tile.dpak
+                ));
+            }
+        }
+        let out_dir = Path::new(filename)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."));
+        match &self.config.emote_dpak_session {
+            Some(session) => session.add(&out_dir, &loader, tiles, &data)?,
+            None => {
+                let session = DpakSession::new();
+                session.add(&out_dir, &loader, tiles, &data)?;
+                session.save(&self.config)?;
+            }
+        }
+        crate::utils::files::write_file(filename)?.write_all(&self.data)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_import_shared_dpak() {
+    let src = crate::utils::testing::temp_dir("dref-src");
+    let out = crate::utils::testing::temp_dir("dref-out");
+    let session = std::sync::Arc::new(DpakSession::new());
+    let config = ExtraConfig {
+        emote_dpak_session: Some(session.clone()),
+        ..Default::default()
+    };
+    let rgba = |f: &dyn Fn(u32) -> [u8; 4]| ImageData {
+        width: 4,
+        height: 4,
+        depth: 8,
+        color_type: ImageColorType::Rgba,
+        data: (0..16).flat_map(f).collect(),
+    };
+    let write_dpak = |path: PathBuf, images: Vec<(&str, ImageData)>| {
+        let mut root = json::object! {};
+        for (i, (name, _)) in images.iter().enumerate() {
+            root[*name] = format!("#resource#{}", i).into();
+        }
+        let mut psb = VirtualPsbFixed::with_json(&json::object! {
+            "version": 3,
+            "encryption": 0,
+            "data": root,
+        })
+        .unwrap();
+        for (_, img) in images {
+            let mut writer = MemWriter::new();
+            encode_img_writer(img, ImageOutputType::Png, &mut writer, &config).unwrap();
+            psb.resources_mut().push(writer.into_inner());
+        }
+        let f = std::fs::File::create(path).unwrap();
+        psb.to_psb(false).finish_v4(f).unwrap();
+    };
+    write_dpak(
+        src.join("a.dpak"),
+        vec![
+            ("base1", rgba(&|_| [10, 20, 30, 0xFF])),
+            ("base2", rgba(&|_| [40, 50, 60, 0xFF])),
+        ],
+    );
+    // Left half is opaque, (3, 0) is translucent and others are transparent.
+    let face = rgba(&|i| match i {
+        _ if i % 4 < 2 => [200, 0, 0, 0xFF],
+        3 => [0, 200, 0, 0x80],
+        _ => [0, 0, 0, 0],
+    });
+    write_dpak(src.join("Face.DPAK"), vec![("face", face)]);
+    let open = |dir: &Path, name: &str, urls: &str| {
+        let path = dir.join(name).to_string_lossy().into_owned();
+        Dref::new(
+            urls.as_bytes().to_vec(),
+            Encoding::Utf8,
+            &path,
+            &config,
+            None,
+        )
+        .unwrap()
+    };
+    let export = |dref: &Dref| {
+        let mut img = dref.export_image().unwrap();
+        convert_to_rgba(&mut img).unwrap();
+        img
+    };
+    let set = |img: &mut ImageData, x: usize, y: usize, pixel: [u8; 4]| {
+        let pos = (y * 4 + x) * 4;
+        img.data[pos..pos + 4].copy_from_slice(&pixel);
+    };
+    let get = |img: &ImageData, x: usize, y: usize| {
+        let pos = (y * 4 + x) * 4;
+        img.data[pos..pos + 4].to_vec()
+    };
+    let dref1 = open(&src, "1.dref", "psb://a.dpak/base1\npsb://face.dpak/face\n");
+    let dref2 = open(&src, "2.dref", "psb://a.dpak/base2\npsb://face.dpak/face\n");
+    let original1 = export(&dref1);
+    let mut img1 = original1.clone();
+    set(&mut img1, 0, 0, [1, 2, 3, 0xFF]);
+    set(&mut img1, 2, 2, [4, 5, 6, 0xFF]);
+    set(&mut img1, 3, 0, [7, 8, 9, 0xFF]);
+    let out1 = out.join("1.dref").to_string_lossy().into_owned();
+    dref1.import_image_filename(img1, "", &out1).unwrap();
+    let mut img2 = export(&dref2);
+    set(&mut img2, 1, 1, [11, 12, 13, 0xFF]);
+    set(&mut img2, 3, 3, [14, 15, 16, 0xFF]);
+    let out2 = out.join("2.dref").to_string_lossy().into_owned();
+    dref2.import_image_filename(img2, "", &out2).unwrap();
+    // Nothing is written until the session is saved.
+    assert!(!out.join("Face.DPAK").exists());
+    assert_eq!(session.save(&config).unwrap(), 2);
+    assert_eq!(session.save(&config).unwrap(), 0);
+    assert!(out.join("Face.DPAK").exists());
+    assert!(!out.join("face.dpak").exists());
+    let patched1 = export(&open(
+        &out,
+        "1.dref",
+        &std::fs::read_to_string(&out1).unwrap(),
+    ));
+    let patched2 = export(&open(
+        &out,
+        "2.dref",
+        &std::fs::read_to_string(&out2).unwrap(),
+    ));
+    std::fs::remove_dir_all(&src).unwrap();
+    std::fs::remove_dir_all(&out).unwrap();
+    assert_eq!(get(&patched1, 0, 0), [1, 2, 3, 0xFF]);
+    // Changes of the shared image from both DREF files are kept.
+    assert_eq!(get(&patched1, 1, 1), [11, 12, 13, 0xFF]);
+    assert_eq!(get(&patched2, 0, 0), [1, 2, 3, 0xFF]);
+    assert_eq!(get(&patched1, 2, 2), [4, 5, 6, 0xFF]);
+    assert_eq!(get(&patched2, 2, 2), [40, 50, 60, 0xFF]);
+    assert_eq!(get(&patched2, 3, 3), [14, 15, 16, 0xFF]);
+    // Change on translucent overlay pixel is discarded.
+    assert_eq!(get(&patched1, 3, 0), get(&original1, 3, 0));
+}
+
+#[cfg(feature = "kirikiri-img")]
+#[test]
+fn test_replace_tlg6_grayscale_tile() {
+    let gray = |f: &dyn Fn(u8) -> u8| (0..16u8).map(f).collect::<Vec<_>>();
+    let mut writer = MemWriter::new();
+    crate::scripts::kirikiri::image::tlg::save_tlg_with_version(
+        libtlg_rs::Tlg {
+            width: 4,
+            height: 4,
+            version: 6,
+            color: TlgColorType::Grayscale8,
+            data: gray(&|i| i * 8),
+            tags: Default::default(),
+        },
+        Box::new(&mut writer),
+    )
+    .unwrap();
+    let mut psb = VirtualPsbFixed::with_json(&json::object! {
+        "version": 3,
+        "encryption": 0,
+        "data": { "tile": "#resource#0" },
+    })
+    .unwrap();
+    psb.resources_mut().push(writer.into_inner());
+    let mut dpak = Dpak { psb };
+    let data = gray(&|i| 255 - i);
+    let img = ImageData {
+        width: 4,
+        height: 4,
+        depth: 8,
+        color_type: ImageColorType::Rgba,
+        data: data.iter().flat_map(|&v| [v, v, v, 0xFF]).collect(),
+    };
+    dpak.replace_image("tile", img, None, &ExtraConfig::default())
+        .unwrap();
+    let tlg = libtlg_rs::load_tlg(MemReaderRef::new(&dpak.psb.resources()[0])).unwrap();
+    assert_eq!(tlg.version, 6);
+    assert_eq!(tlg.color, TlgColorType::Grayscale8);
+    assert_eq!(tlg.data, data);
 }
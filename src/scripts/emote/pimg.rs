@@ -14,6 +14,8 @@ use std::collections::HashMap;
 use std::io::{Read, Seek};
 use std::path::Path;
 
+const PNG_SIGNATURE: &[u8] = b"\x89PNG";
+
 #[derive(Debug)]
 /// Emote PImg Script Builder
 pub struct PImgBuilder {}
@@ -37,7 +39,7 @@ impl ScriptBuilder for PImgBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(PImg::new(MemReader::new(buf), filename, config)?))
     }
@@ -48,7 +50,7 @@ impl ScriptBuilder for PImgBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -67,7 +69,7 @@ impl ScriptBuilder for PImgBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(PImg::new(reader, filename, config)?))
     }
@@ -359,6 +361,7 @@ pub struct PImg {
     psd_compress: bool,
     zlib_compression_level: u32,
     psd_no_diff: bool,
+    config: ExtraConfig,
 }
 
 impl PImg {
@@ -376,6 +379,7 @@ impl PImg {
             psd_compress: config.psd_compress,
             zlib_compression_level: config.zlib_compression_level,
             psd_no_diff: config.emote_pimg_psd_no_diff,
+            config: config.clone(),
         })
     }
 
@@ -395,6 +399,71 @@ impl PImg {
         let resource = &self.psb.resources()[resource_id];
         Ok(load_tlg(MemReaderRef::new(&resource))?)
     }
+
+    /// Decodes a layer resource (TLG or PNG) to a RGBA image.
+    fn decode_resource(resource: &[u8]) -> Result<ImageData> {
+        let mut img = if resource.starts_with(PNG_SIGNATURE) {
+            load_png(MemReaderRef::new(resource))?
+        } else {
+            let tlg = load_tlg(MemReaderRef::new(resource))?;
+            ImageData {
+                width: tlg.width,
+                height: tlg.height,
+                color_type: match tlg.color {
+                    TlgColorType::Bgr24 => ImageColorType::Bgr,
+                    TlgColorType::Bgra32 => ImageColorType::Bgra,
+                    TlgColorType::Grayscale8 => ImageColorType::Grayscale,
+                },
+                depth: 8,
+                data: tlg.data,
+            }
+        };
+        convert_to_rgba(&mut img)?;
+        Ok(img)
+    }
+
+    /// Encodes a RGBA image in the same format, TLG version and color type as the original resource.
+    fn encode_resource(&self, original: &[u8], img: ImageData) -> Result<Vec<u8>> {
+        let mut writer = MemWriter::new();
+        if original.starts_with(PNG_SIGNATURE) {
+            encode_img_writer(img, ImageOutputType::Png, &mut writer, &self.config)?;
+            return Ok(writer.into_inner());
+        }
+        let tlg = load_tlg(MemReaderRef::new(original))?;
+        encode_tlg(&tlg, img, &mut writer)?;
+        Ok(writer.into_inner())
+    }
+
+    /// Finds the PIMG layer for a PSD layer.
+    ///
+    /// The layer id stored in PSD by export is preferred. Layer name is used if the id is missing.
+    fn find_layer_index(
+        psb: &VirtualPsbFixed,
+        layer: &NormalLayer,
+        name: &str,
+        used: &[bool],
+    ) -> Result<Option<usize>> {
+        if let Some(data) = layer.additional_info(LAYER_NAME_SOURCE_SETTING_KEY) {
+            let setting = LayerNameSourceSetting::unpack(
+                &mut MemReaderRef::new(data),
+                true,
+                Encoding::Utf8,
+                &None,
+            )?;
+            let id = setting.id as i64;
+            if let Some(idx) = psb.root()["layers"]
+                .members()
+                .position(|l| l["layer_id"].as_i64() == Some(id))
+            {
+                return Ok(Some(idx));
+            }
+        }
+        Ok(psb.root()["layers"]
+            .members()
+            .enumerate()
+            .find(|(i, l)| !used[*i] && l["name"].as_str() == Some(name))
+            .map(|(i, _)| i))
+    }
 }
 
 impl Script for PImg {
@@ -563,6 +632,205 @@ impl Script for PImg {
         psd.save(base, &mut writer)?;
         Ok(())
     }
+
+    fn custom_import<'a>(
+        &'a self,
+        custom_filename: &'a str,
+        file: Box<dyn WriteSeek + 'a>,
+        _encoding: Encoding,
+        output_encoding: Encoding,
+    ) -> Result<()> {
+        let rfile = std::fs::File::open(custom_filename)?;
+        let mut reader = std::io::BufReader::new(rfile);
+        let psd = PsdReader::new(&mut reader, output_encoding)?;
+        let mut psb = self.psb.clone();
+        let layer_count = psb.root()["layers"].len();
+        let mut used = vec![false; layer_count];
+        for layer in psd.read_normal_layers()? {
+            let name = layer.layer_name()?;
+            let idx = match Self::find_layer_index(&psb, &layer, &name, &used)? {
+                Some(idx) => idx,
+                None => {
+                    eprintln!(
+                        "Warning: PSD layer '{}' does not match any PIMG layer, skipped.",
+                        name
+                    );
+                    crate::COUNTER.inc_warning();
+                    continue;
+                }
+            };
+            used[idx] = true;
+            let info = &psb.root()["layers"][idx];
+            let layer_id = info["layer_id"]
+                .as_i64()
+                .ok_or_else(|| anyhow::anyhow!("Layer does not have a valid layer_id"))?;
+            // Group layers and layers without image data.
+            if info["layer_type"].as_i64() != Some(0) || layer_id == -1 {
+                continue;
+            }
+            let resource_id = psb.root()[format!("{layer_id}.tlg")]
+                .resource_id()
+                .ok_or_else(|| anyhow::anyhow!("Layer {layer_id} does not have a resource ID"))?
+                as usize;
+            if resource_id >= psb.resources().len() {
+                return Err(anyhow::anyhow!(
+                    "Resource ID {resource_id} for layer {layer_id} is out of bounds"
+                ));
+            }
+            let orig_left = info["left"].as_u32().unwrap_or(0);
+            let orig_top = info["top"].as_u32().unwrap_or(0);
+            let original = Self::decode_resource(&psb.resources()[resource_id])?;
+            let (mut left, mut top) = (layer.left(), layer.top());
+            let mut img = if layer.width() == 0 || layer.height() == 0 {
+                // Fully transparent layer
+                left = orig_left as i32;
+                top = orig_top as i32;
+                ImageData {
+                    width: original.width,
+                    height: original.height,
+                    color_type: ImageColorType::Rgba,
+                    depth: 8,
+                    data: vec![0u8; original.data.len()],
+                }
+            } else {
+                let mut img = layer.image()?;
+                convert_to_rgba(&mut img)?;
+                img
+            };
+            if left < 0 || top < 0 {
+                let x = (-left).max(0) as u32;
+                let y = (-top).max(0) as u32;
+                if x >= img.width || y >= img.height {
+                    eprintln!(
+                        "Warning: PSD layer '{}' is outside of canvas, skipped.",
+                        name
+                    );
+                    crate::COUNTER.inc_warning();
+                    continue;
+                }
+                img = crop_image(&img, x, y, img.width - x, img.height - y)?;
+                left = left.max(0);
+                top = top.max(0);
+            }
+            let (mut left, mut top) = (left as u32, top as u32);
+            // Editors may trim transparent borders of layers when saving,
+            // so put the image back to the original bounds if possible.
+            if left >= orig_left
+                && top >= orig_top
+                && left + img.width <= orig_left + original.width
+                && top + img.height <= orig_top + original.height
+                && (img.width != original.width || img.height != original.height)
+            {
+                img = draw_on_canvas(
+                    img,
+                    original.width,
+                    original.height,
+                    left - orig_left,
+                    top - orig_top,
+                )?;
+                left = orig_left;
+                top = orig_top;
+            }
+            if left == orig_left
+                && top == orig_top
+                && img.width == original.width
+                && img.height == original.height
+                && same_visible_pixels(&img.data, &original.data)
+            {
+                continue;
+            }
+            let width = img.width;
+            let height = img.height;
+            let data = self.encode_resource(&psb.resources()[resource_id], img)?;
+            psb.resources_mut()[resource_id] = data;
+            let info = &mut psb.root_mut()["layers"][idx];
+            info["left"] = (left as i64).into();
+            info["top"] = (top as i64).into();
+            info["width"] = (width as i64).into();
+            info["height"] = (height as i64).into();
+        }
+        let psb = psb.to_psb(false);
+        psb.finish_v4(file)
+            .map_err(|e| anyhow::anyhow!("Failed to write PSB file: {:?}", e))?;
+        Ok(())
+    }
+}
+
+/// Returns true if two RGBA images have the same visible pixels.
+///
+/// Editors may not keep the color of fully transparent pixels, so it is ignored.
+fn same_visible_pixels(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .all(|(a, b)| a == b || (a[3] == 0 && b[3] == 0))
+}
+
+/// Encodes a RGBA image as TLG with the same version, color type and tags as `original`.
+pub(super) fn encode_tlg(original: &Tlg, mut img: ImageData, writer: &mut MemWriter) -> Result<()> {
+    match original.color {
+        TlgColorType::Bgr24 => {
+            convert_rgba_to_bgra(&mut img)?;
+            convert_bgra_to_bgr(&mut img)?;
+        }
+        TlgColorType::Bgra32 => convert_rgba_to_bgra(&mut img)?,
+        TlgColorType::Grayscale8 => img = rgba_to_grayscale(img),
+    }
+    #[allow(unused_mut)]
+    let mut tlg = Tlg {
+        width: img.width,
+        height: img.height,
+        version: original.version,
+        color: original.color,
+        data: img.data,
+        tags: original.tags.clone(),
+    };
+    #[cfg(feature = "kirikiri-img")]
+    crate::scripts::kirikiri::image::tlg::save_tlg_with_version(tlg, Box::new(writer))?;
+    #[cfg(not(feature = "kirikiri-img"))]
+    {
+        if tlg.version != 5 {
+            eprintln!(
+                "Warning: Saving TLG{} requires feature kirikiri-img, TLG5 is used instead.",
+                tlg.version
+            );
+            crate::COUNTER.inc_warning();
+            tlg.version = 5;
+        }
+        save_tlg(&tlg, writer)?;
+    }
+    Ok(())
+}
+
+/// Converts a RGBA image to grayscale. Pixels which are not opaque gray are converted with
+/// their luminance, and a warning is printed.
+fn rgba_to_grayscale(img: ImageData) -> ImageData {
+    let mut lossy = false;
+    let data = img
+        .data
+        .chunks_exact(4)
+        .map(|p| {
+            if p[0] == p[1] && p[1] == p[2] && p[3] == 0xFF {
+                p[0]
+            } else {
+                lossy = true;
+                ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8
+            }
+        })
+        .collect();
+    if lossy {
+        eprintln!(
+            "Warning: Grayscale layer contains colored or transparent pixels, which are converted to gray."
+        );
+        crate::COUNTER.inc_warning();
+    }
+    ImageData {
+        width: img.width,
+        height: img.height,
+        color_type: ImageColorType::Grayscale,
+        depth: 8,
+        data,
+    }
 }
 
 struct PImgIter<'a> {
@@ -770,3 +1038,165 @@ impl<'a> Iterator for PImgIter2<'a> {
         }
     }
 }
+
+#[test]
+fn test_custom_import_roundtrip() {
+    let dir = crate::utils::testing::temp_dir("pimg");
+    let tlg = |version: u32, color: TlgColorType, width: u32, height: u32, data: Vec<u8>| {
+        let tlg = Tlg {
+            width,
+            height,
+            version,
+            color,
+            data,
+            tags: Default::default(),
+        };
+        let mut writer = MemWriter::new();
+        #[cfg(feature = "kirikiri-img")]
+        crate::scripts::kirikiri::image::tlg::save_tlg_with_version(tlg, Box::new(&mut writer))
+            .unwrap();
+        #[cfg(not(feature = "kirikiri-img"))]
+        save_tlg(&tlg, &mut writer).unwrap();
+        writer.into_inner()
+    };
+    let base_version = if cfg!(feature = "kirikiri-img") { 6 } else { 5 };
+    // Fully transparent pixels have color.
+    let base: Vec<u8> = (0..8 * 6u32)
+        .flat_map(|i| {
+            [
+                i as u8,
+                (i * 3) as u8,
+                (i * 5) as u8,
+                if i % 4 == 0 { 0 } else { 0xFF },
+            ]
+        })
+        .collect();
+    let mask: Vec<u8> = (0..4 * 3u32).map(|i| (i * 20) as u8).collect();
+    let mut psb = VirtualPsbFixed::with_json(&json::object! {
+        "version": 3,
+        "encryption": 0,
+        "data": {
+            "width": 8,
+            "height": 6,
+            "1.tlg": "#resource#0",
+            "2.tlg": "#resource#1",
+            "layers": [
+                {"layer_id": 1, "layer_type": 0, "name": "base", "left": 0, "top": 0, "width": 8, "height": 6, "opacity": 255, "type": 13, "visible": 1},
+                {"layer_id": 2, "layer_type": 0, "name": "mask", "left": 2, "top": 1, "width": 4, "height": 3, "opacity": 255, "type": 13, "visible": 1},
+            ],
+        },
+    })
+    .unwrap();
+    psb.resources_mut()
+        .push(tlg(base_version, TlgColorType::Bgra32, 8, 6, base.clone()));
+    psb.resources_mut()
+        .push(tlg(5, TlgColorType::Grayscale8, 4, 3, mask.clone()));
+    let open = |psb: VirtualPsbFixed| {
+        let mut writer = MemWriter::new();
+        psb.to_psb(false).finish_v4(&mut writer).unwrap();
+        PImg::new(
+            MemReader::new(writer.into_inner()),
+            "",
+            &ExtraConfig::default(),
+        )
+        .unwrap()
+    };
+    let pimg = open(psb);
+    let psd = dir.join("test.psd");
+    pimg.custom_export(&psd, Encoding::Utf8).unwrap();
+    let mut writer = MemWriter::new();
+    pimg.custom_import(
+        &psd.to_string_lossy(),
+        Box::new(&mut writer),
+        Encoding::Utf8,
+        Encoding::Utf8,
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let imported = PImg::new(
+        MemReader::new(writer.into_inner()),
+        "",
+        &ExtraConfig::default(),
+    )
+    .unwrap();
+    // Unchanged layers keep the original resources.
+    assert_eq!(imported.psb.resources(), pimg.psb.resources());
+
+    // Changed layers keep TLG version and color type.
+    for (resource, version, color) in [
+        (0, base_version, TlgColorType::Bgra32),
+        (1, 5, TlgColorType::Grayscale8),
+    ] {
+        let original = &pimg.psb.resources()[resource];
+        let mut img = PImg::decode_resource(original).unwrap();
+        img.data[0] ^= 0x10;
+        img.data[1] = img.data[0];
+        img.data[2] = img.data[0];
+        img.data[3] = 0xFF;
+        let encoded = pimg.encode_resource(original, img.clone()).unwrap();
+        let tlg = load_tlg(MemReaderRef::new(&encoded)).unwrap();
+        assert_eq!(tlg.version, version);
+        assert_eq!(tlg.color, color);
+        assert_eq!(PImg::decode_resource(&encoded).unwrap().data, img.data);
+    }
+
+    // Base layer is matched by name, and the renamed mask layer is matched by its id.
+    let dir = crate::utils::testing::temp_dir("pimg-edit");
+    let psd = dir.join("edited.psd");
+    let base_img = PImg::decode_resource(&pimg.psb.resources()[0]).unwrap();
+    let mut mask_img = PImg::decode_resource(&pimg.psb.resources()[1]).unwrap();
+    mask_img.data[4..8].copy_from_slice(&[0x99, 0x99, 0x99, 0xFF]);
+    let mut packed = Vec::new();
+    LayerNameSourceSetting { id: 2 }
+        .pack(&mut packed, true, Encoding::Utf8, &None)
+        .unwrap();
+    let mut writer = PsdWriter::new(8, 6, ImageColorType::Rgba, 8, Encoding::Utf8).unwrap();
+    writer.add_layer("base", 0, 0, base_img, None).unwrap();
+    writer
+        .add_layer(
+            "renamed",
+            2,
+            1,
+            mask_img.clone(),
+            Some(PsdLayerOption {
+                additional_info: vec![AdditionalLayerInfo {
+                    signature: *IMAGE_RESOURCE_SIGNATURE,
+                    key: *LAYER_NAME_SOURCE_SETTING_KEY,
+                    data: packed,
+                }],
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    let canvas = ImageData {
+        width: 8,
+        height: 6,
+        color_type: ImageColorType::Rgba,
+        depth: 8,
+        data: vec![0; 8 * 6 * 4],
+    };
+    writer
+        .save(canvas, std::fs::File::create(&psd).unwrap())
+        .unwrap();
+    let mut writer = MemWriter::new();
+    pimg.custom_import(
+        &psd.to_string_lossy(),
+        Box::new(&mut writer),
+        Encoding::Utf8,
+        Encoding::Utf8,
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let imported = PImg::new(
+        MemReader::new(writer.into_inner()),
+        "",
+        &ExtraConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(imported.psb.resources()[0], pimg.psb.resources()[0]);
+    assert_ne!(imported.psb.resources()[1], pimg.psb.resources()[1]);
+    let mask = &imported.psb.resources()[1];
+    assert_eq!(PImg::decode_resource(mask).unwrap().data, mask_img.data);
+    let tlg = load_tlg(MemReaderRef::new(mask)).unwrap();
+    assert_eq!(tlg.color, TlgColorType::Grayscale8);
+}
@@ -38,7 +38,7 @@ impl ScriptBuilder for PsbBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Psb::new(MemReader::new(buf), encoding, config)?))
     }
@@ -50,7 +50,7 @@ impl ScriptBuilder for PsbBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(Psb::new(reader, encoding, config)?))
     }
@@ -61,7 +61,7 @@ impl ScriptBuilder for PsbBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let file = std::fs::File::open(filename)?;
         let f = std::io::BufReader::new(file);
@@ -884,6 +884,7 @@ impl ECSImage for ECSExecutionImage {
                         messages.push(Message {
                             name: if name.is_empty() { None } else { Some(name) },
                             message,
+                            ..Default::default()
                         });
                     }
                 }
@@ -932,6 +933,7 @@ impl ECSImage for ECSExecutionImage {
                     messages.push(Message {
                         name: None,
                         message,
+                        ..Default::default()
                     });
                 }
                 string_stack.clear();
@@ -1074,6 +1076,7 @@ impl ECSImage for ECSExecutionImage {
                             .push(Message {
                                 name: if name.is_empty() { None } else { Some(name) },
                                 message,
+                                ..Default::default()
                             });
                     } else if func_name == "WitchWizard::SetCurrentScriptName" && arg_count == 2 {
                         if string_stack.is_empty() {
@@ -1139,6 +1142,7 @@ impl ECSImage for ECSExecutionImage {
                         .push(Message {
                             name: None,
                             message,
+                            ..Default::default()
                         });
                 }
                 string_stack.clear();
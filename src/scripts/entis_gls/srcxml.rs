@@ -33,7 +33,7 @@ impl ScriptBuilder for SrcXmlScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(SrcXmlScript::new(buf, encoding, config)?))
     }
@@ -128,7 +128,11 @@ impl Script for SrcXmlScript {
                                 let message = ins
                                     .get_attr_value(text_ref)?
                                     .ok_or(anyhow::anyhow!("text not found"))?;
-                                messages.push(Message { name, message })
+                                messages.push(Message {
+                                    name,
+                                    message,
+                                    ..Default::default()
+                                })
                             } else if ins.is_element("select") {
                                 for menu in ins.children.lock().unwrap().iter() {
                                     if menu.is_element("menu") {
@@ -157,6 +161,7 @@ impl Script for SrcXmlScript {
                                         messages.push(Message {
                                             name: None,
                                             message,
+                                            ..Default::default()
                                         });
                                     }
                                 }
@@ -37,7 +37,7 @@ impl ScriptBuilder for EscudeBinArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(EscudeBinArchive::new(
             MemReader::new(data),
@@ -52,7 +52,7 @@ impl ScriptBuilder for EscudeBinArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -79,7 +79,7 @@ impl ScriptBuilder for EscudeBinArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(EscudeBinArchive::new(
             reader,
@@ -107,6 +107,10 @@ impl ScriptBuilder for EscudeBinArchiveBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
@@ -32,7 +32,7 @@ impl ScriptBuilder for EscudeBinListBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(EscudeBinList::new(
             data, filename, encoding, config,
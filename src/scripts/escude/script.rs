@@ -46,7 +46,7 @@ impl ScriptBuilder for EscudeBinScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(EscudeBinScript::new(data, encoding, config)?))
     }
@@ -247,6 +247,7 @@ impl Script for EscudeBinScript {
             .map(|(i, s)| Message {
                 message: s.replace("<r>", "\n"),
                 name: self.names.as_ref().map(|n| n.get(&i).cloned()).flatten(),
+                ..Default::default()
             })
             .collect())
     }
@@ -3,8 +3,9 @@ use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
@@ -35,7 +36,7 @@ impl ScriptBuilder for ExHibitGrpArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(ExHibitGrpArchive::new(
             MemReader::new(data),
@@ -51,7 +52,7 @@ impl ScriptBuilder for ExHibitGrpArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             return Err(anyhow::anyhow!(
@@ -76,7 +77,7 @@ impl ScriptBuilder for ExHibitGrpArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(ExHibitGrpArchive::new(
             reader,
@@ -107,6 +108,27 @@ impl ScriptBuilder for ExHibitGrpArchiveBuilder {
     fn is_archive(&self) -> bool {
         true
     }
+
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let (toc_path, arc_index) =
+            locate_toc_file(Path::new(filename)).context("Failed to locate TOC file.")?;
+        let f = std::fs::File::create(filename)
+            .with_context(|| format!("Failed to create '{}'.", filename))?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(ExHibitGrpArchiveWriter::new(
+            writer, toc_path, arc_index, files, encoding, config,
+        )?))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -406,13 +428,10 @@ fn locate_toc_file(path: &Path) -> Result<(PathBuf, u32)> {
     ))
 }
 
-fn parse_toc_entries(
-    toc_path: &Path,
-    arc_index: u32,
-    archive_size: u64,
-) -> Result<Vec<GrpFileEntry>> {
-    let file = std::fs::File::open(toc_path)?;
-    let mut reader = std::io::BufReader::new(file);
+/// Finds the index of the archive in TOC.
+///
+/// Returns the offset of the index and the length of TOC.
+fn find_toc_index<R: Read + Seek>(reader: &mut R, arc_index: u32) -> Result<(u64, u64)> {
     let toc_len = reader.stream_length()?;
     if toc_len < 0x10 {
         return Err(anyhow::anyhow!("TOC file is too small."));
@@ -466,6 +485,17 @@ fn parse_toc_entries(
 
     let arc_offset =
         arc_offset.ok_or_else(|| anyhow::anyhow!("Archive reference not found in TOC."))?;
+    Ok((arc_offset, toc_len))
+}
+
+fn parse_toc_entries(
+    toc_path: &Path,
+    arc_index: u32,
+    archive_size: u64,
+) -> Result<Vec<GrpFileEntry>> {
+    let file = std::fs::File::open(toc_path)?;
+    let mut reader = std::io::BufReader::new(file);
+    let (arc_offset, toc_len) = find_toc_index(&mut reader, arc_index)?;
 
     reader.seek(SeekFrom::Start(arc_offset + 4))?;
     let start_index = reader.read_i32()?;
@@ -525,3 +555,163 @@ fn parse_toc_entries(
 
     Ok(entries)
 }
+
+#[derive(Clone, Debug, Default)]
+struct GrpWriterEntry {
+    offset: u32,
+    size: u32,
+    written: bool,
+}
+
+/// Writer for ExHibit GRP archives.
+///
+/// The entry table of the archive is stored in the TOC file, which is updated in place.
+/// Files are named by resource index (e.g. `00012.ogg`), like the names used when extracting.
+pub struct ExHibitGrpArchiveWriter<T: Write + Seek> {
+    writer: T,
+    toc_path: PathBuf,
+    table_offset: u64,
+    entries: Vec<GrpWriterEntry>,
+    indices: HashMap<String, usize>,
+}
+
+impl<T: Write + Seek> ExHibitGrpArchiveWriter<T> {
+    /// Creates a new writer.
+    ///
+    /// * `writer` - The writer to write the archive to.
+    /// * `toc_path` - The path of TOC file.
+    /// * `arc_index` - The index of the archive in TOC.
+    /// * `files` - The list of files to include in the archive.
+    /// * `encoding` - The encoding used for the archive filenames.
+    /// * `config` - Extra configuration options.
+    pub fn new(
+        writer: T,
+        toc_path: PathBuf,
+        arc_index: u32,
+        files: &[&str],
+        _encoding: Encoding,
+        _config: &ExtraConfig,
+    ) -> Result<Self> {
+        let file = std::fs::File::open(&toc_path)
+            .with_context(|| format!("Failed to open TOC '{}'.", toc_path.display()))?;
+        let mut reader = std::io::BufReader::new(file);
+        let (arc_offset, toc_len) = find_toc_index(&mut reader, arc_index)
+            .with_context(|| format!("Failed to parse TOC '{}'.", toc_path.display()))?;
+        reader.seek(SeekFrom::Start(arc_offset + 4))?;
+        let start_index = reader.read_u32()?;
+        reader.seek(SeekFrom::Start(arc_offset + 0xC))?;
+        let entry_count = reader.read_u32()?;
+        let table_offset = arc_offset + 0x10;
+        if table_offset + entry_count as u64 * 8 > toc_len {
+            return Err(anyhow::anyhow!("TOC entry table exceeds file size."));
+        }
+        let mut indices = HashMap::new();
+        for file in files {
+            let stem = file.rsplit_once('.').map_or(*file, |(stem, _)| stem);
+            let index = stem
+                .parse::<u32>()
+                .map_err(|_| anyhow::anyhow!("File name '{}' is not a resource index.", file))?;
+            if index < start_index || index - start_index >= entry_count {
+                return Err(anyhow::anyhow!(
+                    "Resource index {} of '{}' is out of range ({}..{}).",
+                    index,
+                    file,
+                    start_index,
+                    start_index as u64 + entry_count as u64
+                ));
+            }
+            indices.insert(file.to_string(), (index - start_index) as usize);
+        }
+        Ok(Self {
+            writer,
+            toc_path,
+            table_offset,
+            entries: vec![GrpWriterEntry::default(); entry_count as usize],
+            indices,
+        })
+    }
+}
+
+impl<T: Write + Seek> Archive for ExHibitGrpArchiveWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let index = *self
+            .indices
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("File '{}' not found in archive.", name))?;
+        let entry = &mut self.entries[index];
+        if entry.written {
+            return Err(anyhow::anyhow!(
+                "File '{}' already exists in archive.",
+                name
+            ));
+        }
+        entry.offset = u32::try_from(self.writer.seek(SeekFrom::End(0))?)
+            .map_err(|_| anyhow::anyhow!("Archive is too large."))?;
+        entry.written = true;
+        Ok(Box::new(GrpArchiveFile {
+            header: entry,
+            writer: &mut self.writer,
+            pos: 0,
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        let mut toc = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&self.toc_path)
+            .with_context(|| format!("Failed to open TOC '{}'.", self.toc_path.display()))?;
+        let mut table = MemWriter::new();
+        for entry in &self.entries {
+            table.write_u32(entry.offset)?;
+            table.write_u32(entry.size)?;
+        }
+        toc.seek(SeekFrom::Start(self.table_offset))?;
+        toc.write_all(table.as_slice())?;
+        Ok(())
+    }
+}
+
+/// A file in ExHibit GRP archive being written.
+pub struct GrpArchiveFile<'a, T: Write + Seek> {
+    header: &'a mut GrpWriterEntry,
+    writer: &'a mut T,
+    pos: u64,
+}
+
+impl<'a, T: Write + Seek> Write for GrpArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer
+            .seek(SeekFrom::Start(self.header.offset as u64 + self.pos))?;
+        let bytes = self.writer.write(buf)?;
+        self.pos += bytes as u64;
+        self.header.size = self.header.size.max(self.pos as u32);
+        Ok(bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for GrpArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i128,
+            SeekFrom::End(offset) => self.header.size as i128 + offset as i128,
+            SeekFrom::Current(offset) => self.pos as i128 + offset as i128,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek before entry start is not allowed.",
+            ));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
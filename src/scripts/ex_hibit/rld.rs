@@ -34,7 +34,7 @@ impl ScriptBuilder for RldScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(RldScript::new(buf, filename, encoding, config)?))
     }
@@ -415,6 +415,7 @@ impl Script for RldScript {
                 messages.push(Message {
                     name,
                     message: text,
+                    ..Default::default()
                 });
             } else if op.op == 21 || op.op == 191 {
                 eprintln!("{op:?}");
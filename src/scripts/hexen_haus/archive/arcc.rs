@@ -2,9 +2,10 @@
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
-use crate::utils::encoding::decode_to_string;
+use crate::utils::encoding::{decode_to_string, encode_string};
 use anyhow::Result;
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
@@ -34,7 +35,7 @@ impl ScriptBuilder for HexenHausArccArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(HexenHausArccArchive::new(
             MemReader::new(buf),
@@ -49,7 +50,7 @@ impl ScriptBuilder for HexenHausArccArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -75,7 +76,7 @@ impl ScriptBuilder for HexenHausArccArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(HexenHausArccArchive::new(
             reader,
@@ -103,6 +104,24 @@ impl ScriptBuilder for HexenHausArccArchiveBuilder {
     fn is_archive(&self) -> bool {
         true
     }
+
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(HexenHausArccArchiveWriter::new(
+            writer, files, encoding, config,
+        )?))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -345,6 +364,169 @@ impl<T: Read + Seek> Seek for Entry<T> {
     }
 }
 
+const FILE_HEADER_SIZE: u64 = 0x22;
+
+#[derive(Debug)]
+struct ArccWriterEntry {
+    offset: u64,
+    size: u32,
+    written: bool,
+}
+
+/// HexenHaus ARCC archive writer
+///
+/// Each file is stored in a `FILE` chunk, and the `ADDR` table of file offsets is written after
+/// all files. File names are encrypted in the same way as the original archives.
+/// Files which are not written have offset 0 in the `ADDR` table.
+pub struct HexenHausArccArchiveWriter<T: Write + Seek> {
+    writer: T,
+    entries: Vec<ArccWriterEntry>,
+    indices: HashMap<String, usize>,
+}
+
+impl<T: Write + Seek> HexenHausArccArchiveWriter<T> {
+    /// Creates a new `HexenHausArccArchiveWriter`
+    ///
+    /// * `writer` - The writer to write the archive to
+    /// * `files` - The list of files to include in the archive
+    /// * `encoding` - The encoding used for the archive filenames
+    /// * `config` - Extra configuration options
+    pub fn new(
+        mut writer: T,
+        files: &[&str],
+        encoding: Encoding,
+        _config: &ExtraConfig,
+    ) -> Result<Self> {
+        let file_count = files.len() as u32;
+        writer.write_all(b"ARCC")?;
+        writer.write_all(&[0; 0x10])?;
+        writer.write_u32(file_count)?;
+        writer.write_all(&[0; 0x12])?;
+        writer.write_all(b"NAME")?;
+        writer.write_u64(0)?; // Placeholder for ADDR offset
+        writer.write_u16(0)?;
+        for tag in [b"NIDX", b"EIDX"] {
+            writer.write_all(tag)?;
+            for i in 0..file_count {
+                writer.write_u16(0)?;
+                writer.write_u32(i)?;
+                writer.write_u16(0)?;
+            }
+        }
+        writer.write_all(b"CINF")?;
+        let mut entries = Vec::with_capacity(files.len());
+        let mut indices = HashMap::new();
+        for (i, file) in files.iter().enumerate() {
+            let mut name = encode_string(encoding, file, true)?;
+            let name_len = u16::try_from(name.len())
+                .map_err(|_| anyhow::anyhow!("File name '{}' is too long", file))?;
+            // XOR is symmetric.
+            decrypt_name(&mut name);
+            writer.write_all(&[0; 6])?;
+            writer.write_u16(name_len)?;
+            writer.write_u16(0)?;
+            writer.write_all(&name)?;
+            writer.write_u16(0)?;
+            entries.push(ArccWriterEntry {
+                offset: 0,
+                size: 0,
+                written: false,
+            });
+            indices.insert(file.to_string(), i);
+        }
+        Ok(HexenHausArccArchiveWriter {
+            writer,
+            entries,
+            indices,
+        })
+    }
+}
+
+impl<T: Write + Seek> Archive for HexenHausArccArchiveWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let index = *self
+            .indices
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("File '{}' not found in archive", name))?;
+        let entry = &mut self.entries[index];
+        if entry.written {
+            return Err(anyhow::anyhow!("File '{}' already exists in archive", name));
+        }
+        entry.offset = self.writer.seek(SeekFrom::End(0))?;
+        entry.written = true;
+        self.writer.write_all(b"FILE")?;
+        self.writer.write_all(&[0; FILE_HEADER_SIZE as usize - 4])?;
+        Ok(Box::new(ArccArchiveFile {
+            header: entry,
+            writer: &mut self.writer,
+            pos: 0,
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        for entry in self.entries.iter().filter(|e| e.written) {
+            self.writer.seek(SeekFrom::Start(entry.offset + 0x18))?;
+            self.writer.write_u32(entry.size)?;
+        }
+        let addr_offset = self.writer.seek(SeekFrom::End(0))?;
+        self.writer.write_all(b"ADDR")?;
+        for entry in &self.entries {
+            self.writer.write_u16(0)?;
+            self.writer.write_u64(entry.offset)?;
+            self.writer.write_u16(0)?;
+        }
+        self.writer.seek(SeekFrom::Start(0x2e))?;
+        self.writer.write_u64(addr_offset)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// HexenHaus ARCC archive file
+pub struct ArccArchiveFile<'a, T: Write + Seek> {
+    header: &'a mut ArccWriterEntry,
+    writer: &'a mut T,
+    pos: u64,
+}
+
+impl<'a, T: Write + Seek> Write for ArccArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.seek(SeekFrom::Start(
+            self.header.offset + FILE_HEADER_SIZE + self.pos,
+        ))?;
+        let bytes_written = self.writer.write(buf)?;
+        self.pos += bytes_written as u64;
+        self.header.size = self.header.size.max(self.pos as u32);
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for ArccArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.header.size as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek before start",
+            ));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
 fn decrypt_name(buf: &mut [u8]) {
     for byte in buf.iter_mut() {
         *byte ^= 0x69;
@@ -3,7 +3,8 @@ use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use anyhow::{Result, anyhow};
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
 const ODIO_SIGNATURE: &[u8; 4] = b"ODIO";
@@ -40,7 +41,7 @@ impl ScriptBuilder for HexenHausOdioArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(HexenHausOdioArchive::new(
             MemReader::new(buf),
@@ -55,7 +56,7 @@ impl ScriptBuilder for HexenHausOdioArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -81,7 +82,7 @@ impl ScriptBuilder for HexenHausOdioArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(HexenHausOdioArchive::new(
             reader,
@@ -109,6 +110,24 @@ impl ScriptBuilder for HexenHausOdioArchiveBuilder {
     fn is_archive(&self) -> bool {
         true
     }
+
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(HexenHausOdioArchiveWriter::new(
+            writer, files, encoding, config,
+        )?))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -425,3 +444,196 @@ impl<T: Read + Seek> Seek for OdioEntry<T> {
         Ok(self.pos)
     }
 }
+
+#[derive(Debug)]
+struct OdioWriterEntry {
+    index: usize,
+    offset: u64,
+    size: u64,
+    written: bool,
+}
+
+/// HexenHaus ODIO archive writer
+///
+/// Files are stored without encryption. Files named by a number (e.g. `0012.ogg`) are stored
+/// at that index, other files are placed after them.
+///
+/// The index only contains offsets, so files must be written in index order.
+/// Missing indices are stored as empty files.
+pub struct HexenHausOdioArchiveWriter<T: Write + Seek> {
+    writer: T,
+    headers: HashMap<String, OdioWriterEntry>,
+    entry_count: usize,
+    last_index: Option<usize>,
+}
+
+impl<T: Write + Seek> HexenHausOdioArchiveWriter<T> {
+    /// Creates a new `HexenHausOdioArchiveWriter`
+    ///
+    /// * `writer` - The writer to write the archive to
+    /// * `files` - The list of files to include in the archive
+    /// * `encoding` - The encoding used for the archive filenames
+    /// * `config` - Extra configuration options
+    pub fn new(
+        mut writer: T,
+        files: &[&str],
+        _encoding: Encoding,
+        _config: &ExtraConfig,
+    ) -> Result<Self> {
+        let mut headers = HashMap::new();
+        let mut others = Vec::new();
+        let mut used = HashSet::new();
+        let mut entry_count = 0;
+        for file in files {
+            let stem = file.rsplit_once('.').map_or(*file, |(stem, _)| stem);
+            match stem.parse::<usize>() {
+                Ok(index) if used.insert(index) => {
+                    entry_count = entry_count.max(index + 1);
+                    headers.insert(
+                        file.to_string(),
+                        OdioWriterEntry {
+                            index,
+                            offset: 0,
+                            size: 0,
+                            written: false,
+                        },
+                    );
+                }
+                _ => others.push(file.to_string()),
+            }
+        }
+        for file in others {
+            headers.insert(
+                file,
+                OdioWriterEntry {
+                    index: entry_count,
+                    offset: 0,
+                    size: 0,
+                    written: false,
+                },
+            );
+            entry_count += 1;
+        }
+        if entry_count == 0 {
+            return Err(anyhow!("ODIO archive must contain at least one file"));
+        }
+        writer.write_all(ODIO_SIGNATURE)?;
+        writer.write_u32(0)?;
+        writer.write_u16(0)?;
+        writer.write_u32(HEADER_CHECK_VALUE)?;
+        writer.write_u32(0)?;
+        writer.write_all(&vec![0; entry_count * INDEX_ENTRY_SIZE as usize])?;
+        Ok(HexenHausOdioArchiveWriter {
+            writer,
+            headers,
+            entry_count,
+            last_index: None,
+        })
+    }
+}
+
+impl<T: Write + Seek> Archive for HexenHausOdioArchiveWriter<T> {
+    fn prelist<'a>(&'a self) -> Result<Option<Box<dyn Iterator<Item = Result<String>> + 'a>>> {
+        // Sizes are computed from offsets, so files must be written in index order.
+        let mut files: Vec<_> = self.headers.iter().collect();
+        files.sort_by_key(|(_, e)| e.index);
+        Ok(Some(Box::new(
+            files.into_iter().map(|(name, _)| Ok(name.clone())),
+        )))
+    }
+
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let entry = self
+            .headers
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("File '{}' not found in archive", name))?;
+        if entry.written {
+            return Err(anyhow!("File '{}' already exists in archive", name));
+        }
+        if self.last_index.is_some_and(|last| last > entry.index) {
+            return Err(anyhow!(
+                "File '{}' is not written in index order of ODIO archive",
+                name
+            ));
+        }
+        self.last_index = Some(entry.index);
+        entry.offset = self.writer.seek(SeekFrom::End(0))?;
+        entry.written = true;
+        Ok(Box::new(OdioArchiveFile {
+            header: entry,
+            writer: &mut self.writer,
+            pos: 0,
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let file_len = self.writer.seek(SeekFrom::End(0))?;
+        let mut offsets = vec![None; self.entry_count];
+        for entry in self.headers.values() {
+            if entry.written {
+                offsets[entry.index] = Some(entry.offset);
+            }
+        }
+        // Empty entries share the offset of the next entry.
+        let mut next = file_len;
+        for offset in offsets.iter_mut().rev() {
+            match offset {
+                Some(offset) => next = *offset,
+                None => *offset = Some(next),
+            }
+        }
+        self.writer.seek(SeekFrom::Start(INDEX_START))?;
+        for offset in offsets {
+            let offset = offset.unwrap_or(file_len);
+            let offset = u32::try_from(offset).map_err(|_| anyhow!("ODIO archive is too large"))?;
+            self.writer.write_u32(offset)?;
+            self.writer.write_u16(0)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// HexenHaus ODIO archive file
+pub struct OdioArchiveFile<'a, T: Write + Seek> {
+    header: &'a mut OdioWriterEntry,
+    writer: &'a mut T,
+    pos: u64,
+}
+
+impl<'a, T: Write + Seek> Write for OdioArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer
+            .seek(SeekFrom::Start(self.header.offset + self.pos))?;
+        let bytes_written = self.writer.write(buf)?;
+        self.pos += bytes_written as u64;
+        self.header.size = self.header.size.max(self.pos);
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for OdioArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.header.size as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek before start",
+            ));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
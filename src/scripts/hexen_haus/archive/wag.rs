@@ -2,10 +2,11 @@
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
-use crate::utils::encoding::decode_to_string;
+use crate::utils::encoding::{decode_to_string, encode_string};
 use anyhow::{Result, anyhow};
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
 const WAG_SIGNATURE: &[u8; 4] = b"IAF_";
@@ -41,7 +42,7 @@ impl ScriptBuilder for HexenHausWagArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(HexenHausWagArchive::new(
             MemReader::new(buf),
@@ -56,7 +57,7 @@ impl ScriptBuilder for HexenHausWagArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -82,7 +83,7 @@ impl ScriptBuilder for HexenHausWagArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(HexenHausWagArchive::new(
             reader,
@@ -110,6 +111,24 @@ impl ScriptBuilder for HexenHausWagArchiveBuilder {
     fn is_archive(&self) -> bool {
         true
     }
+
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(HexenHausWagArchiveWriter::new(
+            writer, files, encoding, config,
+        )?))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -508,3 +527,182 @@ fn read_decrypted_exact<T: Read + Seek>(
     }
     Ok(())
 }
+
+/// Size of the IMGD header which is not counted in the size of IMGD section.
+const IMAGE_HEADER_SIZE: u32 = 0x10;
+
+#[derive(Debug)]
+struct WagWriterEntry {
+    name: Vec<u8>,
+    offset: u64,
+    data_offset: u64,
+    size: u64,
+    head: Vec<u8>,
+    written: bool,
+}
+
+/// HexenHaus WAG archive writer
+///
+/// Only IMGD images can be stored, because the archive stores files as image sections.
+/// Each file contains a name section and an image section, which are encrypted like the offset
+/// table. Files which are not written have offset 0 in the offset table.
+pub struct HexenHausWagArchiveWriter<T: Write + Seek> {
+    writer: T,
+    entries: Vec<WagWriterEntry>,
+    indices: HashMap<String, usize>,
+}
+
+impl<T: Write + Seek> HexenHausWagArchiveWriter<T> {
+    /// Creates a new `HexenHausWagArchiveWriter`
+    ///
+    /// * `writer` - The writer to write the archive to
+    /// * `files` - The list of files to include in the archive
+    /// * `encoding` - The encoding used for the archive filenames
+    /// * `config` - Extra configuration options
+    pub fn new(
+        mut writer: T,
+        files: &[&str],
+        encoding: Encoding,
+        _config: &ExtraConfig,
+    ) -> Result<Self> {
+        writer.write_all(WAG_SIGNATURE)?;
+        writer.write_u16(0)?;
+        writer.write_u32(files.len() as u32)?;
+        writer.write_all(&vec![0; OFFSET_TABLE_START as usize - 10 + files.len() * 4])?;
+        let mut entries = Vec::with_capacity(files.len());
+        let mut indices = HashMap::new();
+        for (i, file) in files.iter().enumerate() {
+            entries.push(WagWriterEntry {
+                name: encode_string(encoding, file, true)?,
+                offset: 0,
+                data_offset: 0,
+                size: 0,
+                head: Vec::new(),
+                written: false,
+            });
+            indices.insert(file.to_string(), i);
+        }
+        Ok(HexenHausWagArchiveWriter {
+            writer,
+            entries,
+            indices,
+        })
+    }
+}
+
+impl<T: Write + Seek> Archive for HexenHausWagArchiveWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let index = *self
+            .indices
+            .get(name)
+            .ok_or_else(|| anyhow!("File '{}' not found in archive", name))?;
+        let entry = &mut self.entries[index];
+        if entry.written {
+            return Err(anyhow!("File '{}' already exists in archive", name));
+        }
+        entry.offset = self.writer.seek(SeekFrom::End(0))?;
+        entry.written = true;
+        let mut header = MemWriter::new();
+        header.write_u32(DATA_SIGNATURE)?;
+        // Name section and image section
+        header.write_u32(2)?;
+        header.write_u16(0)?;
+        header.write_u32(SECTION_NAME)?;
+        header.write_u32(entry.name.len() as u32 + 2)?;
+        header.write_u16(0)?;
+        header.write_all(&entry.name)?;
+        header.write_u16(0)?;
+        write_encrypted(&mut self.writer, header.as_slice())?;
+        entry.data_offset = self.writer.stream_position()?;
+        Ok(Box::new(WagArchiveFile {
+            header: entry,
+            writer: &mut self.writer,
+            pos: 0,
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let mut offsets = MemWriter::new();
+        for entry in &self.entries {
+            if !entry.written {
+                offsets.write_u32(0)?;
+                continue;
+            }
+            if entry.size < IMAGE_HEADER_SIZE as u64 || !entry.head.starts_with(b"IMGD") {
+                return Err(anyhow!(
+                    "File '{}' is not an IMGD image",
+                    String::from_utf8_lossy(&entry.name)
+                ));
+            }
+            let offset =
+                u32::try_from(entry.offset).map_err(|_| anyhow!("WAG archive is too large"))?;
+            offsets.write_u32(offset)?;
+            // The size of image section is stored in the IMGD header.
+            let image_size = u32::try_from(entry.size - IMAGE_HEADER_SIZE as u64)
+                .map_err(|_| anyhow!("IMGD image is too large"))?;
+            self.writer.seek(SeekFrom::Start(entry.data_offset + 4))?;
+            write_encrypted(&mut self.writer, &image_size.to_le_bytes())?;
+        }
+        self.writer.seek(SeekFrom::Start(OFFSET_TABLE_START))?;
+        write_encrypted(&mut self.writer, offsets.as_slice())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// HexenHaus WAG archive file
+pub struct WagArchiveFile<'a, T: Write + Seek> {
+    header: &'a mut WagWriterEntry,
+    writer: &'a mut T,
+    pos: u64,
+}
+
+impl<'a, T: Write + Seek> Write for WagArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer
+            .seek(SeekFrom::Start(self.header.data_offset + self.pos))?;
+        write_encrypted(&mut self.writer, buf)?;
+        if self.pos < 4 {
+            let end = (self.pos as usize + buf.len()).min(4);
+            if self.header.head.len() < end {
+                self.header.head.resize(end, 0);
+            }
+            self.header.head[self.pos as usize..end]
+                .copy_from_slice(&buf[..end - self.pos as usize]);
+        }
+        self.pos += buf.len() as u64;
+        self.header.size = self.header.size.max(self.pos);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for WagArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.header.size as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek before start",
+            ));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
+fn write_encrypted<T: Write>(writer: &mut T, data: &[u8]) -> std::io::Result<()> {
+    let encrypted: Vec<u8> = data.iter().map(|byte| byte.rotate_left(4)).collect();
+    writer.write_all(&encrypted)
+}
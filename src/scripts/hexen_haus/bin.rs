@@ -30,7 +30,7 @@ impl ScriptBuilder for BinScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(BinScript::new(buf, encoding, config)?))
     }
@@ -167,11 +167,13 @@ impl Script for BinScript {
                 Message {
                     name: Some(name.to_string()),
                     message: mes.to_string(),
+                    ..Default::default()
                 }
             } else {
                 Message {
                     name: None,
                     message: str.str.clone(),
+                    ..Default::default()
                 }
             };
             messages.push(message);
@@ -27,6 +27,8 @@ pub struct ArchiveItem {
     pub original_size: u64,
     pub archived_size: u64,
     pub segments: Vec<Segment>,
+    /// Whether the file's contents are encrypted.
+    pub is_encrypted: bool,
 }
 
 #[derive(Debug, Clone)]
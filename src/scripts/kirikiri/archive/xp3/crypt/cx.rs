@@ -450,6 +450,14 @@ impl Crypt for Arc<CxEncryption> {
     fn decrypt_seek_supported(&self) -> bool {
         true
     }
+    fn encrypt_supported(&self) -> bool {
+        true
+    }
+    fn encrypt(&self, entry: &Xp3Entry, offset: u64, buf: &mut [u8]) -> Result<()> {
+        // CxEncryption only XORs data with a keystream, so encrypting is the same as decrypting.
+        let count = buf.len();
+        self.inner_decrypt(entry.file_hash, offset, buf, 0, count)
+    }
     fn decrypt<'a>(
         &self,
         entry: &Xp3Entry,
@@ -56,6 +56,26 @@ fn default_read_name<'a>(reader: &mut Box<dyn Read + 'a>) -> Result<(String, u64
     ))
 }
 
+/// Encrypts data with a crypt whose decryption is only a XOR with a keystream,
+/// which means decrypting plain data is the same as encrypting it.
+fn symmetric_encrypt<C: Crypt + ?Sized>(
+    crypt: &C,
+    entry: &Xp3Entry,
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<()> {
+    let seg = Segment {
+        is_compressed: false,
+        start: 0,
+        offset_in_file: offset,
+        original_size: buf.len() as u64,
+        archived_size: buf.len() as u64,
+    };
+    let mut reader = crypt.decrypt(entry, &seg, Box::new(MemReader::new(buf.to_vec())))?;
+    reader.read_exact(buf)?;
+    Ok(())
+}
+
 pub trait Crypt: std::fmt::Debug {
     #[allow(dead_code)]
     /// whether Adler32 checksum should be calculated after contents have been encrypted.
@@ -112,6 +132,20 @@ pub trait Crypt: std::fmt::Debug {
         false
     }
 
+    /// Returns true if this crypt support encrypt
+    fn encrypt_supported(&self) -> bool {
+        false
+    }
+
+    /// Encrypts the given buffer in place for the specified entry.
+    ///
+    /// * `entry` - The entry which the data belongs to. `file_hash` must be already set.
+    /// * `offset` - The offset of the buffer within the original file.
+    /// * `buf` - The plain data to encrypt.
+    fn encrypt(&self, _entry: &Xp3Entry, _offset: u64, _buf: &mut [u8]) -> Result<()> {
+        Err(anyhow::anyhow!("This crypt does not support encrypt"))
+    }
+
     /// Determine whether the file with the given name and content need to be extra processed after decryption. (e.g. extra decryption by file type)
     fn need_filter(&self, _filename: &str, _buf: &[u8], _buf_len: usize) -> bool {
         false
@@ -710,8 +744,28 @@ macro_rules! base_schema2_impl {
     };
 }
 
+macro_rules! encrypt_impl {
+    () => {};
+    (symmetric) => {
+        fn encrypt_supported(&self) -> bool {
+            true
+        }
+        fn encrypt(&self, entry: &Xp3Entry, offset: u64, buf: &mut [u8]) -> Result<()> {
+            symmetric_encrypt(self, entry, offset, buf)
+        }
+    };
+    (inherent) => {
+        fn encrypt_supported(&self) -> bool {
+            true
+        }
+        fn encrypt(&self, entry: &Xp3Entry, offset: u64, buf: &mut [u8]) -> Result<()> {
+            self.encrypt_data(entry, offset, buf)
+        }
+    };
+}
+
 macro_rules! seek_crypt_base_impl {
-    ($crypt:ident, $reader:ident) => {
+    ($crypt:ident, $reader:ident $(, $sym:ident)?) => {
         #[derive(Debug)]
         pub struct $crypt {
             base: BaseSchema,
@@ -723,6 +777,7 @@ macro_rules! seek_crypt_base_impl {
         }
         impl Crypt for $crypt {
             base_schema_impl!();
+            encrypt_impl!($($sym)?);
             fn decrypt_supported(&self) -> bool {
                 true
             }
@@ -750,13 +805,13 @@ macro_rules! seek_crypt_base_impl {
 }
 
 macro_rules! seek_crypt_impl {
-    ($crypt:ident, $reader:ident<$t:ident>) => {
-        seek_crypt_base_impl!($crypt, $reader);
+    ($crypt:ident, $reader:ident<$t:ident> $(, $sym:ident)?) => {
+        seek_crypt_base_impl!($crypt, $reader $(, $sym)?);
         seek_reader_impl!($reader<$t>);
     };
 }
 
-seek_crypt_impl!(FateCrypt, FateCryptReader<T>);
+seek_crypt_impl!(FateCrypt, FateCryptReader<T>, symmetric);
 
 impl<R: Read> Read for FateCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -777,7 +832,26 @@ impl<R: Read> Read for FateCryptReader<R> {
     }
 }
 
-seek_crypt_impl!(MizukakeCrypt, MizukakeCryptReader<T>);
+seek_crypt_impl!(MizukakeCrypt, MizukakeCryptReader<T>, inherent);
+
+impl MizukakeCrypt {
+    fn encrypt_data(&self, _entry: &Xp3Entry, offset: u64, buf: &mut [u8]) -> Result<()> {
+        for (i, t) in buf.iter_mut().enumerate() {
+            let tpos = offset + i as u64;
+            if tpos == 0x83 {
+                *t ^= 3;
+            }
+            if tpos == 0x3F82 {
+                *t ^= 1;
+            }
+            *t ^= 0xb6;
+            if tpos == 0x103 {
+                *t = (*t).wrapping_add(1);
+            }
+        }
+        Ok(())
+    }
+}
 
 impl<R: Read> Read for MizukakeCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -801,7 +875,7 @@ impl<R: Read> Read for MizukakeCryptReader<R> {
 }
 
 macro_rules! seek_crypt_filehash_key_u8_base_impl {
-    ($crypt:ident, $reader:ident) => {
+    ($crypt:ident, $reader:ident $(, $sym:ident)?) => {
         #[derive(Debug)]
         pub struct $crypt {
             base: BaseSchema,
@@ -813,6 +887,7 @@ macro_rules! seek_crypt_filehash_key_u8_base_impl {
         }
         impl Crypt for $crypt {
             base_schema_impl!();
+            encrypt_impl!($($sym)?);
             fn decrypt_supported(&self) -> bool {
                 true
             }
@@ -848,13 +923,13 @@ macro_rules! seek_crypt_filehash_key_u8_base_impl {
 }
 
 macro_rules! seek_crypt_filehash_key_u8_impl {
-    ($crypt:ident,$reader:ident<$t:ident>) => {
-        seek_crypt_filehash_key_u8_base_impl!($crypt, $reader);
+    ($crypt:ident,$reader:ident<$t:ident> $(, $sym:ident)?) => {
+        seek_crypt_filehash_key_u8_base_impl!($crypt, $reader $(, $sym)?);
         seek_reader_key_impl!($reader<$t>, u8);
     };
 }
 
-seek_crypt_filehash_key_u8_impl!(HashCrypt, HashCryptReader<T>);
+seek_crypt_filehash_key_u8_impl!(HashCrypt, HashCryptReader<T>, symmetric);
 
 impl<R: Read> Read for HashCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -868,7 +943,7 @@ impl<R: Read> Read for HashCryptReader<R> {
 }
 
 macro_rules! seek_crypt_key_base_impl {
-    ($crypt:ident, $reader:ident, $typ:ty) => {
+    ($crypt:ident, $reader:ident, $typ:ty $(, $sym:ident)?) => {
         #[derive(Debug)]
         pub struct $crypt {
             base: BaseSchema,
@@ -881,6 +956,7 @@ macro_rules! seek_crypt_key_base_impl {
         }
         impl Crypt for $crypt {
             base_schema_impl!();
+            encrypt_impl!($($sym)?);
             fn decrypt_supported(&self) -> bool {
                 true
             }
@@ -908,13 +984,13 @@ macro_rules! seek_crypt_key_base_impl {
 }
 
 macro_rules! seek_crypt_key_impl {
-    ($crypt:ident, $reader:ident<$t:ident>, $typ:ty) => {
-        seek_crypt_key_base_impl!($crypt, $reader, $typ);
+    ($crypt:ident, $reader:ident<$t:ident>, $typ:ty $(, $sym:ident)?) => {
+        seek_crypt_key_base_impl!($crypt, $reader, $typ $(, $sym)?);
         seek_reader_key_impl!($reader<$t>, $typ);
     };
 }
 
-seek_crypt_key_impl!(XorCrypt, XorCryptReader<T>, u8);
+seek_crypt_key_impl!(XorCrypt, XorCryptReader<T>, u8, symmetric);
 
 impl<R: Read> Read for XorCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -958,6 +1034,17 @@ impl Crypt for FlyingShineCrypt {
     fn decrypt_seek_supported(&self) -> bool {
         true
     }
+    fn encrypt_supported(&self) -> bool {
+        true
+    }
+    fn encrypt(&self, entry: &Xp3Entry, _offset: u64, buf: &mut [u8]) -> Result<()> {
+        let (xor, shift) = self.adjust(entry.file_hash);
+        for t in buf.iter_mut() {
+            *t = t.rotate_left(shift);
+            *t ^= xor;
+        }
+        Ok(())
+    }
     fn decrypt<'a>(
         &self,
         entry: &Xp3Entry,
@@ -1000,7 +1087,7 @@ impl<R: Read> Read for FlyingShineCryptReader<R> {
 }
 
 macro_rules! seek_crypt_filehash_key_base_impl {
-    ($crypt:ident, $reader:ident) => {
+    ($crypt:ident, $reader:ident $(, $sym:ident)?) => {
         #[derive(Debug)]
         pub struct $crypt {
             base: BaseSchema,
@@ -1012,6 +1099,7 @@ macro_rules! seek_crypt_filehash_key_base_impl {
         }
         impl Crypt for $crypt {
             base_schema_impl!();
+            encrypt_impl!($($sym)?);
             fn decrypt_supported(&self) -> bool {
                 true
             }
@@ -1039,8 +1127,8 @@ macro_rules! seek_crypt_filehash_key_base_impl {
 }
 
 macro_rules! seek_crypt_filehash_key_impl {
-    ($crypt:ident,$reader:ident<$t:ident>) => {
-        seek_crypt_filehash_key_base_impl!($crypt, $reader);
+    ($crypt:ident,$reader:ident<$t:ident> $(, $sym:ident)?) => {
+        seek_crypt_filehash_key_base_impl!($crypt, $reader $(, $sym)?);
         seek_reader_key_impl!($reader<$t>, u32);
     };
 }
@@ -1074,7 +1162,7 @@ impl<R: Read> Read for SeitenCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(OkibaCrypt, OkibaCryptReader<T>);
+seek_crypt_filehash_key_impl!(OkibaCrypt, OkibaCryptReader<T>, symmetric);
 
 impl<R: Read> Read for OkibaCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1111,7 +1199,7 @@ impl<R: Read> Read for OkibaCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_u8_impl!(DieselmineCrypt, DieselmineCryptReader<T>);
+seek_crypt_filehash_key_u8_impl!(DieselmineCrypt, DieselmineCryptReader<T>, symmetric);
 
 impl<R: Read> Read for DieselmineCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1135,7 +1223,7 @@ impl<R: Read> Read for DieselmineCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_u8_impl!(DameganeCrypt, DameganeCryptReader<T>);
+seek_crypt_filehash_key_u8_impl!(DameganeCrypt, DameganeCryptReader<T>, symmetric);
 
 impl<R: Read> Read for DameganeCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1154,7 +1242,7 @@ impl<R: Read> Read for DameganeCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_u8_impl!(NephriteCrypt, NephriteCryptReader<T>);
+seek_crypt_filehash_key_u8_impl!(NephriteCrypt, NephriteCryptReader<T>, symmetric);
 
 impl<R: Read> Read for NephriteCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1173,7 +1261,7 @@ impl<R: Read> Read for NephriteCryptReader<R> {
     }
 }
 
-seek_crypt_impl!(AlteredPinkCrypt, AlteredPinkCryptReader<T>);
+seek_crypt_impl!(AlteredPinkCrypt, AlteredPinkCryptReader<T>, symmetric);
 
 impl<R: Read> Read for AlteredPinkCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1187,7 +1275,7 @@ impl<R: Read> Read for AlteredPinkCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(NatsupochiCrypt, NatsupochiCryptReader<T>);
+seek_crypt_filehash_key_impl!(NatsupochiCrypt, NatsupochiCryptReader<T>, symmetric);
 
 impl<R: Read> Read for NatsupochiCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1201,7 +1289,7 @@ impl<R: Read> Read for NatsupochiCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(PoringSoftCrypt, PoringSoftCryptReader<T>);
+seek_crypt_filehash_key_impl!(PoringSoftCrypt, PoringSoftCryptReader<T>, symmetric);
 
 impl<R: Read> Read for PoringSoftCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1215,7 +1303,7 @@ impl<R: Read> Read for PoringSoftCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(AppliqueCrypt, AppliqueCryptReader<T>);
+seek_crypt_filehash_key_impl!(AppliqueCrypt, AppliqueCryptReader<T>, symmetric);
 
 impl<R: Read> Read for AppliqueCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1316,7 +1404,7 @@ impl<R: Read> Read for TokidokiCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(SourireCrypt, SourireCryptReader<T>);
+seek_crypt_filehash_key_impl!(SourireCrypt, SourireCryptReader<T>, symmetric);
 
 impl<R: Read> Read for SourireCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1330,7 +1418,7 @@ impl<R: Read> Read for SourireCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(HibikiCrypt, HibikiCryptReader<T>);
+seek_crypt_filehash_key_impl!(HibikiCrypt, HibikiCryptReader<T>, symmetric);
 
 impl<R: Read> Read for HibikiCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1382,6 +1470,12 @@ impl Crypt for AkabeiCrypt {
     fn decrypt_seek_supported(&self) -> bool {
         true
     }
+    fn encrypt_supported(&self) -> bool {
+        true
+    }
+    fn encrypt(&self, entry: &Xp3Entry, offset: u64, buf: &mut [u8]) -> Result<()> {
+        symmetric_encrypt(self, entry, offset, buf)
+    }
     fn decrypt<'a>(
         &self,
         entry: &Xp3Entry,
@@ -1422,7 +1516,7 @@ impl<R: Read> Read for AkabeiCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(HaikuoCrypt, HaikuoCryptReader<T>);
+seek_crypt_filehash_key_impl!(HaikuoCrypt, HaikuoCryptReader<T>, symmetric);
 
 impl<R: Read> Read for HaikuoCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1436,7 +1530,17 @@ impl<R: Read> Read for HaikuoCryptReader<R> {
     }
 }
 
-seek_crypt_key_impl!(StripeCrypt, StripeCryptReader<T>, u8);
+seek_crypt_key_impl!(StripeCrypt, StripeCryptReader<T>, u8, inherent);
+
+impl StripeCrypt {
+    fn encrypt_data(&self, _entry: &Xp3Entry, _offset: u64, buf: &mut [u8]) -> Result<()> {
+        for t in buf.iter_mut() {
+            w!(*t -= 1);
+            *t ^= self.key;
+        }
+        Ok(())
+    }
+}
 
 impl<R: Read> Read for StripeCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1450,7 +1554,7 @@ impl<R: Read> Read for StripeCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(ExaCrypt, ExaCryptReader<T>);
+seek_crypt_filehash_key_impl!(ExaCrypt, ExaCryptReader<T>, symmetric);
 
 impl<R: Read> Read for ExaCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1492,6 +1596,12 @@ impl Crypt for SmileCrypt {
     fn decrypt_seek_supported(&self) -> bool {
         true
     }
+    fn encrypt_supported(&self) -> bool {
+        true
+    }
+    fn encrypt(&self, entry: &Xp3Entry, offset: u64, buf: &mut [u8]) -> Result<()> {
+        symmetric_encrypt(self, entry, offset, buf)
+    }
     fn decrypt<'a>(
         &self,
         entry: &Xp3Entry,
@@ -1544,7 +1654,7 @@ impl<R: Read> Read for SmileCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(YuzuCrypt, YuzuCryptReader<T>);
+seek_crypt_filehash_key_impl!(YuzuCrypt, YuzuCryptReader<T>, symmetric);
 
 impl<R: Read> Read for YuzuCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1562,7 +1672,7 @@ impl<R: Read> Read for YuzuCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_u8_impl!(HighRunningCrypt, HighRunningCryptReader<T>);
+seek_crypt_filehash_key_u8_impl!(HighRunningCrypt, HighRunningCryptReader<T>, symmetric);
 
 impl<R: Read> Read for HighRunningCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -1861,6 +1971,12 @@ impl Crypt for MadoCrypt {
     fn decrypt_seek_supported(&self) -> bool {
         true
     }
+    fn encrypt_supported(&self) -> bool {
+        true
+    }
+    fn encrypt(&self, entry: &Xp3Entry, offset: u64, buf: &mut [u8]) -> Result<()> {
+        symmetric_encrypt(self, entry, offset, buf)
+    }
     fn decrypt<'a>(
         &self,
         entry: &Xp3Entry,
@@ -1997,7 +2113,7 @@ impl<R: Read> Read for SmxCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(FestivalCrypt, FestivalCryptReader<T>);
+seek_crypt_filehash_key_impl!(FestivalCrypt, FestivalCryptReader<T>, symmetric);
 
 impl<R: Read> Read for FestivalCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -2036,7 +2152,7 @@ impl<R: Read> Read for PinPointCryptReader<R> {
     }
 }
 
-seek_crypt_filehash_key_impl!(HybridCrypt, HybridCryptReader<T>);
+seek_crypt_filehash_key_impl!(HybridCrypt, HybridCryptReader<T>, symmetric);
 
 impl<R: Read> Read for HybridCryptReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -2590,3 +2706,56 @@ fn test_cx_cb_table() {
 fn test_altered_pink_key_table() {
     assert_eq!(ALTERED_PINK_KEY_TABLE.len(), 0x100);
 }
+
+#[test]
+fn test_encrypt_roundtrip() {
+    let data: Vec<u8> = (0..0x5000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+    let entry = Xp3Entry {
+        name: "test.ks".to_string(),
+        flags: super::consts::TVP_XP3_FILE_PROTECTED,
+        file_hash: 0x9E3779B9,
+        original_size: data.len() as u64,
+        archived_size: data.len() as u64,
+        timestamp: None,
+        segments: Vec::new(),
+        extras: Vec::new(),
+        extra: None,
+    };
+    let config = ExtraConfig::default();
+    for game in [
+        "Boku no Mirai wa, Koi to Kakin to",
+        "Aibo Nyuujoku",
+        "Aa Mama ni Naru!",
+        "Boku to Koi Suru Ponkotsu Akuma.",
+        "Hentai Keimusho 24-ji",
+        "11eyes -Resona Forma-",
+        "Specialite!",
+    ] {
+        let crypt = query_crypt_schema(game)
+            .expect("game not found")
+            .create_crypt("test.xp3", &config)
+            .unwrap();
+        assert!(
+            crypt.encrypt_supported(),
+            "{} does not support encrypt",
+            game
+        );
+        let mut encrypted = data.clone();
+        crypt.encrypt(&entry, 0, &mut encrypted).unwrap();
+        assert_ne!(encrypted, data, "{} does not change data", game);
+        let seg = Segment {
+            is_compressed: false,
+            start: 0,
+            offset_in_file: 0,
+            original_size: data.len() as u64,
+            archived_size: data.len() as u64,
+        };
+        let mut decrypted = Vec::new();
+        crypt
+            .decrypt(&entry, &seg, Box::new(MemReader::new(encrypted)))
+            .unwrap()
+            .read_to_end(&mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted, data, "{} failed to roundtrip", game);
+    }
+}
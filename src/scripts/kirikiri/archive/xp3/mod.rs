@@ -143,7 +143,7 @@ impl ScriptBuilder for Xp3ArchiveBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let mut base_offset = 0;
         if buf.starts_with(b"MZ") {
@@ -163,7 +163,7 @@ impl ScriptBuilder for Xp3ArchiveBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let mut file = std::fs::File::open(filename)?;
         let mut base_offset = 0;
@@ -186,7 +186,7 @@ impl ScriptBuilder for Xp3ArchiveBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         let mut base_offset = 0;
         if reader.peek_and_equal(b"MZ").is_ok() {
@@ -216,6 +216,10 @@ impl ScriptBuilder for Xp3ArchiveBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
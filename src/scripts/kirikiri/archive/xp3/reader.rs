@@ -1,21 +1,40 @@
+use crate::ext::io::MemReader;
 use adler::Adler32;
+use anyhow::Result;
 use std::io::{PipeReader, Read};
 
 pub struct Reader {
-    inner: PipeReader,
+    inner: Box<dyn Read + Send>,
     adler: Adler32,
     readed: u64,
+    /// Whether the inner stream is already processed data. Checksum is not updated in that case.
+    processed: bool,
 }
 
 impl Reader {
     pub fn new(inner: PipeReader) -> Self {
         Self {
-            inner,
+            inner: Box::new(inner),
             adler: Adler32::new(),
             readed: 0,
+            processed: false,
         }
     }
 
+    /// Reads all remaining data and replaces it with the data processed by `f`.
+    ///
+    /// `f` receives the checksum of the original data. The checksum still refers to the original
+    /// data after calling this, while the processed data is readed from the beginning again.
+    pub fn process_all<F: FnOnce(u32, &mut Vec<u8>) -> Result<()>>(&mut self, f: F) -> Result<()> {
+        let mut data = Vec::new();
+        self.read_to_end(&mut data)?;
+        f(self.adler.checksum(), &mut data)?;
+        self.inner = Box::new(MemReader::new(data));
+        self.readed = 0;
+        self.processed = true;
+        Ok(())
+    }
+
     pub fn into_checksum(self) -> u32 {
         self.adler.checksum()
     }
@@ -28,7 +47,9 @@ impl Reader {
 impl Read for Reader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let n = self.inner.read(buf)?;
-        self.adler.write_slice(&buf[..n]);
+        if !self.processed {
+            self.adler.write_slice(&buf[..n]);
+        }
         self.readed += n as u64;
         Ok(n)
     }
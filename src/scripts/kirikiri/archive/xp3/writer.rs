@@ -1,5 +1,6 @@
 use super::archive::*;
 use super::consts::*;
+use super::crypt::*;
 use super::reader::*;
 use super::segmenter::*;
 use crate::ext::io::*;
@@ -15,6 +16,46 @@ use std::io::{Seek, Write};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
+/// Re-encoding applied to a file before it is written into archive.
+#[derive(Clone, Copy, Debug)]
+enum Reencode {
+    /// Encrypt UTF-16LE text with SimpleCrypt in given mode.
+    SimpleCrypt(u8),
+    /// Compress into mdf.
+    Mdf,
+}
+
+impl Reencode {
+    fn apply(self, name: &str, data: Vec<u8>, level: u32) -> Result<Vec<u8>> {
+        use crate::scripts::kirikiri::mdf::Mdf;
+        use crate::scripts::kirikiri::simple_crypt::SimpleCrypt;
+        match self {
+            Self::SimpleCrypt(crypt) => {
+                // Only plain UTF-16LE text can be encrypted without guessing its encoding.
+                if data.starts_with(&[0xff, 0xfe]) {
+                    SimpleCrypt::pack(crypt, &data[2..], level)
+                } else {
+                    if !SimpleCrypt::is_simple_crypt(&data) {
+                        eprintln!(
+                            "Warning: {} is not UTF-16LE text with BOM, stored without SimpleCrypt encryption.",
+                            name
+                        );
+                        crate::COUNTER.inc_warning();
+                    }
+                    Ok(data)
+                }
+            }
+            Self::Mdf => {
+                if data.starts_with(b"mdf\0") {
+                    Ok(data)
+                } else {
+                    Mdf::pack(&data, level)
+                }
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 struct WrittenSegment {
     is_compressed: bool,
@@ -66,6 +107,7 @@ pub struct Xp3ArchiveWriter<T: Write + Seek> {
     segments: Arc<Mutex<HashMap<[u8; 32], WrittenSegment>>>,
     items: Arc<Mutex<BTreeMap<String, ArchiveItem>>>,
     runner: ThreadPool<Result<()>>,
+    crypt: Option<Arc<Box<dyn Crypt + Send + Sync>>>,
     compress_files: bool,
     compress_index: bool,
     zlib_compression_level: u32,
@@ -76,6 +118,9 @@ pub struct Xp3ArchiveWriter<T: Write + Seek> {
     use_zstd: bool,
     zstd_compression_level: i32,
     no_adler: bool,
+    simple_crypt_mode: u8,
+    simple_crypt_exts: Arc<HashSet<String>>,
+    mdf_compress_exts: Arc<HashSet<String>>,
     #[cfg(feature = "zopfli")]
     use_zopfli: bool,
     #[cfg(feature = "zopfli")]
@@ -98,9 +143,36 @@ impl Xp3ArchiveWriter<std::io::BufWriter<std::fs::File>> {
                 original_size: 0,
                 archived_size: 0,
                 segments: Vec::new(),
+                is_encrypted: false,
             };
             items.insert(file.to_string(), item);
         }
+        let crypt = match &config.xp3_game_title {
+            Some(game_title) => {
+                let crypt = query_crypt_schema(game_title)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Unsupported game title for XP3 archive: {}", game_title)
+                    })?
+                    .create_crypt(filename, config)?;
+                if !crypt.decrypt_supported() {
+                    // The game does not encrypt its archives.
+                    None
+                } else if !crypt.encrypt_supported() {
+                    anyhow::bail!(
+                        "The encryption used by game {} is not supported when creating XP3 archive.",
+                        game_title
+                    );
+                } else if crypt.hash_after_crypt() {
+                    anyhow::bail!(
+                        "The encryption used by game {} requires checksum of encrypted contents, which is not supported when creating XP3 archive.",
+                        game_title
+                    );
+                } else {
+                    Some(Arc::new(crypt))
+                }
+            }
+            None => None,
+        };
         let segmenter = create_segmenter(&config.xp3_segmenter).map(|s| Arc::new(s));
         file.write_all(XP3_MAGIC)?;
         file.write_u64(0)?; // Placeholder for index offset
@@ -117,6 +189,7 @@ impl Xp3ArchiveWriter<std::io::BufWriter<std::fs::File>> {
                 Some("xp3-writer"),
                 false,
             )?,
+            crypt,
             compress_files: config.xp3_compress_files,
             compress_index: config.xp3_compress_index,
             zlib_compression_level: config.zlib_compression_level,
@@ -127,6 +200,9 @@ impl Xp3ArchiveWriter<std::io::BufWriter<std::fs::File>> {
             use_zstd: config.xp3_zstd,
             zstd_compression_level: config.zstd_compression_level,
             no_adler: config.xp3_no_adler,
+            simple_crypt_mode: config.kirikiri_simple_crypt_mode,
+            simple_crypt_exts: config.xp3_simple_crypt_exts.clone(),
+            mdf_compress_exts: config.xp3_mdf_compress_exts.clone(),
             #[cfg(feature = "zopfli")]
             use_zopfli: config.xp3_zopfli,
             #[cfg(feature = "zopfli")]
@@ -142,6 +218,8 @@ impl Xp3ArchiveWriter<std::io::BufWriter<std::fs::File>> {
 struct Writer<'a> {
     inner: Box<dyn Write + 'a>,
     mem: MemWriter,
+    name: String,
+    reencode: Option<(Reencode, u32)>,
 }
 
 impl std::fmt::Debug for Writer<'_> {
@@ -176,11 +254,39 @@ impl<'a> Seek for Writer<'a> {
 
 impl<'a> Drop for Writer<'a> {
     fn drop(&mut self) {
-        let _ = self.inner.write_all(&self.mem.data);
+        let data = std::mem::take(&mut self.mem.data);
+        let data = match self.reencode {
+            Some((reencode, level)) => match reencode.apply(&self.name, data, level) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Failed to re-encode file in XP3 archive: {}", e);
+                    crate::COUNTER.inc_error();
+                    return;
+                }
+            },
+            None => data,
+        };
+        let _ = self.inner.write_all(&data);
         let _ = self.inner.flush();
     }
 }
 
+impl<T: Write + Seek> Xp3ArchiveWriter<T> {
+    fn reencode_for(&self, name: &str) -> Option<Reencode> {
+        let ext = std::path::Path::new(name)
+            .extension()
+            .and_then(|s| s.to_str())?
+            .to_ascii_lowercase();
+        if self.simple_crypt_exts.contains(&ext) {
+            Some(Reencode::SimpleCrypt(self.simple_crypt_mode))
+        } else if self.mdf_compress_exts.contains(&ext) {
+            Some(Reencode::Mdf)
+        } else {
+            None
+        }
+    }
+}
+
 impl<T: Write + Seek + Sync + Send + 'static> Archive for Xp3ArchiveWriter<T> {
     fn new_file<'a>(
         &'a mut self,
@@ -191,6 +297,8 @@ impl<T: Write + Seek + Sync + Send + 'static> Archive for Xp3ArchiveWriter<T> {
         Ok(Box::new(Writer {
             inner,
             mem: MemWriter::new(),
+            name: name.to_string(),
+            reencode: None,
         }))
     }
 
@@ -244,10 +352,32 @@ impl<T: Write + Seek + Sync + Send + 'static> Archive for Xp3ArchiveWriter<T> {
             #[cfg(feature = "zopfli")]
             let zopfli_maximum_block_splits = self.zopfli_maximum_block_splits;
             let zstd_compression_level = self.zstd_compression_level;
+            let crypt = self
+                .crypt
+                .clone()
+                .filter(|crypt| !(name == "startup.tjs" && crypt.startup_tjs_not_encrypted()));
             let name = name.to_owned();
             self.runner.execute(
                 move |_| {
                     let mut reader = reader;
+                    let is_encrypted = crypt.is_some();
+                    if let Some(crypt) = crypt {
+                        // The key depends on the checksum of whole file, so the file must be readed fully before encrypting.
+                        reader.process_all(|file_hash, data| {
+                            let entry = Xp3Entry {
+                                name: name.clone(),
+                                flags: TVP_XP3_FILE_PROTECTED,
+                                file_hash,
+                                original_size: data.len() as u64,
+                                archived_size: data.len() as u64,
+                                timestamp: None,
+                                segments: Vec::new(),
+                                extras: Vec::new(),
+                                extra: None,
+                            };
+                            crypt.encrypt(&entry, 0, data)
+                        })?;
+                    }
                     let mut offset_in_file = 0u64;
                     if let Some(segmenter) = segmenter {
                         for seg in segmenter.segment(&mut reader, &name) {
@@ -509,6 +639,7 @@ impl<T: Write + Seek + Sync + Send + 'static> Archive for Xp3ArchiveWriter<T> {
                     }
                     let mut item = item.lock_blocking().to_owned();
                     item.file_hash = reader.into_checksum();
+                    item.is_encrypted = is_encrypted;
                     item.segments.sort_by_key(|s| s.offset_in_file);
                     let mut items = items.lock_blocking();
                     items.insert(item.name.clone(), item);
@@ -517,7 +648,15 @@ impl<T: Write + Seek + Sync + Send + 'static> Archive for Xp3ArchiveWriter<T> {
                 true,
             )?;
         }
-        Ok(Box::new(writer))
+        match self.reencode_for(name) {
+            Some(reencode) => Ok(Box::new(Writer {
+                inner: Box::new(writer),
+                mem: MemWriter::new(),
+                name: name.to_string(),
+                reencode: Some((reencode, self.zlib_compression_level)),
+            })),
+            None => Ok(Box::new(writer)),
+        }
     }
 
     fn write_header(&mut self) -> Result<()> {
@@ -535,7 +674,12 @@ impl<T: Write + Seek + Sync + Send + 'static> Archive for Xp3ArchiveWriter<T> {
             let info_data_size = name.len() as u64 + 22;
             file_chunk.write_all(CHUNK_INFO)?;
             file_chunk.write_u64(info_data_size)?;
-            file_chunk.write_u32(0)?; // flags
+            let flags = if item.is_encrypted {
+                TVP_XP3_FILE_PROTECTED
+            } else {
+                0
+            };
+            file_chunk.write_u32(flags)?;
             file_chunk.write_u64(item.original_size)?;
             file_chunk.write_u64(item.archived_size)?;
             file_chunk.write_u16(name.len() as u16 / 2)?;
@@ -557,7 +701,8 @@ impl<T: Write + Seek + Sync + Send + 'static> Archive for Xp3ArchiveWriter<T> {
             let adlr_data_size = 4;
             file_chunk.write_all(CHUNK_ADLR)?;
             file_chunk.write_u64(adlr_data_size)?;
-            if self.no_adler {
+            // Encrypted files need checksum to derive the key.
+            if self.no_adler && !item.is_encrypted {
                 file_chunk.write_u32(0)?;
             } else {
                 file_chunk.write_u32(item.file_hash)?;
@@ -1,2 +1,3 @@
 //! Kirikiri Images
 pub mod tlg;
+mod tlg6;
@@ -1,4 +1,5 @@
 //! Kirikiri TLG Image File (.tlg)
+use super::tlg6::save_tlg6;
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
@@ -7,6 +8,17 @@ use anyhow::Result;
 use libtlg_rs::*;
 use std::io::{Read, Seek};
 
+/// Saves the TLG image with its version. Unknown versions are saved as TLG5.
+pub fn save_tlg_with_version<'a>(mut tlg: Tlg, writer: Box<dyn WriteSeek + 'a>) -> Result<()> {
+    if tlg.version == 6 {
+        save_tlg6(&tlg, writer)
+    } else {
+        tlg.version = 5;
+        save_tlg(&tlg, writer)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 /// Kirikiri TLG Script Builder
 pub struct TlgImageBuilder {}
@@ -30,7 +42,7 @@ impl ScriptBuilder for TlgImageBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(TlgImage::new(MemReader::new(data), config)?))
     }
@@ -65,7 +77,7 @@ impl ScriptBuilder for TlgImageBuilder {
         mut data: ImageData,
         _filename: &str,
         writer: Box<dyn WriteSeek + 'a>,
-        _options: &ExtraConfig,
+        options: &ExtraConfig,
     ) -> Result<()> {
         if data.depth != 8 {
             return Err(anyhow::anyhow!("Unsupported image depth: {}", data.depth));
@@ -89,10 +101,9 @@ impl ScriptBuilder for TlgImageBuilder {
             color: color_type,
             data: data.data,
             tags: Default::default(),
-            version: 5, // Currently only version 5 is supported
+            version: options.kirikiri_tlg_version.unwrap_or(5),
         };
-        save_tlg(&tlg, writer)?;
-        Ok(())
+        save_tlg_with_version(tlg, writer)
     }
 }
 
@@ -100,6 +111,7 @@ impl ScriptBuilder for TlgImageBuilder {
 /// Kirikiri TLG Script
 pub struct TlgImage {
     data: Tlg,
+    version: Option<u32>,
 }
 
 impl TlgImage {
@@ -107,9 +119,12 @@ impl TlgImage {
     ///
     /// * `data` - The reader containing the TLG script data
     /// * `config` - Extra configuration options
-    pub fn new<T: Read + Seek>(data: T, _config: &ExtraConfig) -> Result<Self> {
+    pub fn new<T: Read + Seek>(data: T, config: &ExtraConfig) -> Result<Self> {
         let tlg = load_tlg(data)?;
-        Ok(TlgImage { data: tlg })
+        Ok(TlgImage {
+            data: tlg,
+            version: config.kirikiri_tlg_version,
+        })
     }
 }
 
@@ -168,9 +183,8 @@ impl Script for TlgImage {
             color: color_type,
             data: data.data,
             tags: self.data.tags.clone(),
-            version: 5, // Currently only version 5 is supported
+            version: self.version.unwrap_or(self.data.version),
         };
-        save_tlg(&tlg, file)?;
-        Ok(())
+        save_tlg_with_version(tlg, file)
     }
 }
@@ -0,0 +1,508 @@
+//! Kirikiri TLG6 Image Encoder
+use crate::ext::io::*;
+use anyhow::Result;
+use libtlg_rs::{Tlg, TlgColorType};
+use std::io::{Seek, Write};
+
+const W_BLOCK_SIZE: usize = 8;
+const H_BLOCK_SIZE: usize = 8;
+const GOLOMB_N_COUNT: usize = 4;
+const GOLOMB_TABLE_SIZE: usize = GOLOMB_N_COUNT * 2 * 128;
+const GOLOMB_COMPRESSED: [[u16; 9]; GOLOMB_N_COUNT] = [
+    [3, 7, 15, 27, 63, 108, 223, 448, 130],
+    [3, 5, 13, 24, 51, 95, 192, 384, 257],
+    [2, 5, 12, 21, 39, 86, 155, 320, 384],
+    [2, 3, 9, 18, 33, 61, 129, 258, 511],
+];
+/// Number of filters: 16 color correlation transforms, each with MED or average predictor.
+const FILTER_COUNT: usize = 32;
+const SLIDE_N: usize = 4096;
+const SLIDE_MAX_MATCH: usize = 18 + 255;
+
+lazy_static::lazy_static! {
+    static ref GOLOMB_BIT_LENGTH_TABLE: Vec<[u8; GOLOMB_N_COUNT]> = init_golomb_table();
+}
+
+fn init_golomb_table() -> Vec<[u8; GOLOMB_N_COUNT]> {
+    let mut table = vec![[0; GOLOMB_N_COUNT]; GOLOMB_TABLE_SIZE];
+    for n in 0..GOLOMB_N_COUNT {
+        let mut a = 0;
+        for (i, &count) in GOLOMB_COMPRESSED[n].iter().enumerate() {
+            for _ in 0..count {
+                table[a][n] = i as u8;
+                a += 1;
+            }
+        }
+    }
+    table
+}
+
+/// LSB-first bit writer.
+struct BitWriter {
+    data: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            bits: 0,
+        }
+    }
+
+    fn put_bit(&mut self, bit: bool) {
+        if self.bits.is_multiple_of(8) {
+            self.data.push(0);
+        }
+        if bit {
+            let last = self.data.len() - 1;
+            self.data[last] |= 1 << (self.bits % 8);
+        }
+        self.bits += 1;
+    }
+
+    fn put_bits(&mut self, value: u32, count: usize) {
+        for i in 0..count {
+            self.put_bit((value >> i) & 1 != 0);
+        }
+    }
+
+    fn put_zeros(&mut self, count: usize) {
+        for _ in 0..count {
+            self.put_bit(false);
+        }
+    }
+}
+
+/// Writes a run length with Elias gamma code.
+fn put_gamma(writer: &mut BitWriter, count: usize) {
+    let bits = usize::BITS - 1 - count.leading_zeros();
+    writer.put_zeros(bits as usize);
+    writer.put_bit(true);
+    writer.put_bits(count as u32, bits as usize);
+}
+
+/// Encodes residuals of a color channel with run-length coded zeros and adaptive Golomb codes.
+fn encode_golomb(values: &[i8], writer: &mut BitWriter) {
+    let mut n = GOLOMB_N_COUNT - 1;
+    let mut a = 0;
+    writer.put_bit(values[0] != 0);
+    let mut i = 0;
+    while i < values.len() {
+        let nonzero = values[i] != 0;
+        let mut j = i + 1;
+        while j < values.len() && (values[j] != 0) == nonzero {
+            j += 1;
+        }
+        put_gamma(writer, j - i);
+        if nonzero {
+            for &v in &values[i..j] {
+                let e = v as i32;
+                let m = if e > 0 { 2 * e - 1 } else { -2 * e - 2 } as u32;
+                let k = GOLOMB_BIT_LENGTH_TABLE[a][n] as usize;
+                let q = (m >> k) as usize;
+                let bit_pos = writer.bits % 8;
+                if q < 32 - bit_pos {
+                    writer.put_zeros(q);
+                    writer.put_bit(true);
+                } else {
+                    // The quotient is too long to be found in 32 bits,
+                    // so it is stored in the byte after them.
+                    writer.put_zeros(32 - bit_pos);
+                    writer.put_bits(q as u32, 8);
+                }
+                writer.put_bits(m, k);
+                a += (m >> 1) as usize;
+                if n == 0 {
+                    n = GOLOMB_N_COUNT - 1;
+                    a >>= 1;
+                } else {
+                    n -= 1;
+                }
+            }
+        }
+        i = j;
+    }
+}
+
+fn map_bytes(a: u32, b: u32, f: impl Fn(u8, u8) -> u8) -> u32 {
+    let a = a.to_le_bytes();
+    let b = b.to_le_bytes();
+    u32::from_le_bytes([f(a[0], b[0]), f(a[1], b[1]), f(a[2], b[2]), f(a[3], b[3])])
+}
+
+/// Median edge detector predictor of every byte.
+fn med(a: u32, b: u32, c: u32) -> u32 {
+    let a = a.to_le_bytes();
+    let b = b.to_le_bytes();
+    let c = c.to_le_bytes();
+    let mut out = [0u8; 4];
+    for i in 0..4 {
+        let (max, min) = (a[i].max(b[i]), a[i].min(b[i]));
+        out[i] = if c[i] >= max {
+            min
+        } else if c[i] <= min {
+            max
+        } else {
+            a[i].wrapping_add(b[i]).wrapping_sub(c[i])
+        };
+    }
+    u32::from_le_bytes(out)
+}
+
+/// Average predictor of every byte (rounded up).
+fn avg(a: u32, b: u32) -> u32 {
+    map_bytes(a, b, |x, y| ((x as u16 + y as u16 + 1) >> 1) as u8)
+}
+
+/// Applies the color correlation transform to the residual.
+///
+/// This is the inverse of the transform done by the decoder.
+fn transform(t: usize, v: u32) -> u32 {
+    let [b, g, r, a] = v.to_le_bytes();
+    let (ib, ig, ir) = match t {
+        0 => (b, g, r),
+        1 => (b.wrapping_sub(g), g, r.wrapping_sub(g)),
+        2 => (b, g.wrapping_sub(b), r.wrapping_sub(g)),
+        3 => (b.wrapping_sub(g), g.wrapping_sub(r), r),
+        4 => {
+            let ir = r.wrapping_sub(g);
+            (b.wrapping_sub(ir), g.wrapping_sub(b), ir)
+        }
+        5 => (b.wrapping_sub(r), g.wrapping_sub(b), r),
+        6 => (b.wrapping_sub(g), g, r),
+        7 => (b, g.wrapping_sub(b), r),
+        8 => (b, g, r.wrapping_sub(g)),
+        9 => {
+            let ib = b.wrapping_sub(g);
+            (ib, g.wrapping_sub(r), r.wrapping_sub(ib))
+        }
+        10 => (b.wrapping_sub(r), g.wrapping_sub(r), r),
+        11 => (b, g.wrapping_sub(b), r.wrapping_sub(b)),
+        12 => (b, g.wrapping_sub(r), r.wrapping_sub(b)),
+        13 => {
+            let ig = g.wrapping_sub(r);
+            (b.wrapping_sub(ig), ig, r.wrapping_sub(b))
+        }
+        14 => {
+            let ir = r.wrapping_sub(b);
+            (b.wrapping_sub(g), g.wrapping_sub(ir), ir)
+        }
+        _ => (
+            b,
+            g.wrapping_sub(b.wrapping_shl(1)),
+            r.wrapping_sub(b.wrapping_shl(1)),
+        ),
+    };
+    u32::from_le_bytes([ib, ig, ir, a])
+}
+
+/// Returns the cost of encoding the residual.
+fn cost(v: u32, colors: usize) -> u32 {
+    v.to_le_bytes()[..colors]
+        .iter()
+        .map(|&x| (x as i8).unsigned_abs() as u32)
+        .sum()
+}
+
+fn initial_slide_text() -> Vec<u8> {
+    let mut text = Vec::with_capacity(SLIDE_N);
+    for i in 0..32u8 {
+        for j in 0..16u8 {
+            text.extend_from_slice(&[i, i, i, i, j, j, j, j]);
+        }
+    }
+    text
+}
+
+/// Finds the longest match of `cur` in the sliding window.
+///
+/// Bytes written by the match itself are taken into account.
+fn find_slide_match(text: &[u8], r: usize, cur: &[u8]) -> (usize, usize) {
+    let max_len = cur.len().min(SLIDE_MAX_MATCH);
+    let mut best = (0, 0);
+    for back in 1..=SLIDE_N {
+        let mpos = (r + SLIDE_N - back) % SLIDE_N;
+        if text[mpos] != cur[0] {
+            continue;
+        }
+        let mut len = 1;
+        while len < max_len {
+            let d = (mpos + len + SLIDE_N - r) % SLIDE_N;
+            let c = if d < len {
+                cur[d]
+            } else {
+                text[(mpos + len) % SLIDE_N]
+            };
+            if c != cur[len] {
+                break;
+            }
+            len += 1;
+        }
+        if len > best.1 {
+            best = (mpos, len);
+            if len == max_len {
+                break;
+            }
+        }
+    }
+    best
+}
+
+/// Compresses data with the sliding window compression used by TLG5/TLG6.
+fn compress_slide(data: &[u8]) -> Vec<u8> {
+    let mut text = initial_slide_text();
+    let mut r = 0;
+    let mut out = Vec::new();
+    let mut flag_pos = 0;
+    let mut flag_bit = 8;
+    let mut pos = 0;
+    while pos < data.len() {
+        if flag_bit == 8 {
+            flag_pos = out.len();
+            out.push(0);
+            flag_bit = 0;
+        }
+        let (mpos, len) = find_slide_match(&text, r, &data[pos..]);
+        if len >= 3 {
+            out[flag_pos] |= 1 << flag_bit;
+            if len >= 18 {
+                out.push(mpos as u8);
+                out.push((mpos >> 8) as u8 | 0xF0);
+                out.push((len - 18) as u8);
+            } else {
+                out.push(mpos as u8);
+                out.push((mpos >> 8) as u8 | ((len - 3) << 4) as u8);
+            }
+            for j in 0..len {
+                let c = text[(mpos + j) % SLIDE_N];
+                debug_assert_eq!(c, data[pos + j]);
+                text[r] = c;
+                r = (r + 1) % SLIDE_N;
+            }
+            pos += len;
+        } else {
+            out.push(data[pos]);
+            text[r] = data[pos];
+            r = (r + 1) % SLIDE_N;
+            pos += 1;
+        }
+        flag_bit += 1;
+    }
+    out
+}
+
+/// Encodes the image as TLG6 raw data.
+///
+/// * `filter` - Use the specified filter for all blocks instead of choosing the best one.
+fn encode_tlg6(img: &Tlg, filter: Option<u8>) -> Result<Vec<u8>> {
+    let colors = match img.color {
+        TlgColorType::Bgra32 => 4,
+        TlgColorType::Bgr24 => 3,
+        TlgColorType::Grayscale8 => 1,
+    };
+    let width = img.width as usize;
+    let height = img.height as usize;
+    if width == 0 || height == 0 {
+        return Err(anyhow::anyhow!("Image size must not be zero."));
+    }
+    let img_size = width * height * colors;
+    if img.data.len() < img_size {
+        return Err(anyhow::anyhow!(
+            "Image data size too small: expected {}, got {}",
+            img_size,
+            img.data.len()
+        ));
+    }
+    // Only the filters without color correlation are allowed for grayscale images.
+    let filter_count = if colors == 1 { 2 } else { FILTER_COUNT };
+    if let Some(f) = filter
+        && f as usize >= filter_count
+    {
+        return Err(anyhow::anyhow!("Invalid TLG6 filter: {}", f));
+    }
+    let zero = if colors == 3 { 0xff000000u32 } else { 0 };
+    let pixels: Vec<u32> = img.data[..img_size]
+        .chunks_exact(colors)
+        .map(|p| match colors {
+            4 => u32::from_le_bytes([p[0], p[1], p[2], p[3]]),
+            3 => u32::from_le_bytes([p[0], p[1], p[2], 0xff]),
+            _ => p[0] as u32,
+        })
+        .collect();
+    let x_block_count = (width - 1) / W_BLOCK_SIZE + 1;
+    let y_block_count = (height - 1) / H_BLOCK_SIZE + 1;
+    let mut filter_types = Vec::with_capacity(x_block_count * y_block_count);
+    let mut pixelbuf = vec![0u32; width * H_BLOCK_SIZE];
+    let mut body = Vec::new();
+    let mut max_bit_length = 0;
+    let mut residuals = Vec::with_capacity(W_BLOCK_SIZE * H_BLOCK_SIZE);
+    for y in (0..height).step_by(H_BLOCK_SIZE) {
+        let y_lim = (y + H_BLOCK_SIZE).min(height);
+        let bh = y_lim - y;
+        for bx in 0..x_block_count {
+            let x = bx * W_BLOCK_SIZE;
+            let ww = W_BLOCK_SIZE.min(width - x);
+            residuals.clear();
+            for yy in y..y_lim {
+                for xx in x..x + ww {
+                    let cur = pixels[yy * width + xx];
+                    let left = if xx == 0 {
+                        zero
+                    } else {
+                        pixels[yy * width + xx - 1]
+                    };
+                    let (up, up_left) = if yy == 0 {
+                        (zero, zero)
+                    } else if xx == 0 {
+                        (pixels[(yy - 1) * width], zero)
+                    } else {
+                        (
+                            pixels[(yy - 1) * width + xx],
+                            pixels[(yy - 1) * width + xx - 1],
+                        )
+                    };
+                    let sub = |pred: u32| map_bytes(cur, pred, |a, b| a.wrapping_sub(b));
+                    residuals.push([sub(med(left, up, up_left)), sub(avg(left, up))]);
+                }
+            }
+            let ft = match filter {
+                Some(f) => f as usize,
+                None => {
+                    let mut costs = [0u32; FILTER_COUNT];
+                    for res in &residuals {
+                        for (f, c) in costs[..filter_count].iter_mut().enumerate() {
+                            *c += cost(transform(f >> 1, res[f & 1]), colors);
+                        }
+                    }
+                    (0..filter_count).min_by_key(|&f| costs[f]).unwrap_or(0)
+                }
+            };
+            filter_types.push(ft as u8);
+            let base = bx * W_BLOCK_SIZE * bh;
+            for (i, res) in residuals.iter().enumerate() {
+                let l = i / ww;
+                let xi = i % ww;
+                let line = if bx & 1 != 0 { bh - 1 - l } else { l };
+                let pos = if (y + l) & 1 == 0 { xi } else { ww - 1 - xi };
+                pixelbuf[base + line * ww + pos] = transform(ft >> 1, res[ft & 1]);
+            }
+        }
+        let pixel_count = bh * width;
+        let mut values = vec![0i8; pixel_count];
+        for c in 0..colors {
+            for (v, p) in values.iter_mut().zip(&pixelbuf[..pixel_count]) {
+                *v = p.to_le_bytes()[c] as i8;
+            }
+            let mut writer = BitWriter::new();
+            encode_golomb(&values, &mut writer);
+            if writer.bits > 0x3fff_ffff {
+                return Err(anyhow::anyhow!("TLG6 block line is too large."));
+            }
+            max_bit_length = max_bit_length.max(writer.bits);
+            body.write_u32(writer.bits as u32)?;
+            body.write_all(&writer.data)?;
+        }
+    }
+    let mut out = Vec::with_capacity(body.len() + 64);
+    out.write_all(b"TLG6.0\x00raw\x1a")?;
+    out.write_all(&[colors as u8, 0, 0, 0])?;
+    out.write_u32(img.width)?;
+    out.write_u32(img.height)?;
+    out.write_u32(max_bit_length as u32)?;
+    let filter_data = compress_slide(&filter_types);
+    out.write_u32(filter_data.len() as u32)?;
+    out.write_all(&filter_data)?;
+    out.write_all(&body)?;
+    Ok(out)
+}
+
+/// Encodes the image as TLG6.
+///
+/// If the image has tags, the TLG6 data is wrapped in a TLG0 container like [libtlg_rs::save_tlg] does.
+pub fn save_tlg6<W: Write + Seek>(img: &Tlg, mut writer: W) -> Result<()> {
+    let data = encode_tlg6(img, None)?;
+    if img.tags.is_empty() {
+        writer.write_all(&data)?;
+        return Ok(());
+    }
+    writer.write_all(b"TLG0.0\x00sds\x1a")?;
+    writer.write_u32(data.len() as u32)?;
+    writer.write_all(&data)?;
+    writer.write_all(b"tags")?;
+    let mut ss = Vec::new();
+    for (k, v) in &img.tags {
+        ss.write_all(k.len().to_string().as_bytes())?;
+        ss.write_all(b":")?;
+        ss.write_all(k)?;
+        ss.write_all(b"=")?;
+        ss.write_all(v.len().to_string().as_bytes())?;
+        ss.write_all(b":")?;
+        ss.write_all(v)?;
+        ss.write_all(b",")?;
+    }
+    writer.write_u32(ss.len() as u32)?;
+    writer.write_all(&ss)?;
+    Ok(())
+}
+
+#[test]
+fn test_tlg6_roundtrip() {
+    let (width, height) = (67u32, 45u32);
+    let mut data = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let noise = ((x * 7919 + y * 104729) % 61) as u8;
+            data.extend_from_slice(&[
+                (x * 12) as u8 ^ noise,
+                (y * 13) as u8,
+                (x * y) as u8,
+                if (x + y) % 5 == 0 { 0 } else { 255 - noise },
+            ]);
+        }
+    }
+    // Large residual which needs the escape code of golomb quotient.
+    data[0] = 0x80;
+    let gray: Vec<u8> = data.chunks_exact(4).map(|p| p[0]).collect();
+    let bgr: Vec<u8> = data
+        .chunks_exact(4)
+        .flat_map(|p| [p[0], p[1], p[2]])
+        .collect();
+    let images = [
+        (TlgColorType::Bgra32, data, FILTER_COUNT),
+        (TlgColorType::Bgr24, bgr, FILTER_COUNT),
+        (TlgColorType::Grayscale8, gray, 2),
+    ];
+    for (color, data, filter_count) in images {
+        let img = Tlg {
+            tags: Default::default(),
+            version: 6,
+            width,
+            height,
+            color,
+            data,
+        };
+        let filters = (0..filter_count as u8).map(Some).chain([None]);
+        for filter in filters {
+            let encoded = encode_tlg6(&img, filter).unwrap();
+            let decoded = libtlg_rs::load_tlg(std::io::Cursor::new(encoded)).unwrap();
+            assert_eq!(decoded.version, 6);
+            assert_eq!(decoded.data, img.data, "{:?} filter {:?}", color, filter);
+        }
+    }
+    let mut img = Tlg {
+        tags: Default::default(),
+        version: 6,
+        width: 1,
+        height: 1,
+        color: TlgColorType::Grayscale8,
+        data: vec![0x80],
+    };
+    img.tags.insert(b"mode".to_vec(), b"alpha".to_vec());
+    let mut writer = MemWriter::new();
+    save_tlg6(&img, &mut writer).unwrap();
+    let decoded = libtlg_rs::load_tlg(MemReader::new(writer.into_inner())).unwrap();
+    assert_eq!(decoded.data, img.data);
+    assert_eq!(decoded.tags, img.tags);
+}
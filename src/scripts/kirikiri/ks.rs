@@ -1,5 +1,6 @@
 //! Kirikiri Script File (.ks)
 use crate::ext::fancy_regex::*;
+use crate::format::markup::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use crate::utils::encoding::*;
@@ -35,7 +36,7 @@ impl ScriptBuilder for KsBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(KsScript::new(buf, encoding, config)?))
     }
@@ -47,6 +48,31 @@ impl ScriptBuilder for KsBuilder {
     fn script_type(&self) -> &'static ScriptType {
         &ScriptType::Kirikiri
     }
+
+    fn inline_markup(&self) -> Option<&'static dyn InlineMarkup> {
+        Some(&KsMarkup)
+    }
+}
+
+#[derive(Debug)]
+/// Inline tags in Kirikiri messages (`<ruby text="...">`, `<r>`, etc.)
+///
+/// `[ruby]` applies to the next character only, so it is kept together with that character.
+struct KsMarkup;
+
+impl InlineMarkup for KsMarkup {
+    fn parse(&self, text: &str) -> Option<(Markup, usize)> {
+        let len = xml_tag_len(text)?;
+        if xml_tag_name(&text[..len]) == "r" {
+            Some((Markup::LineBreak, len))
+        } else {
+            Some((Markup::Tag, len))
+        }
+    }
+
+    fn binds_next_grapheme(&self, tag: &str) -> bool {
+        !tag.starts_with("</") && xml_tag_name(tag) == "ruby"
+    }
 }
 
 /// Kirikiri Script Node Trait
@@ -746,6 +772,7 @@ pub struct KsScript {
     tree: ParsedScript,
     name_commands: Arc<HashSet<String>>,
     message_commands: Arc<HashSet<String>>,
+    voice_commands: Arc<HashSet<String>>,
     remove_empty_lines: bool,
     hitret: bool,
     lf: Option<String>,
@@ -790,12 +817,36 @@ impl KsScript {
             tree,
             name_commands: config.kirikiri_name_commands.clone(),
             message_commands: config.kirikiri_message_commands.clone(),
+            voice_commands: config.kirikiri_voice_commands.clone(),
             remove_empty_lines: config.kirikiri_remove_empty_lines,
             hitret,
             lf: config.kirikiri_ks_lf.clone(),
             message_tags: config.kirikiri_message_tags.clone(),
         })
     }
+
+    /// Returns the voice played by the tag, if it is a voice command or has a `voice` attribute.
+    fn voice_of(&self, tag: &TagNode) -> Option<String> {
+        if let Some(TagAttr::Str(voice)) = tag.get_attr("voice") {
+            return Some(voice.clone());
+        }
+        if !self.voice_commands.contains(&tag.name) {
+            return None;
+        }
+        ["storage", "file"]
+            .iter()
+            .find_map(|key| match tag.get_attr(key) {
+                Some(TagAttr::Str(voice)) => Some(voice),
+                _ => None,
+            })
+            .or_else(|| {
+                tag.attributes.iter().find_map(|(_, attr)| match attr {
+                    TagAttr::Str(voice) if !voice.is_empty() => Some(voice),
+                    _ => None,
+                })
+            })
+            .cloned()
+    }
 }
 
 impl Script for KsScript {
@@ -810,7 +861,9 @@ impl Script for KsScript {
     fn extract_messages(&self) -> Result<Vec<Message>> {
         let mut messages = Vec::new();
         let mut name = None;
+        let mut voice = None;
         let mut message = String::new();
+        let mut label: Option<&str> = None;
         if self.hitret {
             for obj in self.tree.iter() {
                 match obj {
@@ -818,6 +871,9 @@ impl Script for KsScript {
                         for node in line.iter() {
                             if node.is_tag("Talk") {
                                 if let ParsedLineNode::Tag(tag) = &node {
+                                    if let Some(v) = self.voice_of(tag) {
+                                        voice = Some(v);
+                                    }
                                     if let Some(attr) = tag.get_attr("name") {
                                         if let TagAttr::Str(s) = attr {
                                             name = Some(s.to_string());
@@ -842,26 +898,34 @@ impl Script for KsScript {
                                 if let Some(lf) = self.lf.as_ref() {
                                     message = message.replace(lf, "\n");
                                 }
-                                messages.push(Message {
-                                    name: if name.as_ref().is_some_and(|name| name == "心の声") {
-                                        None
-                                    } else {
-                                        name.clone()
-                                    },
-                                    message: message.trim_end_matches("<np>").to_owned(),
-                                });
+                                messages.push(
+                                    Message::new(
+                                        message.trim_end_matches("<np>").to_owned(),
+                                        if name.as_ref().is_some_and(|name| name == "心の声") {
+                                            None
+                                        } else {
+                                            name.clone()
+                                        },
+                                    )
+                                    .with_kind(MessageKind::Dialogue)
+                                    .with_optional_meta(meta_key::VOICE, voice.take())
+                                    .with_optional_meta(meta_key::LABEL, label),
+                                );
                                 message.clear();
                                 name = None;
                                 continue;
                             } else if let ParsedLineNode::Tag(tag) = &node {
+                                if let Some(v) = self.voice_of(tag) {
+                                    voice = Some(v);
+                                }
                                 if self.message_tags.contains(&tag.name) {
                                     for attr in &tag.attributes {
                                         if let TagAttr::Str(value) = &attr.1 {
                                             if !value.is_empty() && !value.is_ascii() {
-                                                messages.push(Message {
-                                                    name: None,
-                                                    message: value.clone(),
-                                                });
+                                                messages.push(
+                                                    Message::new(value.clone(), None)
+                                                        .with_optional_meta(meta_key::LABEL, label),
+                                                );
                                                 break;
                                             }
                                         }
@@ -873,6 +937,9 @@ impl Script for KsScript {
                             }
                         }
                     }
+                    ParsedScriptNode::Label(node) => {
+                        label = Some(&node.name);
+                    }
                     _ => {}
                 }
             }
@@ -880,25 +947,38 @@ impl Script for KsScript {
         }
         for obj in self.tree.iter() {
             match obj {
-                ParsedScriptNode::Label(_) => {
+                ParsedScriptNode::Label(node) => {
                     if !message.is_empty() {
                         if let Some(lf) = self.lf.as_ref() {
                             message = message.replace(lf, "\n");
                         }
-                        messages.push(Message {
-                            name: name.clone(),
-                            message: message.trim_end_matches("<np>").to_owned(),
-                        });
+                        messages.push(
+                            Message::new(message.trim_end_matches("<np>").to_owned(), name.clone())
+                                .with_kind(MessageKind::Dialogue)
+                                .with_optional_meta(meta_key::VOICE, voice.take())
+                                .with_optional_meta(meta_key::LABEL, label),
+                        );
                         message.clear();
                         name = None;
                     }
+                    label = Some(&node.name);
                 }
                 ParsedScriptNode::Line(line) => {
                     if !message.ends_with("<np>") {
+                        for node in line.iter() {
+                            if let ParsedLineNode::Tag(tag) = node {
+                                if let Some(v) = self.voice_of(tag) {
+                                    voice = Some(v);
+                                }
+                            }
+                        }
                         message.push_str(&line.to_xml())
                     }
                 }
                 ParsedScriptNode::Command(cmd) => {
+                    if let Some(v) = self.voice_of(cmd) {
+                        voice = Some(v);
+                    }
                     if self.name_commands.contains(&cmd.name) {
                         for attr in &cmd.attributes {
                             if let TagAttr::Str(value) = &attr.1 {
@@ -912,10 +992,10 @@ impl Script for KsScript {
                         for attr in &cmd.attributes {
                             if let TagAttr::Str(value) = &attr.1 {
                                 if !value.is_empty() && !value.is_ascii() {
-                                    messages.push(Message {
-                                        name: None,
-                                        message: value.clone(),
-                                    });
+                                    messages.push(
+                                        Message::new(value.clone(), None)
+                                            .with_optional_meta(meta_key::LABEL, label),
+                                    );
                                     break; // Only take the first message found
                                 }
                             }
@@ -929,10 +1009,12 @@ impl Script for KsScript {
             if let Some(lf) = self.lf.as_ref() {
                 message = message.replace(lf, "\n");
             }
-            messages.push(Message {
-                name,
-                message: message.trim_end_matches("<np>").to_owned(),
-            });
+            messages.push(
+                Message::new(message.trim_end_matches("<np>").to_owned(), name)
+                    .with_kind(MessageKind::Dialogue)
+                    .with_optional_meta(meta_key::VOICE, voice)
+                    .with_optional_meta(meta_key::LABEL, label),
+            );
         }
         Ok(messages)
     }
@@ -1252,3 +1334,21 @@ impl Script for KsScript {
         Ok(())
     }
 }
+
+#[test]
+fn test_extract_voice() {
+    let config = ExtraConfig {
+        kirikiri_name_commands: Arc::new(HashSet::from(["nm".to_string()])),
+        kirikiri_voice_commands: Arc::new(HashSet::from(["voice".to_string()])),
+        kirikiri_ks_hitret: Some(false),
+        ..Default::default()
+    };
+    let script = "*start\n@voice storage=ali_0001\n@nm t=\"アリス\"\nこんにちは[p]\n*next\n[voice storage=ali_0002]さようなら[p]\n*last\nナレーション[p]\n";
+    let script = KsScript::new(script.as_bytes().to_vec(), Encoding::Utf8, &config).unwrap();
+    let messages = script.extract_messages().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].name.as_deref(), Some("アリス"));
+    assert_eq!(messages[0].meta[meta_key::VOICE], "ali_0001");
+    assert_eq!(messages[1].meta[meta_key::VOICE], "ali_0002");
+    assert!(!messages[2].meta.contains_key(meta_key::VOICE));
+}
@@ -3,7 +3,7 @@ use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use anyhow::Result;
-use std::io::Read;
+use std::io::{Read, Write};
 
 #[derive(Debug)]
 /// Kirikiri MDF Script Builder
@@ -27,10 +27,10 @@ impl ScriptBuilder for MdfBuilder {
         filename: &str,
         _encoding: Encoding,
         _archive_encoding: Encoding,
-        _config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        config: &ExtraConfig,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
-        Ok(Box::new(Mdf::new(buf, filename)?))
+        Ok(Box::new(Mdf::new(buf, filename, config)?))
     }
 
     fn extensions(&self) -> &'static [&'static str] {
@@ -48,6 +48,24 @@ impl ScriptBuilder for MdfBuilder {
             None
         }
     }
+
+    fn can_create_file(&self) -> bool {
+        true
+    }
+
+    fn create_file<'a>(
+        &'a self,
+        filename: &'a str,
+        mut writer: Box<dyn WriteSeek + 'a>,
+        _encoding: Encoding,
+        _file_encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<()> {
+        let data = crate::utils::files::read_file(filename)?;
+        let packed = Mdf::pack(&data, config.zlib_compression_level)?;
+        writer.write_all(&packed)?;
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -55,6 +73,7 @@ impl ScriptBuilder for MdfBuilder {
 pub struct Mdf {
     data: MemReader,
     ext: String,
+    compress_level: u32,
 }
 
 impl Mdf {
@@ -62,7 +81,8 @@ impl Mdf {
     ///
     /// * `buf` - The buffer containing the MDF data
     /// * `filename` - The name of the file (used for extension detection)
-    pub fn new(buf: Vec<u8>, filename: &str) -> Result<Self> {
+    /// * `config` - Extra configuration options
+    pub fn new(buf: Vec<u8>, filename: &str, config: &ExtraConfig) -> Result<Self> {
         let mut data = MemReader::new(buf);
         let mut header = [0u8; 4];
         data.read_exact(&mut header)?;
@@ -76,9 +96,19 @@ impl Mdf {
                 .and_then(|s| s.to_str())
                 .unwrap_or("")
                 .to_string(),
+            compress_level: config.zlib_compression_level,
         })
     }
 
+    /// Compresses data into a MDF file.
+    pub(crate) fn pack(data: &[u8], level: u32) -> Result<Vec<u8>> {
+        let mut result = b"mdf\0".to_vec();
+        result.write_u32(data.len() as u32)?;
+        let mut encoder = flate2::write::ZlibEncoder::new(result, flate2::Compression::new(level));
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+
     pub(crate) fn unpack(mut data: MemReaderRef) -> Result<Vec<u8>> {
         let size = data.read_u32()?;
         let mut decoder = flate2::read::ZlibDecoder::new(data);
@@ -119,4 +149,30 @@ impl Script for Mdf {
         writer.write_all(&data)?;
         Ok(())
     }
+    fn custom_import<'a>(
+        &'a self,
+        custom_filename: &'a str,
+        mut file: Box<dyn WriteSeek + 'a>,
+        _encoding: Encoding,
+        _output_encoding: Encoding,
+    ) -> Result<()> {
+        let data = crate::utils::files::read_file(custom_filename)?;
+        let packed = Self::pack(&data, self.compress_level)?;
+        file.write_all(&packed)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mdf_round_trip() {
+        let data = b"\xff\xfe@\x00w\x00a\x00i\x00t\x00".repeat(16);
+        let packed = Mdf::pack(&data, 6).unwrap();
+        assert!(packed.starts_with(b"mdf\0"));
+        let unpacked = Mdf::unpack(MemReaderRef::new(&packed[4..])).unwrap();
+        assert_eq!(unpacked, data);
+    }
 }
@@ -37,7 +37,7 @@ impl ScriptBuilder for ScnScriptBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(ScnScript::new(
             MemReader::new(buf),
@@ -52,7 +52,7 @@ impl ScriptBuilder for ScnScriptBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -75,7 +75,7 @@ impl ScriptBuilder for ScnScriptBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(ScnScript::new(reader, filename, config)?))
     }
@@ -231,19 +231,19 @@ impl Script for ScnScript {
                 PsbValueFixed::Object(obj) => obj,
                 _ => return Err(anyhow::anyhow!("scene at index {} is not an object", i)),
             };
+            let label = scene["label"].as_str();
+            let new_message = |message: String, name: Option<String>, kind: MessageKind| {
+                Message::new(message, name)
+                    .with_kind(kind)
+                    .with_optional_meta(meta_key::LABEL, label)
+            };
             if self.title {
                 if let Some(title) = scene["title"].as_str() {
-                    messages.push(Message {
-                        name: None,
-                        message: title.to_string(),
-                    });
+                    messages.push(new_message(title.to_string(), None, MessageKind::Title));
                 }
                 if scene["title"].is_list() {
                     if let Some(title) = scene["title"][self.language_index].as_str() {
-                        messages.push(Message {
-                            name: None,
-                            message: title.to_string(),
-                        });
+                        messages.push(new_message(title.to_string(), None, MessageKind::Title));
                     }
                 }
             }
@@ -262,9 +262,11 @@ impl Script for ScnScript {
                         };
                         let mut display_name;
                         let mut message;
+                        let message_index;
                         if matches!(values[1], PsbValueFixed::List(_)) {
                             display_name = None;
                             message = &values[1];
+                            message_index = 1;
                         } else {
                             if values.len() <= 2 {
                                 continue; // Skip if there is no message
@@ -279,7 +281,15 @@ impl Script for ScnScript {
                                 }
                             };
                             message = &values[2];
+                            message_index = 2;
                         }
+                        // Voices are stored after message: [{"name": ..., "voice": ...}, ...]
+                        let voice = match values.get(message_index + 1) {
+                            Some(PsbValueFixed::List(voices)) => {
+                                voices.iter().find_map(|v| v["voice"].as_str())
+                            }
+                            _ => None,
+                        };
                         if matches!(message, PsbValueFixed::List(_)) {
                             let tmp = message;
                             if let PsbValueFixed::List(list) = tmp {
@@ -312,17 +322,21 @@ impl Script for ScnScript {
                                         None => name.string(),
                                     };
                                     let message = message.string();
-                                    messages.push(Message {
-                                        name: Some(name.to_string()),
-                                        message: message.replace("\\n", "\n"),
-                                    });
+                                    let message = new_message(
+                                        message.replace("\\n", "\n"),
+                                        Some(name.to_string()),
+                                        MessageKind::Dialogue,
+                                    );
+                                    messages
+                                        .push(message.with_optional_meta(meta_key::VOICE, voice));
                                 }
                                 None => {
                                     let message = message.string();
-                                    messages.push(Message {
-                                        name: None,
-                                        message: message.replace("\\n", "\n"),
-                                    });
+                                    messages.push(new_message(
+                                        message.replace("\\n", "\n"),
+                                        None,
+                                        MessageKind::Dialogue,
+                                    ));
                                 }
                             }
                         }
@@ -364,10 +378,11 @@ impl Script for ScnScript {
                         }
                         if let Some(text) = text {
                             let text = text.string();
-                            messages.push(Message {
-                                name: None,
-                                message: text.replace("\\n", "\n"),
-                            });
+                            messages.push(new_message(
+                                text.replace("\\n", "\n"),
+                                None,
+                                MessageKind::Choice,
+                            ));
                         }
                     }
                 }
@@ -2,9 +2,10 @@
 use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
+use crate::utils::encoding::*;
 use anyhow::Result;
 use overf::wrapping;
-use std::io::Read;
+use std::io::{Read, Write};
 
 #[derive(Debug)]
 /// Kirikiri Simple Crypt Script Builder
@@ -28,10 +29,10 @@ impl ScriptBuilder for SimpleCryptBuilder {
         filename: &str,
         _encoding: Encoding,
         _archive_encoding: Encoding,
-        _config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        config: &ExtraConfig,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
-        Ok(Box::new(SimpleCrypt::new(buf, filename)?))
+        Ok(Box::new(SimpleCrypt::new(buf, filename, config)?))
     }
 
     fn extensions(&self) -> &'static [&'static str] {
@@ -43,18 +44,35 @@ impl ScriptBuilder for SimpleCryptBuilder {
     }
 
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
-        if buf_len >= 5
-            && buf[0] == 0xfe
-            && buf[1] == 0xfe
-            && (buf[2] == 0 || buf[2] == 1 || buf[2] == 2)
-            && buf[3] == 0xff
-            && buf[4] == 0xfe
-        {
+        if SimpleCrypt::is_simple_crypt(&buf[..buf_len]) {
             Some(10)
         } else {
             None
         }
     }
+
+    fn can_create_file(&self) -> bool {
+        true
+    }
+
+    fn create_file<'a>(
+        &'a self,
+        filename: &'a str,
+        mut writer: Box<dyn WriteSeek + 'a>,
+        encoding: Encoding,
+        _file_encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<()> {
+        let data = crate::utils::files::read_file(filename)?;
+        let text = SimpleCrypt::to_utf16le(&data, encoding)?;
+        let packed = SimpleCrypt::pack(
+            config.kirikiri_simple_crypt_mode,
+            &text,
+            config.zlib_compression_level,
+        )?;
+        writer.write_all(&packed)?;
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -64,6 +82,7 @@ pub struct SimpleCrypt {
     crypt: u8,
     data: MemReader,
     ext: String,
+    compress_level: u32,
 }
 
 impl SimpleCrypt {
@@ -71,7 +90,8 @@ impl SimpleCrypt {
     ///
     /// * `buf` - The buffer containing the SimpleCrypt data
     /// * `filename` - The name of the file
-    pub fn new(buf: Vec<u8>, filename: &str) -> Result<Self> {
+    /// * `config` - Extra configuration options
+    pub fn new(buf: Vec<u8>, filename: &str, config: &ExtraConfig) -> Result<Self> {
         let mut reader = MemReader::new(buf);
         let mut header = [0u8; 5];
         reader.read_exact(&mut header)?;
@@ -91,9 +111,31 @@ impl SimpleCrypt {
                 .and_then(|s| s.to_str())
                 .unwrap_or("")
                 .to_string(),
+            compress_level: config.zlib_compression_level,
         })
     }
 
+    /// Returns true if the data starts with a SimpleCrypt header.
+    pub(crate) fn is_simple_crypt(data: &[u8]) -> bool {
+        data.len() >= 5
+            && data[0] == 0xfe
+            && data[1] == 0xfe
+            && (data[2] == 0 || data[2] == 1 || data[2] == 2)
+            && data[3] == 0xff
+            && data[4] == 0xfe
+    }
+
+    /// Converts text file data to UTF-16LE without BOM.
+    ///
+    /// Files without BOM are decoded with the given encoding.
+    pub(crate) fn to_utf16le(data: &[u8], encoding: Encoding) -> Result<Vec<u8>> {
+        if data.starts_with(&[0xff, 0xfe]) {
+            return Ok(data[2..].to_vec());
+        }
+        let (text, _) = decode_with_bom_detect(encoding, data, true)?;
+        encode_string(Encoding::Utf16LE, &text, true)
+    }
+
     pub(crate) fn unpack(crypt: u8, data: MemReaderRef) -> Result<Vec<u8>> {
         match crypt {
             0 => Self::unpack_mode0(data),
@@ -103,17 +145,66 @@ impl SimpleCrypt {
         }
     }
 
+    /// Packs UTF-16LE text (without BOM) into a SimpleCrypt file.
+    pub(crate) fn pack(crypt: u8, text: &[u8], level: u32) -> Result<Vec<u8>> {
+        let mut data = vec![0xfe, 0xfe, crypt, 0xff, 0xfe];
+        match crypt {
+            // Both transforms are involutions, so encrypting is the same as decrypting.
+            0 => {
+                for unit in text.chunks_exact(2) {
+                    let ch = u16::from_le_bytes([unit[0], unit[1]]);
+                    let encrypted = Self::crypt_mode0(ch);
+                    // The engine only decrypts units which are not less than 0x20.
+                    if ch >= 0x20 && encrypted < 0x20 {
+                        return Err(anyhow::anyhow!(
+                            "Character U+{:04X} can not be encrypted with SimpleCrypt mode 0",
+                            ch
+                        ));
+                    }
+                    data.extend_from_slice(&encrypted.to_le_bytes());
+                }
+            }
+            1 => {
+                data.extend(text.iter().map(|&b| {
+                    let ch = b as u32;
+                    let ch = wrapping! {((ch & 0xaaaaaaaa) >> 1) | ((ch & 0x55555555) << 1)};
+                    ch as u8
+                }));
+            }
+            2 => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::new(level));
+                encoder.write_all(text)?;
+                let compressed = encoder.finish()?;
+                data.write_u64(compressed.len() as u64)?;
+                data.write_u64(text.len() as u64)?;
+                data.extend_from_slice(&compressed);
+            }
+            _ => return Err(anyhow::anyhow!("Unsupported SimpleCrypt mode: {}", crypt)),
+        }
+        Ok(data)
+    }
+
+    /// Transforms a UTF-16 code unit in mode 0.
+    fn crypt_mode0(ch: u16) -> u16 {
+        if ch >= 0x20 {
+            ch ^ (((ch & 0xfe) << 8) ^ 1)
+        } else {
+            ch
+        }
+    }
+
     fn unpack_mode0(input: MemReaderRef) -> Result<Vec<u8>> {
         let mut data = Vec::with_capacity(input.data.len() - 3);
         data.push(0xff);
         data.push(0xfe);
-        data.extend_from_slice(&input.data[5..]);
-        for i in 2..data.len() {
-            let ch = data[i] as u16;
-            if ch >= 20 {
-                data[i] = wrapping! {ch ^ (((ch & 0xfe) << 8) ^ 1)} as u8;
-            }
+        let units = input.data[5..].chunks_exact(2);
+        let remainder = units.remainder();
+        for unit in units {
+            let ch = Self::crypt_mode0(u16::from_le_bytes([unit[0], unit[1]]));
+            data.extend_from_slice(&ch.to_le_bytes());
         }
+        data.extend_from_slice(remainder);
         Ok(data)
     }
 
@@ -133,9 +224,9 @@ impl SimpleCrypt {
     fn unpack_mode2(mut reader: MemReaderRef) -> Result<Vec<u8>> {
         reader.pos = 5;
         let compressed = reader.read_u64()?;
-        debug_assert!(compressed + 5 == reader.data.len() as u64);
+        debug_assert!(compressed + 0x15 == reader.data.len() as u64);
         let uncompressed = reader.read_u64()?;
-        let mut stream = flate2::Decompress::new(false);
+        let mut stream = flate2::Decompress::new(true);
         let mut data = Vec::with_capacity(uncompressed as usize + 2);
         data.push(0xff);
         data.push(0xfe);
@@ -172,4 +263,49 @@ impl Script for SimpleCrypt {
         writer.write_all(&data)?;
         Ok(())
     }
+    fn custom_import<'a>(
+        &'a self,
+        custom_filename: &'a str,
+        mut file: Box<dyn WriteSeek + 'a>,
+        encoding: Encoding,
+        _output_encoding: Encoding,
+    ) -> Result<()> {
+        let data = crate::utils::files::read_file(custom_filename)?;
+        let text = Self::to_utf16le(&data, encoding)?;
+        let packed = Self::pack(self.crypt, &text, self.compress_level)?;
+        file.write_all(&packed)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_crypt_round_trip() {
+        let text = encode_string(Encoding::Utf16LE, "@wait time=100\r\nこんにちは", true).unwrap();
+        for crypt in 0..=2 {
+            let packed = SimpleCrypt::pack(crypt, &text, 6).unwrap();
+            assert_eq!(&packed[..5], &[0xfe, 0xfe, crypt, 0xff, 0xfe]);
+            let unpacked = SimpleCrypt::unpack(crypt, MemReaderRef::new(&packed)).unwrap();
+            assert_eq!(&unpacked[..2], &[0xff, 0xfe]);
+            assert_eq!(&unpacked[2..], &text[..]);
+        }
+    }
+
+    #[test]
+    fn test_simple_crypt_mode0_sample() {
+        // "Aあ\r\n" encrypted by the engine: units below 0x20 are kept as is.
+        let sample = [
+            0xfe, 0xfe, 0x00, 0xff, 0xfe, 0x40, 0x40, 0x43, 0x72, 0x0d, 0x00, 0x0a, 0x00,
+        ];
+        let text = encode_string(Encoding::Utf16LE, "Aあ\r\n", true).unwrap();
+        let unpacked = SimpleCrypt::unpack(0, MemReaderRef::new(&sample)).unwrap();
+        assert_eq!(&unpacked[2..], &text[..]);
+        assert_eq!(SimpleCrypt::pack(0, &text, 6).unwrap(), sample);
+        // U+0405 is encrypted to 0x0004, which is not decrypted by the engine.
+        let text = encode_string(Encoding::Utf16LE, "\u{405}", true).unwrap();
+        assert!(SimpleCrypt::pack(0, &text, 6).is_err());
+    }
 }
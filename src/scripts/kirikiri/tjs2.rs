@@ -31,7 +31,7 @@ impl ScriptBuilder for Tjs2Builder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Tjs2::new(buf, encoding, config)?))
     }
@@ -245,6 +245,7 @@ impl Script for Tjs2 {
             messages.push(Message {
                 name: None,
                 message: s.clone(),
+                ..Default::default()
             });
         }
         Ok(messages)
@@ -34,7 +34,7 @@ impl ScriptBuilder for TjsNs0Builder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(TjsNs0::new(buf, filename, encoding, config)?))
     }
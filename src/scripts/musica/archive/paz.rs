@@ -132,7 +132,7 @@ impl ScriptBuilder for PazArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(PazArc::new(
             MemReader::new(buf),
@@ -148,7 +148,7 @@ impl ScriptBuilder for PazArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let f = std::fs::File::open(filename)?;
         let f = std::io::BufReader::new(f);
@@ -167,7 +167,7 @@ impl ScriptBuilder for PazArcBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(PazArc::new(
             reader,
@@ -199,6 +199,10 @@ impl ScriptBuilder for PazArcBuilder {
         None
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
@@ -28,7 +28,7 @@ impl ScriptBuilder for MusicaBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(MusicaScript::new(buf, encoding, config)?))
     }
@@ -83,6 +83,7 @@ impl Script for MusicaScript {
                 let message = Message {
                     name: if name.is_empty() { None } else { Some(name) },
                     message: text,
+                    ..Default::default()
                 };
                 messages.push(message);
             }
@@ -107,6 +107,26 @@ pub fn encrypt(data: &mut [u8], key: u32) -> Result<()> {
     Ok(())
 }
 
+/// Encrypts data with the pack 1.0 scheme, which does not mix the data length into the seed.
+pub fn encrypt10(data: &mut [u8], key: u32) -> Result<()> {
+    let length = data.len();
+    let mut data = MemWriterRef::new(data);
+    const C1: u64 = 0xA73C5F9D;
+    const C2: u64 = 0xCE24F523;
+    const C3: u64 = 0xFEC9753E;
+    let mut v5 = mmx_punpckldq2(C1);
+    const V7: u64 = mmx_punpckldq2(C2);
+    let mut v9 = mmx_punpckldq2((key as u64) ^ C3);
+    for _ in 0..length / 8 {
+        let mut d = data.peek_u64()?;
+        v5 = mmx_p_add_d(v5, V7) ^ v9;
+        v9 = d;
+        d ^= v5;
+        data.write_u64(d)?;
+    }
+    Ok(())
+}
+
 pub fn get_common_key(data: &[u8]) -> Result<Vec<u8>> {
     let mut reader = MemReaderRef::new(data);
     let mut key = vec![0u8; 0x400];
@@ -1042,12 +1062,18 @@ impl Encryption30 {
     }
 }
 
-impl Encryption for Encryption30 {
-    fn decrypt_name(&self, name: &mut [u8], hash: i32, encoding: Encoding) -> Result<String> {
+impl Encryption30 {
+    pub fn encrypt_name(&self, name: &mut [u8], hash: i32) {
         let key = (hash ^ 0x3E) + name.len() as i32;
         for i in 1..=name.len() {
             name[i - 1] ^= ((key ^ i as i32).wrapping_add(i as i32)) as u8;
         }
+    }
+}
+
+impl Encryption for Encryption30 {
+    fn decrypt_name(&self, name: &mut [u8], hash: i32, encoding: Encoding) -> Result<String> {
+        self.encrypt_name(name, hash);
         Ok(decode_to_string(encoding, name, true)?)
     }
 
@@ -1194,6 +1220,47 @@ struct Encryption30Decrypt<'a> {
     t: usize,
 }
 
+/// Builds the xor table, the initial feedback value and the start index used by pack 3.0 entries.
+fn encryption30_state(
+    raw_name: &[u8],
+    common_key: &[u8],
+    size: u32,
+    key: u32,
+    game_key: &[u8],
+) -> ([u64; 0x10], u64, usize) {
+    let mut hash = 0x85F532u32;
+    let mut seed = 0x33F641u32;
+    for (i, n) in raw_name.iter().enumerate() {
+        hash = hash.wrapping_add(((i & 0xFF) as u32) * (*n as u32));
+        seed ^= hash;
+    }
+    seed = seed.wrapping_add(
+        key ^ ((7 * (size & 0xFFFFFF))
+            .wrapping_add(size)
+            .wrapping_add(hash)
+            .wrapping_add(hash ^ size ^ 0x8F32DC)),
+    );
+    seed = 9 * (seed & 0xFFFFFF);
+    seed ^= 0x453A;
+    let mut mt = MersenneTwister::new(seed);
+    if !common_key.is_empty() {
+        mt.xor_state(common_key);
+    }
+    if !game_key.is_empty() {
+        mt.xor_state(game_key);
+    }
+    let mut table = [0u64; 0x10];
+    for i in 0..0x10 {
+        table[i] = mt.rand64();
+    }
+    for _ in 0..9 {
+        mt.rand();
+    }
+    let hash64 = mt.rand64();
+    let t = mt.rand() as usize & 0xF;
+    (table, hash64, t)
+}
+
 impl<'a> Encryption30Decrypt<'a> {
     pub fn new<'b>(
         stream: Box<dyn ReadSeek + Send + Sync + 'a>,
@@ -1203,36 +1270,7 @@ impl<'a> Encryption30Decrypt<'a> {
         key: u32,
         game_key: &'b [u8],
     ) -> AlignedReader<8, Self> {
-        let mut hash = 0x85F532u32;
-        let mut seed = 0x33F641u32;
-        for (i, n) in raw_name.iter().enumerate() {
-            hash = hash.wrapping_add(((i & 0xFF) as u32) * (*n as u32));
-            seed ^= hash;
-        }
-        seed = seed.wrapping_add(
-            key ^ ((7 * (size & 0xFFFFFF))
-                .wrapping_add(size)
-                .wrapping_add(hash)
-                .wrapping_add(hash ^ size ^ 0x8F32DC)),
-        );
-        seed = 9 * (seed & 0xFFFFFF);
-        seed ^= 0x453A;
-        let mut mt = MersenneTwister::new(seed);
-        if !common_key.is_empty() {
-            mt.xor_state(common_key);
-        }
-        if !game_key.is_empty() {
-            mt.xor_state(game_key);
-        }
-        let mut table = [0u64; 0x10];
-        for i in 0..0x10 {
-            table[i] = mt.rand64();
-        }
-        for _ in 0..9 {
-            mt.rand();
-        }
-        let hash64 = mt.rand64();
-        let t = mt.rand() as usize & 0xF;
+        let (table, hash64, t) = encryption30_state(raw_name, common_key, size, key, game_key);
         AlignedReader::new(Self {
             stream,
             table,
@@ -1242,6 +1280,30 @@ impl<'a> Encryption30Decrypt<'a> {
     }
 }
 
+/// Encrypts a pack 3.0 entry which uses both common key and game key.
+///
+/// `raw_name` is the encoded (not encrypted) entry name.
+pub fn encrypt30(
+    data: &mut [u8],
+    raw_name: &[u8],
+    common_key: &[u8],
+    key: u32,
+    game_key: &[u8],
+) -> Result<()> {
+    let size = data.len() as u32;
+    let (table, mut hash64, mut t) = encryption30_state(raw_name, common_key, size, key, game_key);
+    let mut writer = MemWriterRef::new(data);
+    for _ in 0..size / 8 {
+        let d = writer.peek_u64()?;
+        hash64 = mmx_p_add_d(hash64 ^ table[t], table[t]);
+        writer.write_u64(d ^ hash64)?;
+        hash64 = mmx_p_add_b(hash64, d) ^ d;
+        hash64 = mmx_p_add_w(mmx_p_sll_d(hash64, 1), d);
+        t = (t + 1) & 0xF;
+    }
+    Ok(())
+}
+
 impl<'a> Read for Encryption30Decrypt<'a> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let readed = self.stream.read_most(buf)?;
@@ -1269,13 +1331,19 @@ impl Encryption10 {
     }
 }
 
-impl Encryption for Encryption10 {
-    fn decrypt_name(&self, name: &mut [u8], _hash: i32, encoding: Encoding) -> Result<String> {
+impl Encryption10 {
+    pub fn encrypt_name(&self, name: &mut [u8]) {
         const KEY: u32 = 0xC4 ^ 0x3E;
         for (i, b) in name.iter_mut().enumerate() {
             let i = (i + 1) as u32;
             *b ^= ((KEY ^ i).wrapping_add(i)) as u8;
         }
+    }
+}
+
+impl Encryption for Encryption10 {
+    fn decrypt_name(&self, name: &mut [u8], _hash: i32, encoding: Encoding) -> Result<String> {
+        self.encrypt_name(name);
         decode_to_string(encoding, name, true)
     }
 
@@ -1314,13 +1382,19 @@ impl Encryption20 {
     }
 }
 
-impl Encryption for Encryption20 {
-    fn decrypt_name(&self, name: &mut [u8], _hash: i32, encoding: Encoding) -> Result<String> {
+impl Encryption20 {
+    pub fn encrypt_name(&self, name: &mut [u8]) {
         let key = (0xC4u32 ^ 0x3E).wrapping_add(name.len() as u32);
         for (i, b) in name.iter_mut().enumerate() {
             let i = (i + 1) as u32;
             *b ^= ((key ^ i).wrapping_add(i)) as u8;
         }
+    }
+}
+
+impl Encryption for Encryption20 {
+    fn decrypt_name(&self, name: &mut [u8], _hash: i32, encoding: Encoding) -> Result<String> {
+        self.encrypt_name(name);
         decode_to_string(encoding, name, true)
     }
 
@@ -1390,3 +1464,26 @@ fn test_compress_decompress() -> Result<()> {
     assert_eq!(data.as_slice(), output.as_slice());
     Ok(())
 }
+
+#[test]
+fn test_encrypt30_decrypt() -> Result<()> {
+    let data = b"The quick brown fox jumps over the lazy dog.".repeat(10);
+    let name = b"data\\test.s";
+    let common_key: Vec<u8> = (0..0x100).map(|i| i as u8).collect();
+    let game_key: Vec<u8> = (0..0x100).map(|i| (i * 7) as u8).collect();
+    let mut encrypted = data.clone();
+    encrypt30(&mut encrypted, name, &common_key, 0x1234567, &game_key)?;
+    assert_ne!(data, encrypted);
+    let mut decrypted = Vec::new();
+    Encryption30Decrypt::new(
+        Box::new(MemReader::new(encrypted)),
+        name,
+        &common_key,
+        data.len() as u32,
+        0x1234567,
+        &game_key,
+    )
+    .read_to_end(&mut decrypted)?;
+    assert_eq!(data, decrypted);
+    Ok(())
+}
@@ -0,0 +1,453 @@
+//! Writer for Qlie pack archive 1.0/2.0/3.0
+use super::QliePackVersion;
+use super::encryption::*;
+use super::types::*;
+use crate::ext::io::*;
+use crate::scripts::base::*;
+use crate::types::*;
+use crate::utils::encoding::*;
+use anyhow::Result;
+use rand::RngExt;
+use std::io::{Seek, Write};
+
+/// Qlie pack archive writer for pack 1.0, 2.0 and 3.0
+///
+/// Pack 2.0 and 3.0 contain a key block. The key data of the original pack is reused if it is
+/// provided, otherwise new key data is generated. In pack 3.0, the key of all files is computed
+/// from the key data, and files are encrypted with the key data from game executable and
+/// the key file if they are available.
+pub struct QliePackArchiveWriterLegacy<T: Write + Seek> {
+    writer: T,
+    version: QliePackVersion,
+    header: QlieHeader,
+    qkey: Option<QlieKey>,
+    /// Packed HashVer1.2/1.3 block
+    hash: Vec<u8>,
+    has_key_file: bool,
+    key_file_written: bool,
+    entries: Vec<QlieEntry>,
+    key: u32,
+    /// Key data from `IconKeyImage` in game executable. Only used in pack 3.0.
+    game_key: Option<Vec<u8>>,
+    /// Res key at first, replaced by the content of key file once it is written. Only used in pack 3.0.
+    common_key: Option<Vec<u8>>,
+    compress_files: bool,
+}
+
+/// Same algorithm as [Encryption31::compute_name_hash], but on encoded bytes.
+fn compute_name_hash(name: &[u8]) -> u32 {
+    let mut hash = 0u32;
+    for (i, c) in name.iter().enumerate() {
+        hash = hash.wrapping_add((*c as u32) << ((i + 1) & 7)) & 0x3FFFFFFF;
+    }
+    hash
+}
+
+fn get_pos(hash: u32, count: u32) -> u32 {
+    let v = (hash as u16 as u32)
+        .wrapping_add(hash >> 8)
+        .wrapping_add(hash >> 16);
+    v % count
+}
+
+impl<T: Write + Seek> QliePackArchiveWriterLegacy<T> {
+    /// Creates a new writer.
+    ///
+    /// * `writer` - The writer to write the archive to
+    /// * `files` - The list of files to include in the archive
+    /// * `filename` - The path of the archive, used to find the game executable
+    /// * `encoding` - The encoding used for the archive filenames
+    /// * `version` - The pack version to write
+    /// * `original_key` - The key data of the original pack
+    /// * `config` - Extra configuration options
+    pub fn new(
+        writer: T,
+        files: &[&str],
+        filename: &str,
+        encoding: Encoding,
+        version: QliePackVersion,
+        original_key: Option<[u8; 0x400]>,
+        config: &ExtraConfig,
+    ) -> Result<Self> {
+        let signature: &[u8; 16] = match version {
+            QliePackVersion::V10 => b"FilePackVer1.0\x00\x00",
+            QliePackVersion::V20 => b"FilePackVer2.0\x00\x00",
+            QliePackVersion::V30 => b"FilePackVer3.0\x00\x00",
+            QliePackVersion::V31 => {
+                anyhow::bail!("Qlie pack 3.1 should be written with the v3.1 writer")
+            }
+        };
+        let mut has_key_file = files.contains(&QLIE_KEY_FILE);
+        let mut names: Vec<&str> = Vec::with_capacity(files.len() + 1);
+        if version == QliePackVersion::V30 {
+            if has_key_file || config.qlie_pack_keyfile.is_some() {
+                names.push(QLIE_KEY_FILE);
+            }
+            names.extend(files.iter().filter(|f| **f != QLIE_KEY_FILE));
+        } else {
+            // Key file only has special meaning since pack 3.0.
+            has_key_file = false;
+            names.extend(files.iter());
+        }
+        let header = QlieHeader {
+            signature: *signature,
+            file_count: names.len() as u32,
+            index_offset: 0,
+        };
+        let mut key = 0;
+        let mut qkey = None;
+        if version >= QliePackVersion::V20 {
+            let mut qk = QlieKey {
+                signature: *QLIE_KEY_SIGNATURE,
+                hash_size: 0,
+                key: original_key.unwrap_or([0; 0x400]),
+            };
+            if version == QliePackVersion::V30 {
+                if original_key.is_none() {
+                    rand::rng().fill(&mut qk.key[..0x100]);
+                }
+                let mut hasher = Encryption30Hasher::new();
+                hasher.update(&qk.key[..0x100])?;
+                key = hasher.finalize()? & 0xFFFFFFF;
+            }
+            encrypt(&mut qk.signature, key)?;
+            qkey = Some(qk);
+        }
+        let mut entries = Vec::with_capacity(names.len());
+        for name in names {
+            let raw_name = encode_string(encoding, name, true)?;
+            if raw_name.len() > u16::MAX as usize {
+                anyhow::bail!("File name is too long: {}", name);
+            }
+            entries.push(QlieEntry {
+                raw_name,
+                name: name.to_string(),
+                key,
+                ..Default::default()
+            });
+        }
+        let hash = match version {
+            QliePackVersion::V20 => Self::build_hash(&entries, HASH_VER_1_2_SIGNATURE, 0x200)?,
+            QliePackVersion::V30 => Self::build_hash(&entries, HASH_VER_1_3_SIGNATURE, 0x100)?,
+            _ => Vec::new(),
+        };
+        if let Some(qk) = qkey.as_mut() {
+            qk.hash_size = hash.len() as u32;
+        }
+        let (game_key, common_key) = if version == QliePackVersion::V30 {
+            (find_key_data(filename)?, find_game_key(filename)?)
+        } else {
+            (None, None)
+        };
+        if version == QliePackVersion::V30 && game_key.is_none() {
+            eprintln!(
+                "Warning: Key data is not found in game executable, pack 3.0 files are encrypted without it."
+            );
+            crate::COUNTER.inc_warning();
+        }
+        let mut inner = Self {
+            writer,
+            version,
+            header,
+            qkey,
+            hash,
+            has_key_file,
+            key_file_written: false,
+            entries,
+            key,
+            game_key,
+            common_key,
+            compress_files: config.qlie_pack_compress_files,
+        };
+        if version == QliePackVersion::V30
+            && !has_key_file
+            && let Some(key_path) = &config.qlie_pack_keyfile
+        {
+            let key_data = std::fs::read(key_path)?;
+            inner.has_key_file = true;
+            inner.write_entry(0, key_data)?;
+        }
+        Ok(inner)
+    }
+
+    fn build_hash(entries: &[QlieEntry], signature: &[u8; 16], table_size: u32) -> Result<Vec<u8>> {
+        let mut list: Vec<Vec<(&[u8], u32, u32)>> = vec![Vec::new(); table_size as usize];
+        for (i, entry) in entries.iter().enumerate() {
+            let hash = compute_name_hash(&entry.raw_name);
+            list[get_pos(hash, table_size) as usize].push((&entry.raw_name, hash, i as u32));
+        }
+        let mut hash_data = MemWriter::new();
+        for bucket in list {
+            hash_data.write_u32(bucket.len() as u32)?;
+            for (name, hash, index) in bucket {
+                hash_data.write_u16(name.len() as u16)?;
+                hash_data.write_all(name)?;
+                hash_data.write_u64(index as u64 * 4)?;
+                hash_data.write_u32(hash)?;
+            }
+        }
+        for i in 0..entries.len() as u32 {
+            hash_data.write_u32(i)?;
+        }
+        let mut hash_data = hash_data.into_inner();
+        encrypt(&mut hash_data, 0x0428)?;
+        let hash = QlieHash13 {
+            signature: *signature,
+            const_: table_size,
+            file_count: entries.len() as u32,
+            index_size: entries.len() as u32 * 4,
+            hash_data,
+        };
+        let mut mem = MemWriter::new();
+        mem.write_struct(&hash, false, Encoding::Utf8, &None)?;
+        Ok(mem.into_inner())
+    }
+
+    fn write_entry(&mut self, entry_idx: usize, mut data: Vec<u8>) -> Result<()> {
+        let is_key_file = self.has_key_file && entry_idx == 0;
+        let entry = &mut self.entries[entry_idx];
+        entry.offset = self.writer.stream_position()?;
+        entry.unpacked_size = data.len() as u32;
+        entry.is_packed = 0;
+        entry.is_encrypted = 1;
+        if is_key_file {
+            // The content of key file is needed as common key of other files.
+            let key_data = data.clone();
+            match (&self.game_key, &self.common_key) {
+                (Some(game_key), Some(common_key)) => {
+                    encrypt30(&mut data, &entry.raw_name, common_key, self.key, game_key)?
+                }
+                _ => encrypt(&mut data, self.key)?,
+            }
+            self.common_key = Some(key_data);
+            self.key_file_written = true;
+        } else {
+            if self.compress_files {
+                let compressed = compress(&data)?;
+                if compressed.len() < data.len() {
+                    entry.is_packed = 1;
+                    data = compressed;
+                }
+            }
+            match self.version {
+                QliePackVersion::V10 => encrypt10(&mut data, 0)?,
+                QliePackVersion::V20 => encrypt(&mut data, 0)?,
+                _ => match (&self.game_key, &self.common_key) {
+                    (Some(game_key), Some(common_key)) => {
+                        encrypt30(&mut data, &entry.raw_name, common_key, self.key, game_key)?
+                    }
+                    _ => encrypt(&mut data, self.key)?,
+                },
+            }
+        }
+        entry.size = data.len() as u32;
+        if self.version >= QliePackVersion::V20 {
+            let mut hasher = Encryption30Hasher::new();
+            hasher.update(&data)?;
+            entry.hash = hasher.finalize()?;
+        }
+        self.writer.write_all(&data)?;
+        Ok(())
+    }
+}
+
+struct Writer<'a, T: Write + Seek> {
+    inner: &'a mut QliePackArchiveWriterLegacy<T>,
+    entry_idx: usize,
+    mem: MemWriter,
+}
+
+impl<T: Write + Seek> std::fmt::Debug for Writer<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Writer").field("mem", &self.mem).finish()
+    }
+}
+
+impl<'a, T: Write + Seek> Write for Writer<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.mem.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.mem.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for Writer<'a, T> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.mem.seek(pos)
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        self.mem.stream_position()
+    }
+
+    fn rewind(&mut self) -> std::io::Result<()> {
+        self.mem.rewind()
+    }
+}
+
+impl<'a, T: Write + Seek> Drop for Writer<'a, T> {
+    fn drop(&mut self) {
+        let mut nw = MemWriter::new();
+        std::mem::swap(&mut self.mem, &mut nw);
+        if let Err(e) = self.inner.write_entry(self.entry_idx, nw.into_inner()) {
+            eprintln!("Failed to write file to Qlie pack archive: {}", e);
+            crate::COUNTER.inc_error();
+        }
+    }
+}
+
+impl<T: Write + Seek> Archive for QliePackArchiveWriterLegacy<T> {
+    fn prelist<'a>(&'a self) -> Result<Option<Box<dyn Iterator<Item = Result<String>> + 'a>>> {
+        if !self.has_key_file || self.key_file_written {
+            Ok(None)
+        } else {
+            let iter = std::iter::once(Ok(QLIE_KEY_FILE.to_string()));
+            Ok(Some(Box::new(iter)))
+        }
+    }
+
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        if self.has_key_file && !self.key_file_written && name != QLIE_KEY_FILE {
+            anyhow::bail!("Common key is not available before writing key file");
+        }
+        let entry_idx = self
+            .entries
+            .iter()
+            .position(|e| e.name == name)
+            .ok_or_else(|| anyhow::anyhow!("File {} not found in entries", name))?;
+        Ok(Box::new(Writer {
+            inner: self,
+            entry_idx,
+            mem: MemWriter::new(),
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        self.header.index_offset = self.writer.stream_position()?;
+        for entry in &self.entries {
+            let mut name = entry.raw_name.clone();
+            match self.version {
+                QliePackVersion::V10 => Encryption10::new().encrypt_name(&mut name),
+                QliePackVersion::V20 => Encryption20::new().encrypt_name(&mut name),
+                _ => Encryption30::new(None).encrypt_name(&mut name, self.key as i32),
+            }
+            self.writer.write_u16(name.len() as u16)?;
+            self.writer.write_all(&name)?;
+            self.writer.write_u64(entry.offset)?;
+            self.writer.write_u32(entry.size)?;
+            self.writer.write_u32(entry.unpacked_size)?;
+            self.writer.write_u32(entry.is_packed)?;
+            self.writer.write_u32(entry.is_encrypted)?;
+            if self.version >= QliePackVersion::V20 {
+                self.writer.write_u32(entry.hash)?;
+            }
+        }
+        self.writer.write_all(&self.hash)?;
+        if let Some(qkey) = &self.qkey {
+            self.writer
+                .write_struct(qkey, false, Encoding::Utf8, &None)?;
+        }
+        self.writer
+            .write_struct(&self.header, false, Encoding::Utf8, &None)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_roundtrip() {
+    use super::QliePackArchive;
+    let dir = crate::utils::testing::temp_dir("qlie-legacy");
+    let files = [
+        ("a.txt", b"Hello world.".repeat(100)),
+        (
+            "b/c.bin",
+            (0..5000u32).map(|i| (i * 7 + i / 13) as u8).collect(),
+        ),
+    ];
+    let names: Vec<&str> = files.iter().map(|(n, _)| *n).collect();
+    let config = ExtraConfig {
+        qlie_pack_compress_files: true,
+        ..Default::default()
+    };
+    let write = |filename: &str, version: QliePackVersion, key: Option<[u8; 0x400]>| {
+        let f = std::fs::File::create(filename).unwrap();
+        let mut writer = QliePackArchiveWriterLegacy::new(
+            std::io::BufWriter::new(f),
+            &names,
+            filename,
+            Encoding::Cp932,
+            version,
+            key,
+            &config,
+        )
+        .unwrap();
+        for (name, data) in &files {
+            writer
+                .new_file(name, None)
+                .unwrap()
+                .write_all(data)
+                .unwrap();
+        }
+        writer.write_header().unwrap();
+    };
+    let read = |filename: &str| {
+        let f = std::io::BufReader::new(std::fs::File::open(filename).unwrap());
+        QliePackArchive::new(f, Encoding::Cp932, &config, filename).unwrap()
+    };
+    for (version, signature) in [
+        (QliePackVersion::V10, "FilePackVer1.0"),
+        (QliePackVersion::V20, "FilePackVer2.0"),
+        (QliePackVersion::V30, "FilePackVer3.0"),
+    ] {
+        let filename = dir.join(format!("{}.pack", signature));
+        let filename = filename.to_string_lossy();
+        write(&filename, version, None);
+        let pack = read(&filename);
+        assert!(pack.header.signature.starts_with(signature.as_bytes()));
+        for (i, (name, data)) in files.iter().enumerate() {
+            let mut file = pack.open_file(i).unwrap();
+            assert_eq!(file.name(), *name);
+            let mut content = Vec::new();
+            file.to_data().unwrap().read_to_end(&mut content).unwrap();
+            assert_eq!(&content, data);
+        }
+        // Rebuilding the pack from the original one keeps the key data.
+        if version >= QliePackVersion::V20 {
+            let key = super::read_pack_key(&filename).unwrap();
+            let rebuilt = dir.join(format!("{}.rebuilt.pack", signature));
+            let rebuilt = rebuilt.to_string_lossy();
+            let rebuild_config = ExtraConfig {
+                qlie_pack_version: version,
+                qlie_pack_original: Some(filename.to_string()),
+                ..Default::default()
+            };
+            let mut writer = super::QliePackArchiveBuilder::new()
+                .create_archive(&rebuilt, &names, Encoding::Cp932, &rebuild_config)
+                .unwrap();
+            for (name, data) in &files {
+                writer
+                    .new_file(name, None)
+                    .unwrap()
+                    .write_all(data)
+                    .unwrap();
+            }
+            writer.write_header().unwrap();
+            drop(writer);
+            assert_eq!(super::read_pack_key(&rebuilt).unwrap(), key);
+            let pack = read(&rebuilt);
+            let mut content = Vec::new();
+            let mut file = pack.open_file(1).unwrap();
+            file.to_data().unwrap().read_to_end(&mut content).unwrap();
+            assert_eq!(content, files[1].1);
+        }
+    }
+    let v10 = dir.join("FilePackVer1.0.pack");
+    assert!(super::read_pack_key(&v10.to_string_lossy()).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
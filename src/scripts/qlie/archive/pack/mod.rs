@@ -1,6 +1,7 @@
 //! Qlie Pack Archive (.pack)
 mod delphi;
 mod encryption;
+mod legacy;
 mod twister;
 mod types;
 mod v31;
@@ -10,11 +11,30 @@ use crate::scripts::base::*;
 use crate::types::*;
 use crate::utils::struct_pack::*;
 use anyhow::Result;
+use clap::ValueEnum;
 use encryption::Encryption;
 use std::io::{Read, Seek, SeekFrom};
 use std::sync::{Arc, Mutex};
 use types::*;
 
+#[derive(Clone, Copy, Debug, Default, ValueEnum, PartialEq, Eq, PartialOrd, Ord)]
+/// Qlie Pack Archive version used when packing
+pub enum QliePackVersion {
+    #[value(name = "1.0")]
+    /// FilePackVer1.0
+    V10,
+    #[value(name = "2.0")]
+    /// FilePackVer2.0
+    V20,
+    #[value(name = "3.0")]
+    /// FilePackVer3.0, files are encrypted with key data from game executable if found.
+    V30,
+    #[default]
+    #[value(name = "3.1")]
+    /// FilePackVer3.1
+    V31,
+}
+
 #[derive(Debug)]
 pub struct QliePackArchiveBuilder {}
 
@@ -40,7 +60,7 @@ impl ScriptBuilder for QliePackArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(QliePackArchive::new(
             MemReader::new(data),
@@ -56,7 +76,7 @@ impl ScriptBuilder for QliePackArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if filename == "-" {
             let data = crate::utils::files::read_file(filename)?;
@@ -85,7 +105,7 @@ impl ScriptBuilder for QliePackArchiveBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(QliePackArchive::new(
             reader,
@@ -117,18 +137,37 @@ impl ScriptBuilder for QliePackArchiveBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
     fn create_archive(
         &self,
         filename: &str,
         files: &[&str],
-        _encoding: Encoding,
+        encoding: Encoding,
         config: &ExtraConfig,
     ) -> Result<Box<dyn Archive>> {
+        let original_key = match (config.qlie_pack_version, &config.qlie_pack_original) {
+            (QliePackVersion::V31, _) | (_, None) => None,
+            (_, Some(original)) => Some(read_pack_key(original)?),
+        };
         let f = std::fs::File::create(filename)?;
         let buf = std::io::BufWriter::new(f);
-        Ok(Box::new(v31::QliePackArchiveWriterV31::new(
-            buf, files, config,
-        )?))
+        match config.qlie_pack_version {
+            QliePackVersion::V31 => Ok(Box::new(v31::QliePackArchiveWriterV31::new(
+                buf, files, config,
+            )?)),
+            version => Ok(Box::new(legacy::QliePackArchiveWriterLegacy::new(
+                buf,
+                files,
+                filename,
+                encoding,
+                version,
+                original_key,
+                config,
+            )?)),
+        }
     }
 }
 
@@ -144,6 +183,26 @@ pub fn is_this_format<P: AsRef<std::path::Path> + ?Sized>(path: &P) -> Result<bo
     Ok(header.is_valid())
 }
 
+/// Reads the key data of an existing pack, so a rebuilt pack keeps the key of the original one.
+fn read_pack_key(filename: &str) -> Result<[u8; 0x400]> {
+    if !is_this_format(filename)? {
+        anyhow::bail!("'{}' is not a Qlie pack archive", filename);
+    }
+    let mut file = std::fs::File::open(filename)?;
+    file.seek(SeekFrom::End(-0x1C))?;
+    let header = QlieHeader::unpack(&mut file, false, Encoding::Utf8, &None)?;
+    if header.major_version() < 2 {
+        anyhow::bail!(
+            "Qlie pack archive '{}' (version {}) does not contain key data",
+            filename,
+            header.major_version()
+        );
+    }
+    file.seek(SeekFrom::End(-0x440))?;
+    let qkey = QlieKey::unpack(&mut file, false, Encoding::Utf8, &None)?;
+    Ok(qkey.key)
+}
+
 #[derive(Debug)]
 pub struct QliePackArchive<'b, T: Read + Seek + std::fmt::Debug + Send + Sync + 'b> {
     header: QlieHeader,
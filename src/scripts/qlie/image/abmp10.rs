@@ -30,7 +30,7 @@ impl ScriptBuilder for Abmp10ImageBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Abmp10Image::new(
             MemReader::new(buf),
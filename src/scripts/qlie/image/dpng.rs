@@ -63,7 +63,7 @@ impl ScriptBuilder for DpngImageBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(DpngImage::new(MemReader::new(buf), config)?))
     }
@@ -31,7 +31,7 @@ impl ScriptBuilder for MesBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Mes::new(buf, encoding, config)?))
     }
@@ -279,6 +279,7 @@ impl Script for Mes {
                     messages.push(Message {
                         name: name.take(),
                         message,
+                        ..Default::default()
                     });
                 }
             }
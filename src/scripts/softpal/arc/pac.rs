@@ -4,7 +4,8 @@ use crate::ext::io::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use anyhow::{Result, anyhow, ensure};
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
 const SOFTPAL_INDEX_OFFSET: u64 = 0x3FE;
@@ -55,7 +56,7 @@ impl ScriptBuilder for SoftpalPacBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(SoftpalPacArchive::new(
             MemReader::new(buf),
@@ -71,7 +72,7 @@ impl ScriptBuilder for SoftpalPacBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         let file = std::fs::File::open(filename)?;
         let reader = std::io::BufReader::new(file);
@@ -90,7 +91,7 @@ impl ScriptBuilder for SoftpalPacBuilder {
         _encoding: Encoding,
         archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
         Ok(Box::new(SoftpalPacArchive::new(
             reader,
@@ -115,6 +116,28 @@ impl ScriptBuilder for SoftpalPacBuilder {
         true
     }
 
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(SoftpalPacWriter::new(
+            writer,
+            files,
+            encoding,
+            config,
+            self.variant,
+        )?))
+    }
+
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
         match self.variant {
             SoftpalPacVariant::Softpal => None,
@@ -292,7 +315,7 @@ impl<'b, T: Read + Seek + std::fmt::Debug + Send + Sync + 'b> Script for Softpal
 }
 
 fn should_decrypt_entry(data: &[u8]) -> bool {
-    data.len() > 16 && data[0] == b'$'
+    data.len() >= 16 && data[0] == b'$'
 }
 
 fn decrypt_entry(data: &mut [u8]) {
@@ -311,6 +334,21 @@ fn decrypt_entry(data: &mut [u8]) {
     }
 }
 
+/// Reverses [decrypt_entry].
+fn encrypt_entry(data: &mut [u8]) {
+    if data.len() <= 16 {
+        return;
+    }
+    let mut shift: u32 = 4;
+    for chunk in data[16..].chunks_exact_mut(4) {
+        let mut block =
+            (u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) ^ XOR_KEY).to_le_bytes();
+        block[0] = block[0].rotate_right(shift & 7);
+        shift = shift.wrapping_add(1);
+        chunk.copy_from_slice(&block);
+    }
+}
+
 #[derive(Debug)]
 struct MemEntry {
     name: String,
@@ -465,3 +503,194 @@ impl<T: Read + Seek + std::fmt::Debug> Seek for PacEntry<T> {
         Ok(self.pos as u64)
     }
 }
+
+/// Softpal PAC archive writer.
+pub struct SoftpalPacWriter<T: Write + Seek> {
+    writer: T,
+    headers: HashMap<String, SoftpalPacEntry>,
+    index_offset: u64,
+    encoding: Encoding,
+}
+
+impl<T: Write + Seek> SoftpalPacWriter<T> {
+    fn new(
+        mut writer: T,
+        files: &[&str],
+        archive_encoding: Encoding,
+        _config: &ExtraConfig,
+        variant: SoftpalPacVariant,
+    ) -> Result<Self> {
+        let encoding = match archive_encoding {
+            Encoding::Auto => Encoding::Cp932,
+            other => other,
+        };
+        let index_offset = match variant {
+            SoftpalPacVariant::Softpal => {
+                writer.write_u32(files.len() as u32)?;
+                SOFTPAL_INDEX_OFFSET
+            }
+            SoftpalPacVariant::Amuse => {
+                writer.write_all(b"PAC ")?;
+                writer.write_u32(0)?;
+                writer.write_u32(files.len() as u32)?;
+                AMUSE_INDEX_OFFSET
+            }
+        };
+        let header_len = writer.stream_position()?;
+        writer.write_all(&vec![0; (index_offset - header_len) as usize])?;
+        let mut headers = HashMap::new();
+        for file in files {
+            // Names are checked here, the index is written in write_header.
+            writer.write_fstring(file, 0x20, encoding, 0, false)?;
+            writer.write_u32(0)?;
+            writer.write_u32(0)?;
+            headers.insert(
+                file.to_string(),
+                SoftpalPacEntry {
+                    name: file.to_string(),
+                    offset: 0,
+                    size: 0,
+                },
+            );
+        }
+        Ok(Self {
+            writer,
+            headers,
+            index_offset,
+            encoding,
+        })
+    }
+}
+
+impl<T: Write + Seek> Archive for SoftpalPacWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let entry = self
+            .headers
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("File '{name}' not found in archive"))?;
+        if entry.offset != 0 {
+            return Err(anyhow!("File '{name}' already exists in archive"));
+        }
+        entry.offset = u32::try_from(self.writer.seek(SeekFrom::End(0))?)
+            .map_err(|_| anyhow!("PAC archive is too large"))?;
+        Ok(Box::new(PacArchiveBufferedFile {
+            file: PacArchiveFile {
+                header: entry,
+                writer: &mut self.writer,
+                pos: 0,
+            },
+            buf: MemWriter::new(),
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let file_len = u32::try_from(self.writer.seek(SeekFrom::End(0))?)
+            .map_err(|_| anyhow!("PAC archive is too large"))?;
+        let mut files = self.headers.values_mut().collect::<Vec<_>>();
+        for file in files.iter_mut() {
+            if file.offset == 0 {
+                file.offset = file_len;
+            }
+        }
+        // The reader checks that the first entry starts right after the index.
+        files.sort_by_key(|f| f.offset);
+        self.writer.seek(SeekFrom::Start(self.index_offset))?;
+        for file in files {
+            self.writer
+                .write_fstring(&file.name, 0x20, self.encoding, 0, false)?;
+            self.writer.write_u32(file.size)?;
+            self.writer.write_u32(file.offset)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Softpal PAC archive file.
+pub struct PacArchiveFile<'a, T: Write + Seek> {
+    header: &'a mut SoftpalPacEntry,
+    writer: &'a mut T,
+    pos: u64,
+}
+
+impl<'a, T: Write + Seek> Write for PacArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer
+            .seek(SeekFrom::Start(self.header.offset as u64 + self.pos))?;
+        let bytes_written = self.writer.write(buf)?;
+        self.pos += bytes_written as u64;
+        self.header.size = self.header.size.max(self.pos as u32);
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for PacArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.header.size as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek position is out of bounds",
+            ));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
+/// Softpal PAC archive file which is written when dropped.
+///
+/// Entries starting with `$` are encrypted, so the whole content is needed.
+pub struct PacArchiveBufferedFile<'a, T: Write + Seek> {
+    file: PacArchiveFile<'a, T>,
+    buf: MemWriter,
+}
+
+impl<'a, T: Write + Seek> Write for PacArchiveBufferedFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.buf.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for PacArchiveBufferedFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.buf.seek(pos)
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        self.buf.stream_position()
+    }
+
+    fn rewind(&mut self) -> std::io::Result<()> {
+        self.buf.rewind()
+    }
+}
+
+impl<'a, T: Write + Seek> Drop for PacArchiveBufferedFile<'a, T> {
+    fn drop(&mut self) {
+        let mut data = std::mem::take(&mut self.buf.data);
+        if should_decrypt_entry(&data) {
+            encrypt_entry(&mut data);
+        }
+        if let Err(e) = self.file.write_all(&data) {
+            eprintln!("Failed to write PAC entry: {}", e);
+            crate::COUNTER.inc_error();
+        }
+    }
+}
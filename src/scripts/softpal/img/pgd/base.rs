@@ -314,16 +314,26 @@ impl PgdWriter {
         self
     }
 
-    pub fn pack_ge<W: Write>(mut self, mut writer: W) -> Result<()> {
+    pub fn pack_ge<W: Write>(mut self, writer: W) -> Result<()> {
         let data = match self.method {
             3 => self.process3()?,
             _ => panic!("Unsupported GE mode: {}", self.method),
         };
+        self.write_compressed(&data, writer)
+    }
+
+    /// Packs the image as overlay data of differential PGD images.
+    pub fn pack_overlay<W: Write>(mut self, writer: W) -> Result<()> {
+        let data = self.process_pal()?;
+        self.write_compressed(&data, writer)
+    }
+
+    fn write_compressed<W: Write>(&self, data: &[u8], mut writer: W) -> Result<()> {
         let unpacked_len = data.len() as u32;
         let compressed = if self.fake_compress {
-            ge_fake_compress(&data)?
+            ge_fake_compress(data)?
         } else {
-            ge_compress(&data)?
+            ge_compress(data)?
         };
         let packed_len = compressed.len() as u32;
         writer.write_u32(unpacked_len)?;
@@ -27,7 +27,7 @@ impl ScriptBuilder for PgdGeBuilder {
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(PgdGe::new(MemReader::new(buf), config)?))
     }
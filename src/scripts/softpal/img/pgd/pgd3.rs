@@ -28,7 +28,7 @@ impl ScriptBuilder for Pgd3Builder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(Pgd3::new(
             MemReader::new(buf),
@@ -61,11 +61,13 @@ impl ScriptBuilder for Pgd3Builder {
 
 #[derive(Debug)]
 pub struct Pgd3 {
+    signature: [u8; 4],
     header: PgdDiffHeader,
     base_header: PgdGeHeader,
     base: ImageData,
     diff: ImageData,
     fake_compress: bool,
+    encoding: Encoding,
 }
 
 impl Pgd3 {
@@ -74,13 +76,14 @@ impl Pgd3 {
         filename: &str,
         encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Self> {
         let mut sig = [0u8; 4];
         reader.read_exact(&mut sig)?;
         if &sig != b"PGD3" && &sig != b"PGD2" {
             return Err(anyhow::anyhow!("Not a valid PGD3/PGD2 file"));
         }
+        let signature = sig;
         let header = PgdDiffHeader::unpack(&mut reader, false, encoding, &None)?;
         let diff = PgdReader::with_diff_header(reader, &header)?.unpack_overlay()?;
         let base: Vec<u8> = if let Some(archive) = archive {
@@ -107,13 +110,30 @@ impl Pgd3 {
         let base_header = PgdGeHeader::unpack(&mut reader, false, encoding, &None)?;
         let base = PgdReader::with_ge_header(reader, &base_header)?.unpack_ge()?;
         Ok(Self {
+            signature,
             header,
             base_header,
             base,
             diff,
             fake_compress: config.pgd_fake_compress,
+            encoding,
         })
     }
+
+    /// Returns the base image drawn on its canvas.
+    fn base_image(&self) -> Result<ImageData> {
+        if self.base_header.is_base_file() {
+            Ok(self.base.clone())
+        } else {
+            draw_on_canvas(
+                self.base.clone(),
+                self.base_header.canvas_width,
+                self.base_header.canvas_height,
+                self.base_header.offset_x,
+                self.base_header.offset_y,
+            )
+        }
+    }
 }
 
 impl Script for Pgd3 {
@@ -130,17 +150,7 @@ impl Script for Pgd3 {
     }
 
     fn export_image(&self) -> Result<ImageData> {
-        let mut base = if self.base_header.is_base_file() {
-            self.base.clone()
-        } else {
-            draw_on_canvas(
-                self.base.clone(),
-                self.base_header.canvas_width,
-                self.base_header.canvas_height,
-                self.base_header.offset_x,
-                self.base_header.offset_y,
-            )?
-        };
+        let mut base = self.base_image()?;
         draw_on_img(
             &mut base,
             &self.diff,
@@ -152,40 +162,94 @@ impl Script for Pgd3 {
 
     fn import_image<'a>(
         &'a self,
-        data: ImageData,
+        mut data: ImageData,
         _filename: &str,
         mut file: Box<dyn WriteSeek + 'a>,
     ) -> Result<()> {
-        let mut header = PgdGeHeader {
-            offset_x: self.base_header.offset_x,
-            offset_y: self.base_header.offset_y,
-            width: self.base_header.width,
-            height: self.base_header.height,
-            canvas_height: self.base_header.canvas_height,
-            canvas_width: self.base_header.canvas_width,
-            mode: self.base_header.mode,
-            _unk: self.base_header._unk,
-        };
-        if data.height != header.height {
+        let base = self.base_image()?;
+        if data.height != base.height {
             return Err(anyhow::anyhow!(
                 "Image height does not match: expected {}, got {}",
-                header.height,
+                base.height,
                 data.height
             ));
         }
-        if data.width != header.width {
+        if data.width != base.width {
             return Err(anyhow::anyhow!(
                 "Image width does not match: expected {}, got {}",
-                header.width,
+                base.width,
                 data.width
             ));
         }
-        header.mode = 3;
-        file.write_all(b"GE \0")?;
-        header.pack(&mut file, false, Encoding::Utf8, &None)?;
-        PgdWriter::new(data, self.fake_compress)
-            .with_method(3)
-            .pack_ge(&mut file)?;
+        match data.color_type {
+            ImageColorType::Bgr | ImageColorType::Bgra => {}
+            ImageColorType::Rgb => convert_rgb_to_bgr(&mut data)?,
+            ImageColorType::Rgba => convert_rgba_to_bgra(&mut data)?,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported color type: {:?}",
+                    data.color_type
+                ));
+            }
+        }
+        if data.color_type != base.color_type {
+            if base.color_type == ImageColorType::Bgr {
+                convert_bgra_to_bgr(&mut data)?;
+            } else {
+                convert_bgr_to_bgra(&mut data)?;
+            }
+        }
+        let bpp = base.color_type.bpp(1) as usize;
+        let stride = base.width as usize * bpp;
+        // The original region is kept and extended to cover all changed pixels.
+        let mut left = (self.header.offset_x as u32).min(base.width);
+        let mut top = (self.header.offset_y as u32).min(base.height);
+        let mut right = (self.header.offset_x as u32 + self.header.width as u32).min(base.width);
+        let mut bottom = (self.header.offset_y as u32 + self.header.height as u32).min(base.height);
+        for y in 0..base.height {
+            let row = y as usize * stride;
+            for x in 0..base.width {
+                let pos = row + x as usize * bpp;
+                if data.data[pos..pos + bpp] != base.data[pos..pos + bpp] {
+                    left = left.min(x);
+                    top = top.min(y);
+                    right = right.max(x + 1);
+                    bottom = bottom.max(y + 1);
+                }
+            }
+        }
+        if left >= right || top >= bottom {
+            (left, top, right, bottom) = (0, 0, 1, 1);
+        }
+        let mut diff = Vec::with_capacity(((right - left) * (bottom - top)) as usize * bpp);
+        for y in top..bottom {
+            let start = y as usize * stride + left as usize * bpp;
+            let end = y as usize * stride + right as usize * bpp;
+            diff.extend(
+                data.data[start..end]
+                    .iter()
+                    .zip(&base.data[start..end])
+                    .map(|(a, b)| a ^ b),
+            );
+        }
+        let diff = ImageData {
+            width: right - left,
+            height: bottom - top,
+            color_type: base.color_type,
+            depth: 8,
+            data: diff,
+        };
+        let header = PgdDiffHeader {
+            offset_x: left as u16,
+            offset_y: top as u16,
+            width: diff.width as u16,
+            height: diff.height as u16,
+            bpp: (bpp * 8) as u16,
+            base_name: self.header.base_name.clone(),
+        };
+        file.write_all(&self.signature)?;
+        header.pack(&mut file, false, self.encoding, &None)?;
+        PgdWriter::new(diff, self.fake_compress).pack_overlay(&mut file)?;
         Ok(())
     }
 }
@@ -237,3 +301,73 @@ fn draw_on_img(base: &mut ImageData, diff: &ImageData, left: u32, top: u32) -> R
     }
     Ok(())
 }
+
+#[test]
+fn test_import() {
+    use crate::utils::testing::test_image;
+    use std::io::Write;
+    let config = ExtraConfig::default();
+    let dir = crate::utils::testing::temp_dir("pgd3");
+    let mut base = MemWriter::new();
+    base.write_all(b"GE \0").unwrap();
+    PgdGeHeader {
+        offset_x: 0,
+        offset_y: 0,
+        width: 10,
+        height: 7,
+        canvas_width: 10,
+        canvas_height: 7,
+        mode: 3,
+        _unk: 0,
+    }
+    .pack(&mut base, false, Encoding::Utf8, &None)
+    .unwrap();
+    PgdWriter::new(test_image(10, 7, 7), false)
+        .pack_ge(&mut base)
+        .unwrap();
+    std::fs::write(dir.join("BASE.PGD"), base.into_inner()).unwrap();
+    let mut pgd3 = MemWriter::new();
+    pgd3.write_all(b"PGD3").unwrap();
+    PgdDiffHeader {
+        offset_x: 2,
+        offset_y: 1,
+        width: 4,
+        height: 3,
+        bpp: 32,
+        base_name: "base.pgd".into(),
+    }
+    .pack(&mut pgd3, false, Encoding::Cp932, &None)
+    .unwrap();
+    PgdWriter::new(test_image(4, 3, 13), false)
+        .pack_overlay(&mut pgd3)
+        .unwrap();
+    let filename = dir.join("diff.pgd").to_string_lossy().into_owned();
+    let open = |data: Vec<u8>| {
+        Pgd3::new(
+            MemReader::new(data),
+            &filename,
+            Encoding::Cp932,
+            &config,
+            None,
+        )
+        .unwrap()
+    };
+
+    let script = open(pgd3.into_inner());
+    let mut img = script.export_image().unwrap();
+    // Change pixels inside and outside of the original region.
+    for (x, y) in [(3, 2), (8, 5)] {
+        let pos = (y * 10 + x) * 4;
+        img.data[pos] ^= 0x5A;
+        img.data[pos + 3] = 0x10;
+    }
+    let mut writer = MemWriter::new();
+    script
+        .import_image(img.clone(), &filename, Box::new(&mut writer))
+        .unwrap();
+    let imported = open(writer.into_inner());
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((imported.header.offset_x, imported.header.offset_y), (2, 1));
+    assert_eq!((imported.header.width, imported.header.height), (7, 5));
+    assert_eq!(imported.export_image().unwrap().data, img.data);
+}
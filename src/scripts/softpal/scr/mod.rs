@@ -2,6 +2,7 @@
 mod disasm;
 
 use crate::ext::io::*;
+use crate::format::markup::*;
 use crate::scripts::base::*;
 use crate::types::*;
 use crate::utils::encoding::*;
@@ -33,7 +34,7 @@ impl ScriptBuilder for SoftpalScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(SoftpalScript::new(
             buf, filename, encoding, config, archive,
@@ -48,6 +49,10 @@ impl ScriptBuilder for SoftpalScriptBuilder {
         &ScriptType::Softpal
     }
 
+    fn inline_markup(&self) -> Option<&'static dyn InlineMarkup> {
+        Some(&SoftpalMarkup)
+    }
+
     fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
         if buf_len >= 4 && buf.starts_with(b"Sv20") {
             return Some(10);
@@ -56,6 +61,21 @@ impl ScriptBuilder for SoftpalScriptBuilder {
     }
 }
 
+#[derive(Debug)]
+/// Inline tags (`<br>`, etc.) in Softpal messages
+struct SoftpalMarkup;
+
+impl InlineMarkup for SoftpalMarkup {
+    fn parse(&self, text: &str) -> Option<(Markup, usize)> {
+        let len = xml_tag_len(text)?;
+        if text[..len].eq_ignore_ascii_case("<br>") {
+            Some((Markup::LineBreak, len))
+        } else {
+            Some((Markup::Tag, len))
+        }
+    }
+}
+
 #[derive(Debug)]
 /// Softpal SRC Script
 pub struct SoftpalScript {
@@ -74,7 +94,7 @@ impl SoftpalScript {
         filename: &str,
         encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Self> {
         let texts = Self::load_texts_data(Self::load_file(filename, archive, "TEXT.DAT")?)?;
         let points_data = MemReader::new(Self::load_file(filename, archive, "POINT.DAT")?);
@@ -90,7 +110,7 @@ impl SoftpalScript {
         })
     }
 
-    fn load_file(filename: &str, archive: Option<&Box<dyn Script>>, name: &str) -> Result<Vec<u8>> {
+    fn load_file(filename: &str, archive: Option<&dyn Script>, name: &str) -> Result<Vec<u8>> {
         if let Some(archive) = archive {
             Ok(archive
                 .open_file_by_name(name, true)
@@ -198,6 +218,7 @@ impl Script for SoftpalScript {
                 StringType::Message => messages.push(Message {
                     name: name.take(),
                     message: text,
+                    ..Default::default()
                 }),
                 StringType::Hover => messages.push(Message::new(text, None)),
                 StringType::Label => {} // Ignore labels
@@ -5,6 +5,7 @@ use crate::utils::img::*;
 use crate::utils::struct_pack::*;
 use anyhow::{Context, Result, anyhow};
 use msg_tool_macro::*;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::ops::Range;
@@ -28,16 +29,17 @@ impl ScriptBuilder for WillPlusWipImageBuilder {
     fn build_script(
         &self,
         data: Vec<u8>,
-        _filename: &str,
+        filename: &str,
         _encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
-        Ok(Box::new(WillPlusWipImage::new(
-            MemReader::new(data),
-            config,
-        )?))
+        let mut image = WillPlusWipImage::new(MemReader::new(data), config)?;
+        if archive.is_none() && image.bpp == 24 {
+            image.mask = load_mask(filename, config)?.map(Box::new);
+        }
+        Ok(Box::new(image))
     }
 
     fn extensions(&self) -> &'static [&'static str] {
@@ -60,6 +62,78 @@ impl ScriptBuilder for WillPlusWipImageBuilder {
     fn is_image(&self) -> bool {
         true
     }
+
+    #[cfg(feature = "image")]
+    fn can_create_image_file(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "image")]
+    fn create_image_file<'a>(
+        &'a self,
+        data: ImageData,
+        _filename: &str,
+        writer: Box<dyn WriteSeek + 'a>,
+        _options: &ExtraConfig,
+    ) -> Result<()> {
+        create_wip(vec![data], writer)
+    }
+
+    #[cfg(feature = "image")]
+    fn can_create_multi_image_file(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "image")]
+    fn create_multi_image_file<'a>(
+        &'a self,
+        data: Vec<ImageDataWithName>,
+        writer: Box<dyn WriteSeek + 'a>,
+        _options: &ExtraConfig,
+    ) -> Result<()> {
+        create_wip(data.into_iter().map(|d| d.data).collect(), writer)
+    }
+}
+
+/// Creates a WIP image from frames.
+///
+/// Grayscale frames (e.g. `.msk` masks) are stored as 8bpp images with a grayscale palette,
+/// other frames are stored as 24bpp images.
+fn create_wip<W: Write>(images: Vec<ImageData>, writer: W) -> Result<()> {
+    if images.is_empty() {
+        return Err(anyhow!("No frames to write"));
+    }
+    let bpp = if images
+        .iter()
+        .all(|img| img.color_type == ImageColorType::Grayscale)
+    {
+        8
+    } else {
+        24
+    };
+    let palette: Vec<u8> = (0..=255u8).flat_map(|i| [i, i, i, 0]).collect();
+    let frames = images
+        .into_iter()
+        .enumerate()
+        .map(|(index, img)| {
+            let (width, height) = (img.width, img.height);
+            let (data, palette) = encode_frame(img, bpp, Some(&palette))
+                .with_context(|| format!("Failed to encode frame {}", index))?;
+            Ok(WipFrameData {
+                header: FrameHeader {
+                    width,
+                    height,
+                    offset_x: 0,
+                    offset_y: 0,
+                    _reserved: 0,
+                    frame_size: 0,
+                },
+                data,
+                palette,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    write_wip(writer, bpp, frames)
 }
 
 #[derive(Debug, Clone, StructPack, StructUnpack)]
@@ -72,6 +146,16 @@ struct FrameHeader {
     frame_size: u32,
 }
 
+/// Encoded frame to write.
+struct WipFrameData {
+    /// Frame header, `frame_size` is filled when writing.
+    header: FrameHeader,
+    /// LZSS compressed pixel planes
+    data: Vec<u8>,
+    /// Palette of 8bpp images
+    palette: Option<Vec<u8>>,
+}
+
 #[derive(Debug, Clone)]
 struct WillPlusWipFrame {
     index: usize,
@@ -86,6 +170,8 @@ pub struct WillPlusWipImage {
     data: MemReader,
     frames: Vec<WillPlusWipFrame>,
     bpp: u16,
+    /// The `.msk` image which stores the alpha channel of this image.
+    mask: Option<Box<WillPlusWipImage>>,
 }
 
 impl WillPlusWipImage {
@@ -162,7 +248,12 @@ impl WillPlusWipImage {
             return Err(anyhow!("No valid frames found in WIP image"));
         }
 
-        Ok(WillPlusWipImage { data, frames, bpp })
+        Ok(WillPlusWipImage {
+            data,
+            frames,
+            bpp,
+            mask: None,
+        })
     }
 
     fn decode_frame(&self, frame: &WillPlusWipFrame) -> Result<ImageData> {
@@ -249,6 +340,146 @@ impl WillPlusWipImage {
             _ => Err(anyhow!("Unsupported bits-per-pixel: {}", self.bpp)),
         }
     }
+
+    /// Returns the frame as it is stored in the file.
+    fn raw_frame(&self, frame: &WillPlusWipFrame) -> WipFrameData {
+        WipFrameData {
+            header: frame.header.clone(),
+            data: self.data.data[frame.data_range.clone()].to_vec(),
+            palette: frame
+                .palette_range
+                .as_ref()
+                .map(|range| self.data.data[range.clone()].to_vec()),
+        }
+    }
+
+    /// Writes the image with some frames replaced by `images` (frame index -> image).
+    ///
+    /// Other frames are copied without re-encoding.
+    fn import_frames<W: Write>(
+        &self,
+        mut images: HashMap<usize, ImageData>,
+        writer: W,
+    ) -> Result<()> {
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| match images.remove(&frame.index) {
+                Some(img) => {
+                    let mut header = frame.header.clone();
+                    header.width = img.width;
+                    header.height = img.height;
+                    let palette = frame
+                        .palette_range
+                        .as_ref()
+                        .map(|range| &self.data.data[range.clone()]);
+                    let (data, palette) = encode_frame(img, self.bpp, palette)
+                        .with_context(|| format!("Failed to encode frame {}", frame.index))?;
+                    Ok(WipFrameData {
+                        header,
+                        data,
+                        palette,
+                    })
+                }
+                None => Ok(self.raw_frame(frame)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        write_wip(writer, self.bpp, frames)
+    }
+
+    /// Maps images to frame indices by their names.
+    fn frame_images(&self, data: Vec<ImageDataWithName>) -> HashMap<usize, ImageData> {
+        let mut images = HashMap::new();
+        for img in data {
+            match img.name.parse::<usize>() {
+                Ok(index) if index < self.frames.len() => {
+                    images.insert(index, img.data);
+                }
+                _ => {
+                    eprintln!("WARN: Frame {} not found in WIP image, ignored", img.name);
+                    crate::COUNTER.inc_warning();
+                }
+            }
+        }
+        images
+    }
+
+    /// Writes the image to `filename` with some frames replaced by `images`.
+    ///
+    /// If the original image has a `.msk` file, the alpha channel of the images is
+    /// written to a `.msk` file next to `filename`.
+    fn import_frames_file(
+        &self,
+        mut images: HashMap<usize, ImageData>,
+        filename: &str,
+    ) -> Result<()> {
+        let mut masks = HashMap::new();
+        if let Some(mask) = &self.mask {
+            for (index, img) in images.iter_mut() {
+                if let Some(alpha) = split_alpha(img)? {
+                    if *index < mask.frames.len() {
+                        masks.insert(*index, alpha);
+                    } else {
+                        eprintln!(
+                            "WARN: Frame {} not found in .msk image, alpha channel is ignored",
+                            index
+                        );
+                        crate::COUNTER.inc_warning();
+                    }
+                }
+            }
+        }
+        let f = std::io::BufWriter::new(std::fs::File::create(filename)?);
+        self.import_frames(images, f)?;
+        if let Some(mask) = &self.mask {
+            if !masks.is_empty() {
+                let mask_filename = std::path::Path::new(filename).with_extension("msk");
+                let f = std::io::BufWriter::new(std::fs::File::create(&mask_filename)?);
+                mask.import_frames(masks, f).with_context(|| {
+                    format!("Failed to write mask image {}", mask_filename.display())
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads the `.msk` file next to a `.wip`/`.wi0` image, which stores the alpha channel of the image.
+fn load_mask(filename: &str, config: &ExtraConfig) -> Result<Option<WillPlusWipImage>> {
+    let path = std::path::Path::new(filename);
+    let is_color = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wip") || ext.eq_ignore_ascii_case("wi0"));
+    let mask_path = path.with_extension("msk");
+    if !is_color || !mask_path.is_file() {
+        return Ok(None);
+    }
+    let data = crate::utils::files::read_file(&mask_path)?;
+    let mask = WillPlusWipImage::new(MemReader::new(data), config)
+        .with_context(|| format!("Failed to read mask image {}", mask_path.display()))?;
+    Ok(Some(mask))
+}
+
+/// Removes the alpha channel from an image with alpha.
+///
+/// Returns the alpha channel as a grayscale image, or `None` if the image has no alpha channel.
+fn split_alpha(img: &mut ImageData) -> Result<Option<ImageData>> {
+    if !matches!(img.color_type, ImageColorType::Rgba | ImageColorType::Bgra) {
+        return Ok(None);
+    }
+    convert_to_bgra(img)?;
+    let mut alpha = Vec::with_capacity(img.data.len() / 4);
+    for p in img.data.chunks_exact_mut(4) {
+        alpha.push(p[3]);
+        p[3] = 0xFF;
+    }
+    Ok(Some(ImageData {
+        width: img.width,
+        height: img.height,
+        color_type: ImageColorType::Grayscale,
+        depth: 8,
+        data: alpha,
+    }))
 }
 
 impl Script for WillPlusWipImage {
@@ -287,6 +518,48 @@ impl Script for WillPlusWipImage {
             index: 0,
         }))
     }
+
+    fn import_image<'a>(
+        &'a self,
+        data: ImageData,
+        _filename: &str,
+        file: Box<dyn WriteSeek + 'a>,
+    ) -> Result<()> {
+        if self.frames.len() > 1 {
+            eprintln!("WARN: WIP image contains multiple frames, importing only the first frame");
+            crate::COUNTER.inc_warning();
+        }
+        self.import_frames(HashMap::from([(0, data)]), file)
+    }
+
+    fn import_image_filename(
+        &self,
+        data: ImageData,
+        _image_filename: &str,
+        filename: &str,
+    ) -> Result<()> {
+        if self.frames.len() > 1 {
+            eprintln!("WARN: WIP image contains multiple frames, importing only the first frame");
+            crate::COUNTER.inc_warning();
+        }
+        self.import_frames_file(HashMap::from([(0, data)]), filename)
+    }
+
+    fn import_multi_image<'a>(
+        &'a self,
+        data: Vec<ImageDataWithName>,
+        file: Box<dyn WriteSeek + 'a>,
+    ) -> Result<()> {
+        self.import_frames(self.frame_images(data), file)
+    }
+
+    fn import_multi_image_filename(
+        &self,
+        data: Vec<ImageDataWithName>,
+        filename: &str,
+    ) -> Result<()> {
+        self.import_frames_file(self.frame_images(data), filename)
+    }
 }
 
 struct WillPlusWipIterator<'a> {
@@ -315,6 +588,191 @@ impl<'a> Iterator for WillPlusWipIterator<'a> {
     }
 }
 
+/// Encodes an image as LZSS compressed pixel planes.
+///
+/// Returns the compressed data and the palette for 8bpp images.
+/// * `palette` - Palette of the original frame, which is reused if it contains all colors.
+fn encode_frame(
+    mut img: ImageData,
+    bpp: u16,
+    palette: Option<&[u8]>,
+) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+    if img.depth != 8 {
+        return Err(anyhow!("Unsupported image depth: {}", img.depth));
+    }
+    let plane_size = img.width as usize * img.height as usize;
+    convert_to_bgra(&mut img)?;
+    match bpp {
+        24 => {
+            if img.data.chunks_exact(4).any(|p| p[3] != 0xFF) {
+                eprintln!(
+                    "WARN: Alpha channel is ignored, WIP images store alpha in separate .msk files"
+                );
+                crate::COUNTER.inc_warning();
+            }
+            let mut planes = vec![0u8; plane_size * 3];
+            for (i, p) in img.data.chunks_exact(4).enumerate() {
+                planes[i] = p[0];
+                planes[i + plane_size] = p[1];
+                planes[i + plane_size * 2] = p[2];
+            }
+            Ok((lzss_compress(&planes), None))
+        }
+        8 => {
+            let (indices, palette) = map_to_palette(&img, palette)?;
+            Ok((lzss_compress(&indices), Some(palette)))
+        }
+        _ => Err(anyhow!("Unsupported bits-per-pixel: {}", bpp)),
+    }
+}
+
+/// Maps colors of a BGRA image to palette indices.
+///
+/// Colors are looked up in `palette` (RGBX) first. If some colors are missing,
+/// a new palette is built from the colors of the image.
+fn map_to_palette(img: &ImageData, palette: Option<&[u8]>) -> Result<(Vec<u8>, Vec<u8>)> {
+    let colors: Vec<[u8; 3]> = img
+        .data
+        .chunks_exact(4)
+        .map(|p| [p[2], p[1], p[0]])
+        .collect();
+    if let Some(palette) = palette {
+        let mut map = HashMap::new();
+        // Reversed, so the first entry is used for duplicated colors.
+        for (i, c) in palette.chunks_exact(4).enumerate().take(256).rev() {
+            map.insert([c[0], c[1], c[2]], i as u8);
+        }
+        if let Some(indices) = colors
+            .iter()
+            .map(|c| map.get(c).copied())
+            .collect::<Option<Vec<u8>>>()
+        {
+            return Ok((indices, palette.to_vec()));
+        }
+    }
+    let mut map = HashMap::new();
+    let mut new_palette = Vec::with_capacity(0x400);
+    let mut indices = Vec::with_capacity(colors.len());
+    for c in colors {
+        let index = match map.get(&c) {
+            Some(&index) => index,
+            None => {
+                if map.len() >= 256 {
+                    return Err(anyhow!(
+                        "Image contains more than 256 colors, can not be stored as 8bpp WIP image"
+                    ));
+                }
+                let index = map.len() as u8;
+                map.insert(c, index);
+                new_palette.extend_from_slice(&[c[0], c[1], c[2], 0]);
+                index
+            }
+        };
+        indices.push(index);
+    }
+    new_palette.resize(0x400, 0);
+    Ok((indices, new_palette))
+}
+
+/// Writes a WIP image.
+fn write_wip<W: Write>(mut writer: W, bpp: u16, frames: Vec<WipFrameData>) -> Result<()> {
+    let frame_count =
+        u16::try_from(frames.len()).map_err(|_| anyhow!("Too many frames: {}", frames.len()))?;
+    writer.write_all(b"WIPF")?;
+    writer.write_u16(frame_count)?;
+    writer.write_u16(bpp)?;
+    for frame in frames.iter() {
+        let mut header = frame.header.clone();
+        header.frame_size = frame.data.len() as u32;
+        header.pack(&mut writer, false, Encoding::Utf8, &None)?;
+    }
+    for (index, frame) in frames.iter().enumerate() {
+        writer.write_all(&frame.data)?;
+        if bpp == 8 {
+            let palette = frame
+                .palette
+                .as_ref()
+                .ok_or_else(|| anyhow!("Frame {} missing palette data for 8bpp image", index))?;
+            writer.write_all(palette)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Compresses data in the LZSS format read by [lzss_decompress].
+fn lzss_compress(data: &[u8]) -> Vec<u8> {
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 0x11;
+    const MAX_DISTANCE: usize = 0xFFF;
+    const MAX_CHAIN: usize = 64;
+    const HASH_BITS: u32 = 16;
+
+    fn hash(b: &[u8]) -> usize {
+        let v = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        (v.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+    }
+
+    let mut output = Vec::with_capacity(data.len() + data.len() / 8 + 1);
+    // Latest position of each hash and previous position with the same hash.
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+    let mut flag_pos = 0;
+    let mut bit = 8;
+    let mut pos = 0;
+    while pos < data.len() {
+        if bit == 8 {
+            flag_pos = output.len();
+            output.push(0);
+            bit = 0;
+        }
+        let mut best_len = 0;
+        let mut best_pos = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut cand = head[hash(&data[pos..])];
+            let mut chain = 0;
+            while cand != usize::MAX && pos - cand <= MAX_DISTANCE && chain < MAX_CHAIN {
+                let len = data[cand..]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_pos = cand;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                cand = prev[cand];
+                chain += 1;
+            }
+        }
+        let step = if best_len >= MIN_MATCH {
+            // The window starts at position 1.
+            let offset = (best_pos + 1) & 0xFFF;
+            output.push((offset >> 4) as u8);
+            output.push((((offset & 0xF) << 4) | (best_len - 2)) as u8);
+            best_len
+        } else {
+            output[flag_pos] |= 1 << bit;
+            output.push(data[pos]);
+            1
+        };
+        bit += 1;
+        for p in pos..pos + step {
+            if p + MIN_MATCH <= data.len() {
+                let h = hash(&data[p..]);
+                prev[p] = head[h];
+                head[h] = p;
+            }
+        }
+        pos += step;
+    }
+    output
+}
+
 fn lzss_decompress(compressed: &[u8], expected: usize) -> Result<Vec<u8>> {
     let mut output = Vec::with_capacity(expected.max(0x1000));
     let mut window = [0u8; 0x1000];
@@ -378,3 +836,118 @@ fn lzss_decompress(compressed: &[u8], expected: usize) -> Result<Vec<u8>> {
 
     Ok(output)
 }
+
+#[test]
+fn test_wip_roundtrip() {
+    let mut pixels = Vec::new();
+    for y in 0..40u32 {
+        for x in 0..50u32 {
+            pixels.extend_from_slice(&[(x * 5) as u8, (y * 6) as u8, ((x + y) % 7) as u8]);
+        }
+    }
+    let color = ImageData {
+        width: 50,
+        height: 40,
+        color_type: ImageColorType::Bgr,
+        depth: 8,
+        data: pixels,
+    };
+    let mut writer = MemWriter::new();
+    create_wip(vec![color.clone(), color.clone()], &mut writer).unwrap();
+    let wip = WillPlusWipImage::new(MemReader::new(writer.into_inner()), &ExtraConfig::default())
+        .unwrap();
+    assert_eq!(wip.frames.len(), 2);
+    assert_eq!(wip.decode_frame(&wip.frames[1]).unwrap().data, color.data);
+
+    let mask = ImageData {
+        width: 30,
+        height: 20,
+        color_type: ImageColorType::Grayscale,
+        depth: 8,
+        data: (0..600).map(|i| (i / 3) as u8).collect(),
+    };
+    let mut writer = MemWriter::new();
+    create_wip(vec![mask.clone()], &mut writer).unwrap();
+    let wip = WillPlusWipImage::new(MemReader::new(writer.into_inner()), &ExtraConfig::default())
+        .unwrap();
+    assert_eq!(wip.bpp, 8);
+    let decoded = wip.export_image().unwrap();
+    let gray: Vec<u8> = decoded.data.chunks_exact(3).map(|p| p[0]).collect();
+    assert_eq!(gray, mask.data);
+}
+
+#[test]
+fn test_import_alpha_to_mask() {
+    let dir = crate::utils::testing::temp_dir("wip-mask");
+    let color = ImageData {
+        width: 4,
+        height: 3,
+        color_type: ImageColorType::Bgr,
+        depth: 8,
+        data: (0..36).collect(),
+    };
+    let mask = ImageData {
+        width: 4,
+        height: 3,
+        color_type: ImageColorType::Grayscale,
+        depth: 8,
+        data: vec![0xFF; 12],
+    };
+    let original = dir.join("a.wip");
+    create_wip(
+        vec![color.clone()],
+        std::fs::File::create(&original).unwrap(),
+    )
+    .unwrap();
+    create_wip(
+        vec![mask],
+        std::fs::File::create(dir.join("a.msk")).unwrap(),
+    )
+    .unwrap();
+    let builder = WillPlusWipImageBuilder::new();
+    let original = original.to_string_lossy();
+    let script = builder
+        .build_script(
+            std::fs::read(&*original).unwrap(),
+            &original,
+            Encoding::Cp932,
+            Encoding::Cp932,
+            &ExtraConfig::default(),
+            None,
+        )
+        .unwrap();
+    let mut rgba = Vec::new();
+    for (i, p) in color.data.chunks_exact(3).enumerate() {
+        rgba.extend_from_slice(&[p[2], p[1], p[0], (i * 20) as u8]);
+    }
+    let out = dir.join("out");
+    std::fs::create_dir_all(&out).unwrap();
+    let out = out.join("a.wip");
+    let image = ImageData {
+        width: 4,
+        height: 3,
+        color_type: ImageColorType::Rgba,
+        depth: 8,
+        data: rgba,
+    };
+    script
+        .import_image_filename(image, "", &out.to_string_lossy())
+        .unwrap();
+    let read = |path: &std::path::Path| {
+        WillPlusWipImage::new(
+            MemReader::new(std::fs::read(path).unwrap()),
+            &ExtraConfig::default(),
+        )
+        .unwrap()
+        .export_image()
+        .unwrap()
+    };
+    assert_eq!(read(&out).data, color.data);
+    let alpha: Vec<u8> = read(&out.with_extension("msk"))
+        .data
+        .chunks_exact(3)
+        .map(|p| p[0])
+        .collect();
+    assert_eq!(alpha, (0..12).map(|i| (i * 20) as u8).collect::<Vec<_>>());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
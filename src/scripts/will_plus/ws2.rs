@@ -31,7 +31,7 @@ impl ScriptBuilder for Ws2ScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         if !config.will_plus_ws2_no_disasm {
             match Ws2DisasmScript::new(&buf, encoding, config, false) {
@@ -247,6 +247,7 @@ impl Script for Ws2Script {
                         .trim_start_matches("%LF")
                         .to_string()
                 }),
+                ..Default::default()
             };
             messages.push(message);
         }
@@ -407,6 +408,7 @@ impl Script for Ws2DisasmScript {
                     messages.push(Message {
                         message,
                         name: name.take(),
+                        ..Default::default()
                     });
                 }
                 StringType::Internal => {}
@@ -28,7 +28,7 @@ impl ScriptBuilder for ItufuruScriptBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(ItufuruScript::new(data, encoding, config)?))
     }
@@ -136,6 +136,7 @@ impl Script for ItufuruScript {
             messages.push(Message {
                 name: None,
                 message: decoded,
+                ..Default::default()
             });
         }
         Ok(messages)
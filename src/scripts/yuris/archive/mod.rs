@@ -0,0 +1,23 @@
+//! Yu-Ris Archives
+pub mod ypf;
+
+use crate::types::*;
+
+fn detect_script_type(_filename: &str, buf: &[u8], buf_len: usize) -> Option<ScriptType> {
+    if buf_len < 4 {
+        return None;
+    }
+    if buf.starts_with(b"YSTB") {
+        return Some(ScriptType::YurisYSTB);
+    }
+    if buf.starts_with(b"YSCM") {
+        return Some(ScriptType::YurisYSCM);
+    }
+    if buf.starts_with(b"YSER") {
+        return Some(ScriptType::YurisYSER);
+    }
+    if buf.starts_with(b"YSCF") {
+        return Some(ScriptType::YurisYSCFG);
+    }
+    None
+}
@@ -0,0 +1,714 @@
+//! Yu-Ris YPF archive (.ypf)
+use super::*;
+use crate::ext::io::*;
+use crate::scripts::base::*;
+use crate::types::*;
+use crate::utils::encoding::*;
+use anyhow::{Result, anyhow, ensure};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+const YPF_MAGIC: &[u8; 4] = b"YPF\0";
+const HEADER_SIZE: u64 = 0x20;
+/// Minimum size of an index entry (without name).
+const ENTRY_MIN_SIZE: usize = 0x17;
+/// Since this version, offsets are 64-bit and hashes are computed with MurmurHash2
+/// instead of CRC32 (name) and Adler32 (data).
+const MURMUR_HASH_VERSION: u32 = 0x1D9;
+/// Since this version, name lengths are obfuscated with [YpfLengthTable::V10].
+const LENGTH_TABLE_10_VERSION: u32 = 0x1F4;
+
+/// Pairs of name lengths which are swapped.
+const LENGTH_TABLE_00: &[u8] = &[
+    0x03, 0x0A, 0x06, 0x35, 0x0C, 0x10, 0x11, 0x18, 0x1C, 0x1E, 0x09, 0x0B, 0x0D, 0x13, 0x15, 0x1B,
+    0x20, 0x23, 0x26, 0x29, 0x2C, 0x2F, 0x2E, 0x32,
+];
+const LENGTH_TABLE_04: &[u8] = &[
+    0x03, 0x48, 0x06, 0x35, 0x0C, 0x10, 0x11, 0x19, 0x1C, 0x1E, 0x09, 0x0B, 0x0D, 0x13, 0x15, 0x1B,
+    0x20, 0x23, 0x26, 0x29, 0x2C, 0x2F, 0x2E, 0x32,
+];
+const LENGTH_TABLE_10: &[u8] = &[
+    0x09, 0x0B, 0x0D, 0x13, 0x15, 0x1B, 0x20, 0x23, 0x26, 0x29, 0x2C, 0x2F, 0x2E, 0x32,
+];
+
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+/// Table used to obfuscate file name lengths in YPF archive
+pub enum YpfLengthTable {
+    V00,
+    V04,
+    V10,
+}
+
+impl YpfLengthTable {
+    const ALL: [Self; 3] = [Self::V04, Self::V10, Self::V00];
+
+    /// Returns the default table for the archive version.
+    fn from_version(version: u32) -> Self {
+        if version >= LENGTH_TABLE_10_VERSION {
+            Self::V10
+        } else {
+            Self::V04
+        }
+    }
+
+    /// Swaps the name length. The swap is its own inverse.
+    fn swap(self, len: u8) -> u8 {
+        let table = match self {
+            Self::V00 => LENGTH_TABLE_00,
+            Self::V04 => LENGTH_TABLE_04,
+            Self::V10 => LENGTH_TABLE_10,
+        };
+        for pair in table.chunks_exact(2) {
+            if pair[0] == len {
+                return pair[1];
+            } else if pair[1] == len {
+                return pair[0];
+            }
+        }
+        len
+    }
+}
+
+/// MurmurHash2 with seed 0.
+fn murmur_hash2(data: &[u8]) -> u32 {
+    const M: u32 = 0x5BD1E995;
+    let mut h = data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M) ^ k;
+    }
+    let rem = chunks.remainder();
+    if rem.len() >= 3 {
+        h ^= (rem[2] as u32) << 16;
+    }
+    if rem.len() >= 2 {
+        h ^= (rem[1] as u32) << 8;
+    }
+    if !rem.is_empty() {
+        h ^= rem[0] as u32;
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^ (h >> 15)
+}
+
+fn name_hash(version: u32, name: &[u8]) -> u32 {
+    if version >= MURMUR_HASH_VERSION {
+        murmur_hash2(name)
+    } else {
+        crc32fast::hash(name)
+    }
+}
+
+fn data_hash(version: u32, data: &[u8]) -> u32 {
+    if version >= MURMUR_HASH_VERSION {
+        murmur_hash2(data)
+    } else {
+        let mut adler = adler::Adler32::new();
+        adler.write_slice(data);
+        adler.checksum()
+    }
+}
+
+/// Returns the file type stored in index.
+fn file_type(name: &str) -> u8 {
+    let ext = std::path::Path::new(name)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "bmp" => 1,
+        "png" => 2,
+        "jpg" | "jpeg" => 3,
+        "gif" => 4,
+        "wav" => 5,
+        "ogg" => 6,
+        "psd" => 7,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Default)]
+/// Yu-Ris YPF archive builder
+pub struct YpfBuilder {}
+
+impl YpfBuilder {
+    /// Creates a new instance of `YpfBuilder`
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ScriptBuilder for YpfBuilder {
+    fn default_encoding(&self) -> Encoding {
+        Encoding::Cp932
+    }
+
+    fn default_archive_encoding(&self) -> Option<Encoding> {
+        Some(Encoding::Cp932)
+    }
+
+    fn build_script(
+        &self,
+        buf: Vec<u8>,
+        _filename: &str,
+        _encoding: Encoding,
+        archive_encoding: Encoding,
+        config: &ExtraConfig,
+        _archive: Option<&dyn Script>,
+    ) -> Result<Box<dyn Script + Send + Sync>> {
+        Ok(Box::new(YpfArchive::new(
+            MemReader::new(buf),
+            archive_encoding,
+            config,
+        )?))
+    }
+
+    fn build_script_from_file(
+        &self,
+        filename: &str,
+        _encoding: Encoding,
+        archive_encoding: Encoding,
+        config: &ExtraConfig,
+        _archive: Option<&dyn Script>,
+    ) -> Result<Box<dyn Script + Send + Sync>> {
+        let file = std::fs::File::open(filename)?;
+        let reader = std::io::BufReader::new(file);
+        Ok(Box::new(YpfArchive::new(reader, archive_encoding, config)?))
+    }
+
+    fn build_script_from_reader<'a>(
+        &self,
+        reader: Box<dyn ReadSeek + Send + Sync + 'a>,
+        _filename: &str,
+        _encoding: Encoding,
+        archive_encoding: Encoding,
+        config: &ExtraConfig,
+        _archive: Option<&dyn Script>,
+    ) -> Result<Box<dyn Script + Send + Sync + 'a>> {
+        Ok(Box::new(YpfArchive::new(reader, archive_encoding, config)?))
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ypf"]
+    }
+
+    fn script_type(&self) -> &'static ScriptType {
+        &ScriptType::YurisYpf
+    }
+
+    fn is_this_format(&self, _filename: &str, buf: &[u8], buf_len: usize) -> Option<u8> {
+        if buf_len >= 4 && buf.starts_with(YPF_MAGIC) {
+            Some(20)
+        } else {
+            None
+        }
+    }
+
+    fn is_archive(&self) -> bool {
+        true
+    }
+
+    fn can_create_archive(&self) -> bool {
+        true
+    }
+
+    fn create_archive(
+        &self,
+        filename: &str,
+        files: &[&str],
+        encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Box<dyn Archive>> {
+        let f = std::fs::File::create(filename)?;
+        let writer = std::io::BufWriter::new(f);
+        Ok(Box::new(YpfArchiveWriter::new(
+            writer, files, encoding, config,
+        )?))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct YpfEntry {
+    name: String,
+    name_hash: u32,
+    file_type: u8,
+    is_packed: bool,
+    unpacked_size: u32,
+    size: u32,
+    offset: u64,
+    data_hash: u32,
+}
+
+/// Parses the index with given name length table and offset size.
+///
+/// Returns raw (still obfuscated) names and entries without names.
+fn parse_index(
+    index: &[u8],
+    count: usize,
+    table: YpfLengthTable,
+    offset64: bool,
+    file_len: u64,
+) -> Result<Vec<(Vec<u8>, YpfEntry)>> {
+    let mut reader = MemReaderRef::new(index);
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name_hash = reader.read_u32()?;
+        let name_len = table.swap(!reader.read_u8()?) as usize;
+        ensure!(name_len > 0, "Empty file name in YPF index");
+        let raw_name = reader.read_exact_vec(name_len)?;
+        let file_type = reader.read_u8()?;
+        let is_packed = reader.read_u8()?;
+        ensure!(is_packed <= 1, "Invalid compression flag: {is_packed}");
+        let unpacked_size = reader.read_u32()?;
+        let size = reader.read_u32()?;
+        let offset = if offset64 {
+            reader.read_u64()?
+        } else {
+            reader.read_u32()? as u64
+        };
+        let data_hash = reader.read_u32()?;
+        ensure!(
+            offset >= HEADER_SIZE && offset + size as u64 <= file_len,
+            "Entry exceeds archive bounds: offset={offset:#X}, size={size:#X}"
+        );
+        entries.push((
+            raw_name,
+            YpfEntry {
+                name: String::new(),
+                name_hash,
+                file_type,
+                is_packed: is_packed != 0,
+                unpacked_size,
+                size,
+                offset,
+                data_hash,
+            },
+        ));
+    }
+    ensure!(
+        reader.pos == index.len(),
+        "YPF index size mismatch: parsed {:#X}, expected {:#X}",
+        reader.pos,
+        index.len()
+    );
+    Ok(entries)
+}
+
+/// Guesses the name key by assuming most names have a 3 characters extension.
+fn guess_name_key(names: &[&[u8]]) -> u8 {
+    let mut counts = [0usize; 256];
+    for name in names {
+        if name.len() >= 4 {
+            counts[(name[name.len() - 4] ^ b'.') as usize] += 1;
+        }
+    }
+    let mut key = 0xFF;
+    for (k, &count) in counts.iter().enumerate() {
+        if count > counts[key as usize] {
+            key = k as u8;
+        }
+    }
+    key
+}
+
+#[derive(Debug)]
+/// Yu-Ris YPF archive
+pub struct YpfArchive<T: Read + Seek + std::fmt::Debug> {
+    reader: Arc<Mutex<T>>,
+    entries: Vec<YpfEntry>,
+}
+
+impl<T: Read + Seek + std::fmt::Debug> YpfArchive<T> {
+    /// Opens a YPF archive
+    ///
+    /// * `reader` - The reader of archive
+    /// * `archive_encoding` - The encoding of file names
+    /// * `config` - Extra configuration options
+    pub fn new(mut reader: T, archive_encoding: Encoding, _config: &ExtraConfig) -> Result<Self> {
+        let encoding = match archive_encoding {
+            Encoding::Auto => Encoding::Cp932,
+            other => other,
+        };
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        ensure!(&magic == YPF_MAGIC, "Invalid YPF signature");
+        let version = reader.read_u32()?;
+        let count = reader.read_u32()? as usize;
+        let index_size = reader.read_u32()? as usize;
+        ensure!(
+            index_size >= count * ENTRY_MIN_SIZE,
+            "Invalid YPF index size: {index_size:#X}"
+        );
+        let file_len = reader.stream_length()?;
+        let index = reader.peek_exact_at_vec(HEADER_SIZE, index_size)?;
+        let offset64 = version >= MURMUR_HASH_VERSION;
+        let mut parsed = None;
+        let mut last_err = None;
+        'outer: for offset64 in [offset64, !offset64] {
+            for table in YpfLengthTable::ALL {
+                match parse_index(&index, count, table, offset64, file_len) {
+                    Ok(entries) => {
+                        parsed = Some(entries);
+                        break 'outer;
+                    }
+                    Err(e) => last_err = Some(e),
+                }
+            }
+        }
+        let parsed = match (parsed, last_err) {
+            (Some(parsed), _) => parsed,
+            (None, Some(e)) => return Err(e.context("Unsupported YPF index")),
+            (None, None) => Vec::new(),
+        };
+        let key = guess_name_key(
+            &parsed
+                .iter()
+                .map(|(name, _)| name.as_slice())
+                .collect::<Vec<_>>(),
+        );
+        let mut entries = Vec::with_capacity(parsed.len());
+        for (raw_name, mut entry) in parsed {
+            let name: Vec<u8> = raw_name.iter().map(|b| b ^ key).collect();
+            entry.name = decode_to_string(encoding, &name, true)?;
+            entries.push(entry);
+        }
+        Ok(Self {
+            reader: Arc::new(Mutex::new(reader)),
+            entries,
+        })
+    }
+}
+
+impl<T: Read + Seek + std::fmt::Debug + Send + Sync> Script for YpfArchive<T> {
+    fn default_output_script_type(&self) -> OutputScriptType {
+        OutputScriptType::Json
+    }
+
+    fn default_format_type(&self) -> FormatOptions {
+        FormatOptions::None
+    }
+
+    fn is_archive(&self) -> bool {
+        true
+    }
+
+    fn iter_archive_filename<'a>(
+        &'a self,
+    ) -> Result<Box<dyn Iterator<Item = Result<String>> + 'a>> {
+        Ok(Box::new(self.entries.iter().map(|e| Ok(e.name.clone()))))
+    }
+
+    fn iter_archive_offset<'a>(&'a self) -> Result<Box<dyn Iterator<Item = Result<u64>> + 'a>> {
+        Ok(Box::new(self.entries.iter().map(|e| Ok(e.offset))))
+    }
+
+    fn open_file<'a>(&'a self, index: usize) -> Result<Box<dyn ArchiveContent + Send + Sync + 'a>> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| anyhow!("Index out of bounds: {index}"))?;
+        let mut data = self
+            .reader
+            .cpeek_exact_at_vec(entry.offset, entry.size as usize)?;
+        if entry.is_packed {
+            let mut decoder = flate2::read::ZlibDecoder::new(MemReaderRef::new(&data));
+            let mut unpacked = Vec::with_capacity(entry.unpacked_size as usize);
+            decoder.read_to_end(&mut unpacked)?;
+            data = unpacked;
+        }
+        if data.len() != entry.unpacked_size as usize {
+            eprintln!(
+                "Warning: YPF entry {} size mismatch: expected {}, got {}",
+                entry.name,
+                entry.unpacked_size,
+                data.len()
+            );
+            crate::COUNTER.inc_warning();
+        }
+        let script_type = detect_script_type(&entry.name, &data, data.len());
+        Ok(Box::new(MemEntry {
+            name: entry.name.clone(),
+            data: MemReader::new(data),
+            script_type,
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct MemEntry {
+    name: String,
+    data: MemReader,
+    script_type: Option<ScriptType>,
+}
+
+impl ArchiveContent for MemEntry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn script_type(&self) -> Option<&ScriptType> {
+        self.script_type.as_ref()
+    }
+
+    fn data(&mut self) -> Result<Vec<u8>> {
+        Ok(self.data.data.clone())
+    }
+
+    fn to_data<'a>(&'a mut self) -> Result<Box<dyn ReadSeek + Send + Sync + 'a>> {
+        Ok(Box::new(self.data.to_ref()))
+    }
+}
+
+impl Read for MemEntry {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.data.read(buf)
+    }
+}
+
+/// Yu-Ris YPF archive writer
+pub struct YpfArchiveWriter<T: Write + Seek> {
+    writer: T,
+    version: u32,
+    name_key: u8,
+    length_table: YpfLengthTable,
+    entries: Vec<(Vec<u8>, YpfEntry)>,
+    indexes: HashMap<String, usize>,
+    index_size: usize,
+    compress_level: u32,
+}
+
+impl<T: Write + Seek> YpfArchiveWriter<T> {
+    fn new(
+        mut writer: T,
+        files: &[&str],
+        archive_encoding: Encoding,
+        config: &ExtraConfig,
+    ) -> Result<Self> {
+        let encoding = match archive_encoding {
+            Encoding::Auto => Encoding::Cp932,
+            other => other,
+        };
+        let version = config.ypf_version;
+        let offset_size = if version >= MURMUR_HASH_VERSION { 8 } else { 4 };
+        let mut entries = Vec::with_capacity(files.len());
+        let mut indexes = HashMap::new();
+        let mut index_size = 0;
+        for file in files {
+            let name = file.to_string();
+            let raw_name = encode_string(encoding, &name, true)?;
+            ensure!(
+                !raw_name.is_empty() && raw_name.len() <= 0xFF,
+                "File name is too long: {name}"
+            );
+            index_size += ENTRY_MIN_SIZE - 4 + offset_size + raw_name.len();
+            indexes.insert(file.to_string(), entries.len());
+            entries.push((
+                raw_name.clone(),
+                YpfEntry {
+                    name_hash: name_hash(version, &raw_name),
+                    file_type: file_type(&name),
+                    name,
+                    is_packed: false,
+                    unpacked_size: 0,
+                    size: 0,
+                    offset: 0,
+                    data_hash: 0,
+                },
+            ));
+        }
+        writer.write_all(&vec![0; HEADER_SIZE as usize + index_size])?;
+        Ok(Self {
+            writer,
+            version,
+            name_key: config.ypf_name_key,
+            length_table: config
+                .ypf_length_table
+                .unwrap_or_else(|| YpfLengthTable::from_version(version)),
+            entries,
+            indexes,
+            index_size,
+            compress_level: config.zlib_compression_level,
+        })
+    }
+
+    fn write_entry(&mut self, index: usize, data: Vec<u8>) -> Result<()> {
+        let offset = self.writer.seek(SeekFrom::End(0))?;
+        let entry = &mut self.entries[index].1;
+        entry.unpacked_size =
+            u32::try_from(data.len()).map_err(|_| anyhow!("File {} is too large", entry.name))?;
+        let mut encoder = flate2::write::ZlibEncoder::new(
+            Vec::new(),
+            flate2::Compression::new(self.compress_level),
+        );
+        encoder.write_all(&data)?;
+        let compressed = encoder.finish()?;
+        let data = if compressed.len() < data.len() {
+            entry.is_packed = true;
+            compressed
+        } else {
+            entry.is_packed = false;
+            data
+        };
+        if self.version < MURMUR_HASH_VERSION && offset + data.len() as u64 > u32::MAX as u64 {
+            anyhow::bail!("YPF archive is too large");
+        }
+        entry.offset = offset;
+        entry.size = data.len() as u32;
+        entry.data_hash = data_hash(self.version, &data);
+        self.writer.write_all(&data)?;
+        Ok(())
+    }
+}
+
+impl<T: Write + Seek> Archive for YpfArchiveWriter<T> {
+    fn new_file<'a>(
+        &'a mut self,
+        name: &str,
+        _size: Option<u64>,
+    ) -> Result<Box<dyn WriteSeek + 'a>> {
+        let index = *self
+            .indexes
+            .get(name)
+            .ok_or_else(|| anyhow!("File '{name}' not found in archive"))?;
+        Ok(Box::new(YpfArchiveFile {
+            inner: self,
+            index,
+            mem: MemWriter::new(),
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        // Entries which are not written have offset 0, which is rejected by the reader.
+        if let Some((_, entry)) = self.entries.iter().find(|(_, e)| e.offset < HEADER_SIZE) {
+            anyhow::bail!("File '{}' is not written to YPF archive", entry.name);
+        }
+        let mut index = MemWriter::new();
+        for (raw_name, entry) in &self.entries {
+            index.write_u32(entry.name_hash)?;
+            index.write_u8(!self.length_table.swap(raw_name.len() as u8))?;
+            index.write_all(
+                &raw_name
+                    .iter()
+                    .map(|b| b ^ self.name_key)
+                    .collect::<Vec<_>>(),
+            )?;
+            index.write_u8(entry.file_type)?;
+            index.write_u8(entry.is_packed as u8)?;
+            index.write_u32(entry.unpacked_size)?;
+            index.write_u32(entry.size)?;
+            if self.version >= MURMUR_HASH_VERSION {
+                index.write_u64(entry.offset)?;
+            } else {
+                index.write_u32(entry.offset as u32)?;
+            }
+            index.write_u32(entry.data_hash)?;
+        }
+        debug_assert_eq!(index.data.len(), self.index_size);
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(YPF_MAGIC)?;
+        self.writer.write_u32(self.version)?;
+        self.writer.write_u32(self.entries.len() as u32)?;
+        self.writer.write_u32(self.index_size as u32)?;
+        self.writer.write_all(&[0; 16])?;
+        self.writer.write_all(&index.data)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct YpfArchiveFile<'a, T: Write + Seek> {
+    inner: &'a mut YpfArchiveWriter<T>,
+    index: usize,
+    mem: MemWriter,
+}
+
+impl<'a, T: Write + Seek> std::fmt::Debug for YpfArchiveFile<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("YpfArchiveFile")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl<'a, T: Write + Seek> Write for YpfArchiveFile<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.mem.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.mem.flush()
+    }
+}
+
+impl<'a, T: Write + Seek> Seek for YpfArchiveFile<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.mem.seek(pos)
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        self.mem.stream_position()
+    }
+}
+
+impl<'a, T: Write + Seek> Drop for YpfArchiveFile<'a, T> {
+    fn drop(&mut self) {
+        let data = std::mem::take(&mut self.mem.data);
+        if let Err(e) = self.inner.write_entry(self.index, data) {
+            eprintln!("Failed to write file to YPF archive: {}", e);
+            crate::COUNTER.inc_error();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_murmur_hash2() {
+        assert_eq!(murmur_hash2(b""), 0);
+        assert_eq!(murmur_hash2(b"a"), 0x92685F5E);
+        assert_eq!(murmur_hash2(b"abcd"), 0x26873021);
+    }
+
+    #[test]
+    fn test_length_table_swap() {
+        for table in YpfLengthTable::ALL {
+            for len in 0..=0xFF {
+                assert_eq!(table.swap(table.swap(len)), len);
+            }
+        }
+        assert_eq!(YpfLengthTable::V04.swap(0x03), 0x48);
+        assert_eq!(YpfLengthTable::V10.swap(0x03), 0x03);
+    }
+
+    #[test]
+    fn test_writer_rejects_unwritten_files() {
+        let config = ExtraConfig::default();
+        let mut writer = YpfArchiveWriter::new(
+            MemWriter::new(),
+            &["a.txt", "b.txt"],
+            Encoding::Cp932,
+            &config,
+        )
+        .unwrap();
+        writer
+            .new_file("a.txt", None)
+            .unwrap()
+            .write_all(b"a")
+            .unwrap();
+        let err = writer.write_header().unwrap_err();
+        assert!(err.to_string().contains("b.txt"));
+        writer.new_file("b.txt", None).unwrap();
+        writer.write_header().unwrap();
+    }
+}
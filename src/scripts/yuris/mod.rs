@@ -1,4 +1,6 @@
 //! Yu-Ris Engine Scripts
+#[cfg(feature = "yuris-arc")]
+pub mod archive;
 pub mod txt;
 mod types;
 pub mod yscfg;
@@ -28,7 +28,7 @@ impl ScriptBuilder for YurisTxtBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         _config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(YurisTxt::new(&buf, encoding)?))
     }
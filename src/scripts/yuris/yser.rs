@@ -31,7 +31,7 @@ impl ScriptBuilder for YSERBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        _archive: Option<&Box<dyn Script>>,
+        _archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(YSER::new(MemReader::new(buf), encoding, config)?))
     }
@@ -34,6 +34,7 @@ struct YSTBHeaderV2 {
     reserved2: u32,
 }
 
+#[derive(Clone)]
 struct YSTBData {
     header: YSTBHeader,
     insts: Vec<YSTBInst>,
@@ -93,13 +94,14 @@ impl StructUnpack for YSTBData {
     }
 }
 
-#[derive(Debug, StructUnpack, StructPack)]
+#[derive(Clone, Debug, StructUnpack, StructPack)]
 struct YSTBInstBase {
     opcode: u8,
     arg_count: u8,
     unk: u16,
 }
 
+#[derive(Clone)]
 struct YSTBInst {
     base: YSTBInstBase,
     args: Vec<YSTBArg>,
@@ -129,13 +131,14 @@ impl std::fmt::Debug for YSTBInst {
     }
 }
 
-#[derive(Debug, StructUnpack, StructPack)]
+#[derive(Clone, Debug, StructUnpack, StructPack)]
 struct YSTBArgBase {
     id: u16,
     typ: u16,
     size: u32,
 }
 
+#[derive(Clone)]
 struct YSTBArg {
     base: YSTBArgBase,
     data: Vec<u8>,
@@ -182,6 +185,50 @@ impl DerefMut for YSTBArg {
     }
 }
 
+impl YSTBArg {
+    /// Returns the content of a string literal (`M` + length + quoted string) argument.
+    fn string_literal(&self) -> Option<&[u8]> {
+        let data = &self.data;
+        if data.len() >= 5 && data[0] == b'M' {
+            let len = u16::from_le_bytes([data[1], data[2]]) as usize;
+            if len == data.len() - 3 && data[3] == b'"' && data[data.len() - 1] == b'"' {
+                return Some(&data[4..data.len() - 1]);
+            }
+        }
+        None
+    }
+
+    /// Decodes the text stored in this argument.
+    fn text(&self) -> Result<String> {
+        match self.string_literal() {
+            Some(s) => decode_to_string(self.encoding, s, true),
+            None => decode_to_string(self.encoding, &self.data, true),
+        }
+    }
+
+    /// Replaces the text stored in this argument, keeping string literal form if it was one.
+    fn set_text(&mut self, text: &str, encoding: Encoding) -> Result<()> {
+        let encoded = encode_string(encoding, text, false)?;
+        if self.string_literal().is_some() {
+            let len = encoded.len() + 2;
+            if len > u16::MAX as usize {
+                anyhow::bail!("String is too long: {}", text);
+            }
+            let mut data = Vec::with_capacity(len + 3);
+            data.push(b'M');
+            data.extend_from_slice(&(len as u16).to_le_bytes());
+            data.push(b'"');
+            data.extend_from_slice(&encoded);
+            data.push(b'"');
+            self.data = data;
+        } else {
+            self.data = encoded;
+        }
+        self.size = self.data.len() as u32;
+        Ok(())
+    }
+}
+
 fn get_info_as_header(info: &Option<Box<dyn Any>>) -> Result<&YSTBHeader> {
     Ok(info
         .as_ref()
@@ -233,7 +280,7 @@ impl ScriptBuilder for YSTBBuilder {
         encoding: Encoding,
         _archive_encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Box<dyn Script + Send + Sync>> {
         Ok(Box::new(YSTB::new(
             MemReader::new(buf),
@@ -264,8 +311,80 @@ impl ScriptBuilder for YSTBBuilder {
 pub struct YSTB {
     data: YSTBData,
     com: YSCMData,
-    #[allow(unused)]
     xor_key: Option<u32>,
+    commands: TextCommands,
+}
+
+/// Commands which contain texts, found in YSCM command descriptors.
+#[derive(Debug)]
+struct TextCommands {
+    /// Opcode of `WORD`, whose arguments are message texts.
+    word: Option<u8>,
+    /// Opcode of `GOSUB`.
+    gosub: Option<u8>,
+    /// Argument ids of string parameters (`PSTR*`) of `GOSUB`.
+    gosub_strings: Vec<u16>,
+    /// Subroutine labels which show choices.
+    choice_labels: std::sync::Arc<std::collections::HashSet<String>>,
+}
+
+impl TextCommands {
+    fn new(com: &YSCMData, config: &ExtraConfig) -> Self {
+        let find = |name: &str| {
+            com.opcodes
+                .iter()
+                .position(|meta| meta.name == name)
+                .map(|i| i as u8)
+        };
+        let gosub = find("GOSUB");
+        let gosub_strings = gosub
+            .map(|op| {
+                com.opcodes[op as usize]
+                    .arguments
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, arg)| arg.name.starts_with("PSTR"))
+                    .map(|(i, _)| i as u16)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            word: find("WORD"),
+            gosub,
+            gosub_strings,
+            choice_labels: config.yuris_ystb_choice_labels.clone(),
+        }
+    }
+
+    /// Returns true if messages can be extracted with these commands.
+    fn has_texts(&self) -> bool {
+        self.word.is_some() || (self.gosub.is_some() && !self.gosub_strings.is_empty())
+    }
+
+    /// Returns true if the instruction calls a subroutine which shows choices.
+    fn is_choice(&self, inst: &YSTBInst) -> bool {
+        Some(inst.opcode) == self.gosub
+            && YSTB::gosub_target(inst).is_some_and(|target| self.choice_labels.contains(&target))
+    }
+
+    /// Returns true if the argument of a choice instruction is a choice text.
+    fn is_choice_text(&self, arg: &YSTBArg) -> bool {
+        self.gosub_strings.contains(&arg.id) && arg.string_literal().is_some()
+    }
+}
+
+/// Splits `【name】` prefix from message text.
+fn split_name(text: &str) -> (Option<&str>, &str, &str) {
+    if let Some(rest) = text.strip_prefix('【')
+        && let Some(end) = rest.find('】')
+    {
+        let name = &rest[..end];
+        let after = &rest[end + '】'.len_utf8()..];
+        let message = after.trim_start_matches([' ', '\t']);
+        let sep = &after[..after.len() - message.len()];
+        return (Some(name), sep, message);
+    }
+    (None, "", text)
 }
 
 impl YSTB {
@@ -274,29 +393,14 @@ impl YSTB {
         filename: &str,
         encoding: Encoding,
         config: &ExtraConfig,
-        archive: Option<&Box<dyn Script>>,
+        archive: Option<&dyn Script>,
     ) -> Result<Self> {
         let mut sig = [0; 4];
         reader.read_exact(&mut sig)?;
         if &sig != b"YSTB" {
             anyhow::bail!("Unsupported YSTB file.");
         }
-        let mut xor_key = None;
-        let data = match YSTBData::unpack(&mut reader, false, encoding, &None) {
-            Ok(data) => data,
-            Err(err) => {
-                let key = Self::get_xor_key(&mut reader)?;
-                if key == 0 {
-                    return Err(err);
-                }
-                xor_key = Some(key);
-                let mut writer = MemWriter::with_capacity(reader.stream_length()? as usize);
-                Self::xor(&mut reader, &mut writer, key)?;
-                let mut reader = writer.to_ref();
-                reader.pos = 4;
-                YSTBData::unpack(&mut reader, false, encoding, &None)?
-            }
-        };
+        let (data, xor_key) = Self::read_data(&mut reader, encoding)?;
         // println!("xor_key: {:?}, {:#?}", xor_key, data);
         let yscm = if let Some(path) = config.yuris_ysc_path.as_ref() {
             crate::utils::files::read_file(path)?
@@ -318,7 +422,83 @@ impl YSTB {
         let mut reader = MemReader::new(yscm);
         reader.pos = 4;
         let com = YSCMData::unpack(&mut reader, false, encoding, &None)?;
-        Ok(Self { data, com, xor_key })
+        let commands = TextCommands::new(&com, config);
+        Ok(Self {
+            data,
+            com,
+            xor_key,
+            commands,
+        })
+    }
+
+    /// Reads YSTB data after signature, decrypting it if needed.
+    ///
+    /// Returns the data and the XOR key used by the file.
+    fn read_data<T: Read + Seek>(
+        reader: &mut T,
+        encoding: Encoding,
+    ) -> Result<(YSTBData, Option<u32>)> {
+        let mut xor_key = None;
+        let data = match YSTBData::unpack(reader, false, encoding, &None) {
+            Ok(data) => data,
+            Err(err) => {
+                let key = Self::get_xor_key(reader)?;
+                if key == 0 {
+                    return Err(err);
+                }
+                xor_key = Some(key);
+                let mut writer = MemWriter::with_capacity(reader.stream_length()? as usize);
+                Self::xor(reader, &mut writer, key)?;
+                let mut reader = writer.to_ref();
+                reader.pos = 4;
+                YSTBData::unpack(&mut reader, false, encoding, &None)?
+            }
+        };
+        Ok((data, xor_key))
+    }
+
+    /// Returns the target label of GOSUB instruction.
+    fn gosub_target(inst: &YSTBInst) -> Option<String> {
+        let arg0 = inst.args.first()?;
+        decode_to_string(arg0.encoding, arg0.string_literal()?, true).ok()
+    }
+
+    fn write_data<W: Write + Seek>(&self, data: &YSTBData, mut writer: W) -> Result<()> {
+        let mut insts = MemWriter::new();
+        let mut args = MemWriter::new();
+        let mut args_data = MemWriter::new();
+        let mut arg_count = 0u32;
+        for inst in &data.insts {
+            insts.write_struct(&inst.base, false, Encoding::Cp932, &None)?;
+            for arg in &inst.args {
+                args.write_struct(&arg.base, false, Encoding::Cp932, &None)?;
+                args.write_u32(args_data.data.len() as u32)?;
+                args_data.write_all(&arg.data)?;
+                arg_count += 1;
+            }
+        }
+        let mut header = data.header.clone();
+        header.inst_entry_count = data.insts.len() as u32;
+        header.inst_index_size = insts.data.len() as u32;
+        header.args_index_size = arg_count * 0xC;
+        header.args_data_size = args_data.data.len() as u32;
+        header.line_numbers_size = data.line_numbers.len() as u32;
+        writer.write_all(b"YSTB")?;
+        writer.write_struct(&header, false, Encoding::Cp932, &None)?;
+        for seg in [&insts.data, &args.data, &args_data.data, &data.line_numbers] {
+            match self.xor_key {
+                Some(key) => {
+                    // Every segment is xored from its own start.
+                    let start = writer.stream_position()?;
+                    let mut stream =
+                        XoredKeyStream::new(&mut writer, key.to_le_bytes().to_vec(), 4 - start % 4);
+                    stream.write_all(seg)?;
+                }
+                None => writer.write_all(seg)?,
+            }
+        }
+        writer.flush()?;
+        Ok(())
     }
 
     fn get_xor_key<T: Read + Seek>(reader: &mut T) -> Result<u32> {
@@ -405,7 +585,7 @@ impl Script for YSTB {
     }
 
     fn is_output_supported(&self, output: OutputScriptType) -> bool {
-        matches!(output, OutputScriptType::Custom)
+        matches!(output, OutputScriptType::Custom) || self.commands.has_texts()
     }
 
     fn default_format_type(&self) -> FormatOptions {
@@ -416,6 +596,86 @@ impl Script for YSTB {
         "txt"
     }
 
+    fn extract_messages(&self) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        for inst in self.data.insts.iter() {
+            if Some(inst.opcode) == self.commands.word {
+                for arg in &inst.args {
+                    let text = arg.text()?;
+                    let (name, _, message) = split_name(&text);
+                    messages.push(Message::new(
+                        message.replace("\\R", "\n"),
+                        name.map(|s| s.to_owned()),
+                    ));
+                }
+            } else if self.commands.is_choice(inst) {
+                for arg in &inst.args[1..] {
+                    if self.commands.is_choice_text(arg) {
+                        messages.push(Message::new(arg.text()?, None));
+                    }
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    fn import_messages<'a>(
+        &'a self,
+        messages: Vec<Message>,
+        file: Box<dyn WriteSeek + 'a>,
+        _filename: &str,
+        encoding: Encoding,
+        replacement: Option<&'a ReplacementTable>,
+    ) -> Result<()> {
+        let mut data = self.data.clone();
+        let mut mess = messages.iter();
+        let replace = |mut s: String| {
+            if let Some(rep) = replacement {
+                for (k, v) in &rep.map {
+                    s = s.replace(k, v);
+                }
+            }
+            s
+        };
+        for inst in data.insts.iter_mut() {
+            if Some(inst.opcode) == self.commands.word {
+                for arg in inst.args.iter_mut() {
+                    let m = mess
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("No more messages to import"))?;
+                    let text = arg.text()?;
+                    let (name, sep, _) = split_name(&text);
+                    let mut s = String::new();
+                    if name.is_some() {
+                        let name = m
+                            .name
+                            .as_ref()
+                            .ok_or_else(|| anyhow::anyhow!("Message don't have name"))?;
+                        s.push('【');
+                        s.push_str(&replace(name.clone()));
+                        s.push('】');
+                        s.push_str(sep);
+                    }
+                    s.push_str(&replace(m.message.replace("\n", "\\R")));
+                    arg.set_text(&s, encoding)?;
+                }
+            } else if self.commands.is_choice(inst) {
+                for arg in inst.args[1..].iter_mut() {
+                    if self.commands.is_choice_text(arg) {
+                        let m = mess
+                            .next()
+                            .ok_or_else(|| anyhow::anyhow!("No more messages to import"))?;
+                        arg.set_text(&replace(m.message.clone()), encoding)?;
+                    }
+                }
+            }
+        }
+        if mess.next().is_some() {
+            return Err(anyhow::anyhow!("Some messages were not processed."));
+        }
+        self.write_data(&data, file)
+    }
+
     fn custom_export(&self, filename: &std::path::Path, encoding: Encoding) -> Result<()> {
         let mut file = MemWriter::new();
         let mut indent = String::new();
@@ -482,3 +742,145 @@ impl Script for YSTB {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::{ArgumentMeta, CodeMeta};
+    use super::*;
+
+    fn literal(s: &str) -> Vec<u8> {
+        let mut data = vec![b'M'];
+        data.extend_from_slice(&((s.len() + 2) as u16).to_le_bytes());
+        data.push(b'"');
+        data.extend_from_slice(s.as_bytes());
+        data.push(b'"');
+        data
+    }
+
+    fn inst(opcode: u8, args: Vec<Vec<u8>>) -> YSTBInst {
+        YSTBInst {
+            base: YSTBInstBase {
+                opcode,
+                arg_count: args.len() as u8,
+                unk: 0,
+            },
+            args: args
+                .into_iter()
+                .enumerate()
+                .map(|(i, data)| YSTBArg {
+                    base: YSTBArgBase {
+                        id: i as u16,
+                        typ: 0,
+                        size: data.len() as u32,
+                    },
+                    data,
+                    encoding: Encoding::Cp932,
+                })
+                .collect(),
+        }
+    }
+
+    fn yscm() -> YSCMData {
+        let meta = |name: &str, arguments: &[&str]| CodeMeta {
+            name: name.to_string(),
+            arguments: arguments
+                .iter()
+                .map(|name| ArgumentMeta {
+                    name: name.to_string(),
+                    data: 0,
+                })
+                .collect(),
+        };
+        YSCMData {
+            engine: 0,
+            opcode_length: 2,
+            unk: 0,
+            opcodes: vec![
+                meta("WORD", &[]),
+                meta("GOSUB", &["#", "PSTR1", "PSTR2", "PINT1"]),
+            ],
+            errmsgs: Vec::new(),
+            unk_tbl: Vec::new(),
+        }
+    }
+
+    fn data() -> YSTBData {
+        YSTBData {
+            header: YSTBHeader {
+                version: 468,
+                inst_entry_count: 0,
+                inst_index_size: 0,
+                args_index_size: 0,
+                args_data_size: 0,
+                line_numbers_size: 0,
+                reserve0: 0,
+            },
+            insts: vec![
+                inst(
+                    0,
+                    vec![b"\x81\x79name\x81\x7a\t\x81\x75hello\\R\x81\x76".to_vec()],
+                ),
+                inst(1, vec![literal("SEL"), literal("A"), literal("B")]),
+                // Not a choice label by default
+                inst(1, vec![literal("SUB"), literal("C")]),
+            ],
+            line_numbers: vec![1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0],
+        }
+    }
+
+    #[test]
+    fn test_import_messages() {
+        let data = data();
+        let ystb = YSTB {
+            data,
+            com: yscm(),
+            xor_key: Some(0x12345678),
+            commands: TextCommands::new(&yscm(), &ExtraConfig::default()),
+        };
+        let mut messages = ystb.extract_messages().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].name.as_deref(), Some("name"));
+        assert_eq!(messages[0].message, "「hello\n」");
+        assert_eq!(messages[2].message, "B");
+        messages[0].name = Some("名前".to_string());
+        messages[0].message = "「こんにちは\n世界」".to_string();
+        messages[1].message = "選択肢".to_string();
+        let mut out = MemWriter::new();
+        ystb.import_messages(messages, Box::new(&mut out), "", Encoding::Cp932, None)
+            .unwrap();
+        let mut reader = MemReader::new(out.into_inner());
+        reader.pos = 4;
+        let (data, xor_key) = YSTB::read_data(&mut reader, Encoding::Cp932).unwrap();
+        assert_eq!(xor_key, Some(0x12345678));
+        assert_eq!(data.line_numbers, vec![1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0]);
+        let imported = YSTB {
+            data,
+            com: yscm(),
+            xor_key,
+            commands: TextCommands::new(&yscm(), &ExtraConfig::default()),
+        };
+        let messages = imported.extract_messages().unwrap();
+        assert_eq!(messages[0].name.as_deref(), Some("名前"));
+        assert_eq!(messages[0].message, "「こんにちは\n世界」");
+        assert_eq!(messages[1].message, "選択肢");
+        assert_eq!(messages[2].message, "B");
+        let word = &imported.data.insts[0].args[0];
+        assert_eq!(word.text().unwrap(), "【名前】\t「こんにちは\\R世界」");
+    }
+
+    #[test]
+    fn test_choice_labels() {
+        let mut config = ExtraConfig::default();
+        config.yuris_ystb_choice_labels =
+            std::sync::Arc::new(["SUB".to_string()].into_iter().collect());
+        let ystb = YSTB {
+            data: data(),
+            com: yscm(),
+            xor_key: None,
+            commands: TextCommands::new(&yscm(), &config),
+        };
+        let messages = ystb.extract_messages().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].message, "C");
+    }
+}
@@ -0,0 +1,131 @@
+//! Create → open round-trip of archives.
+use super::fixtures::{self, Fixture};
+use super::*;
+use crate::scripts::BUILDER;
+use crate::scripts::base::*;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Reads all files in the archive.
+fn read_archive(
+    builder: &dyn ScriptBuilder,
+    filename: &str,
+    config: &ExtraConfig,
+) -> Result<BTreeMap<String, Vec<u8>>> {
+    let (encoding, archive_encoding, _) = encodings(builder);
+    let script =
+        builder.build_script_from_file(filename, encoding, archive_encoding, config, None)?;
+    let mut files = BTreeMap::new();
+    let names = script
+        .iter_archive_filename()?
+        .collect::<Result<Vec<_>>>()?;
+    for (i, name) in names.into_iter().enumerate() {
+        let data = script
+            .open_file(i)
+            .and_then(|mut f| f.data())
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {:#}", name, e))?;
+        files.insert(name, data);
+    }
+    Ok(files)
+}
+
+/// Creates an archive which contains `files` in the same way as packing from command line.
+fn create_archive(
+    builder: &dyn ScriptBuilder,
+    filename: &str,
+    files: &BTreeMap<String, Vec<u8>>,
+    config: &ExtraConfig,
+) -> Result<()> {
+    let (_, archive_encoding, _) = encodings(builder);
+    let mut names: Vec<&str> = files.keys().map(|s| s.as_str()).collect();
+    let mut archive = builder.create_archive(filename, &names, archive_encoding, config)?;
+    if let Some(pre) = archive.prelist()? {
+        let mut index = 0;
+        for name in pre {
+            let name = name?;
+            if let Some(pos) = names.iter().position(|&n| n == name) {
+                let name = names.remove(pos);
+                names.insert(index, name);
+                index += 1;
+            }
+        }
+    }
+    for name in names {
+        let data = &files[name];
+        let mut f = archive.new_file_non_seek(name, Some(data.len() as u64))?;
+        f.write_all(data)?;
+    }
+    archive.write_header()?;
+    Ok(())
+}
+
+/// Packs `files` into a new archive and checks that the same files are read from it.
+fn roundtrip(
+    builder: &dyn ScriptBuilder,
+    dir: &std::path::Path,
+    files: &BTreeMap<String, Vec<u8>>,
+) -> Result<()> {
+    let config = fixtures::config(builder);
+    fixtures::prepare(builder, dir)?;
+    let filename = dir
+        .join(fixtures::archive_filename(builder))
+        .to_string_lossy()
+        .into_owned();
+    create_archive(builder, &filename, files, &config)?;
+    let readed = read_archive(builder, &filename, &config)
+        .map_err(|e| anyhow::anyhow!("failed to open created archive: {:#}", e))?;
+    let _ = std::fs::remove_file(&filename);
+    for (name, data) in files {
+        match readed.get(name) {
+            Some(d) if d == data => {}
+            Some(d) => anyhow::bail!(
+                "content of {} changed ({} bytes -> {} bytes)",
+                name,
+                data.len(),
+                d.len()
+            ),
+            None => anyhow::bail!("{} is missing in created archive", name),
+        }
+    }
+    if readed.len() != files.len() {
+        anyhow::bail!(
+            "created archive contains {} files, expected {}",
+            readed.len(),
+            files.len()
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_archive_roundtrip() {
+    let dir = crate::utils::testing::temp_dir("archive");
+    let mut report = Report::new("archive");
+    for builder in BUILDER.iter() {
+        let builder = builder.as_ref();
+        if !builder.is_archive() {
+            continue;
+        }
+        if !builder.can_create_archive() {
+            report.skip(builder, "creating archives is not supported");
+            continue;
+        }
+        let synthetic = fixtures::synthetic_archive_files(builder)
+            .into_iter()
+            .map(|f| (f.name, f.data))
+            .collect();
+        let mut inputs = vec![("<synthetic>".to_string(), Ok(synthetic))];
+        // Real archives are unpacked and then packed again.
+        for Fixture { name, .. } in fixtures::load_fixtures(builder) {
+            let files = read_archive(builder, &name, &fixtures::config(builder));
+            inputs.push((name, files));
+        }
+        for (name, files) in inputs {
+            let result = files.and_then(|files| roundtrip(builder, &dir, &files));
+            report.check(builder, &name, result);
+        }
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    report.finish();
+}
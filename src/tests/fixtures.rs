@@ -0,0 +1,1118 @@
+//! Inputs for round-trip tests.
+use super::script_type_name;
+use crate::scripts::base::ScriptBuilder;
+use crate::types::*;
+
+/// Environment variable which points to a directory containing real game files.
+pub const FIXTURES_ENV: &str = "MSG_TOOL_TEST_FIXTURES";
+
+/// A file used as input of round-trip tests.
+pub struct Fixture {
+    /// Filename passed to the builder.
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Returns synthetic samples written in UTF-8.
+///
+/// Samples are encoded with the default encoding of the builder before testing.
+fn synthetic_scripts(typ: &ScriptType) -> &'static [(&'static str, &'static str)] {
+    match typ {
+        #[cfg(feature = "artemis")]
+        ScriptType::ArtemisTxt => &[(
+            "sample.txt",
+            "*start\r\n// comment\r\n[bg file=\"bg01\"]\r\n#アリス\r\n「こんにちは、世界。」\r\nナレーションの文章。\r\n#BGM01\r\n（独り言）\r\n",
+        )],
+        #[cfg(feature = "artemis-panmimisoft")]
+        ScriptType::ArtemisPanmimisoftTxt => &[(
+            "sample.txt",
+            "*start\r\n[adv]\r\n[アリス]\r\n「こんにちは」\r\n\r\n地の文です。\r\n二行目。\r\n\r\n[selectbtn_init]\r\n[selectbtn text=\"はい\"]\r\n[/selectbtn]\r\n",
+        )],
+        #[cfg(feature = "artemis")]
+        ScriptType::Artemis => &[(
+            "sample.ast",
+            "astver = 2.0\nast = {\n\tlabel = {top = {block=\"block_00000\"}},\n\tblock_00000 = {\n\t\ttext = {ja = {{name={\"アリス\"}, \"「こんにちは」\", {\"rt2\"}}}},\n\t\tlinknext = \"block_00001\",\n\t},\n\tblock_00001 = {\n\t\ttext = {ja = {{\"地の文です。\", {\"rt2\"}, \"二行目。\", {\"rt2\"}}}},\n\t\tselect = {ja = {\"はい\", \"いいえ\"}},\n\t},\n}\n",
+        )],
+        #[cfg(feature = "entis-gls")]
+        ScriptType::EntisGls => &[(
+            "sample.srcxml",
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xscript>\n<code>\n<msg name=\"アリス\" text=\"「こんにちは」\"/>\n<msg name=\"\" text=\"地の文です。\"/>\n<select>\n<menu text=\"はい\"/>\n<menu text=\"いいえ\"/>\n</select>\n</code>\n</xscript>\n",
+        )],
+        #[cfg(feature = "kirikiri")]
+        ScriptType::Kirikiri => &[(
+            "sample.ks",
+            "*start|\n@bg storage=\"bg01\"\n[cm]\n@nm t=\"Alice\"\nHello world.[p]\n[name text=\"Bob\"]How are you?[l][r]\nFine.[p]\n",
+        )],
+        #[cfg(feature = "musica")]
+        ScriptType::Musica => &[(
+            "sample.sc",
+            ".label start\n.message 1 vo_001 アリス 「こんにちは」\n.message 2 - - 地の文です。\n.goto start\n",
+        )],
+        #[cfg(feature = "qlie")]
+        ScriptType::Qlie => &[(
+            "sample.s",
+            "@@start\r\n\\bg,bg01\r\n【アリス】\r\n「こんにちは」\r\n地の文です。[n]二行目。\r\n^select,はい,いいえ\r\n",
+        )],
+        #[cfg(feature = "yuris")]
+        ScriptType::YurisTxt => &[(
+            "sample.txt",
+            "\\BG(bg01)\r\n\\VO(ALI_0001)【アリス】「こんにちは、世界。」\r\n地の文です。\\R二行目。\r\n//comment\r\n",
+        )],
+        _ => &[],
+    }
+}
+
+/// Returns synthetic binary samples.
+///
+/// Files which are read together with the script are written into a temporary directory, and
+/// the sample is named by its path in that directory.
+fn synthetic_binary_scripts(typ: &ScriptType) -> Vec<Fixture> {
+    match typ {
+        #[cfg(feature = "bgi")]
+        ScriptType::BGI => vec![binary::bgi_script()],
+        #[cfg(feature = "bgi")]
+        ScriptType::BGIBp => vec![binary::bgi_bp_script()],
+        #[cfg(feature = "bgi")]
+        ScriptType::BGIBsi => vec![binary::bgi_bsi_script()],
+        #[cfg(feature = "bgi-arc")]
+        ScriptType::BGIDsc => vec![binary::bgi_dsc_file()],
+        #[cfg(feature = "emote-img")]
+        ScriptType::EmotePsb => vec![binary::psb_script()],
+        #[cfg(feature = "entis-gls")]
+        ScriptType::EntisGlsCsx => vec![binary::csx_script()],
+        #[cfg(feature = "escude")]
+        ScriptType::EscudeList => vec![binary::escude_list()],
+        #[cfg(feature = "ex-hibit")]
+        ScriptType::ExHibit => vec![binary::rld_script(), binary::rld_def_chara()],
+        #[cfg(feature = "favorite")]
+        ScriptType::Favorite => vec![binary::hcb_script()],
+        #[cfg(feature = "hexen-haus")]
+        ScriptType::HexenHaus => vec![binary::hexen_haus_script()],
+        #[cfg(feature = "kirikiri")]
+        ScriptType::KirikiriMdf => vec![binary::mdf_script()],
+        #[cfg(feature = "kirikiri")]
+        ScriptType::KirikiriScn => vec![binary::scn_script()],
+        #[cfg(feature = "kirikiri")]
+        ScriptType::KirikiriSimpleCrypt => vec![binary::simple_crypt_script()],
+        #[cfg(feature = "kirikiri")]
+        ScriptType::KirikiriTjs2 => vec![binary::tjs2_script()],
+        #[cfg(feature = "kirikiri")]
+        ScriptType::KirikiriTjsNs0 => vec![binary::tjs_ns0_script()],
+        #[cfg(feature = "qlie-img")]
+        ScriptType::QlieAbmp10 => vec![binary::abmp_image()],
+        #[cfg(feature = "circus")]
+        ScriptType::Circus => vec![binary::circus_script()],
+        #[cfg(feature = "cat-system")]
+        ScriptType::CatSystem => vec![binary::cst_script()],
+        #[cfg(feature = "cat-system")]
+        ScriptType::CatSystemCstl => vec![binary::cstl_script()],
+        #[cfg(feature = "escude")]
+        ScriptType::Escude => vec![binary::escude_script()],
+        #[cfg(feature = "silky")]
+        ScriptType::Silky => vec![binary::silky_script()],
+        #[cfg(feature = "silky")]
+        ScriptType::SilkyMap => vec![binary::silky_map()],
+        #[cfg(feature = "softpal")]
+        ScriptType::Softpal => vec![binary::softpal_script()],
+        #[cfg(feature = "will-plus")]
+        ScriptType::WillPlusWs2 => vec![binary::ws2_script()],
+        #[cfg(feature = "yaneurao-itufuru")]
+        ScriptType::YaneuraoItufuru => vec![binary::itufuru_script()],
+        #[cfg(feature = "yuris")]
+        ScriptType::YurisYSTB => vec![binary::ystb_script()],
+        #[cfg(feature = "yuris")]
+        ScriptType::YurisYSCM => vec![binary::yscm_script()],
+        #[cfg(feature = "yuris")]
+        ScriptType::YurisYSER => vec![binary::yser_script()],
+        #[cfg(feature = "yuris")]
+        ScriptType::YurisYSCFG => vec![binary::yscfg_script()],
+        #[cfg(feature = "artemis")]
+        ScriptType::ArtemisAsb => vec![binary::asb_script()],
+        // Unreachable if the only enabled script type has a sample.
+        #[allow(unreachable_patterns)]
+        _ => Vec::new(),
+    }
+}
+
+/// Builders of synthetic binary samples.
+#[cfg(any(
+    feature = "artemis",
+    feature = "bgi",
+    feature = "bgi-arc",
+    feature = "circus",
+    feature = "cat-system",
+    feature = "emote-img",
+    feature = "entis-gls",
+    feature = "escude",
+    feature = "ex-hibit",
+    feature = "favorite",
+    feature = "hexen-haus",
+    feature = "kirikiri",
+    feature = "qlie-img",
+    feature = "silky",
+    feature = "softpal",
+    feature = "will-plus",
+    feature = "yaneurao-itufuru",
+    feature = "yuris"
+))]
+mod binary {
+    use super::Fixture;
+    use crate::types::*;
+
+    /// Encodes `text` in CP932 without null terminator.
+    #[cfg_attr(
+        not(any(
+            feature = "bgi",
+            feature = "circus",
+            feature = "cat-system",
+            feature = "escude",
+            feature = "ex-hibit",
+            feature = "qlie-img",
+            feature = "softpal",
+            feature = "yuris"
+        )),
+        allow(dead_code)
+    )]
+    fn sjis(text: &str) -> Vec<u8> {
+        crate::utils::encoding::encode_string(Encoding::Cp932, text, true)
+            .expect("synthetic sample should be encodable")
+    }
+
+    fn fixture(name: &str, data: Vec<u8>) -> Fixture {
+        Fixture {
+            name: name.to_string(),
+            data,
+        }
+    }
+
+    #[cfg_attr(
+        not(any(
+            feature = "artemis",
+            feature = "bgi",
+            feature = "bgi-arc",
+            feature = "circus",
+            feature = "cat-system",
+            feature = "entis-gls",
+            feature = "escude",
+            feature = "ex-hibit",
+            feature = "favorite",
+            feature = "kirikiri",
+            feature = "qlie-img",
+            feature = "silky",
+            feature = "softpal",
+            feature = "yuris"
+        )),
+        allow(dead_code)
+    )]
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// Encodes `text` in UTF-16LE without null terminator.
+    #[cfg(any(feature = "entis-gls", feature = "kirikiri"))]
+    fn utf16(text: &str) -> Vec<u8> {
+        crate::utils::encoding::encode_string(Encoding::Utf16LE, text, true)
+            .expect("synthetic sample should be encodable")
+    }
+
+    /// Writes `psb` as PSB version 4.
+    #[cfg(any(feature = "emote-img", feature = "kirikiri"))]
+    fn psb_file(psb: crate::ext::psb::VirtualPsbFixed) -> Vec<u8> {
+        use crate::ext::psb::PsbWriterExt;
+        let mut writer = crate::ext::io::MemWriter::new();
+        psb.to_psb(false).finish_v4(&mut writer).unwrap();
+        writer.into_inner()
+    }
+
+    /// Artemis ASB script with a named message and a choice.
+    #[cfg(feature = "artemis")]
+    pub fn asb_script() -> Fixture {
+        let string = |s: &str| {
+            let mut data = u32s(&[s.len() as u32]);
+            data.extend(s.as_bytes());
+            data.push(0);
+            data
+        };
+        let commands: [(&str, &[(&str, &str)]); 6] = [
+            ("name", &[("0", "アリス")]),
+            ("print", &[("data", "「こんにちは」")]),
+            ("rt", &[]),
+            ("print", &[("data", "二行目")]),
+            ("hcls", &[("0", "1")]),
+            ("sel_text", &[("text", "はい")]),
+        ];
+        let mut file = b"ASB\0\0".to_vec();
+        file.extend(u32s(&[commands.len() as u32]));
+        for (line, (name, attributes)) in commands.iter().enumerate() {
+            // Command, name, line number and attributes
+            file.extend(u32s(&[0]));
+            file.extend(string(name));
+            file.extend(u32s(&[line as u32 + 1, attributes.len() as u32]));
+            for (key, value) in attributes.iter() {
+                file.extend(string(key));
+                file.extend(string(value));
+            }
+        }
+        fixture("sample.asb", file)
+    }
+
+    /// BGI v1 script with header, which shows (name, message) pairs.
+    #[cfg(feature = "bgi")]
+    pub fn bgi_script() -> Fixture {
+        let strings = ["アリス", "「こんにちは」", "", "地の文です。\n二行目。"];
+        // push name, push message, show message, ..., exit
+        let code_len = (strings.len() * 2 + strings.len() / 2 + 1) * 4;
+        let mut code = Vec::new();
+        let mut data = Vec::new();
+        for pair in strings.chunks(2) {
+            for s in pair {
+                code.extend(u32s(&[0x3, (code_len + data.len()) as u32]));
+                data.extend(sjis(s));
+                data.push(0);
+            }
+            code.extend(u32s(&[0x140]));
+        }
+        code.extend(u32s(&[0x1B]));
+        let mut file = b"BurikoCompiledScriptVer1.00\0".to_vec();
+        // Header size
+        file.extend(u32s(&[4]));
+        file.extend(code);
+        file.extend(data);
+        fixture("sample", file)
+    }
+
+    /// BGI BP script, which pushes two strings.
+    #[cfg(feature = "bgi")]
+    pub fn bgi_bp_script() -> Fixture {
+        let strings = [sjis("はい"), sjis("いいえ")];
+        // Header size and instructions size
+        let mut file = u32s(&[8, 0]);
+        // push string, push string, end with padding. Offsets are relative to the operand.
+        let mut address = 16;
+        for (i, s) in strings.iter().enumerate() {
+            let operand = 9 + i * 3;
+            file.push(0x05);
+            file.extend(((address - operand + 1) as u16).to_le_bytes());
+            address += s.len() + 1;
+        }
+        file.extend([0x17, 0]);
+        for s in &strings {
+            file.extend(s);
+            file.push(0);
+        }
+        let size = file.len() as u32 - 8;
+        file[4..8].copy_from_slice(&size.to_le_bytes());
+        fixture("sample._bp", file)
+    }
+
+    /// BGI BSI script with one section, which is encoded in UTF-8.
+    #[cfg(feature = "bgi")]
+    pub fn bgi_bsi_script() -> Fixture {
+        let mut file = u32s(&[1]);
+        file.extend(b"system\0");
+        file.extend(u32s(&[2]));
+        for s in ["caption", "タイトル", "version", "1.0"] {
+            file.extend(s.as_bytes());
+            file.push(0);
+        }
+        fixture("sample._bsi", file)
+    }
+
+    /// BGI DSC compressed file.
+    #[cfg(feature = "bgi-arc")]
+    pub fn bgi_dsc_file() -> Fixture {
+        use crate::scripts::bgi::archive::dsc::DscEncoder;
+        let data = "Hello world.\r\n".repeat(16);
+        let mut writer = std::io::BufWriter::new(std::io::Cursor::new(Vec::new()));
+        let level = ExtraConfig::default().bgi_compress_level;
+        DscEncoder::new(&mut writer, level)
+            .pack(data.as_bytes())
+            .unwrap();
+        let file = writer.into_inner().unwrap().into_inner();
+        fixture("sample", file)
+    }
+
+    /// Circus MES script of ffexa in the old format, which has one block per token.
+    #[cfg(feature = "circus")]
+    pub fn circus_script() -> Fixture {
+        let enc = |s: &str| -> Vec<u8> { sjis(s).iter().map(|b| b.wrapping_sub(0x20)).collect() };
+        let tokens = [
+            // Name
+            [vec![0x4B], enc("アリス"), vec![0]].concat(),
+            // Encrypted message
+            [vec![0x4A], enc("「こんにちは」"), vec![0]].concat(),
+            // Unencrypted message
+            [vec![0x43], sjis("地の文です。"), vec![0]].concat(),
+            vec![0x00, 0x01, 0x00],
+        ];
+        let mut file = u32s(&[tokens.len() as u32]);
+        let mut offset = 0;
+        for token in &tokens {
+            file.extend(u32s(&[offset as u32 + 2]));
+            offset += token.len();
+        }
+        file.extend(0x7B69u16.to_le_bytes());
+        file.extend(tokens.concat());
+        fixture("sample.mes", file)
+    }
+
+    /// Uncompressed CatSystem2 scene script.
+    #[cfg(feature = "cat-system")]
+    pub fn cst_script() -> Fixture {
+        let strings: [(u8, &str); 6] = [
+            (0xF1, "1"),
+            (0x21, "アリス"),
+            (0x20, "「こんにちは」\\n二行目"),
+            (0x30, "bg 01"),
+            (0x20, "地の文です。"),
+            (0x03, ""),
+        ];
+        let mut addresses = Vec::new();
+        let mut data = Vec::new();
+        for (typ, text) in strings {
+            addresses.push(data.len() as u32);
+            data.extend([1, typ]);
+            data.extend(sjis(text));
+            data.push(0);
+        }
+        let table = u32s(&addresses);
+        // Data length, clear screen count, string table offset and strings offset
+        let mut body = u32s(&[(table.len() + data.len()) as u32, 0, 0, table.len() as u32]);
+        body.extend(table);
+        body.extend(data);
+        let mut file = b"CatScene".to_vec();
+        file.extend(u32s(&[0, body.len() as u32]));
+        file.extend(body);
+        fixture("sample.cst", file)
+    }
+
+    /// CatSystem2 CSTL script with one language, which is encoded in UTF-8.
+    #[cfg(feature = "cat-system")]
+    pub fn cstl_script() -> Fixture {
+        let string = |s: &str| [vec![s.len() as u8], s.as_bytes().to_vec()].concat();
+        let mut file = b"CSTL".to_vec();
+        // Unknown and language count
+        file.extend(u32s(&[0]));
+        file.push(1);
+        file.extend(string("ja"));
+        // Message count, followed by (name, message)
+        file.push(2);
+        file.extend(string("アリス"));
+        file.extend(string("「こんにちは」"));
+        file.push(0);
+        file.extend(string("地の文です。\n二行目。"));
+        fixture("sample.cstl", file)
+    }
+
+    /// Emote PSB with a few values.
+    #[cfg(feature = "emote-img")]
+    pub fn psb_script() -> Fixture {
+        let psb = crate::ext::psb::VirtualPsbFixed::with_json(&json::object! {
+            "version": 3,
+            "encryption": 0,
+            "data": {
+                "id": "sample",
+                "texts": ["「こんにちは」", "地の文です。"],
+                "count": 2,
+            },
+        })
+        .unwrap();
+        fixture("sample.psb", psb_file(psb))
+    }
+
+    /// Entis GLS CSX script of version 1 with a named message and a choice.
+    #[cfg(feature = "entis-gls")]
+    pub fn csx_script() -> Fixture {
+        let string = |s: &str| {
+            let encoded = utf16(s);
+            let mut data = u32s(&[encoded.len() as u32 / 2]);
+            data.extend(encoded);
+            data
+        };
+        // Load immediate string or integer
+        let load_string = |s: &str| [vec![2, 0, 6], string(s)].concat();
+        let load_int = |v: u32| [vec![2, 0, 4], u32s(&[v])].concat();
+        // Call auto function
+        let call = |args: u32, name: &str| [vec![8, 5], u32s(&[args]), string(name)].concat();
+        let image = [
+            load_string("アリス"),
+            load_int(0),
+            call(2, "Talk"),
+            load_string("「こんにちは」"),
+            call(1, "Mess"),
+            // Free
+            vec![1],
+            call(0, "Wait"),
+            load_string("はい"),
+            load_int(1),
+            call(2, "AddSelect"),
+            // Return
+            vec![9, 0],
+        ]
+        .concat();
+        let mut file = b"Entis\x1a\0\0".to_vec();
+        file.extend(u32s(&[0, 0]));
+        file.extend([0; 48]);
+        let sections: [(&[u8; 8], Vec<u8>); 5] = [
+            (b"image   ", image),
+            // Prologue, epilogue and function names
+            (b"function", u32s(&[0, 0, 0])),
+            (b"global  ", u32s(&[0])),
+            (b"data    ", u32s(&[0])),
+            // External and imported references
+            (b"linkinf ", u32s(&[0, 0, 0, 0])),
+        ];
+        for (id, data) in sections {
+            file.extend(id);
+            file.extend((data.len() as u64).to_le_bytes());
+            file.extend(data);
+        }
+        fixture("sample.csx", file)
+    }
+
+    /// Escu:de binary script without enum script.
+    #[cfg(feature = "escude")]
+    pub fn escude_script() -> Fixture {
+        let strings = ["「こんにちは」", "地の文です。<r>二行目。"];
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for s in strings {
+            offsets.push(data.len() as u32);
+            data.extend(sjis(s));
+            data.push(0);
+        }
+        let vms = [0u8; 8];
+        let mut file = b"ESCR1_00".to_vec();
+        file.extend(u32s(&[offsets.len() as u32]));
+        file.extend(u32s(&offsets));
+        file.extend(u32s(&[vms.len() as u32]));
+        file.extend(vms);
+        // unk1
+        file.extend(u32s(&[0]));
+        file.extend(data);
+        fixture("sample.bin", file)
+    }
+
+    /// Escu:de `enum_scr.bin` with character names.
+    #[cfg(feature = "escude")]
+    pub fn escude_list() -> Fixture {
+        let mut names = Vec::new();
+        for (text, color, face) in [("アリス", 0xFFFFFF, "f_ali"), ("ボブ", 0xFF8080, "f_bob")]
+        {
+            let mut text = sjis(text);
+            text.resize(64, 0x20);
+            let mut face = face.as_bytes().to_vec();
+            face.resize(32, 0x20);
+            names.extend(text);
+            names.extend(u32s(&[color]));
+            names.extend(face);
+        }
+        let mut file = b"LIST".to_vec();
+        // Data size, followed by entries of (id, size, data)
+        file.extend(u32s(&[names.len() as u32 + 8, 1, names.len() as u32]));
+        file.extend(names);
+        fixture("enum_scr.bin", file)
+    }
+
+    /// ExHibit rld script with a named message and a message without name.
+    #[cfg(feature = "ex-hibit")]
+    pub fn rld_script() -> Fixture {
+        let mut file = b"\0DLR".to_vec();
+        // Version, offset of ops, op count and flag
+        file.extend(u32s(&[1, 20, 3, 0]));
+        for (name, text) in [("アリス", "「こんにちは」"), ("$noname$", "地の文です。")]
+        {
+            // Message op with a name id and two strings
+            file.extend([28, 0, 1, 0x12]);
+            file.extend(u32s(&[0]));
+            for s in [name, text] {
+                file.extend(sjis(s));
+                file.push(0);
+            }
+        }
+        file.extend([0, 0, 0, 0]);
+        fixture("sample.rld", file)
+    }
+
+    /// ExHibit `defChara.rld`, which contains the name table.
+    #[cfg(feature = "ex-hibit")]
+    pub fn rld_def_chara() -> Fixture {
+        let mut file = b"\0DLR".to_vec();
+        file.extend(u32s(&[1, 20, 2, 0]));
+        for chara in ["1,0,0,アリス", "2,0,0,ボブ"] {
+            file.extend([48, 0, 0, 0x01]);
+            file.extend(sjis(chara));
+            file.push(0);
+        }
+        fixture("defChara.rld", file)
+    }
+
+    /// Favorite HCB script, which calls a speaker function before messages.
+    #[cfg(feature = "favorite")]
+    pub fn hcb_script() -> Fixture {
+        let string = |s: &str| {
+            let s = sjis(s);
+            [vec![0x0E, s.len() as u8 + 1], s, vec![0]].concat()
+        };
+        // Speaker function: initstack(3, 0), pushstring, ret
+        let mut code = vec![0x01, 3, 0];
+        code.extend(string("アリス"));
+        code.push(0x04);
+        let main = code.len() as u32 + 4;
+        // Main script: initstack(0, 0), call speaker, pushstring, pushstring, ret
+        code.extend([0x01, 0, 0, 0x02]);
+        code.extend(u32s(&[4]));
+        code.extend(string("「こんにちは」"));
+        code.extend(string("地の文です。"));
+        code.push(0x04);
+        let mut file = u32s(&[code.len() as u32 + 4]);
+        file.extend(code);
+        // Entry point, unknown, title and system imports
+        file.extend(u32s(&[main]));
+        file.extend([0; 6]);
+        file.extend([7]);
+        file.extend(b"sample\0");
+        file.extend(1u16.to_le_bytes());
+        file.extend([0, 12]);
+        file.extend(b"ThreadStart\0");
+        fixture("sample.hcb", file)
+    }
+
+    /// HexenHaus script with a named message.
+    #[cfg(feature = "hexen-haus")]
+    pub fn hexen_haus_script() -> Fixture {
+        // Messages are separated by units starting with 0x53 after XOR.
+        let mut data = b"_beginrp".to_vec();
+        data.extend([0; 8]);
+        for s in ["アリス「こんにちは」", "地の文です。"] {
+            data.extend(sjis(s));
+            data.extend([0x53, 0x00]);
+        }
+        let mut file = b"NORI".to_vec();
+        file.extend(data.iter().map(|b| b ^ 0x53));
+        fixture("sample.bin", file)
+    }
+
+    /// Kirikiri KAG script compressed into MDF.
+    #[cfg(feature = "kirikiri")]
+    pub fn mdf_script() -> Fixture {
+        let text = crate::utils::encoding::encode_string(
+            Encoding::Utf16LE,
+            "*start|\n@nm t=\"アリス\"\n「こんにちは」[p]\n",
+            true,
+        )
+        .unwrap();
+        let data = [&[0xFF, 0xFE][..], &text].concat();
+        let level = ExtraConfig::default().zlib_compression_level;
+        let file = crate::scripts::kirikiri::mdf::Mdf::pack(&data, level).unwrap();
+        fixture("sample.ks", file)
+    }
+
+    /// Kirikiri KAG script encrypted in SimpleCrypt mode 1.
+    #[cfg(feature = "kirikiri")]
+    pub fn simple_crypt_script() -> Fixture {
+        let text = crate::utils::encoding::encode_string(
+            Encoding::Utf16LE,
+            "*start|\n@nm t=\"アリス\"\n「こんにちは」[p]\n",
+            true,
+        )
+        .unwrap();
+        let level = ExtraConfig::default().zlib_compression_level;
+        let file =
+            crate::scripts::kirikiri::simple_crypt::SimpleCrypt::pack(1, &text, level).unwrap();
+        fixture("sample.ks", file)
+    }
+
+    /// Kirikiri scene file with a named message, a narration and a choice.
+    #[cfg(feature = "kirikiri")]
+    pub fn scn_script() -> Fixture {
+        let psb = crate::ext::psb::VirtualPsbFixed::with_json(&json::object! {
+            "version": 3,
+            "encryption": 0,
+            "data": {
+                "scenes": [{
+                    "label": "*start",
+                    "texts": [
+                        ["アリス", null, "「こんにちは」"],
+                        [null, null, "地の文です。"],
+                    ],
+                    "selects": [{"text": "はい"}],
+                }],
+            },
+        })
+        .unwrap();
+        fixture("sample.scn", psb_file(psb))
+    }
+
+    /// Kirikiri compiled TJS2 script with string constants.
+    #[cfg(feature = "kirikiri")]
+    pub fn tjs2_script() -> Fixture {
+        // Byte, short, long, longlong and double constants
+        let mut data = u32s(&[0, 0, 0, 0, 0]);
+        let strings = ["アリス", "「こんにちは」"];
+        data.extend(u32s(&[strings.len() as u32]));
+        for s in strings {
+            let encoded = utf16(s);
+            data.extend(u32s(&[encoded.len() as u32 / 2]));
+            data.extend(encoded);
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+        // Octet constants
+        data.extend(u32s(&[0]));
+        let mut file = b"TJS2100\0".to_vec();
+        file.extend(u32s(&[0]));
+        file.extend(b"DATA");
+        file.extend(u32s(&[data.len() as u32 + 8]));
+        file.extend(data);
+        // Objects are kept as is.
+        file.extend(b"OBJS");
+        file.extend(u32s(&[8]));
+        let size = file.len() as u32;
+        file[8..12].copy_from_slice(&size.to_le_bytes());
+        fixture("sample.tjs", file)
+    }
+
+    /// Kirikiri TJS/ns0 binary, created from json in the same way as from command line.
+    #[cfg(feature = "kirikiri")]
+    pub fn tjs_ns0_script() -> Fixture {
+        use crate::scripts::base::ScriptBuilder;
+        let dir = crate::utils::testing::temp_dir("fixture-tjs-ns0");
+        let path = dir.join("sample.json");
+        std::fs::write(
+            &path,
+            r#"{"name": "アリス", "texts": ["「こんにちは」", "地の文です。"], "count": 2, "scale": 1.5, "voice": null}"#,
+        )
+        .unwrap();
+        let mut writer = crate::ext::io::MemWriter::new();
+        crate::scripts::kirikiri::tjs_ns0::TjsNs0Builder::new()
+            .create_file(
+                &path.to_string_lossy(),
+                Box::new(&mut writer),
+                Encoding::Utf16LE,
+                Encoding::Utf8,
+                &ExtraConfig::default(),
+            )
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        fixture("sample.pbd", writer.into_inner())
+    }
+
+    /// Qlie abmp12 image with a data block and a PNG part.
+    #[cfg(feature = "qlie-img")]
+    pub fn abmp_image() -> Fixture {
+        let tag = |t: &str| {
+            let mut data = t.as_bytes().to_vec();
+            data.resize(16, 0);
+            data
+        };
+        let mut file = tag("abmp12");
+        file.extend(tag("abdata10"));
+        file.extend(u32s(&[4]));
+        file.extend([1, 2, 3, 4]);
+        file.extend(tag("abimage10"));
+        file.push(1);
+        file.extend(tag("abimgdat11"));
+        let name = sjis("顔");
+        file.extend((name.len() as u16).to_le_bytes());
+        file.extend(name);
+        // Empty internal name and PNG type
+        file.extend([0, 0, 2]);
+        let png = super::noise(32, 7);
+        file.extend(u32s(&[png.len() as u32]));
+        file.extend(png);
+        fixture("sample.b", file)
+    }
+
+    /// Silky Engine script of Silky Plus with a named message.
+    #[cfg(feature = "silky")]
+    pub fn silky_script() -> Fixture {
+        let text = |s: &str| [vec![0x0B], sjis(s), vec![0]].concat();
+        let int = |v: i32| [vec![0x32], v.to_be_bytes().to_vec()].concat();
+        // Message and special message count
+        let mut file = u32s(&[0, 0]);
+        // Set character name: ldstr, ldc.i4, ldc.i4, syscall
+        file.extend([vec![0x33], sjis("アリス"), vec![0]].concat());
+        file.extend(int(11));
+        file.extend(int(29));
+        file.push(0x18);
+        file.extend(text("「こんにちは」"));
+        file.push(0x00);
+        // Message with a new line
+        file.extend(text("地の文です。"));
+        file.extend([0x1C, 0x00]);
+        file.extend(text("二行目。"));
+        file.extend([0x00, 0x01]);
+        fixture("sample.mes", file)
+    }
+
+    /// Silky Engine map file, which is encoded in UTF-16LE.
+    #[cfg(feature = "silky")]
+    pub fn silky_map() -> Fixture {
+        let strings = ["学校", "駅前"];
+        let mut file = u32s(&[strings.len() as u32]);
+        let mut data = Vec::new();
+        for (i, s) in strings.iter().enumerate() {
+            let offset = 4 + strings.len() * 8 + data.len();
+            file.extend(u32s(&[i as u32, offset as u32]));
+            data.extend(crate::utils::encoding::encode_string(Encoding::Utf16LE, s, true).unwrap());
+            data.extend([0, 0]);
+        }
+        file.extend(data);
+        fixture("sample.map", file)
+    }
+
+    /// Softpal script with its `TEXT.DAT` and `POINT.DAT`.
+    #[cfg(feature = "softpal")]
+    pub fn softpal_script() -> Fixture {
+        let strings = [
+            "アリス",
+            "「こんにちは」",
+            "ボブ",
+            "地の文です。<br>二行目。",
+        ];
+        let mut texts = b"$TEXT_LIST__".to_vec();
+        texts.extend(u32s(&[strings.len() as u32]));
+        let mut addresses = Vec::new();
+        for (i, s) in strings.iter().enumerate() {
+            addresses.push(texts.len() as u32);
+            texts.extend(u32s(&[i as u32]));
+            texts.extend(sjis(s));
+            texts.push(0);
+        }
+        let mut script = b"Sv20".to_vec();
+        script.extend([0; 8]);
+        for pair in addresses.chunks(2) {
+            // push 0, push message, push name, push 0, text
+            for value in [0, pair[1], pair[0], 0] {
+                script.extend(u32s(&[0x1001F, value]));
+            }
+            script.extend(u32s(&[0x10088]));
+        }
+        let dir = crate::utils::testing::temp_dir("fixture-softpal");
+        std::fs::write(dir.join("TEXT.DAT"), texts).unwrap();
+        std::fs::write(dir.join("POINT.DAT"), b"_POINT_LIST_****").unwrap();
+        fixture(&dir.join("sample.src").to_string_lossy(), script)
+    }
+
+    /// WillPlus WS2 script with a named message and a choice, which is not encrypted.
+    #[cfg(feature = "will-plus")]
+    pub fn ws2_script() -> Fixture {
+        let mut file = vec![0x10; 4];
+        // Choices
+        file.extend(b"\x00\x0F\x0F\x02");
+        for (i, s) in ["はい", "いいえ"].iter().enumerate() {
+            file.extend([i as u8 + 1, 0]);
+            file.extend(sjis(s));
+            file.push(0);
+            file.extend([0x01, 0x02, 0x03, 0x04, 0x05, 0x00]);
+        }
+        file.push(0xFF);
+        // Name and message
+        file.extend(b"%LC");
+        file.extend(sjis("アリス"));
+        file.extend([0, 1, 2, 3, 4]);
+        file.extend(b"char\0");
+        file.extend(sjis("「こんにちは」%K%P"));
+        file.extend([0, 1, 2, 3, 4]);
+        fixture("sample.ws2", file)
+    }
+
+    /// Yaneurao script of Itufuru with a message and a choice.
+    #[cfg(feature = "yaneurao-itufuru")]
+    pub fn itufuru_script() -> Fixture {
+        let mut file = Vec::new();
+        for (instr, s) in [(0x2u16, "「こんにちは」\n"), (0x1E, "はい")] {
+            let s = sjis(s);
+            file.extend(instr.to_le_bytes());
+            file.extend((s.len() as u16 + 1).to_le_bytes());
+            file.extend(s);
+            file.push(0);
+        }
+        file.extend([0; 2]);
+        fixture("sample.dat", file)
+    }
+
+    /// Yu-Ris YSTB script with its `ysc.ybn`.
+    #[cfg(feature = "yuris")]
+    pub fn ystb_script() -> Fixture {
+        let literal = |s: &str| {
+            let s = sjis(s);
+            let mut data = vec![b'M'];
+            data.extend((s.len() as u16 + 2).to_le_bytes());
+            data.push(b'"');
+            data.extend(s);
+            data.push(b'"');
+            data
+        };
+        // (opcode, args): WORD and GOSUB "SEL"
+        let insts = [
+            (0u8, vec![sjis("【アリス】「こんにちは」\\R二行目")]),
+            (
+                1u8,
+                vec![literal("SEL"), literal("はい"), literal("いいえ")],
+            ),
+        ];
+        let mut inst_index = Vec::new();
+        let mut args_index = Vec::new();
+        let mut args_data = Vec::new();
+        for (opcode, args) in &insts {
+            inst_index.extend([*opcode, args.len() as u8, 0, 0]);
+            for (id, arg) in args.iter().enumerate() {
+                args_index.extend((id as u16).to_le_bytes());
+                args_index.extend(0u16.to_le_bytes());
+                args_index.extend(u32s(&[arg.len() as u32, args_data.len() as u32]));
+                args_data.extend(arg);
+            }
+        }
+        let line_numbers = u32s(&[1, 2]);
+        let mut script = b"YSTB".to_vec();
+        script.extend(u32s(&[
+            468,
+            insts.len() as u32,
+            inst_index.len() as u32,
+            args_index.len() as u32,
+            args_data.len() as u32,
+            line_numbers.len() as u32,
+            0,
+        ]));
+        script.extend([inst_index, args_index, args_data, line_numbers].concat());
+        let dir = crate::utils::testing::temp_dir("fixture-ystb");
+        std::fs::write(dir.join("ysc.ybn"), yscm()).unwrap();
+        fixture(&dir.join("sample.ybn").to_string_lossy(), script)
+    }
+
+    /// Yu-Ris `ysc.ybn`, which describes the opcodes used by [ystb_script].
+    #[cfg(feature = "yuris")]
+    fn yscm() -> Vec<u8> {
+        // Engine, opcode count and unknown, followed by opcodes and their arguments
+        let mut ysc = b"YSCM".to_vec();
+        ysc.extend(u32s(&[0, 2, 0]));
+        ysc.extend(b"WORD\0\0GOSUB\0\x03");
+        for arg in ["#", "PSTR1", "PSTR2"] {
+            ysc.extend(arg.as_bytes());
+            ysc.extend([0, 0, 0]);
+        }
+        // Error messages
+        ysc.extend(sjis("エラー"));
+        ysc.push(0);
+        ysc.extend([0; 0x100]);
+        ysc
+    }
+
+    /// Yu-Ris `ysc.ybn`.
+    #[cfg(feature = "yuris")]
+    pub fn yscm_script() -> Fixture {
+        fixture("ysc.ybn", yscm())
+    }
+
+    /// Yu-Ris `yse.ybn`, which contains error messages.
+    #[cfg(feature = "yuris")]
+    pub fn yser_script() -> Fixture {
+        let mut file = b"YSER".to_vec();
+        file.extend(u32s(&[468]));
+        file.extend(2u64.to_le_bytes());
+        for (i, s) in ["エラー", "ファイルがありません"].iter().enumerate() {
+            file.extend(u32s(&[i as u32]));
+            file.extend(sjis(s));
+            file.push(0);
+        }
+        fixture("yse.ybn", file)
+    }
+
+    /// Yu-Ris `cfg.ybn` of engine before 500.
+    #[cfg(feature = "yuris")]
+    pub fn yscfg_script() -> Fixture {
+        let mut file = b"YSCF".to_vec();
+        // Engine, unknown, compile, screen size and enable
+        file.extend(u32s(&[468, 0, 1, 1280, 720, 1]));
+        // Image and sound type slots
+        file.extend([1, 2, 3, 0, 0, 0, 0, 0, 1, 2, 0, 0]);
+        // Thread, debug mode, sound, window resize, window frame, file priorities and unknown
+        file.extend(u32s(&[1, 0, 1, 1, 1, 0, 1, 2, 0]));
+        let caption = sjis("タイトル");
+        file.extend((caption.len() as u16).to_le_bytes());
+        file.extend(caption);
+        fixture("cfg.ybn", file)
+    }
+}
+
+/// Generates pseudo random data.
+fn noise(len: usize, mut seed: u32) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        })
+        .collect()
+}
+
+/// Returns synthetic files which are packed into archives.
+pub fn synthetic_archive_files(builder: &dyn ScriptBuilder) -> Vec<Fixture> {
+    #[cfg_attr(
+        not(any(
+            feature = "qlie-arc",
+            feature = "ex-hibit-arc",
+            feature = "hexen-haus-arc",
+            feature = "softpal-arc"
+        )),
+        allow(unused_mut)
+    )]
+    let mut files = vec![
+        Fixture {
+            name: "script.txt".to_string(),
+            data: "Hello world.\r\nこんにちは、世界。\r\n"
+                .repeat(64)
+                .into_bytes(),
+        },
+        Fixture {
+            name: "noise.bin".to_string(),
+            data: noise(70000, 0x12345678),
+        },
+        Fixture {
+            name: "zero.dat".to_string(),
+            data: vec![0; 4096],
+        },
+    ];
+    match builder.script_type() {
+        #[cfg(feature = "qlie-arc")]
+        ScriptType::QliePack => files.push(Fixture {
+            name: "pack_keyfile_kfueheish15538fa9or.key".to_string(),
+            data: noise(1024, 0x9abcdef0),
+        }),
+        // Entries starting with `$` are stored encrypted.
+        #[cfg(feature = "softpal-arc")]
+        ScriptType::SoftpalPac | ScriptType::SoftpalPacAmuse => {
+            let mut data = b"$TEXT_LIST__".to_vec();
+            data.extend_from_slice(&noise(4098, 0x2468ace0));
+            files.push(Fixture {
+                name: "TEXT.DAT".to_string(),
+                data,
+            })
+        }
+        // Files are named by index.
+        #[cfg(feature = "ex-hibit-arc")]
+        ScriptType::ExHibitGrp => {
+            for (i, file) in files.iter_mut().enumerate() {
+                file.name = format!("{:05}.ogg", i);
+            }
+        }
+        #[cfg(feature = "hexen-haus-arc")]
+        ScriptType::HexenHausOdio => {
+            for (i, file) in files.iter_mut().enumerate() {
+                file.name = format!("{:04}.ogg", i);
+            }
+        }
+        // Only IMGD images can be stored.
+        #[cfg(feature = "hexen-haus-arc")]
+        ScriptType::HexenHausWag => {
+            for file in files.iter_mut() {
+                let mut data = b"IMGD".to_vec();
+                data.extend_from_slice(&(file.data.len() as u32).to_le_bytes());
+                data.extend_from_slice(&[0; 8]);
+                data.append(&mut file.data);
+                file.data = data;
+            }
+        }
+        _ => {}
+    }
+    files
+}
+
+/// Prepares files needed by the builder to create archives in `dir`.
+#[cfg_attr(not(feature = "ex-hibit-arc"), allow(unused_variables))]
+pub fn prepare(builder: &dyn ScriptBuilder, dir: &std::path::Path) -> std::io::Result<()> {
+    match builder.script_type() {
+        // The entry table is stored in a TOC file.
+        #[cfg(feature = "ex-hibit-arc")]
+        ScriptType::ExHibitGrp => {
+            let mut toc = b"AiFS".to_vec();
+            toc.extend_from_slice(&[0; 8]);
+            // Resource count
+            toc.extend_from_slice(&1i32.to_le_bytes());
+            // Archive number, start index, unknown, entry count
+            for v in [1u32, 0, 0, 3] {
+                toc.extend_from_slice(&v.to_le_bytes());
+            }
+            toc.extend_from_slice(&[0; 3 * 8]);
+            std::fs::write(dir.join("res0000.grp"), toc)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns the config used to test the builder.
+pub fn config(builder: &dyn ScriptBuilder) -> ExtraConfig {
+    #[allow(unused_mut)]
+    let mut config = ExtraConfig::default();
+    match builder.script_type() {
+        #[cfg(feature = "musica-arc")]
+        ScriptType::MusicaPaz => config.musica_game_title = Some("Riddle Garden".to_string()),
+        // Synthetic samples are not complete scripts which can be disassembled.
+        #[cfg(feature = "will-plus")]
+        ScriptType::WillPlusWs2 => config.will_plus_ws2_no_disasm = true,
+        #[allow(unreachable_patterns)]
+        _ => {}
+    }
+    config
+}
+
+/// Returns the filename of archives created by the builder.
+///
+/// Some archives use the filename to find keys.
+pub fn archive_filename(builder: &dyn ScriptBuilder) -> String {
+    let stem = match builder.script_type() {
+        #[cfg(feature = "musica-arc")]
+        ScriptType::MusicaPaz => "scr".to_string(),
+        #[cfg(feature = "ex-hibit-arc")]
+        ScriptType::ExHibitGrp => "res0001".to_string(),
+        _ => script_type_name(builder),
+    };
+    let ext = builder.extensions().first().unwrap_or(&"unk");
+    format!("{}.{}", stem, ext)
+}
+
+/// Returns all script inputs for the builder.
+pub fn scripts(builder: &dyn ScriptBuilder) -> Vec<Fixture> {
+    let encoding = builder.default_encoding();
+    let mut files: Vec<Fixture> = synthetic_scripts(builder.script_type())
+        .iter()
+        .map(|(name, text)| Fixture {
+            name: name.to_string(),
+            data: crate::utils::encoding::encode_string(encoding, text, true)
+                .expect("synthetic sample should be encodable"),
+        })
+        .collect();
+    files.extend(synthetic_binary_scripts(builder.script_type()));
+    files.extend(load_fixtures(builder));
+    files
+}
+
+/// Loads real game files for the builder from [FIXTURES_ENV].
+///
+/// Panics if [FIXTURES_ENV] is set but is not a directory.
+pub fn load_fixtures(builder: &dyn ScriptBuilder) -> Vec<Fixture> {
+    let root = match std::env::var_os(FIXTURES_ENV) {
+        Some(root) => std::path::PathBuf::from(root),
+        None => return Vec::new(),
+    };
+    if !root.is_dir() {
+        panic!(
+            "{} is set to {}, but it is not a directory",
+            FIXTURES_ENV,
+            root.display()
+        );
+    }
+    let dir = root.join(script_type_name(builder));
+    let files = match crate::utils::files::find_files(&dir.to_string_lossy(), true, true) {
+        Ok(files) => files,
+        Err(_) => return Vec::new(),
+    };
+    files
+        .into_iter()
+        .map(|name| Fixture {
+            data: crate::utils::files::read_file(&name)
+                .unwrap_or_else(|e| panic!("Failed to read fixture {}: {}", name, e)),
+            name,
+        })
+        .collect()
+}
@@ -0,0 +1,90 @@
+//! Round-trip tests for all builders in [crate::scripts::BUILDER].
+//!
+//! Every script is exported and imported again with unchanged messages (or its custom output for
+//! scripts without messages), and every archive is created and opened again with the same files.
+//! Inputs come from [fixtures], which provides synthetic samples for every script type. Real game files can be tested too by setting
+//! `MSG_TOOL_TEST_FIXTURES` to a directory containing one subdirectory per script type
+//! (e.g. `<dir>/kirikiri/*.ks`, `<dir>/kirikiri-xp3/*.xp3`).
+mod archive;
+mod fixtures;
+mod script;
+
+use crate::scripts::base::ScriptBuilder;
+use crate::types::*;
+use clap::ValueEnum;
+
+/// Returns the name of the script type used in command line and fixtures directory.
+fn script_type_name(builder: &dyn ScriptBuilder) -> String {
+    builder
+        .script_type()
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_else(|| format!("{:?}", builder.script_type()))
+}
+
+/// Returns the encodings used for (script, archive, patched script).
+fn encodings(builder: &dyn ScriptBuilder) -> (Encoding, Encoding, Encoding) {
+    let encoding = builder.default_encoding();
+    let archive_encoding = builder.default_archive_encoding().unwrap_or(encoding);
+    (
+        encoding,
+        archive_encoding,
+        builder.default_patched_encoding(),
+    )
+}
+
+/// Collects failures of a round-trip test and panics with all of them at the end.
+///
+/// Skipped builders and files are printed, so a green run shows what was not tested.
+struct Report {
+    name: &'static str,
+    passed: usize,
+    failures: Vec<String>,
+    skipped: Vec<String>,
+}
+
+impl Report {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            passed: 0,
+            failures: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    fn skip(&mut self, builder: &dyn ScriptBuilder, reason: &str) {
+        self.skipped
+            .push(format!("[{}] {}", script_type_name(builder), reason));
+    }
+
+    fn check(&mut self, builder: &dyn ScriptBuilder, file: &str, result: anyhow::Result<()>) {
+        match result {
+            Ok(()) => self.passed += 1,
+            Err(e) => {
+                self.failures
+                    .push(format!("[{}] {}: {:#}", script_type_name(builder), file, e))
+            }
+        }
+    }
+
+    fn finish(self) {
+        for skipped in &self.skipped {
+            eprintln!("{} round-trip skipped: {}", self.name, skipped);
+        }
+        eprintln!(
+            "{} round-trip: {} passed, {} failed, {} skipped",
+            self.name,
+            self.passed,
+            self.failures.len(),
+            self.skipped.len()
+        );
+        if !self.failures.is_empty() {
+            panic!(
+                "{} round-trip failed:\n{}",
+                self.name,
+                self.failures.join("\n")
+            );
+        }
+    }
+}
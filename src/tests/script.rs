@@ -0,0 +1,305 @@
+//! Export → import round-trip of message scripts.
+use super::fixtures::{self, Fixture};
+use super::*;
+use crate::ext::io::MemWriter;
+use crate::scripts::BUILDER;
+use crate::scripts::base::*;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Messages in a comparable form.
+type Messages = Vec<(Option<String>, String)>;
+
+fn comparable(messages: &[Message]) -> Messages {
+    messages
+        .iter()
+        .map(|m| (m.name.clone(), m.message.clone()))
+        .collect()
+}
+
+fn diff_messages(old: &Messages, new: &Messages) -> Result<()> {
+    if old.len() != new.len() {
+        anyhow::bail!("message count changed from {} to {}", old.len(), new.len());
+    }
+    for (i, (o, n)) in old.iter().zip(new.iter()).enumerate() {
+        if o != n {
+            anyhow::bail!("message {} changed from {:?} to {:?}", i, o, n);
+        }
+    }
+    Ok(())
+}
+
+/// How a script is exported and imported.
+enum Kind {
+    /// Messages which can be exported as json.
+    Messages,
+    /// Only custom output, such as json dumps of the whole script.
+    Custom,
+    /// Archives, images and audios.
+    Other,
+}
+
+fn kind(builder: &dyn ScriptBuilder, script: &dyn Script) -> Kind {
+    #[cfg(feature = "image")]
+    if script.is_image() {
+        return Kind::Other;
+    }
+    if builder.is_audio() || script.is_archive() {
+        Kind::Other
+    } else if script.is_output_supported(OutputScriptType::Json) {
+        Kind::Messages
+    } else {
+        Kind::Custom
+    }
+}
+
+/// Returns true if the builder can only export, as documented in the support tables of README.
+fn is_export_only(builder: &dyn ScriptBuilder) -> bool {
+    match builder.script_type() {
+        #[cfg(feature = "yuris")]
+        ScriptType::YurisYSCM => true,
+        _ => false,
+    }
+}
+
+enum Extracted {
+    Single(Messages),
+    Multiple(HashMap<String, Messages>),
+}
+
+fn extract(script: &dyn Script) -> Result<Extracted> {
+    Ok(if script.multiple_message_files() {
+        Extracted::Multiple(
+            script
+                .extract_multiple_messages()?
+                .into_iter()
+                .map(|(k, v)| (k, comparable(&v)))
+                .collect(),
+        )
+    } else {
+        Extracted::Single(comparable(&script.extract_messages()?))
+    })
+}
+
+fn to_messages(messages: &Messages) -> Vec<Message> {
+    messages
+        .iter()
+        .map(|(name, message)| Message::new(message.clone(), name.clone()))
+        .collect()
+}
+
+/// Imports `messages` into `script` and returns the patched script.
+fn import(
+    script: &dyn Script,
+    messages: &Extracted,
+    filename: &str,
+    encoding: Encoding,
+) -> Result<Vec<u8>> {
+    let mut writer = MemWriter::new();
+    match messages {
+        Extracted::Single(messages) => script.import_messages(
+            to_messages(messages),
+            Box::new(&mut writer),
+            filename,
+            encoding,
+            None,
+        )?,
+        Extracted::Multiple(messages) => script.import_multiple_messages(
+            messages
+                .iter()
+                .map(|(k, v)| (k.clone(), to_messages(v)))
+                .collect(),
+            Box::new(&mut writer),
+            filename,
+            encoding,
+            None,
+        )?,
+    }
+    Ok(writer.into_inner())
+}
+
+/// Parses the patched script and checks that it contains `expected` messages.
+fn check_patched(
+    builder: &dyn ScriptBuilder,
+    file: &Fixture,
+    patched: Vec<u8>,
+    expected: &Extracted,
+) -> Result<()> {
+    let config = fixtures::config(builder);
+    let (_, archive_encoding, patched_encoding) = encodings(builder);
+    let script = builder
+        .build_script(
+            patched,
+            &file.name,
+            patched_encoding,
+            archive_encoding,
+            &config,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("failed to parse patched script: {:#}", e))?;
+    match (expected, extract(script.as_ref())?) {
+        (Extracted::Single(old), Extracted::Single(new)) => diff_messages(old, &new)?,
+        (Extracted::Multiple(old), Extracted::Multiple(new)) => {
+            let mut keys: Vec<_> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            let empty = Vec::new();
+            for key in keys {
+                diff_messages(
+                    old.get(key).unwrap_or(&empty),
+                    new.get(key).unwrap_or(&empty),
+                )
+                .map_err(|e| anyhow::anyhow!("{}: {:#}", key, e))?;
+            }
+        }
+        _ => anyhow::bail!("patched script changed the message layout"),
+    }
+    Ok(())
+}
+
+/// Line appended to messages by [wrap_lines].
+const WRAPPED_LINE: &str = "次の行";
+
+/// Appends a wrapped line to every message.
+///
+/// Formatters separate wrapped lines with `\n`, so the importer must convert it to the engine
+/// line break.
+fn wrap_lines(extracted: &Extracted) -> Extracted {
+    let wrap = |messages: &Messages| -> Messages {
+        messages
+            .iter()
+            .map(|(name, message)| (name.clone(), format!("{}\n{}", message, WRAPPED_LINE)))
+            .collect()
+    };
+    match extracted {
+        Extracted::Single(messages) => Extracted::Single(wrap(messages)),
+        Extracted::Multiple(messages) => {
+            Extracted::Multiple(messages.iter().map(|(k, v)| (k.clone(), wrap(v))).collect())
+        }
+    }
+}
+
+/// Exports messages from `file`, imports them again and checks the patched script.
+///
+/// The patched script must be byte-identical to the original one, or contain the same messages.
+/// Scripts with inline markup are also checked with wrapped messages.
+fn roundtrip(builder: &dyn ScriptBuilder, file: &Fixture) -> Result<bool> {
+    let config = fixtures::config(builder);
+    let (encoding, archive_encoding, patched_encoding) = encodings(builder);
+    let script = builder.build_script(
+        file.data.clone(),
+        &file.name,
+        encoding,
+        archive_encoding,
+        &config,
+        None,
+    )?;
+    match kind(builder, script.as_ref()) {
+        Kind::Messages => {}
+        Kind::Custom => return custom_roundtrip(builder, file, script.as_ref()).map(|_| true),
+        Kind::Other => return Ok(false),
+    }
+    let extracted = extract(script.as_ref())?;
+    let empty = match &extracted {
+        Extracted::Single(messages) => messages.is_empty(),
+        Extracted::Multiple(messages) => messages.values().all(|m| m.is_empty()),
+    };
+    if empty {
+        anyhow::bail!("no messages found");
+    }
+    let patched = import(script.as_ref(), &extracted, &file.name, patched_encoding)?;
+    if patched != file.data {
+        check_patched(builder, file, patched, &extracted)?;
+    }
+    if builder.inline_markup().is_some() {
+        let wrapped = wrap_lines(&extracted);
+        let patched = import(script.as_ref(), &wrapped, &file.name, patched_encoding)?;
+        check_patched(builder, file, patched, &wrapped)
+            .map_err(|e| anyhow::anyhow!("wrapped messages: {:#}", e))?;
+    }
+    Ok(true)
+}
+
+/// Exports `script` with custom output into `dir` and returns the exported file.
+fn custom_export(script: &dyn Script, dir: &Path) -> Result<Vec<u8>> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("sample.{}", script.custom_output_extension()));
+    script.custom_export(&path, Encoding::Utf8)?;
+    Ok(crate::utils::files::read_file(&path)?)
+}
+
+/// Exports `script` with custom output, imports the exported file again and checks the patched
+/// script.
+///
+/// The patched script must be byte-identical to the original one, or export the same file.
+/// Files exported next to the main file, such as images, are not compared.
+fn custom_roundtrip(
+    builder: &dyn ScriptBuilder,
+    file: &Fixture,
+    script: &dyn Script,
+) -> Result<()> {
+    let dir = crate::utils::testing::temp_dir(&format!("script-{}", script_type_name(builder)));
+    let exported = custom_export(script, &dir.join("exported"))?;
+    if is_export_only(builder) {
+        return Ok(());
+    }
+    let path = dir
+        .join("exported")
+        .join(format!("sample.{}", script.custom_output_extension()));
+    let (_, archive_encoding, patched_encoding) = encodings(builder);
+    let mut writer = MemWriter::new();
+    script.custom_import(
+        &path.to_string_lossy(),
+        Box::new(&mut writer),
+        patched_encoding,
+        Encoding::Utf8,
+    )?;
+    let patched = writer.into_inner();
+    if patched != file.data {
+        let config = fixtures::config(builder);
+        let script = builder
+            .build_script(
+                patched,
+                &file.name,
+                patched_encoding,
+                archive_encoding,
+                &config,
+                None,
+            )
+            .map_err(|e| anyhow::anyhow!("failed to parse patched script: {:#}", e))?;
+        if custom_export(script.as_ref(), &dir.join("patched"))? != exported {
+            anyhow::bail!("patched script exported a different file");
+        }
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(())
+}
+
+#[test]
+fn test_script_roundtrip() {
+    let mut report = Report::new("script");
+    for builder in BUILDER.iter() {
+        let builder = builder.as_ref();
+        // Archives, images and audios do not contain messages.
+        if builder.is_archive() || builder.is_audio() {
+            continue;
+        }
+        #[cfg(feature = "image")]
+        if builder.is_image() {
+            continue;
+        }
+        // Every script type must be tested, so a missing sample is a failure.
+        let files = fixtures::scripts(builder);
+        if files.is_empty() {
+            report.check(builder, "<synthetic>", Err(anyhow::anyhow!("no samples")));
+        }
+        for file in files {
+            match roundtrip(builder, &file) {
+                Ok(false) => report.skip(builder, &format!("{}: no messages", file.name)),
+                result => report.check(builder, &file.name, result.map(|_| ())),
+            }
+        }
+    }
+    report.finish();
+}
@@ -0,0 +1,266 @@
+//! Translation memory, used to reuse translations when scripts are exported again.
+//!
+//! A translation memory is built from translated output scripts. Messages of newly exported
+//! scripts are looked up in it by their source text, either exactly or by similarity (fuzzy).
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Clone, Debug)]
+/// A translated message in translation memory.
+pub struct TmEntry {
+    /// Name of the speaker in original script.
+    pub name: Option<String>,
+    /// Original source text.
+    pub source: String,
+    /// Translated text.
+    pub translated: String,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// Result of a lookup in translation memory.
+pub enum TmMatch<'a> {
+    /// An entry with the same source text.
+    Exact(&'a TmEntry),
+    /// An entry with similar source text and its similarity (`0.0..1.0`).
+    Fuzzy(&'a TmEntry, f64),
+}
+
+impl<'a> TmMatch<'a> {
+    /// Returns the matched entry.
+    pub fn entry(&self) -> &'a TmEntry {
+        match self {
+            TmMatch::Exact(e) => e,
+            TmMatch::Fuzzy(e, _) => e,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// Statistics of applying translation memory.
+pub struct TmStats {
+    /// Messages filled with exact matches.
+    pub exact: usize,
+    /// Messages filled with fuzzy matches.
+    pub fuzzy: usize,
+    /// Untranslated messages without any match.
+    pub missed: usize,
+}
+
+impl TmStats {
+    /// Adds another statistics to this one.
+    pub fn merge(&mut self, other: &TmStats) {
+        self.exact += other.exact;
+        self.fuzzy += other.fuzzy;
+        self.missed += other.missed;
+    }
+}
+
+impl std::fmt::Display for TmStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Translation memory: {} exact, {} fuzzy, {} missed",
+            self.exact, self.fuzzy, self.missed
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+/// A translation memory.
+pub struct TranslationMemory {
+    entries: Vec<TmEntry>,
+    /// Source text in chars of every entry.
+    chars: Vec<Vec<char>>,
+    /// Source text -> indices of entries.
+    exact: HashMap<String, Vec<usize>>,
+    /// Length of source text in chars -> indices of entries.
+    by_len: BTreeMap<usize, Vec<usize>>,
+    /// Translated text of every entry.
+    translations: HashSet<String>,
+}
+
+/// Returns true if the translated text is empty.
+///
+/// M3T files use `「」` as placeholder of untranslated messages in quotes.
+pub fn is_untranslated(s: &str) -> bool {
+    s.trim()
+        .trim_start_matches("「")
+        .trim_end_matches("」")
+        .trim()
+        .is_empty()
+}
+
+/// Computes the Levenshtein distance between `a` and `b`.
+///
+/// Returns None if the distance is greater than `max`.
+fn levenshtein(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        let mut row_min = cur[0];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+            row_min = row_min.min(cur[j + 1]);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    let d = prev[b.len()];
+    if d > max { None } else { Some(d) }
+}
+
+/// Returns the similarity of two strings, from 0.0 (different) to 1.0 (same).
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let len = a.len().max(b.len());
+    if len == 0 {
+        return 1.0;
+    }
+    let d = levenshtein(&a, &b, len).unwrap_or(len);
+    1.0 - d as f64 / len as f64
+}
+
+impl TranslationMemory {
+    /// Creates an empty translation memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds a translated message.
+    ///
+    /// Untranslated messages and messages which already exist (same name and source) are ignored.
+    /// Returns true if the message is added.
+    pub fn add(&mut self, name: Option<String>, source: String, translated: String) -> bool {
+        if source.is_empty() || is_untranslated(&translated) {
+            return false;
+        }
+        if let Some(indices) = self.exact.get(&source)
+            && indices.iter().any(|&i| self.entries[i].name == name)
+        {
+            return false;
+        }
+        let index = self.entries.len();
+        let chars: Vec<char> = source.chars().collect();
+        self.by_len.entry(chars.len()).or_default().push(index);
+        self.exact.entry(source.clone()).or_default().push(index);
+        self.chars.push(chars);
+        self.translations.insert(translated.clone());
+        self.entries.push(TmEntry {
+            name,
+            source,
+            translated,
+        });
+        true
+    }
+
+    /// Returns true if the text is the translated text of an entry.
+    pub fn is_translation(&self, text: &str) -> bool {
+        self.translations.contains(text)
+    }
+
+    /// Finds the best match for the source text.
+    ///
+    /// Entries with the same speaker name are preferred.
+    /// Fuzzy matches need a similarity of at least `threshold`. Set it to 1.0 to disable fuzzy matching.
+    pub fn lookup(&self, name: Option<&str>, source: &str, threshold: f64) -> Option<TmMatch<'_>> {
+        if let Some(indices) = self.exact.get(source) {
+            let index = indices
+                .iter()
+                .find(|&&i| self.entries[i].name.as_deref() == name)
+                .unwrap_or(&indices[0]);
+            return Some(TmMatch::Exact(&self.entries[*index]));
+        }
+        if threshold >= 1.0 {
+            return None;
+        }
+        let chars: Vec<char> = source.chars().collect();
+        if chars.is_empty() {
+            return None;
+        }
+        let len = chars.len();
+        // Tolerance of floating point errors, e.g. (1.0 - 0.8) * 10.0 < 2.0
+        const EPSILON: f64 = 1e-9;
+        // The distance is at least the difference of lengths,
+        // so only entries with similar length need to be checked.
+        let (min_len, max_len) = if threshold > 0.0 {
+            (
+                (len as f64 * threshold - EPSILON).ceil() as usize,
+                (len as f64 / threshold + EPSILON).floor() as usize,
+            )
+        } else {
+            (0, usize::MAX)
+        };
+        let mut best: Option<(usize, f64)> = None;
+        for (&elen, indices) in self.by_len.range(min_len..=max_len) {
+            let longest = len.max(elen);
+            let max_dist = ((1.0 - threshold) * longest as f64 + EPSILON).floor() as usize;
+            for &i in indices {
+                let d = match levenshtein(&chars, &self.chars[i], max_dist) {
+                    Some(d) => d,
+                    None => continue,
+                };
+                let score = 1.0 - d as f64 / longest as f64;
+                let better = match best {
+                    None => true,
+                    Some((bi, bscore)) => {
+                        score > bscore
+                            || (score == bscore
+                                && self.entries[i].name.as_deref() == name
+                                && self.entries[bi].name.as_deref() != name)
+                    }
+                };
+                if better {
+                    best = Some((i, score));
+                }
+            }
+        }
+        best.map(|(i, score)| TmMatch::Fuzzy(&self.entries[i], score))
+    }
+}
+
+#[test]
+fn test_lookup() {
+    let mut tm = TranslationMemory::new();
+    assert!(tm.add(
+        Some("アリス".into()),
+        "こんにちは、世界。".into(),
+        "Hello, world.".into()
+    ));
+    assert!(tm.add(None, "こんにちは、世界。".into(), "Hello world.".into()));
+    assert!(!tm.add(None, "こんにちは、世界。".into(), "Hi.".into()));
+    assert!(!tm.add(None, "おはよう".into(), "「」".into()));
+    assert_eq!(tm.len(), 2);
+    match tm.lookup(None, "こんにちは、世界。", 0.8) {
+        Some(TmMatch::Exact(e)) => assert_eq!(e.translated, "Hello world."),
+        m => panic!("unexpected match: {:?}", m),
+    }
+    match tm.lookup(Some("アリス"), "こんにちは、世界！", 0.8) {
+        Some(TmMatch::Fuzzy(e, score)) => {
+            assert_eq!(e.translated, "Hello, world.");
+            assert!((score - 8.0 / 9.0).abs() < 1e-9);
+        }
+        m => panic!("unexpected match: {:?}", m),
+    }
+    assert!(tm.lookup(None, "こんにちは、世界！", 1.0).is_none());
+    assert!(tm.lookup(None, "さようなら", 0.8).is_none());
+    assert!(tm.is_translation("Hello, world."));
+    assert!(!tm.is_translation("こんにちは、世界。"));
+    assert_eq!(similarity("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+}
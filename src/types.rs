@@ -1,7 +1,7 @@
 //! Basic types
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Copy, Clone, Serialize, Deserialize, Debug)]
 #[serde(untagged, rename_all = "camelCase")]
@@ -19,8 +19,7 @@ pub enum Encoding {
     Utf16LE,
     /// UTF-16 Big Endian encoding
     Utf16BE,
-    /// Code page encoding (Windows only)
-    #[cfg(windows)]
+    /// Code page encoding
     CodePage(u32),
 }
 
@@ -35,7 +34,6 @@ impl Encoding {
     pub fn is_jis(&self) -> bool {
         match self {
             Self::Cp932 => true,
-            #[cfg(windows)]
             Self::CodePage(code_page) => *code_page == 932,
             _ => false,
         }
@@ -45,7 +43,6 @@ impl Encoding {
     pub fn is_utf16le(&self) -> bool {
         match self {
             Self::Utf16LE => true,
-            #[cfg(windows)]
             Self::CodePage(code_page) => *code_page == 1200,
             _ => false,
         }
@@ -55,7 +52,6 @@ impl Encoding {
     pub fn is_utf16be(&self) -> bool {
         match self {
             Self::Utf16BE => true,
-            #[cfg(windows)]
             Self::CodePage(code_page) => *code_page == 1201,
             _ => false,
         }
@@ -65,7 +61,6 @@ impl Encoding {
     pub fn is_utf8(&self) -> bool {
         match self {
             Self::Utf8 => true,
-            #[cfg(windows)]
             Self::CodePage(code_page) => *code_page == 65001,
             _ => false,
         }
@@ -80,12 +75,25 @@ impl Encoding {
             Self::Gb2312 => Some("gbk"),
             Self::Utf16LE => Some("utf-16le"),
             Self::Utf16BE => Some("utf-16be"),
-            #[cfg(windows)]
             Self::CodePage(code_page) => match *code_page {
                 932 => Some("shift_jis"),
                 65001 => Some("utf-8"),
                 1200 => Some("utf-16le"),
+                1201 => Some("utf-16be"),
                 936 => Some("gbk"),
+                54936 => Some("gb18030"),
+                950 => Some("big5"),
+                949 => Some("euc-kr"),
+                20932 | 51932 => Some("euc-jp"),
+                1250 => Some("windows-1250"),
+                1251 => Some("windows-1251"),
+                1252 => Some("windows-1252"),
+                1253 => Some("windows-1253"),
+                1254 => Some("windows-1254"),
+                1255 => Some("windows-1255"),
+                1256 => Some("windows-1256"),
+                1257 => Some("windows-1257"),
+                1258 => Some("windows-1258"),
                 _ => None,
             },
         }
@@ -126,6 +134,10 @@ pub enum OutputScriptType {
     Pot,
     /// Gettext .po file
     Po,
+    /// XLIFF 2.x file for CAT tools
+    Xliff,
+    /// TMX translation memory
+    Tmx,
     /// Custom output
     Custom,
 }
@@ -153,6 +165,8 @@ impl OutputScriptType {
                 | OutputScriptType::M3tTxt
                 | OutputScriptType::Po
                 | OutputScriptType::Pot
+                | OutputScriptType::Xliff
+                | OutputScriptType::Tmx
         )
     }
 }
@@ -168,6 +182,8 @@ impl AsRef<str> for OutputScriptType {
             OutputScriptType::Yaml => "yaml",
             OutputScriptType::Pot => "pot",
             OutputScriptType::Po => "po",
+            OutputScriptType::Xliff => "xliff",
+            OutputScriptType::Tmx => "tmx",
             OutputScriptType::Custom => "",
         }
     }
@@ -345,6 +361,13 @@ pub struct ExtraConfig {
     #[cfg(feature = "kirikiri")]
     /// Kirikiri message commands, used to extract more message from ks script.
     pub kirikiri_message_commands: std::sync::Arc<std::collections::HashSet<String>>,
+    #[cfg(feature = "kirikiri")]
+    /// Kirikiri voice commands, used to extract voice files played with messages in ks script.
+    pub kirikiri_voice_commands: std::sync::Arc<std::collections::HashSet<String>>,
+    #[cfg(feature = "kirikiri")]
+    #[default(2)]
+    /// Kirikiri SimpleCrypt mode (0-2) used when creating SimpleCrypt files.
+    pub kirikiri_simple_crypt_mode: u8,
     #[cfg(feature = "bgi-arc")]
     /// Whether to compress files in BGI archive when packing BGI archive.
     pub bgi_compress_file: bool,
@@ -355,6 +378,10 @@ pub struct ExtraConfig {
     #[cfg(feature = "emote-img")]
     /// Whether to overlay PIMG images. (By default, true if all layers are not group layers.)
     pub emote_pimg_overlay: Option<bool>,
+    #[cfg(feature = "emote-img")]
+    /// DPAK files patched during the current import run. Set by [crate::ops::import_files].
+    /// If not set, DPAK files referenced by an imported DREF file are written immediately.
+    pub emote_dpak_session: Option<std::sync::Arc<crate::scripts::emote::dref::DpakSession>>,
     #[cfg(feature = "artemis-arc")]
     /// Disable Artemis archive (.pfs) XOR encryption when packing.
     pub artemis_arc_disable_xor: bool,
@@ -424,6 +451,14 @@ pub struct ExtraConfig {
     #[cfg(feature = "circus-img")]
     /// Draw Circus CRX images on canvas (if canvas width and height are specified in file)
     pub circus_crx_canvas: bool,
+    #[cfg(feature = "circus-img")]
+    /// Version of Circus CRX images (1 or 2) when creating or importing images.
+    /// When in import mode, the default value depends on the original image. (V3 images are kept as V3 unless version 1 is specified.)
+    /// When in creation mode, version 2 is used by default.
+    pub circus_crx_version: Option<u16>,
+    #[cfg(feature = "circus-audio")]
+    /// Use ADPCM compression when creating Circus PCM audio files from WAV/FLAC files.
+    pub circus_pcm_adpcm: bool,
     /// Try use YAML format instead of JSON when custom exporting.
     pub custom_yaml: bool,
     #[cfg(feature = "entis-gls")]
@@ -470,6 +505,16 @@ pub struct ExtraConfig {
     /// Workers count for decode BGI compressed images v2 in parallel. Default is half of CPU cores.
     /// Set this to 1 to disable parallel decoding. 0 means same as 1.
     pub bgi_img_workers: usize,
+    #[cfg(feature = "bgi-img")]
+    /// Version of BGI compressed images (1 or 2) when creating or importing images.
+    /// When in import mode, the default value depends on the original image.
+    /// When in creation mode, version 1 is used by default.
+    pub bgi_cbg_version: Option<u16>,
+    #[cfg(feature = "bgi-img")]
+    /// Quality of BGI compressed images v2, 1-100. 100 means best quality.
+    /// When importing a v2 image, the quantization tables of the original image are used by default.
+    /// Otherwise, 80 is used by default.
+    pub bgi_cbg_quality: Option<u8>,
     #[cfg(feature = "image-jxl")]
     #[default(true)]
     /// Use JXL lossless compression for output images. Enabled by default.
@@ -510,6 +555,13 @@ pub struct ExtraConfig {
     /// Decompress mdf files in Kirikiri XP3 archive when extracting. Default is true.
     pub xp3_mdf_decompress: bool,
     #[cfg(feature = "kirikiri-arc")]
+    /// Lowercase file extensions of UTF-16LE text files to encrypt with SimpleCrypt in Kirikiri XP3 archive when creating.
+    /// The mode is specified by [Self::kirikiri_simple_crypt_mode].
+    pub xp3_simple_crypt_exts: std::sync::Arc<std::collections::HashSet<String>>,
+    #[cfg(feature = "kirikiri-arc")]
+    /// Lowercase file extensions of files to compress into mdf in Kirikiri XP3 archive when creating.
+    pub xp3_mdf_compress_exts: std::sync::Arc<std::collections::HashSet<String>>,
+    #[cfg(feature = "kirikiri-arc")]
     /// Configuration for Kirikiri XP3 segmenter when creating XP3 archive.
     pub xp3_segmenter: crate::scripts::kirikiri::archive::xp3::SegmenterConfig,
     #[cfg(feature = "kirikiri-arc")]
@@ -542,6 +594,10 @@ pub struct ExtraConfig {
     #[cfg(feature = "kirikiri")]
     /// Insert new language at the specified index in Kirikiri SCN script. If index is out of bounds, this flags will be ignored.
     pub kirikiri_language_insert: bool,
+    #[cfg(feature = "kirikiri-img")]
+    /// TLG version used when creating or importing Kirikiri TLG images (5 or 6).
+    /// By default, new images are TLG5 and imported images keep the version of the original image.
+    pub kirikiri_tlg_version: Option<u32>,
     #[cfg(feature = "musica-arc")]
     /// Musica game title for paz archive.
     pub musica_game_title: Option<String>,
@@ -602,6 +658,13 @@ pub struct ExtraConfig {
     #[cfg(feature = "qlie-arc")]
     /// Whether to compress files in Qlie pack archive.
     pub qlie_pack_compress_files: bool,
+    #[cfg(feature = "qlie-arc")]
+    /// Qlie pack archive version used when packing.
+    pub qlie_pack_version: crate::scripts::qlie::archive::pack::QliePackVersion,
+    #[cfg(feature = "qlie-arc")]
+    /// Path to the original Qlie pack archive.
+    /// Its key data is reused when packing version 2.0 or 3.0, otherwise a new key is generated.
+    pub qlie_pack_original: Option<String>,
     #[cfg(feature = "qlie-img")]
     /// Whether to use PNG file directly for Qlie DPNG images when importing.
     /// Enable this will disable reencoding PNG files. Useful when the PNG files are already optimized by other tools.
@@ -637,6 +700,7 @@ pub struct ExtraConfig {
     pub artemis_asb_end_tags: std::sync::Arc<std::collections::HashSet<String>>,
     #[cfg(feature = "kirikiri-arc")]
     /// Game title for Kirikiri XP3 archive. This is used to decrypt file in archives.
+    /// When creating archive, files will be encrypted with the same method if supported.
     pub xp3_game_title: Option<String>,
     #[cfg(feature = "kirikiri-arc")]
     /// Print debug information for Kirikiri XP3 archive when extracting archive to stdout.
@@ -666,6 +730,22 @@ pub struct ExtraConfig {
     #[cfg(feature = "yuris")]
     /// Path to the ysc.ybn file
     pub yuris_ysc_path: Option<String>,
+    #[cfg(feature = "yuris")]
+    #[default(default_yuris_ystb_choice_labels())]
+    /// Labels of Yu-Ris subroutines which show choices. Texts passed to these subroutines are extracted from YSTB script.
+    pub yuris_ystb_choice_labels: std::sync::Arc<std::collections::HashSet<String>>,
+    #[cfg(feature = "yuris-arc")]
+    #[default(500)]
+    /// Version of Yu-Ris YPF archive when creating.
+    pub ypf_version: u32,
+    #[cfg(feature = "yuris-arc")]
+    #[default(0xFF)]
+    /// Key used to obfuscate file names in Yu-Ris YPF archive when creating.
+    pub ypf_name_key: u8,
+    #[cfg(feature = "yuris-arc")]
+    /// Table used to obfuscate file name lengths in Yu-Ris YPF archive when creating.
+    /// Selected by [Self::ypf_version] if not specified.
+    pub ypf_length_table: Option<crate::scripts::yuris::archive::ypf::YpfLengthTable>,
 }
 
 #[cfg(feature = "artemis")]
@@ -678,6 +758,11 @@ fn default_artemis_asb_end_tags() -> std::sync::Arc<std::collections::HashSet<St
     )
 }
 
+#[cfg(feature = "yuris")]
+fn default_yuris_ystb_choice_labels() -> std::sync::Arc<std::collections::HashSet<String>> {
+    std::sync::Arc::new(["SEL"].into_iter().map(String::from).collect())
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 /// Script type
 pub enum ScriptType {
@@ -926,9 +1011,55 @@ pub enum ScriptType {
     #[cfg(feature = "yuris")]
     /// Yu-Ris scenario text file (.txt)
     YurisTxt,
+    #[cfg(feature = "yuris-arc")]
+    #[value(alias("ypf"))]
+    /// Yu-Ris YPF archive (.ypf)
+    YurisYpf,
+}
+
+/// Well-known keys of [Message::meta].
+///
+/// Scripts may also use engine specific keys.
+pub mod meta_key {
+    /// Kind of the message. See [super::MessageKind].
+    pub const KIND: &str = "kind";
+    /// Voice file or voice ID played with the message.
+    pub const VOICE: &str = "voice";
+    /// Line number (starting from 1) in the original script.
+    pub const LINE: &str = "line";
+    /// Offset of the message in the original script.
+    pub const OFFSET: &str = "offset";
+    /// Opcode of the instruction which contains the message.
+    pub const OPCODE: &str = "opcode";
+    /// Label or block which contains the message.
+    pub const LABEL: &str = "label";
+    /// Translation suggested by translation memory, which needs to be reviewed.
+    pub const TM: &str = "tm";
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Kind of a message
+pub enum MessageKind {
+    /// Text shown in message window, including narration.
+    Dialogue,
+    /// Text of a choice.
+    Choice,
+    /// Title of a scene or save data.
+    Title,
+}
+
+impl MessageKind {
+    /// Returns the value stored in [Message::meta].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageKind::Dialogue => "dialogue",
+            MessageKind::Choice => "choice",
+            MessageKind::Title => "title",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 /// Message structure for scripts
 pub struct Message {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -936,16 +1067,50 @@ pub struct Message {
     pub name: Option<String>,
     /// The actual message content.
     pub message: String,
+    #[serde(flatten, skip_serializing_if = "BTreeMap::is_empty")]
+    /// Additional information about where the message came from, such as voice file or line number.
+    /// See [meta_key] for well-known keys.
+    ///
+    /// Metadata is only used to give translators more context, and is ignored when importing.
+    /// It is stored as extra keys in json/yaml files, so `name` and `message` must not be used as keys.
+    pub meta: BTreeMap<String, serde_json::Value>,
 }
 
 impl Message {
     /// Creates a new `Message` instance.
     pub fn new(message: String, name: Option<String>) -> Self {
-        Message { message, name }
+        Message {
+            message,
+            name,
+            meta: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a metadata entry to the message.
+    pub fn with_meta<V: Into<serde_json::Value>>(mut self, key: &str, value: V) -> Self {
+        self.meta.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Adds a metadata entry to the message if `value` is not `None`.
+    pub fn with_optional_meta<V: Into<serde_json::Value>>(
+        self,
+        key: &str,
+        value: Option<V>,
+    ) -> Self {
+        match value {
+            Some(value) => self.with_meta(key, value),
+            None => self,
+        }
+    }
+
+    /// Sets the kind of the message.
+    pub fn with_kind(self, kind: MessageKind) -> Self {
+        self.with_meta(meta_key::KIND, kind.as_str())
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 /// Extended message structure for scripts
 pub struct ExtendedMessage {
     /// Optional name for the message, used in some scripts.
@@ -956,6 +1121,12 @@ pub struct ExtendedMessage {
     pub translated: String,
     /// Optional LLM translated text.
     pub llm: Option<String>,
+    /// Whether the translation needs to be reviewed, e.g. it comes from a fuzzy match.
+    ///
+    /// Fuzzy translations are not used when importing.
+    pub fuzzy: bool,
+    /// Additional information about where the message came from. See [Message::meta].
+    pub meta: BTreeMap<String, serde_json::Value>,
 }
 
 /// Result of script operation.
@@ -975,6 +1146,8 @@ pub enum ScriptResult {
 pub enum FormatType {
     /// Wrap line with fixed length
     Fixed,
+    /// Wrap line by pixel width using font metrics
+    Pixel,
     /// Do not wrap line
     None,
 }
@@ -1003,6 +1176,33 @@ pub enum FormatOptions {
         /// Do not remove space at the start of the line
         no_remove_space_at_line_start: bool,
     },
+    /// Wrap line by pixel width using font metrics or a glyph width table
+    Pixel {
+        /// Maximum line width in pixels
+        width: usize,
+        /// Path to TTF/OTF font file
+        font: Option<String>,
+        /// Font size in pixels
+        font_size: u32,
+        /// Path to JSON glyph width table
+        width_table: Option<String>,
+        /// Whether to keep original line breaks
+        keep_original: bool,
+        /// Whether to break words(ASCII only) at the end of the line
+        break_words: bool,
+        /// Whether to insert a full-width space after a line break when a sentence starts with a full-width quotation mark.
+        insert_fullwidth_space_at_line_start: bool,
+        /// If a line break occurs in the middle of some symbols, bring the sentence to next line
+        break_with_sentence: bool,
+        #[cfg(feature = "jieba")]
+        /// Whether to break Chinese words at the end of the line.
+        break_chinese_words: bool,
+        #[cfg(feature = "jieba")]
+        /// Path to custom jieba dictionary
+        jieba_dict: Option<String>,
+        /// Do not remove space at the start of the line
+        no_remove_space_at_line_start: bool,
+    },
     /// Do not wrap line
     None,
 }
@@ -154,3 +154,55 @@ impl<T: Read> LsbBitStream<T> {
         Ok(self.get_bits(1)? == 1)
     }
 }
+
+/// A least significant bit (LSB) bit writer.
+pub struct LsbBitWriter<'a, T: Write> {
+    /// The output stream to write to.
+    pub writer: &'a mut T,
+    buffer: u32,
+    buffer_size: u32,
+}
+
+impl<'a, T: Write> LsbBitWriter<'a, T> {
+    /// Creates a new LSB bit writer.
+    pub fn new(writer: &'a mut T) -> Self {
+        LsbBitWriter {
+            writer,
+            buffer: 0,
+            buffer_size: 0,
+        }
+    }
+
+    /// Flushes the buffer to the output stream.
+    /// This writes any remaining bits in the buffer to the stream.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.buffer_size > 0 {
+            self.writer.write_u8((self.buffer & 0xFF) as u8)?;
+            self.buffer = 0;
+            self.buffer_size = 0;
+        }
+        Ok(())
+    }
+
+    /// Puts the lowest `count` bits of `value` into the bit stream, least significant bit first.
+    pub fn put_bits(&mut self, value: u32, count: u8) -> Result<()> {
+        for i in 0..count {
+            self.put_bit((value >> i) & 1 != 0)?;
+        }
+        Ok(())
+    }
+
+    /// Puts a single bit into the bit stream.
+    pub fn put_bit(&mut self, bit: bool) -> Result<()> {
+        if bit {
+            self.buffer |= 1 << self.buffer_size;
+        }
+        self.buffer_size += 1;
+        if self.buffer_size == 8 {
+            self.writer.write_u8((self.buffer & 0xFF) as u8)?;
+            self.buffer = 0;
+            self.buffer_size = 0;
+        }
+        Ok(())
+    }
+}
@@ -168,6 +168,10 @@ pub fn decode_to_string(
         Encoding::CodePage(code_page) => Ok(super::encoding_win::decode_to_string(
             code_page, data, check,
         )?),
+        #[cfg(not(windows))]
+        Encoding::CodePage(code_page) => {
+            super::encoding_cp::decode_to_string(code_page, data, check)
+        }
     }
 }
 
@@ -195,6 +199,47 @@ fn gbk_encoder_trap(_: &mut dyn RawEncoder, _: &str, out: &mut dyn ByteWriter) -
     true
 }
 
+/// Encodes a string to Shift-JIS (CP932).
+///
+/// Characters `U+F8F1`-`U+F8F3` are mapped to `0xFD`-`0xFF` like Windows API.
+pub(crate) fn encode_cp932(data: &str, check: bool) -> Result<Vec<u8>, anyhow::Error> {
+    ENCODE_REPLACED.with(|f| f.qsave(false));
+    let result = encoding::codec::japanese::Windows31JEncoding
+        .encode(
+            data,
+            if check {
+                // Keep same behavior as Windows API (Code Page 932)
+                EncoderTrap::Call(|_, data, out| {
+                    if data == "\u{f8f3}" {
+                        out.write_byte(0xFF); // PUA character for U+F8F3
+                        true
+                    } else if data == "\u{f8f2}" {
+                        out.write_byte(0xFE); // PUA character for U+F8F2
+                        true
+                    } else if data == "\u{f8f1}" {
+                        out.write_byte(0xFD); // PUA character for U+F8F1
+                        true
+                    } else {
+                        false
+                    }
+                })
+            } else {
+                EncoderTrap::Call(jis_encoder_trap)
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("Failed to encode Shift-JIS"))?;
+    ENCODE_REPLACED.with(|f| {
+        if f.qload() {
+            eprintln!(
+                "Warning: Some characters could not be encoded in Shift-JIS: {}",
+                data
+            );
+            crate::COUNTER.inc_warning();
+        }
+    });
+    Ok(result)
+}
+
 /// Encodes a string to a byte vector using the specified encoding.
 ///
 /// * `check` - If true, checks for encoding errors and returns an error if any.
@@ -203,46 +248,13 @@ pub fn encode_string(
     data: &str,
     check: bool,
 ) -> Result<Vec<u8>, anyhow::Error> {
+    if encoding.is_jis() && super::sjis_tunnel::is_enabled() {
+        return super::sjis_tunnel::encode(data, check);
+    }
     match encoding {
         Encoding::Auto => Ok(data.as_bytes().to_vec()),
         Encoding::Utf8 => Ok(data.as_bytes().to_vec()),
-        Encoding::Cp932 => {
-            ENCODE_REPLACED.with(|f| f.qsave(false));
-            let result = encoding::codec::japanese::Windows31JEncoding
-                .encode(
-                    data,
-                    if check {
-                        // Keep same behavior as Windows API (Code Page 932)
-                        EncoderTrap::Call(|_, data, out| {
-                            if data == "\u{f8f3}" {
-                                out.write_byte(0xFF); // PUA character for U+F8F3
-                                true
-                            } else if data == "\u{f8f2}" {
-                                out.write_byte(0xFE); // PUA character for U+F8F2
-                                true
-                            } else if data == "\u{f8f1}" {
-                                out.write_byte(0xFD); // PUA character for U+F8F1
-                                true
-                            } else {
-                                false
-                            }
-                        })
-                    } else {
-                        EncoderTrap::Call(jis_encoder_trap)
-                    },
-                )
-                .map_err(|_| anyhow::anyhow!("Failed to encode Shift-JIS"))?;
-            ENCODE_REPLACED.with(|f| {
-                if f.qload() {
-                    eprintln!(
-                        "Warning: Some characters could not be encoded in Shift-JIS: {}",
-                        data
-                    );
-                    crate::COUNTER.inc_warning();
-                }
-            });
-            Ok(result)
-        }
+        Encoding::Cp932 => encode_cp932(data, check),
         Encoding::Gb2312 => {
             ENCODE_REPLACED.with(|f| f.qsave(false));
             let result = encoding::codec::simpchinese::GBK_ENCODING
@@ -278,6 +290,8 @@ pub fn encode_string(
         Encoding::CodePage(code_page) => {
             Ok(super::encoding_win::encode_string(code_page, data, check)?)
         }
+        #[cfg(not(windows))]
+        Encoding::CodePage(code_page) => super::encoding_cp::encode_string(code_page, data, check),
     }
 }
 
@@ -359,7 +373,6 @@ fn test_decode_to_string() {
         .unwrap(),
         "きゃべつそふと".to_string()
     );
-    #[cfg(windows)]
     assert_eq!(
         decode_to_string(Encoding::CodePage(936), &[214, 208, 206, 196], true).unwrap(),
         "中文".to_string()
@@ -382,7 +395,6 @@ fn test_encode_string() {
         encode_string(Encoding::Gb2312, "中文", true).unwrap(),
         vec![214, 208, 206, 196]
     );
-    #[cfg(windows)]
     assert_eq!(
         encode_string(Encoding::CodePage(936), "中文", true).unwrap(),
         vec![214, 208, 206, 196]
@@ -447,7 +459,6 @@ fn test_encode_string_with_bom() {
 #[test]
 fn shift_jis_pua_test() {
     let ff = [0xFF, 0x01];
-    #[cfg(windows)]
     assert_eq!(
         decode_to_string(Encoding::CodePage(932), &ff, false).unwrap(),
         "\u{f8f3}\x01".to_string()
@@ -456,11 +467,9 @@ fn shift_jis_pua_test() {
         decode_to_string(Encoding::Cp932, &ff, false).unwrap(),
         "\u{f8f3}\x01".to_string()
     );
-    #[cfg(windows)]
     assert!(decode_to_string(Encoding::CodePage(932), &ff, true).is_err());
     assert!(decode_to_string(Encoding::Cp932, &ff, true).is_err());
     let fe = [0xFE, 0x01];
-    #[cfg(windows)]
     assert_eq!(
         decode_to_string(Encoding::CodePage(932), &fe, false).unwrap(),
         "\u{f8f2}\x01".to_string()
@@ -469,11 +478,9 @@ fn shift_jis_pua_test() {
         decode_to_string(Encoding::Cp932, &fe, false).unwrap(),
         "\u{f8f2}\x01".to_string()
     );
-    #[cfg(windows)]
     assert!(decode_to_string(Encoding::CodePage(932), &fe, true).is_err());
     assert!(decode_to_string(Encoding::Cp932, &fe, true).is_err());
     let fd = [0xFD, 0x01];
-    #[cfg(windows)]
     assert_eq!(
         decode_to_string(Encoding::CodePage(932), &fd, false).unwrap(),
         "\u{f8f1}\x01".to_string()
@@ -482,11 +489,9 @@ fn shift_jis_pua_test() {
         decode_to_string(Encoding::Cp932, &fd, false).unwrap(),
         "\u{f8f1}\x01".to_string()
     );
-    #[cfg(windows)]
     assert!(decode_to_string(Encoding::CodePage(932), &fd, true).is_err());
     assert!(decode_to_string(Encoding::Cp932, &fd, true).is_err());
     let ff = "\u{f8f3}\x01";
-    #[cfg(windows)]
     assert_eq!(
         encode_string(Encoding::CodePage(932), ff, false).unwrap(),
         vec![0xFF, 0x01]
@@ -495,7 +500,6 @@ fn shift_jis_pua_test() {
         encode_string(Encoding::Cp932, ff, false).unwrap(),
         vec![0xFF, 0x01]
     );
-    #[cfg(windows)]
     assert_eq!(
         encode_string(Encoding::CodePage(932), ff, true).unwrap(),
         vec![0xFF, 0x01]
@@ -505,7 +509,6 @@ fn shift_jis_pua_test() {
         vec![0xFF, 0x01]
     );
     let fe = "\u{f8f2}\x01";
-    #[cfg(windows)]
     assert_eq!(
         encode_string(Encoding::CodePage(932), fe, false).unwrap(),
         vec![0xFE, 0x01]
@@ -514,7 +517,6 @@ fn shift_jis_pua_test() {
         encode_string(Encoding::Cp932, fe, false).unwrap(),
         vec![0xFE, 0x01]
     );
-    #[cfg(windows)]
     assert_eq!(
         encode_string(Encoding::CodePage(932), fe, true).unwrap(),
         vec![0xFE, 0x01]
@@ -524,7 +526,6 @@ fn shift_jis_pua_test() {
         vec![0xFE, 0x01]
     );
     let fd = "\u{f8f1}\x01";
-    #[cfg(windows)]
     assert_eq!(
         encode_string(Encoding::CodePage(932), fd, false).unwrap(),
         vec![0xFD, 0x01]
@@ -533,7 +534,6 @@ fn shift_jis_pua_test() {
         encode_string(Encoding::Cp932, fd, false).unwrap(),
         vec![0xFD, 0x01]
     );
-    #[cfg(windows)]
     assert_eq!(
         encode_string(Encoding::CodePage(932), fd, true).unwrap(),
         vec![0xFD, 0x01]
@@ -543,7 +543,6 @@ fn shift_jis_pua_test() {
         vec![0xFD, 0x01]
     );
     let failed_test = "\u{f8f4}\x01";
-    #[cfg(windows)]
     assert!(encode_string(Encoding::CodePage(932), failed_test, true).is_err());
     assert!(encode_string(Encoding::Cp932, failed_test, true).is_err());
 }
@@ -0,0 +1,98 @@
+//! Portable code page support based on `encoding` crate.
+use crate::types::Encoding;
+use anyhow::Result;
+use encoding::label::encoding_from_windows_code_page;
+use encoding::{ByteWriter, DecoderTrap, EncoderTrap, EncodingRef, RawEncoder};
+
+fn encoding_from_code_page(cp: u32) -> Option<EncodingRef> {
+    match cp {
+        // Windows code page 936 is GBK, not GB18030.
+        936 => Some(encoding::all::GBK),
+        20127 => Some(encoding::all::ASCII),
+        20932 | 51932 => Some(encoding::all::EUC_JP),
+        51949 => Some(encoding::all::WINDOWS_949),
+        50221 | 50222 => Some(encoding::all::ISO_2022_JP),
+        _ => encoding_from_windows_code_page(cp as usize),
+    }
+}
+
+fn replace_encoder_trap(_: &mut dyn RawEncoder, _: &str, out: &mut dyn ByteWriter) -> bool {
+    out.write_byte(b'?'); // Replacement character
+    true
+}
+
+pub fn decode_to_string(cp: u32, data: &[u8], check: bool) -> Result<String> {
+    match cp {
+        // Keep PUA mapping of 0xFD-0xFF same as Windows API
+        932 => return super::encoding::decode_to_string(Encoding::Cp932, data, check),
+        936 => return super::encoding::decode_to_string(Encoding::Gb2312, data, check),
+        65001 => return super::encoding::decode_to_string(Encoding::Utf8, data, check),
+        _ => {}
+    }
+    let enc = encoding_from_code_page(cp)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported code page: {}", cp))?;
+    let result = enc
+        .decode(
+            data,
+            if check {
+                DecoderTrap::Strict
+            } else {
+                DecoderTrap::Replace
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("Failed to decode code page {}", cp))?;
+    if result.contains('\u{FFFD}') {
+        eprintln!(
+            "Warning: Some characters could not be decoded in code page {}: {:?}",
+            cp, data
+        );
+        crate::COUNTER.inc_warning();
+    }
+    Ok(result)
+}
+
+pub fn encode_string(cp: u32, data: &str, check: bool) -> Result<Vec<u8>> {
+    match cp {
+        932 => return super::encoding::encode_string(Encoding::Cp932, data, check),
+        936 => return super::encoding::encode_string(Encoding::Gb2312, data, check),
+        65001 => return super::encoding::encode_string(Encoding::Utf8, data, check),
+        _ => {}
+    }
+    let enc = encoding_from_code_page(cp)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported code page: {}", cp))?;
+    match enc.encode(data, EncoderTrap::Strict) {
+        Ok(result) => Ok(result),
+        Err(_) if check => Err(anyhow::anyhow!(
+            "Some characters could not be encoded in code page {}: {}",
+            cp,
+            data
+        )),
+        Err(_) => {
+            eprintln!(
+                "Warning: Some characters could not be encoded in code page {}: {}",
+                cp, data
+            );
+            crate::COUNTER.inc_warning();
+            enc.encode(data, EncoderTrap::Call(replace_encoder_trap))
+                .map_err(|_| anyhow::anyhow!("Failed to encode code page {}", cp))
+        }
+    }
+}
+
+#[test]
+fn test_code_page_round_trip() {
+    let cases: [(u32, &str, &[u8]); 5] = [
+        (950, "中文", &[0xA4, 0xA4, 0xA4, 0xE5]),
+        (949, "한국", &[0xC7, 0xD1, 0xB1, 0xB9]),
+        (1252, "café€", &[0x63, 0x61, 0x66, 0xE9, 0x80]),
+        (51932, "日本", &[0xC6, 0xFC, 0xCB, 0xDC]),
+        (54936, "中文", &[214, 208, 206, 196]),
+    ];
+    for (cp, text, bytes) in cases {
+        assert_eq!(decode_to_string(cp, bytes, true).unwrap(), text);
+        assert_eq!(encode_string(cp, text, true).unwrap(), bytes);
+    }
+    assert!(encode_string(1252, "中文", true).is_err());
+    assert_eq!(encode_string(1252, "a中", false).unwrap(), b"a?");
+    assert!(decode_to_string(12345, b"abc", true).is_err());
+}
@@ -190,3 +190,244 @@ pub fn write_flac<W: Write + Seek, R: Read>(
     }
     Ok(())
 }
+
+/// State shared with the FLAC decoder callbacks.
+struct DecoderState<R> {
+    reader: R,
+    format: Option<PcmFormat>,
+    data: Vec<u8>,
+    error: Option<String>,
+}
+
+unsafe extern "C" fn decoder_read_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    buffer: *mut u8,
+    bytes: *mut usize,
+    client_data: *mut std::ffi::c_void,
+) -> FLAC__StreamDecoderReadStatus {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    let len = unsafe { *bytes };
+    if len == 0 {
+        return FLAC__STREAM_DECODER_READ_STATUS_ABORT;
+    }
+    let slice = unsafe { std::slice::from_raw_parts_mut(buffer, len) };
+    match state.reader.read(slice) {
+        Ok(0) => {
+            unsafe { *bytes = 0 };
+            FLAC__STREAM_DECODER_READ_STATUS_END_OF_STREAM
+        }
+        Ok(n) => {
+            unsafe { *bytes = n };
+            FLAC__STREAM_DECODER_READ_STATUS_CONTINUE
+        }
+        Err(_) => FLAC__STREAM_DECODER_READ_STATUS_ABORT,
+    }
+}
+
+unsafe extern "C" fn decoder_seek_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    absolute_byte_offset: u64,
+    client_data: *mut std::ffi::c_void,
+) -> FLAC__StreamDecoderSeekStatus {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    match state
+        .reader
+        .seek(std::io::SeekFrom::Start(absolute_byte_offset))
+    {
+        Ok(_) => FLAC__STREAM_DECODER_SEEK_STATUS_OK,
+        Err(_) => FLAC__STREAM_DECODER_SEEK_STATUS_ERROR,
+    }
+}
+
+unsafe extern "C" fn decoder_tell_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    absolute_byte_offset: *mut u64,
+    client_data: *mut std::ffi::c_void,
+) -> FLAC__StreamDecoderTellStatus {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    match state.reader.stream_position() {
+        Ok(pos) => {
+            unsafe { *absolute_byte_offset = pos };
+            FLAC__STREAM_DECODER_TELL_STATUS_OK
+        }
+        Err(_) => FLAC__STREAM_DECODER_TELL_STATUS_ERROR,
+    }
+}
+
+unsafe extern "C" fn decoder_length_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    stream_length: *mut u64,
+    client_data: *mut std::ffi::c_void,
+) -> FLAC__StreamDecoderLengthStatus {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    match state.reader.stream_length() {
+        Ok(len) => {
+            unsafe { *stream_length = len };
+            FLAC__STREAM_DECODER_LENGTH_STATUS_OK
+        }
+        Err(_) => FLAC__STREAM_DECODER_LENGTH_STATUS_ERROR,
+    }
+}
+
+unsafe extern "C" fn decoder_eof_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    client_data: *mut std::ffi::c_void,
+) -> FLAC__bool {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    match (state.reader.stream_position(), state.reader.stream_length()) {
+        (Ok(pos), Ok(len)) => (pos >= len) as FLAC__bool,
+        _ => 1,
+    }
+}
+
+unsafe extern "C" fn decoder_write_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    frame: *const FLAC__Frame,
+    buffer: *const *const i32,
+    client_data: *mut std::ffi::c_void,
+) -> FLAC__StreamDecoderWriteStatus {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    let header = unsafe { &(*frame).header };
+    let bytes = (header.bits_per_sample as usize).div_ceil(8);
+    // Samples are stored in the most significant bits, e.g. 20 bits samples in 24 bits.
+    let shift = bytes as u32 * 8 - header.bits_per_sample;
+    let channels = header.channels as usize;
+    let buffers = unsafe { std::slice::from_raw_parts(buffer, channels) };
+    let buffers: Vec<&[i32]> = buffers
+        .iter()
+        .map(|&b| unsafe { std::slice::from_raw_parts(b, header.blocksize as usize) })
+        .collect();
+    state
+        .data
+        .reserve(header.blocksize as usize * channels * bytes);
+    for i in 0..header.blocksize as usize {
+        for b in buffers.iter() {
+            let sample = b[i] << shift;
+            match bytes {
+                // 8 bits PCM is unsigned in wav files.
+                1 => state.data.push((sample + 128) as u8),
+                _ => state
+                    .data
+                    .extend_from_slice(&sample.to_le_bytes()[..bytes.min(4)]),
+            }
+        }
+    }
+    FLAC__STREAM_DECODER_WRITE_STATUS_CONTINUE
+}
+
+unsafe extern "C" fn decoder_metadata_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    metadata: *const FLAC__StreamMetadata,
+    client_data: *mut std::ffi::c_void,
+) {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    let metadata = unsafe { &*metadata };
+    if metadata.type_ == FLAC__METADATA_TYPE_STREAMINFO {
+        let info = unsafe { &metadata.data.stream_info };
+        let block_align = (info.bits_per_sample.div_ceil(8) * info.channels) as u16;
+        state.format = Some(PcmFormat {
+            format_tag: 1,
+            channels: info.channels as u16,
+            sample_rate: info.sample_rate,
+            average_bytes_per_second: info.sample_rate * block_align as u32,
+            block_align,
+            bits_per_sample: (info.bits_per_sample.div_ceil(8) * 8) as u16,
+        });
+        state
+            .data
+            .reserve(info.total_samples as usize * block_align as usize);
+    }
+}
+
+unsafe extern "C" fn decoder_error_callback<R: Read + Seek>(
+    _decoder: *const FLAC__StreamDecoder,
+    status: FLAC__StreamDecoderErrorStatus,
+    client_data: *mut std::ffi::c_void,
+) {
+    let state = unsafe { &mut *(client_data as *mut DecoderState<R>) };
+    let s = unsafe {
+        CStr::from_ptr(
+            *FLAC__StreamDecoderErrorStatusString
+                .as_ptr()
+                .add(status as usize),
+        )
+    };
+    state.error = Some(s.to_string_lossy().into_owned());
+}
+
+struct DecoderHandle {
+    decoder: *mut FLAC__StreamDecoder,
+}
+
+impl Drop for DecoderHandle {
+    fn drop(&mut self) {
+        unsafe {
+            FLAC__stream_decoder_delete(self.decoder);
+        }
+    }
+}
+
+fn decoder_state_error(decoder: *mut FLAC__StreamDecoder) -> anyhow::Error {
+    let state = unsafe { FLAC__stream_decoder_get_state(decoder) };
+    let s = unsafe { CStr::from_ptr(*FLAC__StreamDecoderStateString.as_ptr().add(state as usize)) };
+    anyhow::anyhow!("FLAC decoding error: {}", s.to_string_lossy())
+}
+
+/// Reads lossless audio data from a flac file.
+///
+/// Returns the PCM format and the raw sample data, in the same layout as wav files.
+///
+/// * `reader` - The reader to read the flac file from.
+pub fn read_flac<R: Read + Seek>(reader: R) -> Result<(PcmFormat, Vec<u8>)> {
+    let decoder = unsafe { FLAC__stream_decoder_new() };
+    if decoder.is_null() {
+        return Err(anyhow::anyhow!("Failed to create FLAC decoder"));
+    }
+    let decoder = DecoderHandle { decoder };
+    let mut state = DecoderState {
+        reader,
+        format: None,
+        data: Vec::new(),
+        error: None,
+    };
+    let status = unsafe {
+        FLAC__stream_decoder_init_stream(
+            decoder.decoder,
+            Some(decoder_read_callback::<R>),
+            Some(decoder_seek_callback::<R>),
+            Some(decoder_tell_callback::<R>),
+            Some(decoder_length_callback::<R>),
+            Some(decoder_eof_callback::<R>),
+            Some(decoder_write_callback::<R>),
+            Some(decoder_metadata_callback::<R>),
+            Some(decoder_error_callback::<R>),
+            &mut state as *mut DecoderState<R> as *mut std::ffi::c_void,
+        )
+    };
+    if status != FLAC__STREAM_DECODER_INIT_STATUS_OK {
+        let s = unsafe {
+            CStr::from_ptr(
+                *FLAC__StreamDecoderInitStatusString
+                    .as_ptr()
+                    .add(status as usize),
+            )
+        };
+        return Err(anyhow::anyhow!(
+            "FLAC decoder error: {}",
+            s.to_string_lossy()
+        ));
+    }
+    if unsafe { FLAC__stream_decoder_process_until_end_of_stream(decoder.decoder) } == 0 {
+        return Err(decoder_state_error(decoder.decoder));
+    }
+    if unsafe { FLAC__stream_decoder_finish(decoder.decoder) } == 0 {
+        return Err(decoder_state_error(decoder.decoder));
+    }
+    if let Some(error) = state.error {
+        return Err(anyhow::anyhow!("FLAC decoding error: {}", error));
+    }
+    let format = state
+        .format
+        .ok_or_else(|| anyhow::anyhow!("STREAMINFO not found in flac file"))?;
+    Ok((format, state.data))
+}
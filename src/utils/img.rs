@@ -234,6 +234,18 @@ pub fn convert_to_rgba(data: &mut ImageData) -> Result<()> {
     }
 }
 
+/// Converts an image to BGRA format.
+pub fn convert_to_bgra(data: &mut ImageData) -> Result<()> {
+    match data.color_type {
+        ImageColorType::Bgr => convert_bgr_to_bgra(data),
+        ImageColorType::Bgra => Ok(()),
+        _ => {
+            convert_to_rgba(data)?;
+            convert_rgba_to_bgra(data)
+        }
+    }
+}
+
 /// Encodes an image to the specified format and writes it to a file.
 ///
 /// * `data` - The image data to encode.
@@ -309,19 +321,7 @@ pub fn encode_img_writer<T: Write>(
                     mozjpeg::ColorSpace::JCS_EXT_RGBA
                 }
             };
-            if data.depth != 8 {
-                return Err(anyhow::anyhow!(
-                    "JPEG encoding only supports 8-bit depth, found: {}",
-                    data.depth
-                ));
-            }
-            let mut encoder = mozjpeg::compress::Compress::new(color_type);
-            encoder.set_size(data.width as usize, data.height as usize);
-            encoder.set_quality(config.jpeg_quality as f32);
-            let mut start = encoder.start_compress(file)?;
-            start.write_scanlines(&data.data)?;
-            start.finish()?;
-            Ok(())
+            encode_jpg_writer(&data, color_type, file, config.jpeg_quality)
         }
         #[cfg(feature = "image-webp")]
         ImageOutputType::Webp => {
@@ -494,6 +494,34 @@ pub fn load_png<R: std::io::Read + std::io::Seek>(data: R) -> Result<ImageData>
     })
 }
 
+#[cfg(feature = "mozjpeg")]
+/// Encodes 8-bit image data to JPEG and writes it to a writer.
+///
+/// * `data` - The image data to encode.
+/// * `color_type` - The color space of the image data.
+/// * `file` - The writer to write the JPEG data to.
+/// * `quality` - JPEG quality, 0-100.
+pub fn encode_jpg_writer<T: Write>(
+    data: &ImageData,
+    color_type: mozjpeg::ColorSpace,
+    file: T,
+    quality: u8,
+) -> Result<()> {
+    if data.depth != 8 {
+        return Err(anyhow::anyhow!(
+            "JPEG encoding only supports 8-bit depth, found: {}",
+            data.depth
+        ));
+    }
+    let mut encoder = mozjpeg::compress::Compress::new(color_type);
+    encoder.set_size(data.width as usize, data.height as usize);
+    encoder.set_quality(quality as f32);
+    let mut start = encoder.start_compress(file)?;
+    start.write_scanlines(&data.data)?;
+    start.finish()?;
+    Ok(())
+}
+
 #[cfg(feature = "mozjpeg")]
 pub fn load_jpg<R: std::io::Read>(data: R) -> Result<ImageData> {
     let decoder = mozjpeg::decompress::Decompress::new_reader(std::io::BufReader::new(data))?;
@@ -626,6 +654,42 @@ pub fn draw_on_canvas(
     })
 }
 
+/// Crops a region from an image. The inverse of [draw_on_canvas].
+///
+/// * `img` - The image data to crop.
+/// * `x` - The horizontal offset of the region.
+/// * `y` - The vertical offset of the region.
+/// * `width` - The width of the region.
+/// * `height` - The height of the region.
+pub fn crop_image(img: &ImageData, x: u32, y: u32, width: u32, height: u32) -> Result<ImageData> {
+    if x as u64 + width as u64 > img.width as u64 || y as u64 + height as u64 > img.height as u64 {
+        return Err(anyhow::anyhow!(
+            "Crop region ({}, {}, {}x{}) is out of image bounds ({}x{})",
+            x,
+            y,
+            width,
+            height,
+            img.width,
+            img.height
+        ));
+    }
+    let bytes_per_pixel = img.color_type.bpp(img.depth) as usize / 8;
+    let img_stride = img.width as usize * bytes_per_pixel;
+    let stride = width as usize * bytes_per_pixel;
+    let mut data = Vec::with_capacity(stride * height as usize);
+    for row in y as usize..(y + height) as usize {
+        let start = row * img_stride + x as usize * bytes_per_pixel;
+        data.extend_from_slice(&img.data[start..start + stride]);
+    }
+    Ok(ImageData {
+        width,
+        height,
+        color_type: img.color_type,
+        depth: img.depth,
+        data,
+    })
+}
+
 /// Flips an image vertically.
 pub fn flip_image(data: &mut ImageData) -> Result<()> {
     if data.height <= 1 {
@@ -993,3 +1057,124 @@ fn read_bits_as_usize(data: &[u8], bit_offset: usize, bit_len: usize) -> Result<
     }
     Ok(value)
 }
+
+/// Reduces colors to a palette with at most `max_colors` colors using median cut.
+///
+/// `max_colors` must be in `1..=256`. If there are no more colors than `max_colors`,
+/// the palette contains all colors exactly.
+/// Returns the palette and the palette index of every color in `colors`.
+pub fn quantize_colors(colors: &[[u8; 3]], max_colors: usize) -> Result<(Vec<[u8; 3]>, Vec<u8>)> {
+    if max_colors == 0 || max_colors > 256 {
+        return Err(anyhow::anyhow!(
+            "Invalid palette size {}, expected 1-256",
+            max_colors
+        ));
+    }
+    let mut counts = std::collections::HashMap::new();
+    for color in colors {
+        *counts.entry(*color).or_insert(0u64) += 1;
+    }
+    let mut unique: Vec<([u8; 3], u64)> = counts.into_iter().collect();
+    unique.sort_unstable();
+    let palette: Vec<[u8; 3]> = if unique.len() <= max_colors {
+        unique.iter().map(|(c, _)| *c).collect()
+    } else {
+        median_cut(unique, max_colors)
+    };
+    let mut cache = std::collections::HashMap::new();
+    let indices = colors
+        .iter()
+        .map(|color| {
+            *cache.entry(*color).or_insert_with(|| {
+                let mut best = 0;
+                let mut best_dist = u32::MAX;
+                for (i, p) in palette.iter().enumerate() {
+                    let dist: u32 = (0..3)
+                        .map(|c| (color[c] as i32 - p[c] as i32).pow(2) as u32)
+                        .sum();
+                    if dist < best_dist {
+                        best = i;
+                        best_dist = dist;
+                    }
+                }
+                best as u8
+            })
+        })
+        .collect();
+    Ok((palette, indices))
+}
+
+/// A box of colors in median cut, with the channel which has the widest range.
+struct ColorBox {
+    colors: Vec<([u8; 3], u64)>,
+    channel: usize,
+    range: u8,
+}
+
+impl ColorBox {
+    fn new(colors: Vec<([u8; 3], u64)>) -> Self {
+        let mut channel = 0;
+        let mut range = 0;
+        for c in 0..3 {
+            let (min, max) = colors.iter().fold((u8::MAX, 0), |(min, max), (color, _)| {
+                (min.min(color[c]), max.max(color[c]))
+            });
+            if max.saturating_sub(min) > range {
+                channel = c;
+                range = max - min;
+            }
+        }
+        Self {
+            colors,
+            channel,
+            range,
+        }
+    }
+
+    fn average(&self) -> [u8; 3] {
+        let mut sum = [0u64; 3];
+        let mut total = 0u64;
+        for (color, count) in &self.colors {
+            for c in 0..3 {
+                sum[c] += color[c] as u64 * count;
+            }
+            total += count;
+        }
+        sum.map(|s| ((s + total / 2) / total) as u8)
+    }
+}
+
+fn median_cut(colors: Vec<([u8; 3], u64)>, max_colors: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![ColorBox::new(colors)];
+    while boxes.len() < max_colors {
+        let Some((index, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.colors.len() > 1 && b.range > 0)
+            .max_by_key(|(_, b)| b.range)
+        else {
+            break;
+        };
+        let ColorBox {
+            mut colors,
+            channel,
+            ..
+        } = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+        let total: u64 = colors.iter().map(|(_, count)| count).sum();
+        let mut acc = 0;
+        let mut split = colors.len() / 2;
+        for (i, (_, count)) in colors.iter().enumerate() {
+            acc += count;
+            if acc * 2 >= total {
+                split = i + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, colors.len() - 1);
+        let rest = colors.split_off(split);
+        boxes.push(ColorBox::new(colors));
+        boxes.push(ColorBox::new(rest));
+    }
+    boxes.iter().map(|b| b.average()).collect()
+}
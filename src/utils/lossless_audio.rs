@@ -2,6 +2,7 @@
 #[cfg(feature = "audio-flac")]
 use super::flac::*;
 use super::pcm::*;
+use crate::ext::io::*;
 use crate::types::*;
 use anyhow::Result;
 use std::io::{Read, Seek, Write};
@@ -19,3 +20,20 @@ pub fn write_audio<W: Write + Seek, R: Read>(
     }
     Ok(())
 }
+
+/// Reads a lossless audio file. The format is detected from the file header.
+///
+/// Returns the PCM format and the raw sample data.
+pub fn read_audio<R: Read + Seek>(mut reader: R) -> Result<(PcmFormat, Vec<u8>)> {
+    let mut magic = [0u8; 4];
+    reader.peek_exact(&mut magic)?;
+    match &magic {
+        b"RIFF" => read_pcm(reader),
+        #[cfg(feature = "audio-flac")]
+        b"fLaC" => read_flac(reader),
+        _ => Err(anyhow::anyhow!(
+            "Unsupported lossless audio file, magic: {:?}",
+            magic
+        )),
+    }
+}
@@ -9,6 +9,8 @@ pub mod counter;
 #[cfg(feature = "utils-crc32")]
 pub mod crc32;
 pub mod encoding;
+#[cfg(not(windows))]
+mod encoding_cp;
 #[cfg(windows)]
 mod encoding_win;
 #[cfg(feature = "utils-escape")]
@@ -31,6 +33,8 @@ mod macros;
 pub mod mmx;
 pub mod name_replacement;
 pub mod num_range;
+#[cfg(feature = "utils-ogg")]
+pub mod ogg;
 #[cfg(feature = "utils-pcm")]
 pub mod pcm;
 #[cfg(feature = "utils-psd")]
@@ -41,9 +45,12 @@ pub mod rc4;
 pub mod serde_base64bytes;
 #[cfg(feature = "utils-simple-pack")]
 pub mod simple_pack;
+pub mod sjis_tunnel;
 #[cfg(feature = "utils-str")]
 pub mod str;
 pub mod struct_pack;
+#[cfg(test)]
+pub mod testing;
 pub mod threadpool;
 #[cfg(feature = "utils-xored-stream")]
 pub mod xored_stream;
@@ -0,0 +1,60 @@
+//! Ogg/Vorbis utilities.
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Basic information of an Ogg/Vorbis stream.
+pub struct VorbisInfo {
+    /// The number of channels
+    pub channels: u8,
+    /// The sample rate
+    pub sample_rate: u32,
+    /// The number of samples per channel
+    pub samples: u64,
+}
+
+/// Reads basic information from an Ogg/Vorbis file.
+///
+/// The number of samples is taken from the granule position of the last page of the first
+/// logical stream.
+pub fn read_vorbis_info(data: &[u8]) -> Result<VorbisInfo> {
+    let mut pos = 0;
+    let mut serial = None;
+    let mut info = None;
+    let mut samples = 0;
+    while pos + 27 <= data.len() {
+        if &data[pos..pos + 4] != b"OggS" {
+            return Err(anyhow::anyhow!("Invalid Ogg page at offset {}", pos));
+        }
+        let granule = i64::from_le_bytes(data[pos + 6..pos + 14].try_into()?);
+        let page_serial = u32::from_le_bytes(data[pos + 14..pos + 18].try_into()?);
+        let segments = data[pos + 26] as usize;
+        let body = pos + 27 + segments;
+        if body > data.len() {
+            break;
+        }
+        let body_len: usize = data[pos + 27..body].iter().map(|&s| s as usize).sum();
+        let end = (body + body_len).min(data.len());
+        match serial {
+            None => {
+                // The first page contains the identification header.
+                let packet = &data[body..end];
+                if packet.len() < 16 || &packet[..7] != b"\x01vorbis" {
+                    return Err(anyhow::anyhow!("Vorbis identification header not found"));
+                }
+                info = Some((packet[11], u32::from_le_bytes(packet[12..16].try_into()?)));
+                serial = Some(page_serial);
+            }
+            Some(serial) if serial == page_serial && granule >= 0 => {
+                samples = granule as u64;
+            }
+            _ => {}
+        }
+        pos = end;
+    }
+    let (channels, sample_rate) = info.ok_or_else(|| anyhow::anyhow!("Empty Ogg file"))?;
+    Ok(VorbisInfo {
+        channels,
+        sample_rate,
+        samples,
+    })
+}
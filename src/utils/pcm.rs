@@ -59,3 +59,52 @@ pub fn write_pcm<W: Write + Seek, R: Read>(
     writer.write_u32(data_size)?;
     Ok(())
 }
+
+/// Reads PCM data from a wav file.
+///
+/// Returns the PCM format and the raw sample data.
+///
+/// * `reader` - The reader to read the wav file from.
+pub fn read_pcm<R: Read + Seek>(mut reader: R) -> Result<(PcmFormat, Vec<u8>)> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"RIFF" {
+        return Err(anyhow::anyhow!("Invalid RIFF header: {:?}", magic));
+    }
+    reader.read_u32()?; // Total size
+    reader.read_exact(&mut magic)?;
+    if &magic != b"WAVE" {
+        return Err(anyhow::anyhow!("Invalid WAVE header: {:?}", magic));
+    }
+    let mut format = None;
+    let len = reader.stream_length()?;
+    while reader.stream_position()? + 8 <= len {
+        reader.read_exact(&mut magic)?;
+        let size = reader.read_u32()? as u64;
+        let start = reader.stream_position()?;
+        match &magic {
+            b"fmt " => {
+                let mut fmt = PcmFormat::unpack(&mut reader, false, Encoding::Utf8, &None)?;
+                // WAVE_FORMAT_EXTENSIBLE, the real format tag is stored in the sub format GUID.
+                if fmt.format_tag == 0xFFFE && size >= 26 {
+                    reader.seek(std::io::SeekFrom::Start(start + 24))?;
+                    fmt.format_tag = reader.read_u16()?;
+                }
+                format = Some(fmt);
+            }
+            b"data" => {
+                let format = format
+                    .take()
+                    .ok_or_else(|| anyhow::anyhow!("fmt chunk not found before data chunk"))?;
+                let size = size.min(len - start);
+                let mut data = vec![0u8; size as usize];
+                reader.read_exact(&mut data)?;
+                return Ok((format, data));
+            }
+            _ => {}
+        }
+        // Chunks are aligned to 2 bytes.
+        reader.seek(std::io::SeekFrom::Start(start + size + (size & 1)))?;
+    }
+    Err(anyhow::anyhow!("data chunk not found in wav file"))
+}
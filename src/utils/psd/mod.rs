@@ -522,6 +522,11 @@ impl<'a> NormalLayer<'a> {
         self.layer.base.channels
     }
 
+    /// Returns the data of the additional layer information with the given key.
+    pub fn additional_info(&self, key: &[u8; 4]) -> Option<&'a [u8]> {
+        self.layer.get_info(key)
+    }
+
     /// Reads and returns the raw channel id and data of the layer.
     pub fn read_raw_data(&self) -> Result<Vec<(i16, Vec<u8>)>> {
         let mut start_idx = self.psd.channel_start_indices[self.layer_idx];
@@ -0,0 +1,266 @@
+//! Shift-JIS tunneling
+//!
+//! When enabled, characters which can not be encoded in Shift-JIS (CP932) are remapped
+//! to unused Shift-JIS code points instead of failing or being replaced by `?`.
+//!
+//! A tunneled character is stored as two bytes: a valid Shift-JIS lead byte
+//! (`0x81-0x9F`, `0xE0-0xFC`) followed by a byte in `0x01-0x3F` (excluding `\t`, `\n`, `\r`
+//! and space), which is never a valid trail byte. The `n`-th tunneled character uses
+//! the `n / TRAIL_BYTES.len()`-th lead byte and `TRAIL_BYTES[n % TRAIL_BYTES.len()]`
+//! (see [code_for_index]).
+//!
+//! The mapping table is saved as:
+//! * JSON: an object from the hex code (e.g. `"8101"`) to the character.
+//! * Binary: UTF-16LE characters in tunnel order, so the `n`-th character is the `n`-th entry.
+//!   This is the format consumed by injected DLLs or font hooks.
+use crate::ext::mutex::*;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Lead bytes used by tunneled characters.
+pub const LEAD_BYTES: [std::ops::RangeInclusive<u8>; 2] = [0x81..=0x9F, 0xE0..=0xFC];
+
+const fn is_trail_byte(b: u8) -> bool {
+    b >= 0x01 && b <= 0x3F && b != b'\t' && b != b'\n' && b != b'\r' && b != b' '
+}
+
+const LEAD_COUNT: usize = (0x9F - 0x81 + 1) + (0xFC - 0xE0 + 1);
+const TRAIL_COUNT: usize = {
+    let mut count = 0;
+    let mut b = 0x01u8;
+    while b <= 0x3F {
+        if is_trail_byte(b) {
+            count += 1;
+        }
+        b += 1;
+    }
+    count
+};
+
+/// Trail bytes used by tunneled characters.
+pub const TRAIL_BYTES: [u8; TRAIL_COUNT] = {
+    let mut bytes = [0u8; TRAIL_COUNT];
+    let mut i = 0;
+    let mut b = 0x01u8;
+    while b <= 0x3F {
+        if is_trail_byte(b) {
+            bytes[i] = b;
+            i += 1;
+        }
+        b += 1;
+    }
+    bytes
+};
+
+/// Maximum number of characters which can be tunneled.
+pub const MAX_TUNNEL_CHARS: usize = LEAD_COUNT * TRAIL_COUNT;
+
+/// Returns the Shift-JIS code of the `index`-th tunneled character.
+pub fn code_for_index(index: usize) -> Option<u16> {
+    if index >= MAX_TUNNEL_CHARS {
+        return None;
+    }
+    let lead = index / TRAIL_COUNT;
+    let lead = if lead <= 0x9F - 0x81 {
+        0x81 + lead
+    } else {
+        0xE0 + lead - (0x9F - 0x81 + 1)
+    };
+    Some(((lead as u16) << 8) | TRAIL_BYTES[index % TRAIL_COUNT] as u16)
+}
+
+#[derive(Debug, Default, Clone)]
+/// Shift-JIS tunnel mapping table
+pub struct SjisTunnel {
+    chars: Vec<char>,
+    map: HashMap<char, u16>,
+}
+
+impl SjisTunnel {
+    /// Creates an empty mapping table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a mapping table from a JSON file.
+    pub fn load(path: &str) -> Result<Self> {
+        let data = crate::utils::files::read_file(path)?;
+        let s = String::from_utf8(data)?;
+        let table: BTreeMap<String, String> = serde_json::from_str(&s)?;
+        let mut tunnel = Self::new();
+        for (code, c) in table {
+            let mut chars = c.chars();
+            let ch = match (chars.next(), chars.next()) {
+                (Some(ch), None) => ch,
+                _ => anyhow::bail!("Invalid character in SJIS tunnel mapping: {:?}", c),
+            };
+            let code = u16::from_str_radix(&code, 16)
+                .map_err(|_| anyhow::anyhow!("Invalid code in SJIS tunnel mapping: {}", code))?;
+            let inserted = tunnel.insert(ch)?;
+            if inserted != code {
+                anyhow::bail!(
+                    "SJIS tunnel mapping is not continuous: expected {:04X} for {:?}, got {:04X}",
+                    inserted,
+                    ch,
+                    code
+                );
+            }
+        }
+        Ok(tunnel)
+    }
+
+    /// Loads a mapping table from a JSON file if it exists, otherwise creates an empty one.
+    pub fn load_or_new(path: &str) -> Result<Self> {
+        if std::path::Path::new(path).exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    /// Returns the number of tunneled characters.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Returns true if no characters are tunneled.
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Returns the tunneled code of a character if it is already in the table.
+    pub fn get(&self, c: char) -> Option<u16> {
+        self.map.get(&c).copied()
+    }
+
+    /// Returns the tunneled code of a character, adding it to the table if needed.
+    pub fn insert(&mut self, c: char) -> Result<u16> {
+        if let Some(code) = self.map.get(&c) {
+            return Ok(*code);
+        }
+        if c as u32 > 0xFFFF {
+            anyhow::bail!("Character {:?} is outside BMP and can not be tunneled", c);
+        }
+        let code = code_for_index(self.chars.len())
+            .ok_or_else(|| anyhow::anyhow!("SJIS tunnel mapping is full"))?;
+        self.chars.push(c);
+        self.map.insert(c, code);
+        Ok(code)
+    }
+
+    /// Returns the JSON representation of the table.
+    pub fn to_json(&self) -> Result<String> {
+        let table: BTreeMap<String, String> = self
+            .chars
+            .iter()
+            .map(|c| (format!("{:04X}", self.map[c]), c.to_string()))
+            .collect();
+        Ok(serde_json::to_string_pretty(&table)?)
+    }
+
+    /// Returns the binary representation of the table.
+    pub fn to_bin(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.chars.len() * 2);
+        for c in self.chars.iter() {
+            data.extend_from_slice(&(*c as u16).to_le_bytes());
+        }
+        data
+    }
+
+    /// Saves the table as JSON file and binary file.
+    pub fn save(&self, json_path: &str, bin_path: &str) -> Result<()> {
+        crate::utils::files::write_file(json_path)?.write_all(self.to_json()?.as_bytes())?;
+        crate::utils::files::write_file(bin_path)?.write_all(&self.to_bin())?;
+        Ok(())
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref TUNNEL: Mutex<Option<SjisTunnel>> = Mutex::new(None);
+}
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables Shift-JIS tunneling with the given table.
+///
+/// The table is shared by all encoding calls until [disable] is called,
+/// so all files processed in one run use the same mapping.
+pub fn enable(tunnel: SjisTunnel) {
+    *TUNNEL.lock_blocking() = Some(tunnel);
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Disables Shift-JIS tunneling and returns the table.
+pub fn disable() -> Option<SjisTunnel> {
+    ENABLED.store(false, Ordering::SeqCst);
+    TUNNEL.lock_blocking().take()
+}
+
+/// Returns true if Shift-JIS tunneling is enabled.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Encodes a string to Shift-JIS, tunneling characters which can not be encoded.
+pub(crate) fn encode(data: &str, check: bool) -> Result<Vec<u8>> {
+    if let Ok(re) = super::encoding::encode_cp932(data, true) {
+        return Ok(re);
+    }
+    let mut lock = TUNNEL.lock_blocking();
+    encode_with(lock.get_or_insert_with(SjisTunnel::new), data, check)
+}
+
+/// Encodes a string to Shift-JIS with the given table, tunneling characters which can not be encoded.
+fn encode_with(tunnel: &mut SjisTunnel, data: &str, check: bool) -> Result<Vec<u8>> {
+    let mut result = Vec::with_capacity(data.len() * 2);
+    let mut buf = [0u8; 4];
+    for c in data.chars() {
+        if let Ok(re) = super::encoding::encode_cp932(c.encode_utf8(&mut buf), true) {
+            result.extend_from_slice(&re);
+            continue;
+        }
+        match tunnel.insert(c) {
+            Ok(code) => result.extend_from_slice(&code.to_be_bytes()),
+            Err(e) => {
+                if check {
+                    return Err(e);
+                }
+                eprintln!("Warning: {}", e);
+                crate::COUNTER.inc_warning();
+                result.push(b'?');
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[test]
+fn test_sjis_tunnel() {
+    assert_eq!(TRAIL_COUNT, 59);
+    assert_eq!(code_for_index(0), Some(0x8101));
+    assert_eq!(code_for_index(TRAIL_COUNT), Some(0x8201));
+    assert_eq!(code_for_index(31 * TRAIL_COUNT), Some(0xE001));
+    assert_eq!(code_for_index(MAX_TUNNEL_CHARS - 1), Some(0xFC3F));
+    assert_eq!(code_for_index(MAX_TUNNEL_CHARS), None);
+    let mut tunnel = SjisTunnel::new();
+    assert_eq!(tunnel.insert('한').unwrap(), 0x8101);
+    assert_eq!(tunnel.insert('국').unwrap(), 0x8102);
+    assert_eq!(tunnel.insert('한').unwrap(), 0x8101);
+    assert!(tunnel.insert('😀').is_err());
+    assert_eq!(tunnel.to_bin(), vec![0x5C, 0xD5, 0x6D, 0xAD]);
+    let json = tunnel.to_json().unwrap();
+    let dir = crate::utils::testing::temp_dir("sjis-tunnel");
+    let path = dir.join("tunnel.json").to_string_lossy().into_owned();
+    std::fs::write(&path, json).unwrap();
+    let loaded = SjisTunnel::load(&path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(loaded.get('국'), Some(0x8102));
+    assert_eq!(loaded.len(), 2);
+    let mut tunnel = SjisTunnel::new();
+    assert_eq!(
+        encode_with(&mut tunnel, "a한", true).unwrap(),
+        vec![b'a', 0x81, 0x01]
+    );
+    assert_eq!(tunnel.len(), 1);
+}
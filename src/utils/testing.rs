@@ -0,0 +1,35 @@
+//! Helpers shared by unit tests.
+#[cfg(any(feature = "circus-img", feature = "softpal-img"))]
+use crate::types::*;
+
+/// Creates a temporary directory for a test.
+///
+/// `name` must be unique among tests. The process id is appended, so parallel test runs do not
+/// share the directory.
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("msg-tool-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(any(feature = "circus-img", feature = "softpal-img"))]
+/// Returns a BGRA image whose pixels are derived from `seed`, with some translucent pixels.
+pub fn test_image(width: u32, height: u32, seed: u32) -> ImageData {
+    ImageData {
+        width,
+        height,
+        depth: 8,
+        color_type: ImageColorType::Bgra,
+        data: (0..width * height)
+            .flat_map(|i| {
+                let v = i * seed;
+                [
+                    v as u8,
+                    (v >> 1) as u8,
+                    (v >> 2) as u8,
+                    0xFF - (i % 3) as u8,
+                ]
+            })
+            .collect(),
+    }
+}